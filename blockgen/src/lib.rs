@@ -2,6 +2,9 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+mod stratum;
+
+pub use crate::stratum::{Stratum, StratumWork};
 use cfx_types::{Address, H256, U256, U512};
 use cfxcore::{
     consensus::{
@@ -20,9 +23,12 @@ use primitives::{
     *,
 };
 use std::{
-    sync::{mpsc, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        mpsc, Arc,
+    },
     thread::{self, sleep},
-    time::{self, Duration},
+    time::{self, Duration, Instant},
 };
 use time::{SystemTime, UNIX_EPOCH};
 use txgen::{SharedTransactionGenerator, SpecialTransactionGenerator};
@@ -31,6 +37,11 @@ pub struct BlockGeneratorConfig {
     pub test_chain_path: Option<String>,
 }
 
+/// The number of nonces a worker tries before it checks for a new problem.
+const NONCE_BATCH_SIZE: u64 = 10_000;
+/// The minimal interval to update the hashrate estimation.
+const HASHRATE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
 enum MiningState {
     Start,
     Stop,
@@ -47,6 +58,20 @@ pub struct BlockGenerator {
     sync: SharedSynchronizationService,
     state: RwLock<MiningState>,
     workers: Mutex<Vec<(Worker, mpsc::Sender<ProofOfWorkProblem>)>>,
+    /// The problem of the block being mined, which is also exposed to
+    /// external miners.
+    current_problem: RwLock<Option<ProofOfWorkProblem>>,
+    /// Sends the solutions of external miners to the mining loop.
+    solution_sender: Mutex<Option<mpsc::Sender<ProofOfWorkSolution>>>,
+    stratum: RwLock<Option<Arc<Stratum>>>,
+    /// Whether the internal mining loop is running.
+    mining: AtomicBool,
+    /// The number of hashes computed by the workers since the last hashrate
+    /// update.
+    hash_count: AtomicUsize,
+    /// The hashrate of the workers, in hashes per second, and the time it is
+    /// updated.
+    hashrate: Mutex<(f64, Instant)>,
 }
 
 pub struct Worker {
@@ -55,18 +80,23 @@ pub struct Worker {
 }
 
 impl Worker {
+    /// Create the worker `index` of `num_workers`, which searches the
+    /// `index`-th part of the nonce space.
     pub fn new(
         bg: Arc<BlockGenerator>, sender: mpsc::Sender<ProofOfWorkSolution>,
-        receiver: mpsc::Receiver<ProofOfWorkProblem>,
+        receiver: mpsc::Receiver<ProofOfWorkProblem>, index: usize,
+        num_workers: usize,
     ) -> Self
     {
         let bg_handle = bg.clone();
 
         let thread = thread::Builder::new()
-            .name("blockgen".into())
+            .name(format!("blockgen-{}", index))
             .spawn(move || {
-                let sleep_duration = time::Duration::from_millis(100);
+                let range_size = u64::max_value() / num_workers as u64;
+                let range_start = range_size * index as u64;
                 let mut problem: Option<ProofOfWorkProblem> = None;
+                let mut nonce = range_start;
 
                 loop {
                     match *bg_handle.state.read() {
@@ -74,51 +104,71 @@ impl Worker {
                         _ => {}
                     }
 
-                    // check if there is a new problem
-                    let new_problem = receiver.try_recv();
-                    if new_problem.is_ok() {
-                        problem = Some(new_problem.unwrap());
+                    // Block until there is a problem to solve, and check for
+                    // a new problem after every batch of nonces.
+                    let new_problem = if problem.is_none() {
+                        match receiver.recv() {
+                            Ok(problem) => Some(problem),
+                            Err(_) => return,
+                        }
+                    } else {
+                        match receiver.try_recv() {
+                            Ok(problem) => Some(problem),
+                            Err(mpsc::TryRecvError::Empty) => None,
+                            Err(mpsc::TryRecvError::Disconnected) => return,
+                        }
+                    };
+                    if new_problem.is_some() {
+                        problem = new_problem;
+                        // Start at a random position of the range, so that
+                        // workers of different runs do not repeat the work.
+                        nonce =
+                            range_start + rand::random::<u64>() % range_size;
                     }
-                    // check if there is a problem to be solved
-                    if problem.is_some() {
-                        let boundary = problem.unwrap().boundary;
-                        let block_hash = problem.unwrap().block_hash;
-
-                        #[cfg(test)]
-                        {
-                            let difficulty = problem.unwrap().difficulty;
-                            if difficulty > 500000.into() {
-                                warn!("Difficulty is too high to mine!");
-                            }
+
+                    let boundary = problem.unwrap().boundary;
+                    let block_hash = problem.unwrap().block_hash;
+
+                    #[cfg(test)]
+                    {
+                        let difficulty = problem.unwrap().difficulty;
+                        if difficulty > 500000.into() {
+                            warn!("Difficulty is too high to mine!");
                         }
+                    }
 
-                        for _i in 0..100000 {
-                            //TODO: adjust the number of times
-                            let nonce = rand::random();
-                            let hash = compute(nonce, &block_hash);
-                            if hash < boundary {
-                                // problem solved
-                                match sender.send(ProofOfWorkSolution { nonce })
-                                {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        warn!("{}", e);
-                                    }
+                    for i in 0..NONCE_BATCH_SIZE {
+                        let hash = compute(nonce, &block_hash);
+                        if hash < boundary {
+                            // problem solved
+                            match sender.send(ProofOfWorkSolution { nonce }) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    warn!("{}", e);
                                 }
-                                // TODO Update problem fast. This will cause
-                                // miner to stop mining
-                                // until the previous blocks is processed by
-                                // ConsensusGraph
-                                problem = None;
-                                break;
                             }
+                            bg_handle.hash_count.fetch_add(
+                                i as usize + 1,
+                                AtomicOrdering::Relaxed,
+                            );
+                            problem = None;
+                            break;
                         }
-                    } else {
-                        thread::sleep(sleep_duration);
+                        nonce = if nonce + 1 < range_start + range_size {
+                            nonce + 1
+                        } else {
+                            range_start
+                        };
+                    }
+                    if problem.is_some() {
+                        bg_handle.hash_count.fetch_add(
+                            NONCE_BATCH_SIZE as usize,
+                            AtomicOrdering::Relaxed,
+                        );
                     }
                 }
             })
-            .expect("only one blockgen thread, so it should not fail");
+            .expect("Failed to spawn the mining worker thread");
         Worker { thread }
     }
 }
@@ -141,6 +191,58 @@ impl BlockGenerator {
             sync,
             state: RwLock::new(MiningState::Start),
             workers: Mutex::new(Vec::new()),
+            current_problem: RwLock::new(None),
+            solution_sender: Mutex::new(None),
+            stratum: RwLock::new(None),
+            mining: AtomicBool::new(false),
+            hash_count: AtomicUsize::new(0),
+            hashrate: Mutex::new((0.0, Instant::now())),
+        }
+    }
+
+    /// Push new mining jobs to the miners connected to the Stratum server.
+    pub fn set_stratum(&self, stratum: Arc<Stratum>) {
+        *self.stratum.write() = Some(stratum);
+    }
+
+    /// Returns the problem of the block being mined, or `None` if the node
+    /// is not mining.
+    pub fn get_work(&self) -> Option<ProofOfWorkProblem> {
+        *self.current_problem.read()
+    }
+
+    /// Submit a solution found by an external miner for the problem
+    /// `pow_hash`. The solution is rejected if it does not solve the current
+    /// problem.
+    pub fn submit_work(
+        &self, pow_hash: H256, nonce: u64,
+    ) -> Result<(), String> {
+        let problem = match *self.current_problem.read() {
+            Some(problem) => problem,
+            None => return Err("The node is not mining".into()),
+        };
+        if problem.block_hash != pow_hash {
+            return Err("The work is stale".into());
+        }
+        let solution = ProofOfWorkSolution { nonce };
+        if !validate(&problem, &solution) {
+            return Err("The nonce does not solve the problem".into());
+        }
+        match *self.solution_sender.lock() {
+            Some(ref sender) => sender
+                .send(solution)
+                .map_err(|e| format!("Failed to send the solution: {}", e)),
+            None => Err("The node is not mining".into()),
+        }
+    }
+
+    /// Update the current problem and push it to external miners.
+    fn set_problem(&self, problem: Option<ProofOfWorkProblem>) {
+        *self.current_problem.write() = problem;
+        if let (Some(problem), Some(stratum)) =
+            (problem, self.stratum.read().as_ref())
+        {
+            stratum.notify_work(&problem);
         }
     }
 
@@ -148,6 +250,26 @@ impl BlockGenerator {
     pub fn stop(bg: &BlockGenerator) {
         let mut write = bg.state.write();
         *write = MiningState::Stop;
+        // Wake up the workers waiting for problems.
+        bg.workers.lock().clear();
+    }
+
+    /// Whether the node is mining.
+    pub fn is_mining(&self) -> bool { self.mining.load(AtomicOrdering::SeqCst) }
+
+    /// The number of hashes per second the internal miner computes.
+    pub fn hashrate(&self) -> u64 { self.hashrate.lock().0 as u64 }
+
+    fn update_hashrate(&self) {
+        let mut hashrate = self.hashrate.lock();
+        let elapsed = hashrate.1.elapsed();
+        if elapsed < HASHRATE_UPDATE_INTERVAL {
+            return;
+        }
+        let hash_count = self.hash_count.swap(0, AtomicOrdering::Relaxed);
+        let elapsed_secs =
+            elapsed.as_secs() as f64 + elapsed.subsec_millis() as f64 / 1000.0;
+        *hashrate = (hash_count as f64 / elapsed_secs, Instant::now());
     }
 
     /// Send new PoW problem to workers
@@ -451,34 +573,48 @@ impl BlockGenerator {
 
     /// Start num_worker new workers
     pub fn start_new_worker(
-        num_worker: u32, bg: Arc<BlockGenerator>,
+        num_worker: usize, bg: Arc<BlockGenerator>,
     ) -> mpsc::Receiver<ProofOfWorkSolution> {
         let (tx, rx) = mpsc::channel();
+        *bg.solution_sender.lock() = Some(tx.clone());
         let mut workers = bg.workers.lock();
-        for _ in 0..num_worker {
+        for index in 0..num_worker {
             let (sender_handle, receiver_handle) = mpsc::channel();
             workers.push((
-                Worker::new(bg.clone(), tx.clone(), receiver_handle),
+                Worker::new(
+                    bg.clone(),
+                    tx.clone(),
+                    receiver_handle,
+                    index,
+                    num_worker,
+                ),
                 sender_handle,
             ));
         }
         rx
     }
 
-    pub fn start_mining(bg: Arc<BlockGenerator>, _payload_len: u32) {
+    /// Mine blocks with `num_threads` workers. With no workers, the blocks
+    /// are only mined by external miners.
+    pub fn start_mining(bg: Arc<BlockGenerator>, num_threads: usize) {
         let mut current_mining_block = None;
         let mut current_problem: Option<ProofOfWorkProblem> = None;
         // FIXME: change to notification.
         let sleep_duration = time::Duration::from_millis(50);
 
         let receiver: mpsc::Receiver<ProofOfWorkSolution> =
-            BlockGenerator::start_new_worker(1, bg.clone());
+            BlockGenerator::start_new_worker(num_threads, bg.clone());
+        bg.mining.store(true, AtomicOrdering::SeqCst);
 
         loop {
             match *bg.state.read() {
-                MiningState::Stop => return,
+                MiningState::Stop => {
+                    bg.mining.store(false, AtomicOrdering::SeqCst);
+                    return;
+                }
                 _ => {}
             }
+            bg.update_hashrate();
 
             if bg.is_mining_block_outdated(current_mining_block.as_ref()) {
                 // TODO: #transations TBD
@@ -509,10 +645,12 @@ impl BlockGenerator {
                     boundary: difficulty_to_boundary(current_difficulty),
                 };
                 BlockGenerator::send_problem(bg.clone(), problem);
+                bg.set_problem(Some(problem));
                 current_problem = Some(problem);
             } else {
-                // check if the problem solved
-                let mut new_solution = receiver.try_recv();
+                // Wait for a solution, and check again whether the block is
+                // outdated if there is none for a while.
+                let mut new_solution = receiver.recv_timeout(sleep_duration);
                 loop {
                     // check if the block received valid
                     if new_solution.is_ok()
@@ -521,7 +659,9 @@ impl BlockGenerator {
                             &new_solution.unwrap(),
                         )
                     {
-                        new_solution = receiver.try_recv();
+                        new_solution = receiver
+                            .try_recv()
+                            .map_err(|_| mpsc::RecvTimeoutError::Timeout);
                     } else {
                         break;
                     }
@@ -538,15 +678,22 @@ impl BlockGenerator {
                         .unwrap()
                         .block_header
                         .compute_hash();
+                    bg.set_problem(None);
                     bg.on_mined_block(current_mining_block.unwrap());
                     current_mining_block = None;
                     current_problem = None;
-                } else {
-                    // wait a moment and check again
-                    thread::sleep(sleep_duration);
-                    continue;
                 }
             }
         }
     }
 }
+
+impl StratumWork for BlockGenerator {
+    fn get_work(&self) -> Option<ProofOfWorkProblem> {
+        BlockGenerator::get_work(self)
+    }
+
+    fn submit_work(&self, pow_hash: H256, nonce: u64) -> Result<(), String> {
+        BlockGenerator::submit_work(self, pow_hash, nonce)
+    }
+}
@@ -0,0 +1,468 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! A Stratum-style server for external miners.
+//!
+//! Messages are JSON objects separated by newlines. A miner sends
+//! `{"id": 1, "method": "mining.subscribe", "params": []}` to receive jobs,
+//! and the server pushes every new job as
+//! `{"id": null, "method": "mining.notify", "params": [pow_hash, boundary]}`.
+//! Solutions are submitted with
+//! `{"id": 2, "method": "mining.submit", "params": [pow_hash, nonce]}`, where
+//! the nonce is a hex string, and the result is true if the solution is
+//! accepted.
+
+use cfx_types::H256;
+use cfxcore::pow::ProofOfWorkProblem;
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The number of messages queued for a miner. A miner which does not read
+/// its messages fast enough is disconnected.
+const MAX_QUEUED_MESSAGES: usize = 16;
+/// A miner is disconnected if a message can not be written to it in time.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// A miner is disconnected if it sends nothing for this long.
+const READ_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// A miner is disconnected if it sends a longer message, including the
+/// newline.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// The source of the mining jobs pushed by the Stratum server, which also
+/// verifies the submitted solutions.
+pub trait StratumWork: Send + Sync {
+    /// Returns the current job, or `None` if the node is not mining.
+    fn get_work(&self) -> Option<ProofOfWorkProblem>;
+
+    fn submit_work(&self, pow_hash: H256, nonce: u64) -> Result<(), String>;
+}
+
+/// A subscribed miner. Messages are written to it by its own thread, so
+/// that a slow miner does not block the others.
+struct Subscriber {
+    sender: SyncSender<String>,
+    stream: TcpStream,
+}
+
+pub struct Stratum {
+    work: Weak<dyn StratumWork>,
+    local_addr: SocketAddr,
+    max_connections: usize,
+    connections: AtomicUsize,
+    subscribers: Mutex<HashMap<usize, Subscriber>>,
+    next_id: AtomicUsize,
+}
+
+impl Stratum {
+    /// Start listening at `address`, and serve at most `max_connections`
+    /// miners at the same time. The server does not keep the source of the
+    /// jobs alive.
+    pub fn start(
+        address: &SocketAddr, max_connections: usize,
+        work: Weak<dyn StratumWork>,
+    ) -> io::Result<Arc<Stratum>>
+    {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+        info!("Stratum server listening at {}", local_addr);
+        let stratum = Arc::new(Stratum {
+            work,
+            local_addr,
+            max_connections,
+            connections: AtomicUsize::new(0),
+            subscribers: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+        });
+
+        let server = stratum.clone();
+        thread::Builder::new()
+            .name("stratum".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if server.work.upgrade().is_none() {
+                        return;
+                    }
+                    match stream {
+                        Ok(stream) => server.clone().serve(stream),
+                        Err(e) => debug!("Stratum accept error: {:?}", e),
+                    }
+                }
+            })?;
+        Ok(stratum)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr { self.local_addr }
+
+    /// Serve a miner connection in new threads, one reading requests and one
+    /// writing messages.
+    fn serve(self: Arc<Self>, stream: TcpStream) {
+        if self.connections.load(Ordering::SeqCst) >= self.max_connections {
+            debug!("Too many stratum connections, reject {:?}", stream);
+            return;
+        }
+        self.connections.fetch_add(1, Ordering::SeqCst);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let spawned = thread::Builder::new().name("stratum-conn".into()).spawn(
+            move || {
+                if let Err(e) = self.handle_connection(id, stream) {
+                    debug!("Stratum connection {} closed: {:?}", id, e);
+                }
+                self.subscribers.lock().remove(&id);
+                self.connections.fetch_sub(1, Ordering::SeqCst);
+            },
+        );
+        if let Err(e) = spawned {
+            warn!("Failed to spawn stratum connection thread: {:?}", e);
+        }
+    }
+
+    fn handle_connection(
+        &self, id: usize, stream: TcpStream,
+    ) -> io::Result<()> {
+        // Responses and pushed jobs are written by the same thread, so that
+        // the messages are not interleaved.
+        let (sender, receiver) = sync_channel(MAX_QUEUED_MESSAGES);
+        let writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        thread::Builder::new()
+            .name("stratum-writer".into())
+            .spawn(move || Self::write_messages(writer, receiver))?;
+
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let size = (&mut reader)
+                .take(MAX_MESSAGE_SIZE as u64 + 1)
+                .read_line(&mut line)?;
+            if size == 0 {
+                break;
+            }
+            if size > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Message is too long",
+                ));
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Value = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    let response = json!({
+                        "id": Value::Null,
+                        "result": Value::Null,
+                        "error": format!("Invalid request: {}", e),
+                    });
+                    Self::send_message(&sender, &response)?;
+                    continue;
+                }
+            };
+            let subscribe = request["method"] == "mining.subscribe";
+            let result = match request["method"].as_str() {
+                Some("mining.subscribe") => {
+                    self.subscribers.lock().insert(
+                        id,
+                        Subscriber {
+                            sender: sender.clone(),
+                            stream: stream.try_clone()?,
+                        },
+                    );
+                    Ok(Value::Bool(true))
+                }
+                Some("mining.submit") => self.submit(&request["params"]),
+                _ => Err("Unknown method".to_string()),
+            };
+            let response = match result {
+                Ok(result) => json!({
+                    "id": request["id"],
+                    "result": result,
+                    "error": Value::Null,
+                }),
+                Err(e) => json!({
+                    "id": request["id"],
+                    "result": Value::Null,
+                    "error": e,
+                }),
+            };
+            Self::send_message(&sender, &response)?;
+
+            // Send the current job right after subscription.
+            if subscribe {
+                let work = self.work.upgrade().and_then(|work| work.get_work());
+                if let Some(problem) = work {
+                    let notification = Self::notification(&problem);
+                    Self::send_message(&sender, &notification)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn submit(&self, params: &Value) -> Result<Value, String> {
+        let parse_hex = |index: usize| {
+            params[index]
+                .as_str()
+                .map(|s| s.trim_start_matches("0x").to_string())
+                .ok_or_else(|| "Invalid params".to_string())
+        };
+        let pow_hash = H256::from_str(&parse_hex(0)?)
+            .map_err(|_| "Invalid pow hash".to_string())?;
+        let nonce = u64::from_str_radix(&parse_hex(1)?, 16)
+            .map_err(|_| "Invalid nonce".to_string())?;
+        let work = self.work.upgrade().ok_or("Node is shutting down")?;
+        work.submit_work(pow_hash, nonce)?;
+        Ok(Value::Bool(true))
+    }
+
+    fn notification(problem: &ProofOfWorkProblem) -> Value {
+        json!({
+            "id": Value::Null,
+            "method": "mining.notify",
+            "params": [
+                format!("0x{:x}", problem.block_hash),
+                format!("0x{:x}", problem.boundary),
+            ],
+        })
+    }
+
+    fn encode(message: &Value) -> String {
+        let mut data = message.to_string();
+        data.push('\n');
+        data
+    }
+
+    fn send_message(
+        sender: &SyncSender<String>, message: &Value,
+    ) -> io::Result<()> {
+        sender.send(Self::encode(message)).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "Writer is closed")
+        })
+    }
+
+    fn write_messages(mut stream: TcpStream, receiver: Receiver<String>) {
+        for message in receiver {
+            if let Err(e) = stream.write_all(message.as_bytes()) {
+                debug!("Failed to write to miner: {:?}", e);
+                // Also stop reading from the miner.
+                stream.shutdown(Shutdown::Both).ok();
+                return;
+            }
+        }
+    }
+
+    /// Push the new job to all subscribed miners without blocking. Miners
+    /// whose messages queue up are disconnected.
+    pub fn notify_work(&self, problem: &ProofOfWorkProblem) {
+        let notification = Self::encode(&Self::notification(problem));
+        self.subscribers.lock().retain(|id, subscriber| {
+            match subscriber.sender.try_send(notification.clone()) {
+                Ok(_) => true,
+                Err(e) => {
+                    debug!("Failed to push job to miner {}: {:?}", id, e);
+                    subscriber.stream.shutdown(Shutdown::Both).ok();
+                    false
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Stratum, StratumWork, MAX_MESSAGE_SIZE};
+    use cfx_types::H256;
+    use cfxcore::pow::ProofOfWorkProblem;
+    use parking_lot::Mutex;
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{SocketAddr, TcpStream},
+        sync::Arc,
+    };
+
+    #[derive(Default)]
+    struct TestWork {
+        problem: Mutex<Option<ProofOfWorkProblem>>,
+        submitted: Mutex<Vec<(H256, u64)>>,
+    }
+
+    impl StratumWork for TestWork {
+        fn get_work(&self) -> Option<ProofOfWorkProblem> {
+            *self.problem.lock()
+        }
+
+        fn submit_work(
+            &self, pow_hash: H256, nonce: u64,
+        ) -> Result<(), String> {
+            match *self.problem.lock() {
+                Some(ref problem) if problem.block_hash == pow_hash => {
+                    self.submitted.lock().push((pow_hash, nonce));
+                    Ok(())
+                }
+                _ => Err("The work is stale".into()),
+            }
+        }
+    }
+
+    fn problem(hash: u64) -> ProofOfWorkProblem {
+        ProofOfWorkProblem {
+            block_hash: H256::from(hash),
+            difficulty: 1.into(),
+            boundary: H256::from(2),
+        }
+    }
+
+    struct Miner {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+
+    impl Miner {
+        fn connect(address: SocketAddr) -> Miner {
+            let stream = TcpStream::connect(address).unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            Miner { stream, reader }
+        }
+
+        fn request(&mut self, request: Value) -> Value {
+            let mut data = request.to_string();
+            data.push('\n');
+            self.stream.write_all(data.as_bytes()).unwrap();
+            self.read()
+        }
+
+        fn read(&mut self) -> Value {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+    }
+
+    fn start(work: &Arc<TestWork>, max_connections: usize) -> Arc<Stratum> {
+        let work: Arc<dyn StratumWork> = work.clone();
+        Stratum::start(
+            &"127.0.0.1:0".parse().unwrap(),
+            max_connections,
+            Arc::downgrade(&work),
+        )
+        .unwrap()
+    }
+
+    fn subscribe(miner: &mut Miner) {
+        let response = miner.request(
+            json!({"id": 1, "method": "mining.subscribe", "params": []}),
+        );
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"], true);
+    }
+
+    fn assert_notified(miner: &mut Miner, problem: &ProofOfWorkProblem) {
+        let notification = miner.read();
+        assert_eq!(notification["method"], "mining.notify");
+        assert_eq!(
+            notification["params"][0],
+            format!("0x{:x}", problem.block_hash)
+        );
+        assert_eq!(
+            notification["params"][1],
+            format!("0x{:x}", problem.boundary)
+        );
+    }
+
+    #[test]
+    fn test_subscribe_and_notify() {
+        let work = Arc::new(TestWork::default());
+        *work.problem.lock() = Some(problem(1));
+        let stratum = start(&work, 4);
+
+        // The current job is sent right after subscription.
+        let mut miner = Miner::connect(stratum.local_addr());
+        subscribe(&mut miner);
+        assert_notified(&mut miner, &problem(1));
+
+        stratum.notify_work(&problem(3));
+        assert_notified(&mut miner, &problem(3));
+    }
+
+    #[test]
+    fn test_submit() {
+        let work = Arc::new(TestWork::default());
+        *work.problem.lock() = Some(problem(1));
+        let stratum = start(&work, 4);
+        let mut miner = Miner::connect(stratum.local_addr());
+
+        let response = miner.request(json!({
+            "id": 2,
+            "method": "mining.submit",
+            "params": [format!("0x{:x}", H256::from(1)), "0x1f"],
+        }));
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"], true);
+        assert_eq!(*work.submitted.lock(), vec![(H256::from(1), 0x1f)]);
+
+        let response = miner.request(json!({
+            "id": 3,
+            "method": "mining.submit",
+            "params": [format!("0x{:x}", H256::from(5)), "0x1f"],
+        }));
+        assert_eq!(response["result"], Value::Null);
+        assert_eq!(response["error"], "The work is stale");
+
+        let response = miner.request(
+            json!({"id": 4, "method": "mining.submit", "params": ["0x1"]}),
+        );
+        assert_eq!(response["error"], "Invalid params");
+        assert_eq!(work.submitted.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_max_connections() {
+        let work = Arc::new(TestWork::default());
+        let stratum = start(&work, 1);
+        let mut miner = Miner::connect(stratum.local_addr());
+        subscribe(&mut miner);
+
+        // The connection over the limit is closed right away.
+        let mut rejected = Miner::connect(stratum.local_addr());
+        let mut line = String::new();
+        assert_eq!(rejected.reader.read_line(&mut line).unwrap_or(0), 0);
+
+        // The first miner is still served.
+        let response = miner.request(
+            json!({"id": 5, "method": "mining.unknown", "params": []}),
+        );
+        assert_eq!(response["error"], "Unknown method");
+    }
+
+    #[test]
+    fn test_message_too_long() {
+        let work = Arc::new(TestWork::default());
+        let stratum = start(&work, 4);
+
+        // The miner is disconnected before the message ends.
+        let mut miner = Miner::connect(stratum.local_addr());
+        let data = vec![b' '; MAX_MESSAGE_SIZE + 1];
+        miner.stream.write_all(&data).unwrap();
+        let mut line = String::new();
+        assert_eq!(miner.reader.read_line(&mut line).unwrap_or(0), 0);
+    }
+}
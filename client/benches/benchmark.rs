@@ -50,6 +50,7 @@ fn txexe_benchmark(c: &mut Criterion) {
     .unwrap();
     let receiver_kp = Random.generate().expect("Fail to generate KeyPair.");
 
+    let machine = new_machine();
     let tx = Transaction {
         nonce: 0.into(),
         gas_price: U256::from(100u64),
@@ -57,9 +58,9 @@ fn txexe_benchmark(c: &mut Criterion) {
         value: 1.into(),
         action: Action::Call(receiver_kp.address()),
         data: Bytes::new(),
+        chain_id: machine.params().chain_id,
     };
     let tx = tx.sign(kp.secret());
-    let machine = new_machine();
     let mut env = EnvInfo {
         number: 0, // TODO: replace 0 with correct cardinal number
         author: Default::default(),
@@ -3,9 +3,13 @@
 // See http://www.gnu.org/licenses/
 
 use blockgen::BlockGeneratorConfig;
+use cfx_types::{Address, H256};
 use cfxcore::{
+    chain_spec::ChainSpec,
+    consensus::Checkpoint,
     storage::{self, state_manager::StorageConfiguration},
     sync::ProtocolConfiguration,
+    transaction_pool::{self, EvictionPolicy, TxPoolConfig},
 };
 use txgen::TransactionGeneratorConfig;
 // usage:
@@ -33,8 +37,10 @@ build_config! {
         (jsonrpc_local_http_port, (Option<u16>), None)
         (jsonrpc_tcp_port, (Option<u16>), None)
         (jsonrpc_http_port, (Option<u16>), None)
+        (jsonrpc_ws_port, (Option<u16>), None)
         (jsonrpc_cors, (Option<String>), None)
         (jsonrpc_http_keep_alive, (bool), false)
+        (chain, (Option<String>), None)
         (genesis_accounts, (Option<String>), None)
         (log_conf, (Option<String>), None)
         (log_file, (Option<String>), None)
@@ -50,6 +56,7 @@ build_config! {
         (node_table_timeout, (Option<u64>), Some(300))
         (node_table_promotion_timeout, (Option<u64>), Some(3 * 24 * 3600))
         (fast_recover, (bool), true)
+        (light_node, (bool), false)
         (test_mode, (bool), false)
         (db_cache_size, (Option<usize>), Some(128))
         (db_compaction_profile, (Option<String>), None)
@@ -61,6 +68,7 @@ build_config! {
         (storage_recent_lfu_factor, (f64), storage::defaults::DEFAULT_RECENT_LFU_FACTOR)
         (storage_idle_size, (u32), storage::defaults::DEFAULT_IDLE_SIZE)
         (storage_node_map_size, (u32), storage::defaults::MAX_CACHED_TRIE_NODES_R_LFU_COUNTER)
+        (storage_history_epochs, (Option<u64>), None)
         (send_tx_period_ms, (u64), 1300)
         (check_request_period_ms, (u64), 5000)
         (block_cache_gc_period_ms, (u64), 5000)
@@ -76,16 +84,28 @@ build_config! {
         (request_block_with_public, (bool), false)
         (load_test_chain, (Option<String>), None)
         (start_mining, (bool), false)
+        (mining_threads, (usize), 1)
         (initial_difficulty, (Option<u64>), None)
-        (tx_pool_size, (usize), 500_000)
+        (tx_pool_size, (usize), transaction_pool::DEFAULT_TX_POOL_CAPACITY)
+        (tx_pool_min_replace_gas_price_bump, (u64), transaction_pool::DEFAULT_MIN_REPLACE_GAS_PRICE_BUMP)
+        (tx_pool_max_txs_per_sender, (usize), transaction_pool::DEFAULT_MAX_TRANSACTIONS_PER_SENDER)
         (mining_author, (Option<String>), None)
+        (stratum_port, (Option<u16>), None)
+        (stratum_address, (String), "127.0.0.1".to_string())
+        (stratum_max_connections, (usize), 64)
         (egress_queue_capacity, (usize), 256)
         (egress_min_throttle, (usize), 10)
         (egress_max_throttle, (usize), 64)
         (p2p_nodes_per_ip, (usize), 1)
+        (p2p_require_encryption, (bool), false)
+        (p2p_compression_enabled, (bool), true)
+        (p2p_ban_duration_s, (u64), 24 * 60 * 60)
         (data_propagate_enabled, (bool), false)
         (data_propagate_interval_ms, (u64), 1000)
         (data_propagate_size, (usize), 1000)
+        (checkpoint_epoch, (Option<u64>), None)
+        (checkpoint_hash, (Option<String>), None)
+        (checkpoint_state_root, (Option<String>), None)
     }
     {
         (
@@ -101,6 +121,14 @@ build_config! {
                 }
             }
         )
+        (
+            tx_pool_eviction_policy, (EvictionPolicy),
+            EvictionPolicy::LowestGasPrice, EvictionPolicy::from_str
+        )
+        (
+            tx_pool_local_senders, (Vec<Address>), Vec::new(),
+            to_addresses
+        )
     }
 }
 
@@ -164,6 +192,12 @@ impl Configuration {
         }
         network_config.test_mode = self.raw_conf.test_mode;
         network_config.nodes_per_ip = self.raw_conf.p2p_nodes_per_ip;
+        network_config.require_encryption =
+            self.raw_conf.p2p_require_encryption;
+        network_config.compression_enabled =
+            self.raw_conf.p2p_compression_enabled;
+        network_config.ban_duration =
+            Duration::from_secs(self.raw_conf.p2p_ban_duration_s);
         network_config.fast_discovery_refresh_timeout = Duration::from_millis(
             self.raw_conf.discovery_fast_refresh_timeout_ms,
         );
@@ -215,6 +249,25 @@ impl Configuration {
         )
     }
 
+    pub fn chain_spec(&self) -> Result<ChainSpec, String> {
+        match self.raw_conf.chain {
+            Some(ref path) => ChainSpec::load_file(path),
+            None => Ok(ChainSpec::default()),
+        }
+    }
+
+    pub fn tx_pool_config(&self) -> TxPoolConfig {
+        TxPoolConfig {
+            capacity: self.raw_conf.tx_pool_size,
+            min_replace_gas_price_bump: self
+                .raw_conf
+                .tx_pool_min_replace_gas_price_bump,
+            max_txs_per_sender: self.raw_conf.tx_pool_max_txs_per_sender,
+            eviction_policy: self.raw_conf.tx_pool_eviction_policy,
+            local_senders: self.raw_conf.tx_pool_local_senders.clone(),
+        }
+    }
+
     pub fn verification_config(&self) -> VerificationConfig {
         VerificationConfig::new(self.raw_conf.test_mode)
     }
@@ -233,6 +286,7 @@ impl Configuration {
             idle_size: self.raw_conf.storage_idle_size,
             node_map_size: self.raw_conf.storage_node_map_size,
             recent_lfu_factor: self.raw_conf.storage_recent_lfu_factor,
+            history_epochs: self.raw_conf.storage_history_epochs,
         }
     }
 
@@ -276,6 +330,28 @@ impl Configuration {
         }
     }
 
+    /// The trusted checkpoint to sync from, if configured.
+    pub fn checkpoint(&self) -> Result<Option<Checkpoint>, String> {
+        match (
+            self.raw_conf.checkpoint_epoch,
+            &self.raw_conf.checkpoint_hash,
+            &self.raw_conf.checkpoint_state_root,
+        ) {
+            (None, None, None) => Ok(None),
+            (Some(epoch_number), Some(hash), Some(state_root)) => {
+                Checkpoint::new(
+                    epoch_number,
+                    to_h256(hash)?,
+                    to_h256(state_root)?,
+                )
+                .map(Some)
+            }
+            _ => Err("checkpoint_epoch, checkpoint_hash and \
+                      checkpoint_state_root must be set together"
+                .into()),
+        }
+    }
+
     pub fn blockgen_config(&self) -> BlockGeneratorConfig {
         BlockGeneratorConfig {
             test_chain_path: self.raw_conf.load_test_chain.clone(),
@@ -283,6 +359,25 @@ impl Configuration {
     }
 }
 
+/// Parses a comma separated list of hex addresses.
+pub fn to_addresses(addresses: &str) -> Result<Vec<Address>, String> {
+    addresses
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            Address::from_str(s.trim_start_matches("0x"))
+                .map_err(|_| format!("Invalid address: {}", s))
+        })
+        .collect()
+}
+
+/// Parses a hex hash.
+fn to_h256(hash: &str) -> Result<H256, String> {
+    H256::from_str(hash.trim().trim_start_matches("0x"))
+        .map_err(|_| format!("Invalid hash: {}", hash))
+}
+
 /// Validates and formats bootnodes option.
 pub fn to_bootnodes(bootnodes: &Option<String>) -> Result<Vec<String>, String> {
     match *bootnodes {
@@ -6,6 +6,7 @@
 
 use jsonrpc_http_server as http;
 use jsonrpc_tcp_server as tcp;
+use jsonrpc_ws_server as ws;
 #[macro_use]
 extern crate log;
 
@@ -19,18 +20,22 @@ mod rpc;
 #[cfg(test)]
 mod tests;
 
-use self::{http::Server as HttpServer, tcp::Server as TcpServer};
+use self::{
+    http::Server as HttpServer, tcp::Server as TcpServer,
+    ws::Server as WsServer,
+};
 pub use crate::configuration::Configuration;
-use blockgen::BlockGenerator;
+use blockgen::{BlockGenerator, Stratum, StratumWork};
 use cfxcore::{
     cache_manager::CacheManager, genesis, pow::WORKER_COMPUTATION_PARALLELISM,
     statistics::Statistics, storage::StorageManager,
     transaction_pool::DEFAULT_MAX_BLOCK_GAS_LIMIT, vm_factory::VmFactory,
-    ConsensusGraph, SynchronizationService, TransactionPool,
+    ConsensusGraph, Notifications, SynchronizationService, TransactionPool,
 };
 
 use crate::rpc::{
-    impls::cfx::RpcImpl, setup_debug_rpc_apis, setup_public_rpc_apis, RpcBlock,
+    impls::{cfx::RpcImpl, pubsub::PubSubClient},
+    setup_debug_rpc_apis, setup_public_rpc_apis, setup_ws_apis, RpcBlock,
 };
 use cfx_types::{Address, U256};
 use ctrlc::CtrlC;
@@ -44,6 +49,7 @@ use std::{
     any::Any,
     fs::File,
     io::BufReader,
+    net::{IpAddr, SocketAddr},
     path::Path,
     str::FromStr,
     sync::{Arc, Weak},
@@ -65,6 +71,7 @@ pub struct ClientHandle {
     pub debug_rpc_http_server: Option<HttpServer>,
     pub rpc_tcp_server: Option<TcpServer>,
     pub rpc_http_server: Option<HttpServer>,
+    pub rpc_ws_server: Option<WsServer>,
     pub consensus: Arc<ConsensusGraph>,
     pub txpool: Arc<TransactionPool>,
     pub sync: Arc<SynchronizationService>,
@@ -87,6 +94,7 @@ impl ClientHandle {
                 self.debug_rpc_http_server,
                 self.rpc_tcp_server,
                 self.rpc_http_server,
+                self.rpc_ws_server,
                 self.txpool,
                 self.sync,
                 self.txgen,
@@ -147,7 +155,12 @@ impl Client {
             });
         }
 
-        let genesis_accounts = if conf.raw_conf.test_mode {
+        let chain_spec = conf.chain_spec()?;
+        let machine = Arc::new(chain_spec.machine());
+
+        let genesis_accounts = if let Some(ref accounts) = chain_spec.accounts {
+            accounts.clone()
+        } else if conf.raw_conf.test_mode {
             match conf.raw_conf.genesis_accounts {
                 Some(ref file) => genesis::load_file(file)?,
                 None => genesis::default(secret_store.as_ref()),
@@ -173,17 +186,22 @@ impl Client {
             3 * mb,
         )));
 
-        let txpool = Arc::new(TransactionPool::with_capacity(
-            conf.raw_conf.tx_pool_size,
+        let notifications = Arc::new(Notifications::new());
+
+        let txpool = Arc::new(TransactionPool::new(
+            conf.tx_pool_config(),
             storage_manager.clone(),
+            ledger_db.clone(),
             worker_thread_pool.clone(),
             cache_man.clone(),
+            machine.clone(),
+            notifications.clone(),
         ));
 
         let statistics = Arc::new(Statistics::new());
 
         let vm = VmFactory::new(1024 * 32);
-        let pow_config = conf.pow_config();
+        let pow_config = chain_spec.pow.unwrap_or_else(|| conf.pow_config());
         let consensus = Arc::new(ConsensusGraph::with_genesis_block(
             genesis_block,
             storage_manager.clone(),
@@ -193,6 +211,9 @@ impl Client {
             ledger_db.clone(),
             cache_man.clone(),
             pow_config.clone(),
+            machine.clone(),
+            notifications.clone(),
+            conf.checkpoint()?,
         ));
 
         let verification_config = conf.verification_config();
@@ -205,7 +226,15 @@ impl Client {
             pow_config.clone(),
             conf.fast_recover(),
         );
-        sync.start().unwrap();
+        if conf.raw_conf.light_node {
+            if conf.raw_conf.start_mining {
+                panic!("A light node can not mine blocks");
+            }
+            sync.start_light().unwrap();
+        } else {
+            sync.start().unwrap();
+        }
+        txpool.restore_local_transactions(consensus.best_state_block_hash());
 
         if conf.raw_conf.test_mode && conf.raw_conf.data_propagate_enabled {
             DataPropagation::register(
@@ -232,6 +261,7 @@ impl Client {
                 &public_to_address(secret_store.get_keypair(0).public()),
                 U256::from_dec_str("10000000000000000").unwrap(),
                 U256::from_dec_str("10000000000000000").unwrap(),
+                machine.params().chain_id,
             )));
 
         let blockgen_config = conf.blockgen_config();
@@ -273,14 +303,30 @@ impl Client {
                 panic!("mining-author is not set correctly, so you'll not get mining rewards!!!");
             }
             let bg = blockgen.clone();
-            info!("Start mining with pow config: {:?}", pow_config);
+            let mining_threads = conf.raw_conf.mining_threads;
+            info!(
+                "Start mining with {} threads and pow config: {:?}",
+                mining_threads, pow_config
+            );
             thread::Builder::new()
                 .name("mining".into())
                 .spawn(move || {
-                    BlockGenerator::start_mining(bg, 0);
+                    BlockGenerator::start_mining(bg, mining_threads);
                 })
                 .expect("Mining thread spawn error");
         }
+        if let Some(port) = conf.raw_conf.stratum_port {
+            let ip = IpAddr::from_str(&conf.raw_conf.stratum_address)
+                .map_err(|e| format!("Invalid stratum_address: {:?}", e))?;
+            let work: Weak<dyn StratumWork> = Arc::downgrade(&blockgen);
+            let stratum = Stratum::start(
+                &SocketAddr::new(ip, port),
+                conf.raw_conf.stratum_max_connections,
+                work,
+            )
+            .map_err(|e| format!("Failed to start stratum server {:?}", e))?;
+            blockgen.set_stratum(stratum);
+        }
 
         let tx_conf = conf.tx_gen_config();
         let txgen_handle = if tx_conf.generate_tx {
@@ -343,10 +389,30 @@ impl Client {
             },
         )?;
 
+        // Only listen to notifications when they can be subscribed.
+        let ws_conf =
+            rpc::WsConfiguration::new(None, conf.raw_conf.jsonrpc_ws_port);
+        let rpc_ws_server = if ws_conf.enabled {
+            rpc::new_ws(
+                ws_conf,
+                setup_ws_apis(
+                    rpc_impl.clone(),
+                    PubSubClient::new(
+                        Arc::downgrade(&consensus),
+                        notifications,
+                    ),
+                    conf.raw_conf.test_mode,
+                ),
+            )?
+        } else {
+            None
+        };
+
         Ok(ClientHandle {
             ledger_db: Arc::downgrade(&ledger_db),
             debug_rpc_http_server,
             rpc_http_server,
+            rpc_ws_server,
             rpc_tcp_server,
             txpool,
             txgen,
@@ -379,6 +445,9 @@ impl Client {
     }
 
     pub fn close(handle: ClientHandle) -> i32 {
+        // Local transactions are journaled periodically, so the latest ones
+        // are written before exiting.
+        handle.txpool.flush_local_transactions();
         let (ledger_db, blockgen, to_drop) = handle.into_be_dropped();
         BlockGenerator::stop(&blockgen);
         drop(blockgen);
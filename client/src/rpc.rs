@@ -8,8 +8,13 @@ use crate::{
         ServerBuilder as HttpServerBuilder,
     },
     tcp::{Server as TcpServer, ServerBuilder as TcpServerBuilder},
+    ws::{
+        RequestContext as WsRequestContext, Server as WsServer,
+        ServerBuilder as WsServerBuilder,
+    },
 };
-use jsonrpc_core::IoHandler;
+use jsonrpc_core::{IoHandler, MetaIoHandler};
+use jsonrpc_pubsub::{PubSubHandler, Session};
 use std::{
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     sync::Arc,
@@ -20,8 +25,11 @@ mod traits;
 mod types;
 
 use self::{
-    impls::cfx::{CfxHandler, DebugRpcImpl, RpcImpl, TestRpcImpl},
-    traits::{Cfx, DebugRpc, TestRpc},
+    impls::{
+        cfx::{CfxHandler, DebugRpcImpl, RpcImpl, TestRpcImpl},
+        pubsub::PubSubClient,
+    },
+    traits::{Cfx, DebugRpc, PubSub, TestRpc},
 };
 
 pub use self::types::Block as RpcBlock;
@@ -45,6 +53,25 @@ impl TcpConfiguration {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct WsConfiguration {
+    pub enabled: bool,
+    pub address: SocketAddr,
+}
+
+impl WsConfiguration {
+    pub fn new(ip: Option<(u8, u8, u8, u8)>, port: Option<u16>) -> Self {
+        let ipv4 = match ip {
+            Some(ip) => Ipv4Addr::new(ip.0, ip.1, ip.2, ip.3),
+            None => Ipv4Addr::new(0, 0, 0, 0),
+        };
+        WsConfiguration {
+            enabled: port.is_some(),
+            address: SocketAddr::V4(SocketAddrV4::new(ipv4, port.unwrap_or(0))),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct HttpConfiguration {
     pub enabled: bool,
@@ -104,6 +131,22 @@ pub fn setup_debug_rpc_apis(rpc_impl: Arc<RpcImpl>) -> IoHandler {
     handler
 }
 
+/// The WebSocket apis include the rpc apis and the pub-sub apis.
+pub fn setup_ws_apis(
+    rpc_impl: Arc<RpcImpl>, pubsub: PubSubClient, debug: bool,
+) -> PubSubHandler<Arc<Session>> {
+    let mut handler = PubSubHandler::new(MetaIoHandler::default());
+
+    handler.extend_with(CfxHandler::new(rpc_impl.clone()).to_delegate());
+    if debug {
+        handler.extend_with(TestRpcImpl::new(rpc_impl.clone()).to_delegate());
+        handler.extend_with(DebugRpcImpl::new(rpc_impl).to_delegate());
+    }
+    handler.extend_with(pubsub.to_delegate());
+
+    handler
+}
+
 pub fn new_tcp(
     conf: TcpConfiguration, handler: IoHandler,
 ) -> Result<Option<TcpServer>, String> {
@@ -138,3 +181,23 @@ pub fn new_http(
         )),
     }
 }
+
+pub fn new_ws(
+    conf: WsConfiguration, handler: PubSubHandler<Arc<Session>>,
+) -> Result<Option<WsServer>, String> {
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    match WsServerBuilder::with_meta_extractor(
+        handler,
+        |context: &WsRequestContext| Arc::new(Session::new(context.sender())),
+    )
+    .start(&conf.address)
+    {
+        Ok(server) => Ok(Some(server)),
+        Err(ws_error) => {
+            Err(format!("WS error: {} (addr = {})", ws_error, conf.address))
+        }
+    }
+}
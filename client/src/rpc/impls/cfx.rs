@@ -5,28 +5,57 @@
 use crate::rpc::{
     traits::cfx::{Cfx, DebugRpc, TestRpc},
     types::{
-        Block as RpcBlock, Bytes, EpochNumber, Receipt as RpcReceipt, Receipt,
-        Status as RpcStatus, Transaction as RpcTransaction, H160 as RpcH160,
-        H256 as RpcH256, U256 as RpcU256, U64 as RpcU64,
+        Account as RpcAccount, AccountProof, Block as RpcBlock, Bytes,
+        EpochNumber, FeeHistory as RpcFeeHistory, Filter as RpcFilter,
+        Log as RpcLog, Receipt as RpcReceipt, Status as RpcStatus,
+        Transaction as RpcTransaction, TransactionTrace as RpcTransactionTrace,
+        H160 as RpcH160, H256 as RpcH256, U256 as RpcU256, U64 as RpcU64,
     },
 };
 use blockgen::BlockGenerator;
-use cfx_types::{H160, H256};
+use cfx_types::{H160, H256, U256, U64};
 use cfxcore::{
-    storage::StorageManager, PeerInfo, SharedConsensusGraph,
-    SharedSynchronizationService, SharedTransactionPool,
+    storage::StorageManager, BannedPeer, PeerInfo, PeerSettings,
+    SharedConsensusGraph, SharedSynchronizationService, SharedTransactionPool,
 };
 use jsonrpc_core::{Error as RpcError, Result as RpcResult};
+use keccak_hash::KECCAK_EMPTY;
 use jsonrpc_macros::Trailing;
 use network::node_table::{NodeEndpoint, NodeEntry, NodeId};
 use parking_lot::{Condvar, Mutex};
 use primitives::{
-    block::MAX_BLOCK_SIZE_IN_BYTES, Action,
-    EpochNumber as PrimitiveEpochNumber, SignedTransaction, Transaction,
+    block::MAX_BLOCK_SIZE_IN_BYTES, filter::Filter as PrimitiveFilter, Account,
+    Action, EpochNumber as PrimitiveEpochNumber, SignedTransaction, Transaction,
     TransactionWithSignature,
 };
 use rlp::Rlp;
-use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Filters that are not polled within this duration are removed.
+const FILTER_LIFETIME: Duration = Duration::from_secs(5 * 60);
+/// The maximum number of installed filters. Filters nobody polls count
+/// against it until they expire.
+const MAX_FILTERS: usize = 1000;
+/// The maximum number of epochs scanned by a `cfx_getLogs` call or a
+/// `cfx_getFilterChanges` poll.
+const MAX_LOG_EPOCHS: usize = 1000;
+
+/// A log filter installed by `cfx_newFilter`.
+struct PollFilter {
+    filter: RpcFilter,
+    /// The first epoch which hasn't been returned by previous polls.
+    next_epoch: usize,
+    last_poll: Instant,
+}
 
 pub struct RpcImpl {
     pub consensus: SharedConsensusGraph,
@@ -36,6 +65,8 @@ pub struct RpcImpl {
     block_gen: Arc<BlockGenerator>,
     tx_pool: SharedTransactionPool,
     exit: Arc<(Mutex<bool>, Condvar)>,
+    filters: Mutex<HashMap<usize, PollFilter>>,
+    next_filter_id: AtomicUsize,
 }
 
 impl RpcImpl {
@@ -52,6 +83,8 @@ impl RpcImpl {
             block_gen,
             tx_pool,
             exit,
+            filters: Mutex::new(HashMap::new()),
+            next_filter_id: AtomicUsize::new(0),
         }
     }
 
@@ -66,8 +99,32 @@ impl RpcImpl {
         }
     }
 
+    /// The account in the state of an epoch, fetched from full nodes by a
+    /// light node and verified against the state root in block headers.
+    fn light_account(
+        &self, address: H160, num: EpochNumber,
+    ) -> RpcResult<Option<Account>> {
+        let proof = self
+            .sync
+            .light_account_proof(
+                address,
+                Vec::new(),
+                self.get_primitive_epoch_number(num),
+            )
+            .map_err(|err| RpcError::invalid_params(err))?;
+        match proof.account {
+            Some(raw) => Account::new_from_rlp(&address, &raw, &KECCAK_EMPTY)
+                .map(Some)
+                .map_err(|err| RpcError::invalid_params(format!("{:?}", err))),
+            None => Ok(None),
+        }
+    }
+
     fn best_block_hash(&self) -> RpcResult<RpcH256> {
         info!("RPC Request: cfx_getBestBlockHash()");
+        if let Some(light_handler) = self.sync.light_handler() {
+            return Ok(light_handler.best_block_hash().into());
+        }
         Ok(self.consensus.best_block_hash().into())
     }
 
@@ -76,11 +133,61 @@ impl RpcImpl {
         Ok(self.consensus.gas_price().unwrap_or(0.into()).into())
     }
 
+    fn fee_history(
+        &self, epoch_count: RpcU64, percentiles: Vec<f64>,
+    ) -> RpcResult<RpcFeeHistory> {
+        let epoch_count = epoch_count.as_usize();
+        info!(
+            "RPC Request: cfx_feeHistory({:?}, {:?})",
+            epoch_count, percentiles
+        );
+        self.consensus
+            .fee_history(epoch_count, &percentiles)
+            .map(Into::into)
+            .map_err(|e| RpcError::invalid_params(e))
+    }
+
+    fn hashrate(&self) -> RpcResult<RpcU256> {
+        Ok(self.block_gen.hashrate().into())
+    }
+
+    fn is_mining(&self) -> RpcResult<bool> { Ok(self.block_gen.is_mining()) }
+
+    fn get_work(&self) -> RpcResult<Vec<RpcH256>> {
+        match self.block_gen.get_work() {
+            Some(problem) => {
+                Ok(vec![problem.block_hash.into(), problem.boundary.into()])
+            }
+            None => Err(RpcError::invalid_params("The node is not mining")),
+        }
+    }
+
+    fn submit_work(&self, pow_hash: RpcH256, nonce: RpcU64) -> RpcResult<bool> {
+        let pow_hash: H256 = pow_hash.into();
+        let nonce: U64 = nonce.into();
+        info!("RPC Request: cfx_submitWork({:?}, {:?})", pow_hash, nonce);
+        match self.block_gen.submit_work(pow_hash, nonce.low_u64()) {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                debug!("Work rejected: {}", e);
+                Ok(false)
+            }
+        }
+    }
+
     fn epoch_number(
         &self, epoch_num: Trailing<EpochNumber>,
     ) -> RpcResult<RpcU256> {
         let epoch_num = epoch_num.unwrap_or(EpochNumber::LatestMined);
         info!("RPC Request: cfx_epochNumber({:?})", epoch_num);
+        if let Some(light_handler) = self.sync.light_handler() {
+            return light_handler
+                .get_height_from_epoch_number(
+                    self.get_primitive_epoch_number(epoch_num),
+                )
+                .map(|height| height.into())
+                .map_err(|err| RpcError::invalid_params(err));
+        }
         match self.consensus.get_height_from_epoch_number(
             self.get_primitive_epoch_number(epoch_num),
         ) {
@@ -117,6 +224,16 @@ impl RpcImpl {
             "RPC Request: cfx_getBlockByHash hash={:?} include_txs={:?}",
             hash, include_txs
         );
+        if self.sync.is_light_node() {
+            let block = self
+                .sync
+                .light_block_by_hash(&hash)
+                .map_err(|err| RpcError::invalid_params(err))?;
+            let inner = &mut *self.consensus.inner.write();
+            return Ok(
+                block.map(|block| RpcBlock::new(&block, inner, include_txs))
+            );
+        }
         let inner = &mut *self.consensus.inner.write();
 
         if let Some(block) = self.consensus.data_man.block_by_hash(&hash, false)
@@ -189,12 +306,12 @@ impl RpcImpl {
             return Ok(Some(RpcTransaction::from_signed(&transaction, None)));
         }
 
-        if let Some((transaction, receipt, tx_address)) =
+        if let Some((transaction, receipt)) =
             self.consensus.get_transaction_info_by_hash(&hash)
         {
             Ok(Some(RpcTransaction::from_signed(
                 &transaction,
-                Some(Receipt::new(transaction.clone(), receipt, tx_address)),
+                Some(RpcReceipt::from(receipt)),
             )))
         } else {
             Ok(None)
@@ -221,6 +338,11 @@ impl RpcImpl {
             "RPC Request: cfx_getBalance address={:?} epoch_num={:?}",
             address, num
         );
+        if self.sync.is_light_node() {
+            return self.light_account(address, num).map(|account| {
+                account.map_or(0.into(), |account| account.balance).into()
+            });
+        }
 
         self.consensus
             .get_balance(address, self.get_primitive_epoch_number(num))
@@ -228,38 +350,265 @@ impl RpcImpl {
             .map_err(|err| RpcError::invalid_params(err))
     }
 
-    //    fn account(
-    //        &self, address: RpcH160, include_txs: bool, num_txs: RpcU64,
-    //        epoch_num: Trailing<EpochNumber>,
-    //    ) -> RpcResult<Account>
-    //    {
-    //        let inner = &mut *self.consensus.inner.write();
-    //
-    //        let address: H160 = address.into();
-    //        let num_txs = num_txs.as_usize();
-    //        let epoch_num = epoch_num.unwrap_or(EpochNumber::LatestState);
-    //        info!(
-    //            "RPC Request: cfx_getAccount address={:?} include_txs={:?}
-    // num_txs={:?} epoch_num={:?}",            address, include_txs,
-    // num_txs, epoch_num        );
-    //        self.consensus
-    //            .get_account(
-    //                address,
-    //                num_txs,
-    //                self.get_primitive_epoch_number(epoch_num),
-    //            )
-    //            .and_then(|(balance, transactions)| {
-    //                Ok(Account {
-    //                    balance: balance.into(),
-    //                    transactions: BlockTransactions::new(
-    //                        &transactions,
-    //                        include_txs,
-    //                        inner,
-    //                    ),
-    //                })
-    //            })
-    //            .map_err(|err| RpcError::invalid_params(err))
-    //    }
+    fn proof(
+        &self, address: RpcH160, storage_keys: Vec<RpcH256>,
+        num: Trailing<EpochNumber>,
+    ) -> RpcResult<AccountProof>
+    {
+        let num = num.unwrap_or(EpochNumber::LatestState);
+        let address: H160 = address.into();
+        let storage_keys: Vec<H256> =
+            storage_keys.into_iter().map(|key| key.into()).collect();
+        info!(
+            "RPC Request: cfx_getProof address={:?} storage_keys={:?} \
+             epoch_num={:?}",
+            address, storage_keys, num
+        );
+        if self.sync.is_light_node() {
+            return self
+                .sync
+                .light_account_proof(
+                    address,
+                    storage_keys,
+                    self.get_primitive_epoch_number(num),
+                )
+                .map(|proof| AccountProof::new(address, proof))
+                .map_err(|err| RpcError::invalid_params(err));
+        }
+
+        self.consensus
+            .get_account_proof(
+                address,
+                &storage_keys,
+                self.get_primitive_epoch_number(num),
+            )
+            .map(|proof| AccountProof::new(address, proof))
+            .map_err(|err| RpcError::invalid_params(err))
+    }
+
+    /// Resolve an epoch of a log filter to its height. Unlike
+    /// `get_height_from_epoch_number`, epoch numbers in the future are
+    /// allowed.
+    fn filter_epoch_height(
+        &self, epoch: Option<EpochNumber>, default: usize,
+    ) -> RpcResult<usize> {
+        match epoch {
+            None => Ok(default),
+            Some(EpochNumber::Num(num)) => Ok(num as usize),
+            Some(epoch) => self
+                .consensus
+                .get_height_from_epoch_number(
+                    self.get_primitive_epoch_number(epoch),
+                )
+                .map_err(|err| RpcError::invalid_params(err)),
+        }
+    }
+
+    fn latest_state_epoch_height(&self) -> RpcResult<usize> {
+        self.consensus
+            .get_height_from_epoch_number(PrimitiveEpochNumber::LatestState)
+            .map_err(|err| RpcError::invalid_params(err))
+    }
+
+    fn logs(&self, filter: PrimitiveFilter) -> RpcResult<Vec<RpcLog>> {
+        self.consensus
+            .logs(filter)
+            .map(|logs| logs.into_iter().map(RpcLog::from).collect())
+            .map_err(|err| RpcError::invalid_params(format!("{}", err)))
+    }
+
+    fn get_logs(&self, filter: RpcFilter) -> RpcResult<Vec<RpcLog>> {
+        info!("RPC Request: cfx_getLogs filter={:?}", filter);
+        let latest_state = self.latest_state_epoch_height()?;
+        let from_epoch =
+            self.filter_epoch_height(filter.from_epoch.clone(), latest_state)?;
+        // Logs are only available up to the latest executed epoch.
+        let to_epoch = min(
+            latest_state,
+            self.filter_epoch_height(filter.to_epoch.clone(), latest_state)?,
+        );
+        if filter.block_hashes.is_none() {
+            if from_epoch > to_epoch {
+                return Err(RpcError::invalid_params(format!(
+                    "Invalid epoch range: fromEpoch {} is larger than \
+                     toEpoch {}",
+                    from_epoch, to_epoch
+                )));
+            }
+            if to_epoch - from_epoch >= MAX_LOG_EPOCHS {
+                return Err(RpcError::invalid_params(format!(
+                    "Invalid epoch range: at most {} epochs can be queried",
+                    MAX_LOG_EPOCHS
+                )));
+            }
+        }
+
+        let filter = filter
+            .into_primitive(from_epoch, Self::epoch_range_end(to_epoch)?)
+            .map_err(|err| RpcError::invalid_params(err))?;
+        self.logs(filter)
+    }
+
+    /// The exclusive end of an epoch range whose last epoch is `to_epoch`.
+    fn epoch_range_end(to_epoch: usize) -> RpcResult<usize> {
+        to_epoch.checked_add(1).ok_or_else(|| {
+            RpcError::invalid_params("toEpoch is too large".to_owned())
+        })
+    }
+
+    fn remove_expired_filters(filters: &mut HashMap<usize, PollFilter>) {
+        filters.retain(|_, poll| poll.last_poll.elapsed() < FILTER_LIFETIME);
+    }
+
+    /// The id of an installed filter, or None if `id` is too large to be one.
+    fn filter_id(id: RpcU256) -> Option<usize> {
+        let id: U256 = id.into();
+        if id > U256::from(usize::max_value()) {
+            None
+        } else {
+            Some(id.as_usize())
+        }
+    }
+
+    fn new_filter(&self, filter: RpcFilter) -> RpcResult<RpcU256> {
+        info!("RPC Request: cfx_newFilter filter={:?}", filter);
+        if filter.block_hashes.is_some() {
+            return Err(RpcError::invalid_params(
+                "blockHashes is not supported by filters".to_owned(),
+            ));
+        }
+        // Reject malformed filters at installation rather than at polls.
+        filter
+            .clone()
+            .into_primitive(0, 1)
+            .map_err(|err| RpcError::invalid_params(err))?;
+
+        // Only logs in epochs executed after installation are returned.
+        let next_epoch = self.latest_state_epoch_height()? + 1;
+        let mut filters = self.filters.lock();
+        Self::remove_expired_filters(&mut *filters);
+        if filters.len() >= MAX_FILTERS {
+            return Err(RpcError::invalid_params(
+                "Too many installed filters".to_owned(),
+            ));
+        }
+        let id = self.next_filter_id.fetch_add(1, Ordering::Relaxed);
+        filters.insert(
+            id,
+            PollFilter {
+                filter,
+                next_epoch,
+                last_poll: Instant::now(),
+            },
+        );
+        Ok(id.into())
+    }
+
+    fn filter_changes(&self, id: RpcU256) -> RpcResult<Vec<RpcLog>> {
+        info!("RPC Request: cfx_getFilterChanges id={:?}", id);
+        let filter_not_found =
+            || RpcError::invalid_params("Filter not found".to_owned());
+        let id = Self::filter_id(id).ok_or_else(filter_not_found)?;
+        let latest_state = self.latest_state_epoch_height()?;
+
+        let (filter, from_epoch, to_epoch) = {
+            let mut filters = self.filters.lock();
+            Self::remove_expired_filters(&mut *filters);
+            let poll = filters.get_mut(&id).ok_or_else(filter_not_found)?;
+            poll.last_poll = Instant::now();
+            let from_epoch = max(
+                poll.next_epoch,
+                self.filter_epoch_height(poll.filter.from_epoch.clone(), 0)?,
+            );
+            let to_epoch = min(
+                latest_state,
+                self.filter_epoch_height(
+                    poll.filter.to_epoch.clone(),
+                    latest_state,
+                )?,
+            );
+            // Epochs beyond the limit are left for the next polls.
+            let to_epoch =
+                min(to_epoch, from_epoch.saturating_add(MAX_LOG_EPOCHS - 1));
+            poll.next_epoch =
+                max(poll.next_epoch, Self::epoch_range_end(to_epoch)?);
+            (poll.filter.clone(), from_epoch, to_epoch)
+        };
+        if from_epoch > to_epoch {
+            return Ok(Vec::new());
+        }
+
+        let filter = filter
+            .into_primitive(from_epoch, Self::epoch_range_end(to_epoch)?)
+            .map_err(|err| RpcError::invalid_params(err))?;
+        self.logs(filter)
+    }
+
+    fn uninstall_filter(&self, id: RpcU256) -> RpcResult<bool> {
+        info!("RPC Request: cfx_uninstallFilter id={:?}", id);
+        let id = match Self::filter_id(id) {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        let mut filters = self.filters.lock();
+        Self::remove_expired_filters(&mut *filters);
+        Ok(filters.remove(&id).is_some())
+    }
+
+    fn code(
+        &self, address: RpcH160, num: Trailing<EpochNumber>,
+    ) -> RpcResult<Bytes> {
+        let num = num.unwrap_or(EpochNumber::LatestState);
+        let address: H160 = address.into();
+        info!(
+            "RPC Request: cfx_getCode address={:?} epoch_num={:?}",
+            address, num
+        );
+
+        self.consensus
+            .get_code(address, self.get_primitive_epoch_number(num))
+            .map(Bytes::new)
+            .map_err(|err| RpcError::invalid_params(err))
+    }
+
+    fn storage_at(
+        &self, address: RpcH160, key: RpcH256, num: Trailing<EpochNumber>,
+    ) -> RpcResult<RpcH256> {
+        let num = num.unwrap_or(EpochNumber::LatestState);
+        let address: H160 = address.into();
+        let key: H256 = key.into();
+        info!(
+            "RPC Request: cfx_getStorageAt address={:?} key={:?} \
+             epoch_num={:?}",
+            address, key, num
+        );
+
+        self.consensus
+            .get_storage_at(address, key, self.get_primitive_epoch_number(num))
+            .map(|value| value.into())
+            .map_err(|err| RpcError::invalid_params(err))
+    }
+
+    fn account(
+        &self, address: RpcH160, num: Trailing<EpochNumber>,
+    ) -> RpcResult<Option<RpcAccount>> {
+        let num = num.unwrap_or(EpochNumber::LatestState);
+        let address: H160 = address.into();
+        info!(
+            "RPC Request: cfx_getAccount address={:?} epoch_num={:?}",
+            address, num
+        );
+        if self.sync.is_light_node() {
+            return self
+                .light_account(address, num)
+                .map(|account| account.map(RpcAccount::from));
+        }
+
+        self.consensus
+            .get_account(address, self.get_primitive_epoch_number(num))
+            .map(|account| account.map(RpcAccount::from))
+            .map_err(|err| RpcError::invalid_params(err))
+    }
 
     fn transaction_count(
         &self, address: RpcH160, num: Trailing<EpochNumber>,
@@ -269,6 +618,11 @@ impl RpcImpl {
             "RPC Request: cfx_getTransactionCount address={:?} epoch_num={:?}",
             address, num
         );
+        if self.sync.is_light_node() {
+            return self.light_account(address.into(), num).map(|account| {
+                account.map_or(0.into(), |account| account.nonce).into()
+            });
+        }
 
         self.consensus
             .transaction_count(
@@ -287,7 +641,7 @@ impl RpcImpl {
                 RpcError::invalid_params(format!("Error: {:?}", err))
             })
             .and_then(|tx| {
-                let result = self.tx_pool.insert_new_transactions(
+                let result = self.tx_pool.insert_new_local_transactions(
                     self.consensus.best_state_block_hash(),
                     &vec![tx],
                 );
@@ -466,6 +820,20 @@ impl RpcImpl {
         Ok(self.sync.get_peer_info())
     }
 
+    fn list_banned(&self) -> RpcResult<Vec<BannedPeer>> {
+        info!("RPC Request: list_banned");
+        self.sync
+            .banned_peers()
+            .map_err(|_| RpcError::internal_error())
+    }
+
+    fn clear_banned(&self) -> RpcResult<()> {
+        info!("RPC Request: clear_banned");
+        self.sync
+            .clear_bans()
+            .map_err(|_| RpcError::internal_error())
+    }
+
     fn stop(&self) -> RpcResult<()> {
         *self.exit.0.lock() = true;
         self.exit.1.notify_all();
@@ -505,17 +873,15 @@ impl RpcImpl {
         }
     }
 
-    /// The first element is true if the tx is executed in a confirmed block.
-    /// The second element indicate the execution result (standin
-    /// for receipt)
     fn get_transaction_receipt(
-        &self, tx_hash: H256,
+        &self, tx_hash: RpcH256,
     ) -> RpcResult<Option<RpcReceipt>> {
-        let maybe_receipt =
-            self.consensus.get_transaction_info_by_hash(&tx_hash).map(
-                |(tx, receipt, address)| RpcReceipt::new(tx, receipt, address),
-            );
-        Ok(maybe_receipt)
+        let hash: H256 = tx_hash.into();
+        info!("RPC Request: cfx_getTransactionReceipt({:?})", hash);
+        Ok(self
+            .consensus
+            .get_transaction_info_by_hash(&hash)
+            .map(|(_, receipt)| RpcReceipt::from(receipt)))
     }
 
     fn call(
@@ -534,6 +900,7 @@ impl RpcImpl {
                 None => Action::Create,
             },
             data: rpc_tx.data.into(),
+            chain_id: rpc_tx.chain_id.as_u64(),
         };
         debug!("RPC Request: cfx_call");
         let mut signed_tx = SignedTransaction::new_unsigned(
@@ -558,6 +925,7 @@ impl RpcImpl {
                 None => Action::Create,
             },
             data: rpc_tx.data.into(),
+            chain_id: rpc_tx.chain_id.as_u64(),
         };
         let mut signed_tx = SignedTransaction::new_unsigned(
             TransactionWithSignature::new_unsigned(tx),
@@ -633,6 +1001,107 @@ impl RpcImpl {
 
         Ok(ret)
     }
+
+    fn txpool_remove_transaction(&self, tx_hash: RpcH256) -> RpcResult<bool> {
+        let tx_hash: H256 = tx_hash.into();
+        info!("RPC Request: txpool_removeTransaction({:?})", tx_hash);
+        Ok(self.tx_pool.remove_transaction(&tx_hash).is_some())
+    }
+
+    fn txpool_clear_sender(&self, address: RpcH160) -> RpcResult<usize> {
+        let address: H160 = address.into();
+        info!("RPC Request: txpool_clearSender({:?})", address);
+        Ok(self.tx_pool.remove_sender_transactions(&address))
+    }
+
+    fn txpool_add_local_sender(&self, address: RpcH160) -> RpcResult<bool> {
+        let address: H160 = address.into();
+        info!("RPC Request: txpool_addLocalSender({:?})", address);
+        Ok(self.tx_pool.add_local_sender(address))
+    }
+
+    fn txpool_remove_local_sender(&self, address: RpcH160) -> RpcResult<bool> {
+        let address: H160 = address.into();
+        info!("RPC Request: txpool_removeLocalSender({:?})", address);
+        Ok(self.tx_pool.remove_local_sender(&address))
+    }
+
+    fn txpool_local_senders(&self) -> RpcResult<Vec<RpcH160>> {
+        Ok(self
+            .tx_pool
+            .local_senders()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn trace_block(
+        &self, block_hash: RpcH256,
+    ) -> RpcResult<Vec<RpcTransactionTrace>> {
+        let block_hash: H256 = block_hash.into();
+        info!("RPC Request: trace_block({:?})", block_hash);
+        self.consensus
+            .trace_block(&block_hash, None, false)
+            .map(|traces| traces.into_iter().map(Into::into).collect())
+            .map_err(|e| RpcError::invalid_params(e))
+    }
+
+    fn trace_transaction(
+        &self, tx_hash: RpcH256,
+    ) -> RpcResult<Option<RpcTransactionTrace>> {
+        let tx_hash: H256 = tx_hash.into();
+        info!("RPC Request: trace_transaction({:?})", tx_hash);
+        self.consensus
+            .trace_transaction(&tx_hash)
+            .map(|trace| trace.map(Into::into))
+            .map_err(|e| RpcError::invalid_params(e))
+    }
+
+    fn admin_add_reserved_peer(&self, url: String) -> RpcResult<()> {
+        info!("RPC Request: admin_addReservedPeer({})", url);
+        self.sync
+            .add_reserved_peer(&url)
+            .map_err(|e| RpcError::invalid_params(format!("{}", e)))
+    }
+
+    fn admin_remove_reserved_peer(&self, id: NodeId) -> RpcResult<bool> {
+        info!("RPC Request: admin_removeReservedPeer({:?})", id);
+        self.sync
+            .remove_reserved_peer(&id)
+            .map_err(|_| RpcError::internal_error())
+    }
+
+    fn admin_set_ip_filter(&self, filter: String) -> RpcResult<()> {
+        info!("RPC Request: admin_setIpFilter({})", filter);
+        self.sync
+            .set_ip_filter(&filter)
+            .map_err(|e| RpcError::invalid_params(format!("{}", e)))
+    }
+
+    fn admin_set_max_peers(
+        &self, outgoing: u32, incoming: u32,
+    ) -> RpcResult<()> {
+        info!(
+            "RPC Request: admin_setMaxPeers(outgoing={}, incoming={})",
+            outgoing, incoming
+        );
+        self.sync
+            .set_max_peers(outgoing, incoming)
+            .map_err(|_| RpcError::internal_error())
+    }
+
+    fn admin_set_reserved_only(&self, reserved_only: bool) -> RpcResult<()> {
+        info!("RPC Request: admin_setReservedOnly({})", reserved_only);
+        self.sync
+            .set_reserved_only(reserved_only)
+            .map_err(|_| RpcError::internal_error())
+    }
+
+    fn admin_peer_settings(&self) -> RpcResult<PeerSettings> {
+        self.sync
+            .peer_settings()
+            .map_err(|_| RpcError::internal_error())
+    }
 }
 
 fn grouped_txs<T, F>(
@@ -676,6 +1145,22 @@ impl Cfx for CfxHandler {
 
     fn gas_price(&self) -> RpcResult<RpcU256> { self.rpc_impl.gas_price() }
 
+    fn hashrate(&self) -> RpcResult<RpcU256> { self.rpc_impl.hashrate() }
+
+    fn is_mining(&self) -> RpcResult<bool> { self.rpc_impl.is_mining() }
+
+    fn get_work(&self) -> RpcResult<Vec<RpcH256>> { self.rpc_impl.get_work() }
+
+    fn submit_work(&self, pow_hash: RpcH256, nonce: RpcU64) -> RpcResult<bool> {
+        self.rpc_impl.submit_work(pow_hash, nonce)
+    }
+
+    fn fee_history(
+        &self, epoch_count: RpcU64, percentiles: Vec<f64>,
+    ) -> RpcResult<RpcFeeHistory> {
+        self.rpc_impl.fee_history(epoch_count, percentiles)
+    }
+
     fn epoch_number(
         &self, epoch_num: Trailing<EpochNumber>,
     ) -> RpcResult<RpcU256> {
@@ -710,24 +1195,63 @@ impl Cfx for CfxHandler {
         self.rpc_impl.transaction_by_hash(hash)
     }
 
+    fn transaction_receipt(
+        &self, tx_hash: RpcH256,
+    ) -> RpcResult<Option<RpcReceipt>> {
+        self.rpc_impl.get_transaction_receipt(tx_hash)
+    }
+
     fn blocks_by_epoch(&self, num: EpochNumber) -> RpcResult<Vec<RpcH256>> {
         self.rpc_impl.blocks_by_epoch(num)
     }
 
+    fn get_logs(&self, filter: RpcFilter) -> RpcResult<Vec<RpcLog>> {
+        self.rpc_impl.get_logs(filter)
+    }
+
+    fn new_filter(&self, filter: RpcFilter) -> RpcResult<RpcU256> {
+        self.rpc_impl.new_filter(filter)
+    }
+
+    fn filter_changes(&self, id: RpcU256) -> RpcResult<Vec<RpcLog>> {
+        self.rpc_impl.filter_changes(id)
+    }
+
+    fn uninstall_filter(&self, id: RpcU256) -> RpcResult<bool> {
+        self.rpc_impl.uninstall_filter(id)
+    }
+
     fn balance(
         &self, address: RpcH160, num: Trailing<EpochNumber>,
     ) -> RpcResult<RpcU256> {
         self.rpc_impl.balance(address, num)
     }
 
-    //    fn account(
-    //        &self, address: RpcH160, include_txs: bool, num_txs: RpcU64,
-    //        epoch_num: Trailing<EpochNumber>,
-    //    ) -> RpcResult<Account>
-    //    {
-    //        self.rpc_impl
-    //            .account(address, include_txs, num_txs, epoch_num)
-    //    }
+    fn proof(
+        &self, address: RpcH160, storage_keys: Vec<RpcH256>,
+        num: Trailing<EpochNumber>,
+    ) -> RpcResult<AccountProof>
+    {
+        self.rpc_impl.proof(address, storage_keys, num)
+    }
+
+    fn code(
+        &self, address: RpcH160, num: Trailing<EpochNumber>,
+    ) -> RpcResult<Bytes> {
+        self.rpc_impl.code(address, num)
+    }
+
+    fn storage_at(
+        &self, address: RpcH160, key: RpcH256, num: Trailing<EpochNumber>,
+    ) -> RpcResult<RpcH256> {
+        self.rpc_impl.storage_at(address, key, num)
+    }
+
+    fn account(
+        &self, address: RpcH160, num: Trailing<EpochNumber>,
+    ) -> RpcResult<Option<RpcAccount>> {
+        self.rpc_impl.account(address, num)
+    }
 
     fn transaction_count(
         &self, address: RpcH160, num: Trailing<EpochNumber>,
@@ -825,6 +1349,12 @@ impl TestRpc for TestRpcImpl {
         self.rpc_impl.get_peer_info()
     }
 
+    fn list_banned(&self) -> RpcResult<Vec<BannedPeer>> {
+        self.rpc_impl.list_banned()
+    }
+
+    fn clear_banned(&self) -> RpcResult<()> { self.rpc_impl.clear_banned() }
+
     fn stop(&self) -> RpcResult<()> { self.rpc_impl.stop() }
 
     fn get_nodeid(&self, challenge: Vec<u8>) -> RpcResult<Vec<u8>> {
@@ -837,11 +1367,8 @@ impl TestRpc for TestRpcImpl {
         self.rpc_impl.add_latency(id, latency_ms)
     }
 
-    /// The first element is true if the tx is executed in a confirmed block.
-    /// The second element indicate the execution result (standin
-    /// for receipt)
     fn get_transaction_receipt(
-        &self, tx_hash: H256,
+        &self, tx_hash: RpcH256,
     ) -> RpcResult<Option<RpcReceipt>> {
         self.rpc_impl.get_transaction_receipt(tx_hash)
     }
@@ -878,4 +1405,62 @@ impl DebugRpc for DebugRpcImpl {
     > {
         self.rpc_impl.txpool_content()
     }
+
+    fn txpool_remove_transaction(&self, tx_hash: RpcH256) -> RpcResult<bool> {
+        self.rpc_impl.txpool_remove_transaction(tx_hash)
+    }
+
+    fn txpool_clear_sender(&self, address: RpcH160) -> RpcResult<usize> {
+        self.rpc_impl.txpool_clear_sender(address)
+    }
+
+    fn txpool_add_local_sender(&self, address: RpcH160) -> RpcResult<bool> {
+        self.rpc_impl.txpool_add_local_sender(address)
+    }
+
+    fn txpool_remove_local_sender(&self, address: RpcH160) -> RpcResult<bool> {
+        self.rpc_impl.txpool_remove_local_sender(address)
+    }
+
+    fn txpool_local_senders(&self) -> RpcResult<Vec<RpcH160>> {
+        self.rpc_impl.txpool_local_senders()
+    }
+
+    fn trace_block(
+        &self, block_hash: RpcH256,
+    ) -> RpcResult<Vec<RpcTransactionTrace>> {
+        self.rpc_impl.trace_block(block_hash)
+    }
+
+    fn trace_transaction(
+        &self, tx_hash: RpcH256,
+    ) -> RpcResult<Option<RpcTransactionTrace>> {
+        self.rpc_impl.trace_transaction(tx_hash)
+    }
+
+    fn admin_add_reserved_peer(&self, url: String) -> RpcResult<()> {
+        self.rpc_impl.admin_add_reserved_peer(url)
+    }
+
+    fn admin_remove_reserved_peer(&self, id: NodeId) -> RpcResult<bool> {
+        self.rpc_impl.admin_remove_reserved_peer(id)
+    }
+
+    fn admin_set_ip_filter(&self, filter: String) -> RpcResult<()> {
+        self.rpc_impl.admin_set_ip_filter(filter)
+    }
+
+    fn admin_set_max_peers(
+        &self, outgoing: u32, incoming: u32,
+    ) -> RpcResult<()> {
+        self.rpc_impl.admin_set_max_peers(outgoing, incoming)
+    }
+
+    fn admin_set_reserved_only(&self, reserved_only: bool) -> RpcResult<()> {
+        self.rpc_impl.admin_set_reserved_only(reserved_only)
+    }
+
+    fn admin_peer_settings(&self) -> RpcResult<PeerSettings> {
+        self.rpc_impl.admin_peer_settings()
+    }
 }
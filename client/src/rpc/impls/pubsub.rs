@@ -0,0 +1,298 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::{
+    traits::PubSub,
+    types::{
+        pubsub::{self, Epochs},
+        Filter as RpcFilter, Header as RpcHeader, Log as RpcLog,
+    },
+};
+use cfx_types::H256;
+use cfxcore::{ConsensusGraph, Notification, SharedNotifications};
+use jsonrpc_core::{futures::Future, Error as RpcError, Result as RpcResult};
+use jsonrpc_macros::{
+    pubsub::{Sink, Subscriber},
+    Trailing,
+};
+use jsonrpc_pubsub::{Session, SubscriptionId};
+use parking_lot::Mutex;
+use primitives::{filter::Filter as PrimitiveFilter, EpochNumber};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    thread,
+};
+
+type ResultSink = Sink<pubsub::Result>;
+type Sinks = Vec<(SubscriptionId, ResultSink)>;
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    heads: HashMap<SubscriptionId, ResultSink>,
+    epochs: HashMap<SubscriptionId, ResultSink>,
+    logs: HashMap<SubscriptionId, (ResultSink, PrimitiveFilter)>,
+    transactions: HashMap<SubscriptionId, ResultSink>,
+}
+
+impl Subscribers {
+    fn new_id(&mut self) -> SubscriptionId {
+        let id = self.next_id;
+        self.next_id += 1;
+        SubscriptionId::String(format!("0x{:x}", id))
+    }
+
+    fn remove(&mut self, id: &SubscriptionId) -> bool {
+        self.heads.remove(id).is_some()
+            || self.epochs.remove(id).is_some()
+            || self.logs.remove(id).is_some()
+            || self.transactions.remove(id).is_some()
+    }
+}
+
+/// Serves `cfx_subscribe` with the notifications from the consensus graph and
+/// the transaction pool.
+pub struct PubSubClient {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl PubSubClient {
+    /// Start a thread to push notifications to subscribers. The thread stops
+    /// when the consensus graph and the transaction pool are dropped.
+    pub fn new(
+        consensus: Weak<ConsensusGraph>, notifications: SharedNotifications,
+    ) -> Self {
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let handler = NotificationHandler {
+            consensus,
+            subscribers: subscribers.clone(),
+            next_log_epoch: None,
+        };
+        let receiver = notifications.subscribe();
+        thread::Builder::new()
+            .name("PubSub Notifier".into())
+            .spawn(move || {
+                let mut handler = handler;
+                for notification in receiver.iter() {
+                    handler.handle(notification);
+                }
+            })
+            .expect("PubSub thread spawn error");
+
+        PubSubClient { subscribers }
+    }
+}
+
+impl PubSub for PubSubClient {
+    type Metadata = Arc<Session>;
+
+    fn subscribe(
+        &self, meta: Self::Metadata, subscriber: Subscriber<pubsub::Result>,
+        kind: pubsub::Kind, params: Trailing<pubsub::Params>,
+    )
+    {
+        let params = params.unwrap_or_default();
+        info!(
+            "RPC Request: cfx_subscribe kind={:?} params={:?}",
+            kind, params
+        );
+        let logs_filter = match (&kind, params) {
+            (pubsub::Kind::Logs, pubsub::Params::Logs(filter)) => Some(filter),
+            (pubsub::Kind::Logs, pubsub::Params::None) => {
+                Some(RpcFilter::default())
+            }
+            (_, pubsub::Params::None) => None,
+            _ => {
+                let error = RpcError::invalid_params(
+                    "Invalid parameters: only logs subscription accepts a \
+                     filter",
+                );
+                subscriber.reject(error).ok();
+                return;
+            }
+        };
+        // The epoch range of the filter is ignored because only logs of new
+        // epochs are pushed.
+        let logs_filter = match logs_filter {
+            Some(filter) => match filter.into_primitive(0, 1) {
+                Ok(filter) => Some(filter),
+                Err(e) => {
+                    subscriber.reject(RpcError::invalid_params(e)).ok();
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        let mut subscribers = self.subscribers.lock();
+        let id = subscribers.new_id();
+        if let Ok(sink) = subscriber.assign_id(id.clone()) {
+            // Drop the subscription when the client disconnects without
+            // unsubscribing.
+            let weak_subscribers = Arc::downgrade(&self.subscribers);
+            let session_id = id.clone();
+            meta.on_drop(move || {
+                if let Some(subscribers) = weak_subscribers.upgrade() {
+                    subscribers.lock().remove(&session_id);
+                }
+            });
+            match kind {
+                pubsub::Kind::NewHeads => {
+                    subscribers.heads.insert(id, sink);
+                }
+                pubsub::Kind::Epochs => {
+                    subscribers.epochs.insert(id, sink);
+                }
+                pubsub::Kind::Logs => {
+                    let filter = logs_filter.expect("checked above");
+                    subscribers.logs.insert(id, (sink, filter));
+                }
+                pubsub::Kind::NewPendingTransactions => {
+                    subscribers.transactions.insert(id, sink);
+                }
+            }
+        }
+    }
+
+    fn unsubscribe(
+        &self, _meta: Option<Self::Metadata>, id: SubscriptionId,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: cfx_unsubscribe id={:?}", id);
+        Ok(self.subscribers.lock().remove(&id))
+    }
+}
+
+struct NotificationHandler {
+    consensus: Weak<ConsensusGraph>,
+    subscribers: Arc<Mutex<Subscribers>>,
+    /// The first epoch whose logs are not pushed yet.
+    next_log_epoch: Option<usize>,
+}
+
+impl NotificationHandler {
+    fn handle(&mut self, notification: Notification) {
+        match notification {
+            Notification::NewBlockHeader(header) => {
+                let sinks = Self::sinks(&self.subscribers.lock().heads);
+                self.notify(
+                    &sinks,
+                    pubsub::Result::Header(RpcHeader::new(&header)),
+                );
+            }
+            Notification::NewEpochs {
+                fork_at,
+                reorg,
+                epoch_hashes,
+            } => {
+                let sinks = Self::sinks(&self.subscribers.lock().epochs);
+                self.notify(
+                    &sinks,
+                    pubsub::Result::Epochs(Epochs {
+                        epoch_number: fork_at.into(),
+                        reorg,
+                        epoch_hashes: epoch_hashes
+                            .into_iter()
+                            .map(Into::into)
+                            .collect(),
+                    }),
+                );
+                self.notify_logs(fork_at, reorg);
+            }
+            Notification::NewPendingTransactions(hashes) => {
+                let sinks = Self::sinks(&self.subscribers.lock().transactions);
+                for hash in hashes {
+                    self.notify(
+                        &sinks,
+                        pubsub::Result::TransactionHash(hash.into()),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Push the logs of epochs which have been executed since the last push.
+    /// The logs of reverted epochs are pushed again for the new pivot chain.
+    /// Epochs which are still being executed are left for the next
+    /// notification, so the notifier never waits for the executor.
+    fn notify_logs(&mut self, fork_at: usize, reorg: bool) {
+        let consensus = match self.consensus.upgrade() {
+            Some(consensus) => consensus,
+            None => return,
+        };
+        let latest_state = consensus.best_state_epoch_number();
+        let mut next_log_epoch =
+            self.next_log_epoch.unwrap_or(latest_state + 1);
+        if reorg && fork_at < next_log_epoch {
+            next_log_epoch = fork_at;
+        }
+        self.next_log_epoch = Some(latest_state + 1);
+
+        let subscribers: Vec<(SubscriptionId, ResultSink, PrimitiveFilter)> =
+            self.subscribers
+                .lock()
+                .logs
+                .iter()
+                .map(|(id, (sink, filter))| {
+                    (id.clone(), sink.clone(), filter.clone())
+                })
+                .collect();
+        if subscribers.is_empty() {
+            return;
+        }
+        for epoch in next_log_epoch..=latest_state {
+            let epoch_number = EpochNumber::Number(epoch.into());
+            let epoch_hash = match consensus
+                .get_hash_from_epoch_number(epoch_number.clone())
+            {
+                Ok(hash) => hash,
+                // The pivot chain has changed, and the following epochs
+                // will be notified again.
+                Err(_) => return,
+            };
+            let block_hashes: Vec<H256> = match consensus
+                .inner
+                .read()
+                .block_hashes_by_epoch(epoch_number)
+            {
+                Ok(hashes) => hashes,
+                Err(_) => return,
+            };
+            // The receipts root is stored after the epoch is executed.
+            if consensus.data_man.get_receipts_root(&epoch_hash).is_none() {
+                self.next_log_epoch = Some(epoch);
+                return;
+            }
+            for (id, sink, filter) in &subscribers {
+                let logs = consensus.logs_from_blocks(
+                    block_hashes.clone(),
+                    |entry| filter.matches(entry),
+                    None,
+                );
+                let sinks = vec![(id.clone(), sink.clone())];
+                for log in logs {
+                    self.notify(&sinks, pubsub::Result::Log(RpcLog::from(log)));
+                }
+            }
+        }
+    }
+
+    fn sinks(subscribers: &HashMap<SubscriptionId, ResultSink>) -> Sinks {
+        subscribers
+            .iter()
+            .map(|(id, sink)| (id.clone(), sink.clone()))
+            .collect()
+    }
+
+    /// Push the result to the sinks. A subscription whose sink fails is
+    /// dropped, as its client is gone.
+    fn notify(&self, sinks: &Sinks, result: pubsub::Result) {
+        for (id, sink) in sinks {
+            if let Err(e) = sink.notify(Ok(result.clone())).wait() {
+                debug!("Failed to push notification to {:?}: {:?}", id, e);
+                self.subscribers.lock().remove(id);
+            }
+        }
+    }
+}
@@ -5,5 +5,9 @@
 //! Conflux rpc interfaces.
 
 pub mod cfx;
+pub mod pubsub;
 
-pub use self::cfx::{Cfx, DebugRpc, TestRpc};
+pub use self::{
+    cfx::{Cfx, DebugRpc, TestRpc},
+    pubsub::PubSub,
+};
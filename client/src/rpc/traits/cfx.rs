@@ -3,12 +3,14 @@
 // See http://www.gnu.org/licenses/
 
 use super::super::types::{
-    Block, Bytes, EpochNumber, Receipt as RpcReceipt, Status as RpcStatus,
-    Transaction, Transaction as RpcTransaction, H160 as RpcH160,
-    H256 as RpcH256, U256 as RpcU256, U64 as RpcU64,
+    Account, AccountProof, Block, Bytes, EpochNumber, FeeHistory,
+    Filter as RpcFilter, Log as RpcLog, Receipt as RpcReceipt,
+    Status as RpcStatus, Transaction,
+    Transaction as RpcTransaction, TransactionTrace as RpcTransactionTrace,
+    H160 as RpcH160, H256 as RpcH256, U256 as RpcU256, U64 as RpcU64,
 };
 use cfx_types::H256;
-use cfxcore::PeerInfo;
+use cfxcore::{BannedPeer, PeerInfo, PeerSettings};
 use jsonrpc_core::Result as RpcResult;
 use jsonrpc_macros::{build_rpc_trait, Trailing};
 use network::node_table::NodeId;
@@ -22,21 +24,36 @@ build_rpc_trait! {
 //        fn protocol_version(&self) -> RpcResult<String>;
 //
         /// Returns the number of hashes per second that the node is mining with.
-//        #[rpc(name = "cfx_hashrate")]
-//        fn hashrate(&self) -> RpcResult<RpcU256>;
+        #[rpc(name = "cfx_hashrate")]
+        fn hashrate(&self) -> RpcResult<RpcU256>;
 
 //        /// Returns block author.
 //        #[rpc(name = "cfx_coinbase")]
 //        fn author(&self) -> RpcResult<RpcH160>;
 
-//        /// Returns true if client is actively mining new blocks.
-//        #[rpc(name = "cfx_mining")]
-//        fn is_mining(&self) -> RpcResult<bool>;
+        /// Returns true if client is actively mining new blocks.
+        #[rpc(name = "cfx_mining")]
+        fn is_mining(&self) -> RpcResult<bool>;
 
         /// Returns current gas price.
         #[rpc(name = "cfx_gasPrice")]
         fn gas_price(&self) -> RpcResult<RpcU256>;
 
+        /// Returns the gas used ratio and the given gas price percentiles of
+        /// at most `epoch_count` latest executed epochs.
+        #[rpc(name = "cfx_feeHistory")]
+        fn fee_history(&self, RpcU64, Vec<f64>) -> RpcResult<FeeHistory>;
+
+        /// Returns the pow hash of the block being mined and the boundary
+        /// which its pow result must be below.
+        #[rpc(name = "cfx_getWork")]
+        fn get_work(&self) -> RpcResult<Vec<RpcH256>>;
+
+        /// Submit the nonce found for the pow hash. Returns false if the
+        /// solution is rejected.
+        #[rpc(name = "cfx_submitWork")]
+        fn submit_work(&self, RpcH256, RpcU64) -> RpcResult<bool>;
+
 //        /// Returns accounts list.
 //        #[rpc(name = "cfx_accounts")]
 //        fn accounts(&self) -> RpcResult<Vec<RpcH160>>;
@@ -49,9 +66,14 @@ build_rpc_trait! {
         #[rpc(name = "cfx_getBalance")]
         fn balance(&self, RpcH160, Trailing<EpochNumber>) -> RpcResult<RpcU256>;
 
-//        /// Returns content of the storage at given address.
-//        #[rpc(name = "cfx_getStorageAt")]
-//        fn storage_at(&self, RpcH160, RpcU256, Trailing<BlockNumber>) -> BoxFuture<RpcH256>;
+        /// Returns the account and the storage values at given keys, with their
+        /// proofs against the state root of given epoch.
+        #[rpc(name = "cfx_getProof")]
+        fn proof(&self, RpcH160, Vec<RpcH256>, Trailing<EpochNumber>) -> RpcResult<AccountProof>;
+
+        /// Returns content of the storage at given address and key.
+        #[rpc(name = "cfx_getStorageAt")]
+        fn storage_at(&self, RpcH160, RpcH256, Trailing<EpochNumber>) -> RpcResult<RpcH256>;
 
         /// Returns block with given hash.
         #[rpc(name = "cfx_getBlockByHash")]
@@ -89,9 +111,9 @@ build_rpc_trait! {
 //        #[rpc(name = "cfx_getUnclesCountByBlockNumber")]
 //        fn block_uncles_count_by_number(&self, BlockNumber) -> BoxFuture<Option<RpcU256>>;
 
-//        /// Returns the code at given address at given time (block number).
-//        #[rpc(name = "cfx_getCode")]
-//        fn code_at(&self, RpcH160, Trailing<BlockNumber>) -> BoxFuture<Bytes>;
+        /// Returns the code at given address at given time (epoch number).
+        #[rpc(name = "cfx_getCode")]
+        fn code(&self, RpcH160, Trailing<EpochNumber>) -> RpcResult<Bytes>;
 
         /// Sends signed transaction, returning its hash.
         #[rpc(name = "cfx_sendRawTransaction")]
@@ -113,6 +135,10 @@ build_rpc_trait! {
         #[rpc(name = "cfx_getTransactionByHash")]
         fn transaction_by_hash(&self, RpcH256) -> RpcResult<Option<Transaction>>;
 
+        /// Returns the receipt of an executed transaction.
+        #[rpc(name = "cfx_getTransactionReceipt")]
+        fn transaction_receipt(&self, RpcH256) -> RpcResult<Option<RpcReceipt>>;
+
 
         #[rpc(name = "cfx_estimateGas")]
         fn estimate_gas(&self, RpcTransaction) -> RpcResult<RpcU256>;
@@ -120,8 +146,28 @@ build_rpc_trait! {
         #[rpc(name = "cfx_getBlocksByEpoch")]
         fn blocks_by_epoch(&self, EpochNumber) -> RpcResult<Vec<RpcH256>>;
 
-//        #[rpc(name = "cfx_getAccount")]
-//        fn account(&self, RpcH160, bool, RpcU64, Trailing<EpochNumber>) -> RpcResult<Account>;
+        /// Returns logs matching the filter. At most 1000 epochs can be
+        /// queried at once.
+        #[rpc(name = "cfx_getLogs")]
+        fn get_logs(&self, RpcFilter) -> RpcResult<Vec<RpcLog>>;
+
+        /// Installs a log filter, returning its id. The filter expires if it
+        /// isn't polled for a while.
+        #[rpc(name = "cfx_newFilter")]
+        fn new_filter(&self, RpcFilter) -> RpcResult<RpcU256>;
+
+        /// Returns logs matching the filter in epochs executed since the last
+        /// poll, in at most 1000 epochs.
+        #[rpc(name = "cfx_getFilterChanges")]
+        fn filter_changes(&self, RpcU256) -> RpcResult<Vec<RpcLog>>;
+
+        /// Uninstalls a filter, returning false if it doesn't exist.
+        #[rpc(name = "cfx_uninstallFilter")]
+        fn uninstall_filter(&self, RpcU256) -> RpcResult<bool>;
+
+        /// Returns the account at given address, or null if it doesn't exist.
+        #[rpc(name = "cfx_getAccount")]
+        fn account(&self, RpcH160, Trailing<EpochNumber>) -> RpcResult<Option<Account>>;
 
 //        /// Returns transaction at given block hash and index.
 //        #[rpc(name = "cfx_getTransactionByBlockHashAndIndex")]
@@ -167,6 +213,14 @@ build_rpc_trait! {
         #[rpc(name = "getpeerinfo")]
         fn get_peer_info(&self) -> RpcResult<Vec<PeerInfo>>;
 
+        /// Returns the node ids and IP addresses banned for misbehaviors.
+        #[rpc(name = "listbanned")]
+        fn list_banned(&self) -> RpcResult<Vec<BannedPeer>>;
+
+        /// Lift all bans of misbehaving peers.
+        #[rpc(name = "clearbanned")]
+        fn clear_banned(&self) -> RpcResult<()>;
+
         /// Returns the JSON of whole chain
         #[rpc(name = "cfx_getChain")]
         fn chain(&self) -> RpcResult<Vec<Block>>;
@@ -196,7 +250,7 @@ build_rpc_trait! {
         fn generate_block_with_fake_txs(&self, Bytes, Trailing<usize>) -> RpcResult<H256>;
 
         #[rpc(name = "gettransactionreceipt")]
-        fn get_transaction_receipt(&self, H256) -> RpcResult<Option<RpcReceipt>>;
+        fn get_transaction_receipt(&self, RpcH256) -> RpcResult<Option<RpcReceipt>>;
     }
 }
 
@@ -210,5 +264,60 @@ build_rpc_trait! {
 
         #[rpc(name = "txpool_content")]
         fn txpool_content(&self) -> RpcResult<BTreeMap<String, BTreeMap<String, BTreeMap<usize, Vec<RpcTransaction>>>>>;
+
+        /// Drop the transaction from the pool. Returns false if it is not in
+        /// the pool.
+        #[rpc(name = "txpool_removeTransaction")]
+        fn txpool_remove_transaction(&self, RpcH256) -> RpcResult<bool>;
+
+        /// Drop all transactions of the sender from the pool. Returns the
+        /// number of dropped transactions.
+        #[rpc(name = "txpool_clearSender")]
+        fn txpool_clear_sender(&self, RpcH160) -> RpcResult<usize>;
+
+        /// Mark the sender as local, so that its transactions are never
+        /// evicted and are packed first.
+        #[rpc(name = "txpool_addLocalSender")]
+        fn txpool_add_local_sender(&self, RpcH160) -> RpcResult<bool>;
+
+        #[rpc(name = "txpool_removeLocalSender")]
+        fn txpool_remove_local_sender(&self, RpcH160) -> RpcResult<bool>;
+
+        #[rpc(name = "txpool_localSenders")]
+        fn txpool_local_senders(&self) -> RpcResult<Vec<RpcH160>>;
+
+        /// Re-execute the epoch of the block and return the call traces of
+        /// the transactions in the block.
+        #[rpc(name = "trace_block")]
+        fn trace_block(&self, RpcH256) -> RpcResult<Vec<RpcTransactionTrace>>;
+
+        /// Re-execute the transaction and return its call trace and VM trace.
+        #[rpc(name = "trace_transaction")]
+        fn trace_transaction(&self, RpcH256) -> RpcResult<Option<RpcTransactionTrace>>;
+
+        /// Add a reserved peer `cfxnode://NODEID@IP:PORT`, which is always
+        /// connected. The change is saved in the network config directory.
+        #[rpc(name = "admin_addReservedPeer")]
+        fn admin_add_reserved_peer(&self, String) -> RpcResult<()>;
+
+        /// Returns false if the peer is not reserved.
+        #[rpc(name = "admin_removeReservedPeer")]
+        fn admin_remove_reserved_peer(&self, NodeId) -> RpcResult<bool>;
+
+        /// Replace the IP filter, e.g. "none 10.0.0.0/8 -10.1.0.0/16", and
+        /// disconnect the peers which are no longer allowed.
+        #[rpc(name = "admin_setIpFilter")]
+        fn admin_set_ip_filter(&self, String) -> RpcResult<()>;
+
+        /// Set the maximum numbers of outgoing and incoming peers.
+        #[rpc(name = "admin_setMaxPeers")]
+        fn admin_set_max_peers(&self, u32, u32) -> RpcResult<()>;
+
+        /// Only connect with and accept reserved peers if true.
+        #[rpc(name = "admin_setReservedOnly")]
+        fn admin_set_reserved_only(&self, bool) -> RpcResult<()>;
+
+        #[rpc(name = "admin_peerSettings")]
+        fn admin_peer_settings(&self) -> RpcResult<PeerSettings>;
     }
 }
@@ -0,0 +1,26 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::super::types::pubsub;
+use jsonrpc_core::Result as RpcResult;
+use jsonrpc_macros::{build_rpc_trait, pubsub::Subscriber, Trailing};
+use jsonrpc_pubsub::SubscriptionId;
+
+build_rpc_trait! {
+    /// Cfx pub-sub rpc interface.
+    pub trait PubSub {
+        type Metadata;
+
+        #[pubsub(name = "cfx_subscription")] {
+            /// Subscribe to new block headers, new pivot chain epochs, logs
+            /// or new pending transactions.
+            #[rpc(name = "cfx_subscribe")]
+            fn subscribe(&self, Self::Metadata, Subscriber<pubsub::Result>, pubsub::Kind, Trailing<pubsub::Params>);
+
+            /// Unsubscribe from an existing subscription.
+            #[rpc(name = "cfx_unsubscribe")]
+            fn unsubscribe(&self, Option<Self::Metadata>, SubscriptionId) -> RpcResult<bool>;
+        }
+    }
+}
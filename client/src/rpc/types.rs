@@ -7,23 +7,34 @@ mod block;
 mod bytes;
 mod call_request;
 mod epoch_number;
+mod fee_history;
+mod filter;
 mod hash;
 mod index;
+mod log;
+mod proof;
+pub mod pubsub;
 mod receipt;
 mod status;
+mod trace;
 mod transaction;
 mod uint;
 
 pub use self::{
     account::Account,
-    block::{Block, BlockTransactions},
+    block::{Block, BlockTransactions, Header},
     bytes::Bytes,
     call_request::CallRequest,
     epoch_number::EpochNumber,
+    fee_history::FeeHistory,
+    filter::{Filter, VariadicValue},
     hash::{H160, H2048, H256, H512, H64},
     index::Index,
+    log::Log,
+    proof::{AccountProof, StorageProof},
     receipt::Receipt,
     status::Status,
+    trace::TransactionTrace,
     transaction::Transaction,
     uint::{U128, U256, U64},
 };
@@ -2,11 +2,28 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::rpc::types::{BlockTransactions, U256};
+use crate::rpc::types::{H160, H256, U256};
+use primitives::Account as PrimitiveAccount;
+use serde_derive::Serialize;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
+    pub address: H160,
     pub balance: U256,
-    pub transactions: BlockTransactions,
+    pub nonce: U256,
+    /// Hash of the contract code, or the hash of empty data if the account
+    /// isn't a contract
+    pub code_hash: H256,
+}
+
+impl From<PrimitiveAccount> for Account {
+    fn from(account: PrimitiveAccount) -> Self {
+        Account {
+            address: account.address.into(),
+            balance: account.balance.into(),
+            nonce: account.nonce.into(),
+            code_hash: account.code_hash.into(),
+        }
+    }
 }
@@ -7,7 +7,8 @@ use cfxcore::consensus::ConsensusGraphInner;
 use jsonrpc_core::Error as RpcError;
 use primitives::{
     receipt::{TRANSACTION_OUTCOME_EXCEPTION, TRANSACTION_OUTCOME_SUCCESS},
-    Block as PrimitiveBlock, BlockHeaderBuilder, TransactionAddress,
+    Block as PrimitiveBlock, BlockHeader as PrimitiveBlockHeader,
+    BlockHeaderBuilder,
 };
 use serde::{
     de::{Deserialize, Deserializer, Error, Unexpected},
@@ -146,27 +147,17 @@ impl Block {
                     .collect(),
             ),
             true => {
-                let tx_vec = match consensus_inner
-                    .block_receipts_by_hash(&b.hash(), false)
-                {
-                    Some(receipts) => b
-                        .transactions
-                        .iter()
-                        .enumerate()
-                        .map(|(idx, tx)| {
-                            let receipt = receipts.get(idx).unwrap();
-                            match receipt.outcome_status {
+                let tx_vec =
+                    match consensus_inner.block_localized_receipts(b, false) {
+                        Some(receipts) => b
+                            .transactions
+                            .iter()
+                            .zip(receipts)
+                            .map(|(tx, receipt)| match receipt.outcome_status {
                                 TRANSACTION_OUTCOME_SUCCESS => {
                                     Transaction::from_signed(
                                         tx,
-                                        Some(Receipt::new(
-                                            (**tx).clone(),
-                                            receipt.clone(),
-                                            TransactionAddress {
-                                                block_hash: b.hash(),
-                                                index: idx,
-                                            },
-                                        )),
+                                        Some(Receipt::from(receipt)),
                                     )
                                 }
                                 TRANSACTION_OUTCOME_EXCEPTION => {
@@ -175,15 +166,14 @@ impl Block {
                                 _ => {
                                     unreachable!();
                                 }
-                            }
-                        })
-                        .collect(),
-                    None => b
-                        .transactions
-                        .iter()
-                        .map(|x| Transaction::from_signed(x, None))
-                        .collect(),
-                };
+                            })
+                            .collect(),
+                        None => b
+                            .transactions
+                            .iter()
+                            .map(|x| Transaction::from_signed(x, None))
+                            .collect(),
+                    };
                 BlockTransactions::Full(tx_vec)
             }
         };
@@ -266,6 +256,61 @@ impl Block {
     }
 }
 
+/// Block header without the transactions.
+#[derive(PartialEq, Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    /// Hash of the block
+    pub hash: H256,
+    /// Hash of the parent
+    pub parent_hash: H256,
+    /// Distance to genesis
+    pub height: U256,
+    /// Author's address
+    pub miner: H160,
+    /// State root hash
+    pub deferred_state_root: H256,
+    /// Receipts root hash
+    pub deferred_receipts_root: H256,
+    /// Transactions root hash
+    pub transactions_root: H256,
+    /// Gas limit
+    pub gas_limit: U256,
+    /// Timestamp
+    pub timestamp: U256,
+    /// Difficulty
+    pub difficulty: U256,
+    /// Referee hashes
+    pub referee_hashes: Vec<H256>,
+    /// Nonce of the block
+    pub nonce: U256,
+}
+
+impl Header {
+    pub fn new(h: &PrimitiveBlockHeader) -> Self {
+        Header {
+            hash: H256::from(h.hash()),
+            parent_hash: H256::from(h.parent_hash().clone()),
+            height: h.height().into(),
+            miner: H160::from(h.author().clone()),
+            deferred_state_root: H256::from(h.deferred_state_root().clone()),
+            deferred_receipts_root: H256::from(
+                h.deferred_receipts_root().clone(),
+            ),
+            transactions_root: H256::from(h.transactions_root().clone()),
+            gas_limit: h.gas_limit().into(),
+            timestamp: h.timestamp().into(),
+            difficulty: h.difficulty().clone().into(),
+            referee_hashes: h
+                .referee_hashes()
+                .iter()
+                .map(|x| H256::from(*x))
+                .collect(),
+            nonce: h.nonce().into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Block, BlockTransactions};
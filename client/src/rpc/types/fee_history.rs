@@ -0,0 +1,33 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::U256;
+use cfxcore::consensus::gas_price_oracle::FeeHistory as PrimitiveFeeHistory;
+use serde_derive::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    /// The epoch number of the first entry
+    pub oldest_epoch: U256,
+    /// Gas used divided by the sum of block gas limits of each epoch
+    pub gas_used_ratio: Vec<f64>,
+    /// The requested gas price percentiles of each epoch, weighted by the gas
+    /// used by transactions
+    pub gas_price: Vec<Vec<U256>>,
+}
+
+impl From<PrimitiveFeeHistory> for FeeHistory {
+    fn from(history: PrimitiveFeeHistory) -> Self {
+        FeeHistory {
+            oldest_epoch: history.oldest_epoch.into(),
+            gas_used_ratio: history.gas_used_ratio,
+            gas_price: history
+                .gas_price
+                .into_iter()
+                .map(|prices| prices.into_iter().map(Into::into).collect())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{EpochNumber, H160, H256};
+use cfx_types::H256 as PrimitiveH256;
+use primitives::filter::Filter as PrimitiveFilter;
+use serde_derive::Deserialize;
+
+/// The number of topics a log entry can have.
+const MAX_TOPICS: usize = 4;
+
+/// Either a single value or a list of values.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum VariadicValue<T> {
+    Single(T),
+    Multiple(Vec<T>),
+}
+
+impl<T> VariadicValue<T> {
+    fn into_vec<U>(self) -> Vec<U>
+    where T: Into<U> {
+        match self {
+            VariadicValue::Single(value) => vec![value.into()],
+            VariadicValue::Multiple(values) => {
+                values.into_iter().map(Into::into).collect()
+            }
+        }
+    }
+}
+
+/// Log filter.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    /// Search from this epoch, inclusive.
+    pub from_epoch: Option<EpochNumber>,
+    /// Search till this epoch, inclusive.
+    pub to_epoch: Option<EpochNumber>,
+    /// Search in these blocks instead of the epoch range.
+    pub block_hashes: Option<Vec<H256>>,
+    /// Log must be produced by one of these addresses.
+    pub address: Option<VariadicValue<H160>>,
+    /// Log must contain one of the given topics at each position.
+    pub topics: Option<Vec<Option<VariadicValue<H256>>>>,
+    /// Only return the last `limit` logs.
+    pub limit: Option<usize>,
+}
+
+impl Filter {
+    /// Convert to the core filter over epochs [from_epoch, to_epoch), which
+    /// must have been resolved by the caller.
+    pub fn into_primitive(
+        self, from_epoch: usize, to_epoch: usize,
+    ) -> Result<PrimitiveFilter, String> {
+        let mut topics: Vec<Option<Vec<PrimitiveH256>>> = self
+            .topics
+            .unwrap_or_default()
+            .into_iter()
+            .map(|topic| topic.map(VariadicValue::into_vec))
+            .collect();
+        if topics.len() > MAX_TOPICS {
+            return Err(format!(
+                "Invalid topics: expected at most {} topics, got {}",
+                MAX_TOPICS,
+                topics.len()
+            ));
+        }
+        topics.resize(MAX_TOPICS, None);
+
+        Ok(PrimitiveFilter {
+            from_epoch,
+            to_epoch,
+            block_hashes: self
+                .block_hashes
+                .map(|hashes| hashes.into_iter().map(Into::into).collect()),
+            address: self.address.map(VariadicValue::into_vec),
+            topics,
+            limit: self.limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{EpochNumber, H160, H256},
+        Filter, VariadicValue,
+    };
+    use serde_json;
+
+    #[test]
+    fn filter_deserialize() {
+        let s = r#"{
+			"fromEpoch":"earliest",
+			"toEpoch":"0x10",
+			"address":"0x0000000000000000000000000000000000000001",
+			"topics":[
+				null,
+				"0x0000000000000000000000000000000000000000000000000000000000000002",
+				["0x0000000000000000000000000000000000000000000000000000000000000003"]
+			]
+		}"#;
+        let deserialized: Filter = serde_json::from_str(s).unwrap();
+
+        assert_eq!(
+            deserialized,
+            Filter {
+                from_epoch: Some(EpochNumber::Earliest),
+                to_epoch: Some(EpochNumber::Num(16)),
+                block_hashes: None,
+                address: Some(VariadicValue::Single(H160::from(1))),
+                topics: Some(vec![
+                    None,
+                    Some(VariadicValue::Single(H256::from(2))),
+                    Some(VariadicValue::Multiple(vec![H256::from(3)])),
+                ]),
+                limit: None,
+            }
+        );
+    }
+
+    #[test]
+    fn filter_into_primitive() {
+        let filter: Filter = serde_json::from_str(
+            r#"{"address":["0x0000000000000000000000000000000000000001"]}"#,
+        )
+        .unwrap();
+        let primitive = filter.into_primitive(1, 5).unwrap();
+
+        assert_eq!(primitive.from_epoch, 1);
+        assert_eq!(primitive.to_epoch, 5);
+        assert_eq!(primitive.address, Some(vec![1.into()]));
+        assert_eq!(primitive.topics, vec![None, None, None, None]);
+    }
+
+    #[test]
+    fn filter_too_many_topics() {
+        let filter: Filter =
+            serde_json::from_str(r#"{"topics":[null,null,null,null,null]}"#)
+                .unwrap();
+        assert!(filter.into_primitive(0, 1).is_err());
+    }
+}
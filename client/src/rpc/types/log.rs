@@ -0,0 +1,43 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Bytes, H160, H256};
+use primitives::log_entry::LocalizedLogEntry;
+use serde_derive::Serialize;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    /// Address of the contract which produced the log
+    pub address: H160,
+    /// Topics
+    pub topics: Vec<H256>,
+    /// Data
+    pub data: Bytes,
+    /// Block hash
+    pub block_hash: H256,
+    /// Transaction hash
+    pub transaction_hash: H256,
+    /// Transaction index within the block
+    pub transaction_index: usize,
+    /// Log position in the block
+    pub log_index: usize,
+    /// Log position in the transaction
+    pub transaction_log_index: usize,
+}
+
+impl From<LocalizedLogEntry> for Log {
+    fn from(log: LocalizedLogEntry) -> Log {
+        Log {
+            address: log.entry.address.into(),
+            topics: log.entry.topics.into_iter().map(Into::into).collect(),
+            data: log.entry.data.into(),
+            block_hash: log.block_hash.into(),
+            transaction_hash: log.transaction_hash.into(),
+            transaction_index: log.transaction_index,
+            log_index: log.log_index,
+            transaction_log_index: log.transaction_log_index,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Bytes, H160, H256};
+use cfx_types::H160 as PrimitiveH160;
+use cfxcore::{
+    consensus::AccountProof as PrimitiveAccountProof, storage::TrieProof,
+};
+use serde_derive::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageProof {
+    /// The storage key
+    pub key: H256,
+    /// The rlp of the storage value, null if the key doesn't exist
+    pub value: Option<Bytes>,
+    /// The rlp of the trie nodes from the state root
+    pub proof: Vec<Bytes>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: H160,
+    /// Hash of the epoch of which the state is proved
+    pub epoch_hash: H256,
+    /// The state root to verify the proofs against
+    pub state_root: H256,
+    /// The rlp of the account, null if the account doesn't exist
+    pub account: Option<Bytes>,
+    /// The rlp of the trie nodes from the state root
+    pub account_proof: Vec<Bytes>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+impl AccountProof {
+    pub fn new(address: PrimitiveH160, proof: PrimitiveAccountProof) -> Self {
+        AccountProof {
+            address: address.into(),
+            epoch_hash: proof.epoch_hash.into(),
+            state_root: proof.state_root.into(),
+            account: proof.account.map(|value| value.into_vec().into()),
+            account_proof: Self::proof_nodes(&proof.account_proof),
+            storage_proof: proof
+                .storage_proofs
+                .into_iter()
+                .map(|(key, value, trie_proof)| StorageProof {
+                    key: key.into(),
+                    value: value.map(|value| value.into_vec().into()),
+                    proof: Self::proof_nodes(&trie_proof),
+                })
+                .collect(),
+        }
+    }
+
+    fn proof_nodes(proof: &TrieProof) -> Vec<Bytes> {
+        proof
+            .get_nodes()
+            .iter()
+            .map(|node| rlp::encode(node).to_vec().into())
+            .collect()
+    }
+}
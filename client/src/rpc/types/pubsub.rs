@@ -0,0 +1,155 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Filter, Header, Log, H256, U256};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{from_value, Value};
+
+/// Subscription kind.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone)]
+pub enum Kind {
+    /// New block headers subscription.
+    #[serde(rename = "newHeads")]
+    NewHeads,
+    /// New pivot chain epochs subscription, including reorgs.
+    #[serde(rename = "epochs")]
+    Epochs,
+    /// Logs subscription.
+    #[serde(rename = "logs")]
+    Logs,
+    /// New pending transactions subscription.
+    #[serde(rename = "newPendingTransactions")]
+    NewPendingTransactions,
+}
+
+/// Subscription kind-specific parameters.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Params {
+    /// No parameters passed.
+    None,
+    /// Log parameters.
+    Logs(Filter),
+}
+
+impl Default for Params {
+    fn default() -> Self { Params::None }
+}
+
+impl<'a> Deserialize<'a> for Params {
+    fn deserialize<D>(
+        deserializer: D,
+    ) -> ::std::result::Result<Params, D::Error>
+    where D: Deserializer<'a> {
+        let v: Value = Deserialize::deserialize(deserializer)?;
+
+        if v.is_null() {
+            return Ok(Params::None);
+        }
+
+        from_value(v).map(Params::Logs).map_err(|e| {
+            D::Error::custom(format!("Invalid Pub-Sub parameters: {}", e))
+        })
+    }
+}
+
+/// A pivot chain change. Epochs from `epochNumber` on are replaced by
+/// `epochHashes`, and they are reverted first if `reorg` is true.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Epochs {
+    pub epoch_number: U256,
+    pub reorg: bool,
+    pub epoch_hashes: Vec<H256>,
+}
+
+/// Subscription result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Result {
+    /// New block header.
+    Header(Header),
+    /// New pivot chain epochs.
+    Epochs(Epochs),
+    /// Log
+    Log(Log),
+    /// Transaction hash
+    TransactionHash(H256),
+}
+
+impl Serialize for Result {
+    fn serialize<S>(
+        &self, serializer: S,
+    ) -> ::std::result::Result<S::Ok, S::Error>
+    where S: Serializer {
+        match *self {
+            Result::Header(ref header) => header.serialize(serializer),
+            Result::Epochs(ref epochs) => epochs.serialize(serializer),
+            Result::Log(ref log) => log.serialize(serializer),
+            Result::TransactionHash(ref hash) => hash.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{EpochNumber, Filter, H256},
+        Kind, Params, Result,
+    };
+    use serde_json;
+
+    #[test]
+    fn should_deserialize_kind() {
+        assert_eq!(
+            serde_json::from_str::<Kind>(r#""newHeads""#).unwrap(),
+            Kind::NewHeads
+        );
+        assert_eq!(
+            serde_json::from_str::<Kind>(r#""epochs""#).unwrap(),
+            Kind::Epochs
+        );
+        assert_eq!(
+            serde_json::from_str::<Kind>(r#""logs""#).unwrap(),
+            Kind::Logs
+        );
+        assert_eq!(
+            serde_json::from_str::<Kind>(r#""newPendingTransactions""#)
+                .unwrap(),
+            Kind::NewPendingTransactions
+        );
+    }
+
+    #[test]
+    fn should_deserialize_logs_params() {
+        let none = serde_json::from_str::<Params>("null").unwrap();
+        assert_eq!(none, Params::None);
+
+        let logs = serde_json::from_str::<Params>(
+            r#"{"fromEpoch":"earliest","limit":1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            logs,
+            Params::Logs(Filter {
+                from_epoch: Some(EpochNumber::Earliest),
+                to_epoch: None,
+                block_hashes: None,
+                address: None,
+                topics: None,
+                limit: Some(1),
+            })
+        );
+
+        assert!(serde_json::from_str::<Params>(r#"{"epoch":1}"#).is_err());
+    }
+
+    #[test]
+    fn should_serialize_transaction_hash() {
+        let result = Result::TransactionHash(H256::from(1));
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#""0x0000000000000000000000000000000000000000000000000000000000000001""#
+        );
+    }
+}
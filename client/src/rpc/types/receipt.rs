@@ -2,52 +2,90 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::rpc::types::{H256, U256};
-use cfx_types::Address;
-use cfxcore::{executive::contract_address, vm::CreateContractAddress};
-use primitives::{
-    receipt::Receipt as PrimitiveReceipt, transaction::Action,
-    SignedTransaction as PrimitiveTransaction, TransactionAddress,
-};
+use crate::rpc::types::{Log, H160, H2048, H256, U256};
+use primitives::receipt::LocalizedReceipt;
 use serde_derive::Serialize;
 
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Receipt {
-    /// The total gas used in the block following execution of the transaction.
+    /// Transaction hash
+    pub transaction_hash: H256,
+    /// Transaction index within the block
+    pub transaction_index: usize,
+    /// Block hash
+    pub block_hash: H256,
+    /// The epoch in which the block is executed
+    pub epoch_number: U256,
+    /// Sender of the transaction
+    pub from: H160,
+    /// Callee of the transaction, or null for a contract creation
+    pub to: Option<H160>,
+    /// Gas used by this transaction
     pub gas_used: U256,
+    /// The total gas used in the block following execution of the transaction.
+    pub cumulative_gas_used: U256,
+    /// Address of the contract created by the transaction.
+    pub contract_created: Option<H160>,
+    /// Logs generated by the transaction
+    pub logs: Vec<Log>,
+    /// Bloom filter of the logs
+    pub logs_bloom: H2048,
     /// Transaction outcome.
     pub outcome_status: u8,
-    /// Block hash
-    pub block_hash: H256,
-    /// Transaction index within the block
-    pub index: usize,
-    /// Address of contracts created during execution of transaction.
-    pub contract_created: Option<Address>,
+    /// Why the transaction failed, or null if it succeeded.
+    pub failure_reason: Option<String>,
 }
 
-impl Receipt {
-    pub fn new(
-        transaction: PrimitiveTransaction, receipt: PrimitiveReceipt,
-        transaction_address: TransactionAddress,
-    ) -> Receipt
-    {
-        let mut address = None;
-        if Action::Create == transaction.action {
-            let (created_address, _) = contract_address(
-                CreateContractAddress::FromSenderAndNonce,
-                &transaction.sender,
-                &transaction.nonce,
-                &transaction.data,
-            );
-            address = Some(created_address);
-        }
+impl From<LocalizedReceipt> for Receipt {
+    fn from(receipt: LocalizedReceipt) -> Receipt {
         Receipt {
+            transaction_hash: receipt.transaction_hash.into(),
+            transaction_index: receipt.transaction_index,
+            block_hash: receipt.block_hash.into(),
+            epoch_number: receipt.epoch_number.into(),
+            from: receipt.from.into(),
+            to: receipt.to.map(Into::into),
             gas_used: receipt.gas_used.into(),
+            cumulative_gas_used: receipt.cumulative_gas_used.into(),
+            contract_created: receipt.contract_created.map(Into::into),
+            logs: receipt.logs.into_iter().map(Into::into).collect(),
+            logs_bloom: receipt.log_bloom.into(),
             outcome_status: receipt.outcome_status,
-            block_hash: transaction_address.block_hash.into(),
-            index: transaction_address.index,
-            contract_created: address,
+            failure_reason: receipt.failure_reason,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Receipt;
+    use primitives::receipt::LocalizedReceipt;
+    use serde_json;
+
+    #[test]
+    fn test_serialize_failed_receipt() {
+        let receipt = Receipt::from(LocalizedReceipt {
+            transaction_hash: 1.into(),
+            transaction_index: 2,
+            block_hash: 3.into(),
+            epoch_number: 4,
+            from: 5.into(),
+            to: None,
+            gas_used: 21000.into(),
+            cumulative_gas_used: 42000.into(),
+            contract_created: None,
+            logs: vec![],
+            log_bloom: Default::default(),
+            outcome_status: 1,
+            failure_reason: Some("Out of gas".into()),
+        });
+        let json = serde_json::to_value(&receipt).unwrap();
+        assert_eq!(json["transactionIndex"], 2);
+        assert_eq!(json["epochNumber"], "0x4");
+        assert_eq!(json["to"], serde_json::Value::Null);
+        assert_eq!(json["gasUsed"], "0x5208");
+        assert_eq!(json["cumulativeGasUsed"], "0xa410");
+        assert_eq!(json["failureReason"], "Out of gas");
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Bytes, H160, H256, U256};
+use cfxcore::{
+    trace::{
+        CallTrace as PrimitiveCallTrace, MemoryDiff as PrimitiveMemoryDiff,
+        StorageDiff as PrimitiveStorageDiff,
+        TransactionTrace as PrimitiveTransactionTrace,
+        VmExecutedOperation as PrimitiveVmExecutedOperation,
+        VmOperation as PrimitiveVmOperation, VmTrace as PrimitiveVmTrace,
+    },
+    vm::CallType,
+};
+use serde_derive::Serialize;
+
+/// The traces of re-executing a transaction.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTrace {
+    pub transaction_hash: H256,
+    /// The error if the transaction is not executed, e.g. because of an
+    /// invalid nonce.
+    pub error: Option<String>,
+    /// The outermost call or create frame.
+    pub call: Option<CallTrace>,
+    /// The executed opcodes, only returned by `trace_transaction`.
+    pub vm_trace: Option<VmTrace>,
+}
+
+impl From<PrimitiveTransactionTrace> for TransactionTrace {
+    fn from(trace: PrimitiveTransactionTrace) -> Self {
+        TransactionTrace {
+            transaction_hash: trace.transaction_hash.into(),
+            error: trace.error,
+            call: trace.call.map(Into::into),
+            vm_trace: trace.vm_trace.map(Into::into),
+        }
+    }
+}
+
+/// A call or create frame.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CallTrace {
+    /// One of `call`, `callcode`, `delegatecall`, `staticcall` and `create`.
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub from: H160,
+    /// The callee, or the address of the created contract.
+    pub to: H160,
+    pub value: U256,
+    pub gas: U256,
+    pub input: Bytes,
+    pub gas_used: U256,
+    pub output: Bytes,
+    /// The reason why the frame failed.
+    pub error: Option<String>,
+    /// Frames spawned by this frame.
+    pub calls: Vec<CallTrace>,
+}
+
+impl From<PrimitiveCallTrace> for CallTrace {
+    fn from(trace: PrimitiveCallTrace) -> Self {
+        let call_type = match trace.call_type {
+            CallType::None => "create",
+            CallType::Call => "call",
+            CallType::CallCode => "callcode",
+            CallType::DelegateCall => "delegatecall",
+            CallType::StaticCall => "staticcall",
+        };
+        CallTrace {
+            call_type: call_type.into(),
+            from: trace.from.into(),
+            to: trace.to.into(),
+            value: trace.value.into(),
+            gas: trace.gas.into(),
+            input: trace.input.into(),
+            gas_used: trace.gas_used.into(),
+            output: trace.output.into(),
+            error: trace.error,
+            calls: trace.calls.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// The opcodes executed in a frame.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VmTrace {
+    /// The index of the operation in the parent frame which spawned this
+    /// frame.
+    pub parent_step: usize,
+    pub code: Bytes,
+    pub operations: Vec<VmOperation>,
+    pub subs: Vec<VmTrace>,
+}
+
+impl From<PrimitiveVmTrace> for VmTrace {
+    fn from(trace: PrimitiveVmTrace) -> Self {
+        VmTrace {
+            parent_step: trace.parent_step,
+            code: trace.code.into(),
+            operations: trace.operations.into_iter().map(Into::into).collect(),
+            subs: trace.subs.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VmOperation {
+    pub pc: usize,
+    /// The opcode.
+    pub instruction: u8,
+    pub gas_cost: U256,
+    /// The result of the instruction, or null if it failed.
+    pub executed: Option<VmExecutedOperation>,
+}
+
+impl From<PrimitiveVmOperation> for VmOperation {
+    fn from(operation: PrimitiveVmOperation) -> Self {
+        VmOperation {
+            pc: operation.pc,
+            instruction: operation.instruction,
+            gas_cost: operation.gas_cost.into(),
+            executed: operation.executed.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VmExecutedOperation {
+    pub gas_left: U256,
+    pub stack_push: Vec<U256>,
+    pub mem_diff: Option<MemoryDiff>,
+    pub store_diff: Option<StorageDiff>,
+}
+
+impl From<PrimitiveVmExecutedOperation> for VmExecutedOperation {
+    fn from(executed: PrimitiveVmExecutedOperation) -> Self {
+        VmExecutedOperation {
+            gas_left: executed.gas_left.into(),
+            stack_push: executed
+                .stack_push
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            mem_diff: executed.mem_diff.map(Into::into),
+            store_diff: executed.store_diff.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryDiff {
+    pub offset: usize,
+    pub data: Bytes,
+}
+
+impl From<PrimitiveMemoryDiff> for MemoryDiff {
+    fn from(diff: PrimitiveMemoryDiff) -> Self {
+        MemoryDiff {
+            offset: diff.offset,
+            data: diff.data.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiff {
+    pub location: U256,
+    pub value: U256,
+}
+
+impl From<PrimitiveStorageDiff> for StorageDiff {
+    fn from(diff: PrimitiveStorageDiff) -> Self {
+        StorageDiff {
+            location: diff.location.into(),
+            value: diff.value.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallTrace;
+    use cfxcore::{trace::CallTrace as PrimitiveCallTrace, vm::CallType};
+    use serde_json;
+
+    #[test]
+    fn test_serialize_call_trace() {
+        let trace = CallTrace::from(PrimitiveCallTrace {
+            call_type: CallType::None,
+            from: 1.into(),
+            to: 2.into(),
+            value: 3.into(),
+            gas: 100.into(),
+            input: vec![0x60],
+            gas_used: 100.into(),
+            output: vec![],
+            error: Some("Out of gas".into()),
+            calls: vec![],
+        });
+        assert_eq!(
+            serde_json::to_string(&trace).unwrap(),
+            r#"{"type":"create","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000002","value":"0x3","gas":"0x64","input":"0x60","gasUsed":"0x64","output":"0x","error":"Out of gas","calls":[]}"#
+        );
+    }
+}
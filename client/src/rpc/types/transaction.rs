@@ -4,6 +4,7 @@
 
 use crate::rpc::types::{
     receipt::Receipt, Bytes, H160 as RpcH160, H256 as RpcH256, U256 as RpcU256,
+    U64 as RpcU64,
 };
 use keylib::Error;
 use primitives::{
@@ -25,6 +26,7 @@ pub struct Transaction {
     pub gas: RpcU256,
     pub contract_created: Option<RpcH160>,
     pub data: Bytes,
+    pub chain_id: RpcU64,
     /// The standardised V field of the signature.
     pub v: RpcU256,
     /// The R field of the signature.
@@ -37,18 +39,16 @@ impl Transaction {
     pub fn from_signed(
         t: &SignedTransaction, receipt: Option<Receipt>,
     ) -> Transaction {
-        let mut contract_created = None;
-        if let Some(ref receipt) = receipt {
-            if let Some(ref address) = receipt.contract_created {
-                contract_created = Some(address.clone().into());
-            }
-        }
         Transaction {
             hash: t.transaction.hash().into(),
             nonce: t.nonce.into(),
-            block_hash: receipt.clone().map(|x| x.block_hash.into()),
-            transaction_index: receipt.clone().map(|x| x.index.into()),
-            contract_created,
+            block_hash: receipt.as_ref().map(|x| x.block_hash.clone()),
+            transaction_index: receipt
+                .as_ref()
+                .map(|x| x.transaction_index.into()),
+            contract_created: receipt
+                .as_ref()
+                .and_then(|x| x.contract_created.clone()),
             from: t.sender().into(),
             to: match t.action {
                 Action::Create => None,
@@ -58,6 +58,7 @@ impl Transaction {
             gas_price: t.gas_price.into(),
             gas: t.gas.into(),
             data: t.data.clone().into(),
+            chain_id: t.chain_id.into(),
             v: t.transaction.v.into(),
             r: t.transaction.r.into(),
             s: t.transaction.s.into(),
@@ -76,6 +77,7 @@ impl Transaction {
                 },
                 value: self.value.into(),
                 data: self.data.into(),
+                chain_id: self.chain_id.as_u64(),
             },
             v: self.v.as_usize() as u8,
             r: self.r.into(),
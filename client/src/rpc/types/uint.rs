@@ -142,6 +142,10 @@ impl_uint!(U128, CfxU128, 2);
 impl_uint!(U256, CfxU256, 4);
 impl_uint!(U64, CfxU64, 1);
 
+impl U64 {
+    pub fn as_u64(&self) -> u64 { self.0.as_u64() }
+}
+
 impl serde::Serialize for U128 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer {
@@ -165,7 +169,7 @@ impl serde::Serialize for U64 {
 
 #[cfg(test)]
 mod tests {
-    use super::U256;
+    use super::{U256, U64};
     use serde_json;
 
     type Res = Result<U256, serde_json::Error>;
@@ -213,4 +217,15 @@ mod tests {
         assert_eq!(deserialized4, U256(1.into()));
         assert_eq!(deserialized5, U256(256.into()));
     }
+
+    #[test]
+    fn should_fail_to_deserialize_overflowing_u64() {
+        let deserialized: U64 =
+            serde_json::from_str(r#""0xffffffffffffffff""#).unwrap();
+        assert_eq!(deserialized.as_u64(), u64::max_value());
+
+        let overflowing: Result<U64, _> =
+            serde_json::from_str(r#""0x10000000000000000""#);
+        assert!(overflowing.is_err());
+    }
 }
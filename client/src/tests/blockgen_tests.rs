@@ -17,7 +17,7 @@ fn test_mining_10_epochs_inner(handle: &ClientHandle) {
     let bgen = handle.blockgen.clone();
     //println!("Pow Config: {:?}", bgen.pow_config());
     thread::spawn(move || {
-        BlockGenerator::start_mining(bgen, 0);
+        BlockGenerator::start_mining(bgen, 2);
     });
     let sync_graph = handle.sync.get_synchronization_graph();
     let best_block_hash = sync_graph.get_best_info().as_ref().best_block_hash;
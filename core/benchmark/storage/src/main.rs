@@ -1614,6 +1614,7 @@ impl TxReplayer {
                     cfxcore::storage::defaults::DEFAULT_NODE_MAP_SIZE,
                 recent_lfu_factor:
                     cfxcore::storage::defaults::DEFAULT_RECENT_LFU_FACTOR,
+                history_epochs: None,
             },
         ));
 
@@ -4,6 +4,7 @@
 
 use crate::{
     cache_manager::{CacheId, CacheManager},
+    consensus::Checkpoint,
     db::{COL_BLOCKS, COL_BLOCK_RECEIPTS, COL_TX_ADDRESS},
     ext_db::SystemDB,
     storage::StorageManager,
@@ -34,6 +35,8 @@ pub struct BlockDataManager {
     pub db: Arc<SystemDB>,
     pub storage_manager: Arc<StorageManager>,
     pub cache_man: Arc<Mutex<CacheManager<CacheId>>>,
+    /// The trusted checkpoint to sync from, if any.
+    pub checkpoint: Option<Checkpoint>,
 }
 
 impl BlockDataManager {
@@ -41,6 +44,7 @@ impl BlockDataManager {
         genesis_block: Arc<Block>, txpool: SharedTransactionPool,
         db: Arc<SystemDB>, storage_manager: Arc<StorageManager>,
         cache_man: Arc<Mutex<CacheManager<CacheId>>>,
+        checkpoint: Option<Checkpoint>,
     ) -> Self
     {
         let data_man = Self {
@@ -54,6 +58,7 @@ impl BlockDataManager {
             db,
             storage_manager,
             cache_man,
+            checkpoint,
         };
 
         data_man.insert_receipts_root(
@@ -233,11 +238,20 @@ impl BlockDataManager {
         let epoch: H256 = rlp.val_at(0).expect("encoded");
         let receipts: Vec<Receipt> = rlp.list_at(1).expect("encoded");
         let bloom: Bloom = rlp.val_at(2).expect("encoded");
+        // Results written before the execution errors were stored have only
+        // three items.
+        let tx_execution_errors: Vec<String> =
+            if rlp.item_count().unwrap_or(0) > 3 {
+                rlp.list_at(3).expect("encoded")
+            } else {
+                Vec::new()
+            };
         Some((
             epoch,
             BlockExecutedResult {
                 receipts: Arc::new(receipts),
                 bloom,
+                tx_execution_errors: Arc::new(tx_execution_errors),
             },
         ))
     }
@@ -280,9 +294,11 @@ impl BlockDataManager {
         Some(receipts)
     }
 
+    /// `tx_execution_errors` are the reasons why the transactions failed, and
+    /// an empty string means the transaction succeeded.
     pub fn insert_block_results_to_kv(
         &self, hash: H256, epoch: H256, receipts: Arc<Vec<Receipt>>,
-        persistent: bool,
+        tx_execution_errors: Arc<Vec<String>>, persistent: bool,
     )
     {
         let bloom = receipts.iter().fold(Bloom::zero(), |mut b, r| {
@@ -292,10 +308,11 @@ impl BlockDataManager {
 
         if persistent {
             let mut dbops = self.db.key_value().transaction();
-            let mut rlp_stream = RlpStream::new_list(3);
+            let mut rlp_stream = RlpStream::new_list(4);
             rlp_stream.append(&epoch);
             rlp_stream.append_list(&receipts);
             rlp_stream.append(&bloom);
+            rlp_stream.append_list(&tx_execution_errors);
             dbops.put(COL_BLOCK_RECEIPTS, &hash, &rlp_stream.drain());
             self.db
                 .key_value()
@@ -309,7 +326,11 @@ impl BlockDataManager {
             .or_insert(BlockReceiptsInfo::default());
         receipt_info.insert_receipts_at_epoch(
             &epoch,
-            BlockExecutedResult { receipts, bloom },
+            BlockExecutedResult {
+                receipts,
+                bloom,
+                tx_execution_errors,
+            },
         );
 
         self.cache_man
@@ -442,10 +463,13 @@ impl BlockDataManager {
 pub struct BlockExecutedResult {
     pub receipts: Arc<Vec<Receipt>>,
     pub bloom: Bloom,
+    /// Why each transaction failed, empty if it succeeded.
+    pub tx_execution_errors: Arc<Vec<String>>,
 }
 impl HeapSizeOf for BlockExecutedResult {
     fn heap_size_of_children(&self) -> usize {
         self.receipts.heap_size_of_children()
+            + self.tx_execution_errors.heap_size_of_children()
     }
 }
 type EpochIndex = H256;
@@ -60,7 +60,6 @@ pub trait Pricer: Send + Sync {
 
 /// A linear pricing model. This computes a price using a base cost and a cost
 /// per-word.
-#[allow(dead_code)]
 struct Linear {
     base: usize,
     word: usize,
@@ -80,7 +79,6 @@ impl Pricer for Linear {
 
 /// A alt_bn128_parinig pricing model. This computes a price using a base cost
 /// and a cost per pair.
-#[allow(dead_code)]
 struct AltBn128PairingPricer {
     base: usize,
     pair: usize,
@@ -199,8 +197,63 @@ impl Builtin {
     pub fn is_active(&self, at: u64) -> bool { at >= self.activate_at }
 }
 
+/// Pricing model of a built-in contract in the chain spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PricingSpec {
+    Linear { base: usize, word: usize },
+    Modexp { divisor: usize },
+    AltBn128Pairing { base: usize, pair: usize },
+}
+
+/// Specification of a built-in contract in the chain spec. `name` is one of
+/// the names accepted by `builtin_factory`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinSpec {
+    pub name: String,
+    pub pricing: PricingSpec,
+    pub activate_at: u64,
+}
+
+impl From<BuiltinSpec> for Builtin {
+    fn from(spec: BuiltinSpec) -> Self {
+        let pricer: Box<Pricer> = match spec.pricing {
+            PricingSpec::Linear { base, word } => {
+                Box::new(Linear { base, word })
+            }
+            PricingSpec::Modexp { divisor } => {
+                if divisor == 0 {
+                    warn!(
+                        "Zero modexp divisor specified. Falling back to \
+                         default."
+                    );
+                    Box::new(ModexpPricer { divisor: 10 })
+                } else {
+                    Box::new(ModexpPricer { divisor })
+                }
+            }
+            PricingSpec::AltBn128Pairing { base, pair } => {
+                Box::new(AltBn128PairingPricer { base, pair })
+            }
+        };
+
+        Builtin {
+            pricer,
+            native: builtin_factory(&spec.name),
+            activate_at: spec.activate_at,
+        }
+    }
+}
+
+/// Whether `builtin_factory` accepts the name.
+pub fn is_builtin_name(name: &str) -> bool {
+    match name {
+        "identity" | "ecrecover" | "sha256" | "ripemd160" | "modexp"
+        | "alt_bn128_add" | "alt_bn128_mul" | "alt_bn128_pairing" => true,
+        _ => false,
+    }
+}
+
 /// Built-in instruction factory.
-#[allow(dead_code)]
 pub fn builtin_factory(name: &str) -> Box<Impl> {
     match name {
         "identity" => Box::new(Identity) as Box<Impl>,
@@ -224,35 +277,27 @@ pub fn builtin_factory(name: &str) -> Box<Impl> {
 // - modexp (EIP198)
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Identity;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct EcRecover;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Sha256;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Ripemd160;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct ModexpImpl;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Bn128AddImpl;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Bn128MulImpl;
 
 #[derive(Debug)]
-#[allow(dead_code)]
 struct Bn128PairingImpl;
 
 impl Impl for Identity {
@@ -644,7 +689,8 @@ impl Bn128PairingImpl {
 #[cfg(test)]
 mod tests {
     use super::{
-        builtin_factory, modexp as me, Builtin, Linear, ModexpPricer, Pricer,
+        builtin_factory, modexp as me, Builtin, BuiltinSpec, Linear,
+        ModexpPricer, Pricer, PricingSpec,
     };
     use crate::bytes::BytesRef;
     use cfx_types::U256;
@@ -1161,4 +1207,29 @@ mod tests {
             .expect("Builtin should not fail");
         assert_eq!(i, o);
     }
+
+    #[test]
+    fn from_spec() {
+        let b = Builtin::from(BuiltinSpec {
+            name: "identity".to_owned(),
+            pricing: PricingSpec::Linear { base: 10, word: 20 },
+            activate_at: 100,
+        });
+
+        assert!(!b.is_active(99));
+        assert!(b.is_active(100));
+        assert_eq!(b.cost(&[0; 0]), U256::from(10));
+        assert_eq!(b.cost(&[0; 33]), U256::from(50));
+
+        let b = Builtin::from(BuiltinSpec {
+            name: "alt_bn128_pairing".to_owned(),
+            pricing: PricingSpec::AltBn128Pairing {
+                base: 100_000,
+                pair: 80_000,
+            },
+            activate_at: 0,
+        });
+        assert_eq!(b.cost(&[0; 0]), U256::from(100_000));
+        assert_eq!(b.cost(&[0; 384]), U256::from(260_000));
+    }
 }
@@ -0,0 +1,631 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Chain spec file which describes the genesis accounts, the chain parameters,
+//! the builtin contracts, the spec transitions and the PoW parameters of a
+//! chain. Both TOML and JSON (with a `.json` extension) are accepted, e.g.
+//!
+//! ```toml
+//! [params]
+//! network_id = 10
+//! chain_id = 10
+//! min_gas_limit = "5000"
+//! base_mining_reward = 900
+//! anticone_penalty_ratio = 100
+//!
+//! [pow]
+//! initial_difficulty = 4
+//! block_generation_period = 1000000
+//! difficulty_adjustment_epoch_period = 20
+//!
+//! [accounts]
+//! "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b" = "1000000000000000000"
+//!
+//! [builtins."0x0000000000000000000000000000000000000001"]
+//! name = "ecrecover"
+//! pricing = { linear = { base = 3000, word = 0 } }
+//!
+//! [[transitions]]
+//! activate_at = 100
+//! sload_gas = 800
+//! ```
+//!
+//! Parameters that are not specified keep their default values. When there is
+//! no `builtins` table, the default builtin contracts are installed.
+//!
+//! The epoch counts of the consensus protocol, e.g.
+//! `DEFERRED_STATE_EPOCH_COUNT` and the reward and anticone penalty windows,
+//! are not chain parameters: block headers, checkpoints and light clients
+//! depend on them, so they stay compile-time constants.
+
+use crate::{
+    builtin::{is_builtin_name, BuiltinSpec, PricingSpec},
+    genesis,
+    machine::{
+        default_builtin_specs, new_machine_with_builtins, CommonParams,
+        Machine,
+    },
+    pow::ProofOfWorkConfig,
+    vm::Spec,
+};
+use cfx_types::{Address, U256};
+use primitives::CardinalNumber;
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use toml::{value::Table, Value};
+
+/// Spec parameters which take effect from the epoch `activate_at` on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecTransition {
+    pub activate_at: CardinalNumber,
+    pub overrides: Table,
+}
+
+pub struct ChainSpec {
+    pub params: CommonParams,
+    pub builtins: Vec<(Address, BuiltinSpec)>,
+    /// Sorted by `activate_at`.
+    pub transitions: Vec<SpecTransition>,
+    /// None if the PoW parameters are left to the node configuration.
+    pub pow: Option<ProofOfWorkConfig>,
+    /// None if the genesis accounts are left to the node configuration.
+    pub accounts: Option<HashMap<Address, U256>>,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        ChainSpec {
+            params: CommonParams::common_params(),
+            builtins: default_builtin_specs(),
+            transitions: Vec::new(),
+            pow: None,
+            accounts: None,
+        }
+    }
+}
+
+impl ChainSpec {
+    pub fn load_file(path: &str) -> Result<Self, String> {
+        let mut content = String::new();
+        let mut file = File::open(path)
+            .map_err(|e| format!("failed to open chain spec: {:?}", e))?;
+        file.read_to_string(&mut content).map_err(|e| {
+            format!("failed to read chain spec content: {:?}", e)
+        })?;
+
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&content),
+            _ => Self::from_toml_str(&content),
+        }
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        let value = content
+            .parse::<Value>()
+            .map_err(|e| format!("failed to parse toml chain spec: {:?}", e))?;
+        Self::from_value(value)
+    }
+
+    pub fn from_json_str(content: &str) -> Result<Self, String> {
+        let value = serde_json::from_str::<serde_json::Value>(content)
+            .map_err(|e| format!("failed to parse json chain spec: {:?}", e))?;
+        Self::from_value(json_to_toml(value)?)
+    }
+
+    fn from_value(value: Value) -> Result<Self, String> {
+        let mut spec = ChainSpec::default();
+        let root = match value {
+            Value::Table(table) => table,
+            other => {
+                return Err(format!(
+                    "invalid root value type {:?} in chain spec",
+                    other.type_str()
+                ));
+            }
+        };
+
+        for (key, value) in root {
+            match key.as_str() {
+                "params" => parse_params(&mut spec.params, value)?,
+                "builtins" => spec.builtins = parse_builtins(value)?,
+                "transitions" => spec.transitions = parse_transitions(value)?,
+                "pow" => spec.pow = Some(parse_pow(value)?),
+                "accounts" => {
+                    spec.accounts = Some(genesis::accounts_from_toml(value)?)
+                }
+                _ => return Err(format!("unknown chain spec section {}", key)),
+            }
+        }
+
+        Ok(spec)
+    }
+
+    pub fn machine(&self) -> Machine {
+        let mut machine = new_machine_with_builtins(
+            self.params.clone(),
+            self.builtins.clone(),
+        );
+        if !self.transitions.is_empty() {
+            let transitions = self.transitions.clone();
+            machine.set_spec_creation_rules(Box::new(move |spec, number| {
+                for transition in &transitions {
+                    if number < transition.activate_at {
+                        break;
+                    }
+                    for (name, value) in &transition.overrides {
+                        set_spec_param(spec, name, value)
+                            .expect("Checked when loading chain spec; qed");
+                    }
+                }
+            }));
+        }
+        machine
+    }
+}
+
+fn json_to_toml(value: serde_json::Value) -> Result<Value, String> {
+    use serde_json::Value as Json;
+    Ok(match value {
+        Json::Bool(b) => Value::Boolean(b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => match n.as_f64() {
+                Some(f) => Value::Float(f),
+                None => return Err(format!("invalid number {}", n)),
+            },
+        },
+        Json::String(s) => Value::String(s),
+        Json::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(json_to_toml)
+                .collect::<Result<_, String>>()?,
+        ),
+        Json::Object(object) => {
+            let mut table = Table::new();
+            for (key, value) in object {
+                table.insert(key, json_to_toml(value)?);
+            }
+            Value::Table(table)
+        }
+        Json::Null => return Err("null is not allowed in chain spec".into()),
+    })
+}
+
+fn as_table(value: Value, name: &str) -> Result<Table, String> {
+    match value {
+        Value::Table(table) => Ok(table),
+        other => Err(format!(
+            "{} requires Table type, found {}",
+            name,
+            other.type_str()
+        )),
+    }
+}
+
+fn parse_u64(value: &Value, name: &str) -> Result<u64, String> {
+    match value {
+        Value::Integer(i) if *i >= 0 => Ok(*i as u64),
+        Value::String(s) => {
+            let result = if s.starts_with("0x") {
+                u64::from_str_radix(&s[2..], 16)
+            } else {
+                s.parse::<u64>()
+            };
+            result.map_err(|e| {
+                format!(
+                    "failed to parse {}: value = {}, error = {:?}",
+                    name, s, e
+                )
+            })
+        }
+        _ => Err(format!("{} requires a non-negative integer", name)),
+    }
+}
+
+fn parse_u256(value: &Value, name: &str) -> Result<U256, String> {
+    match value {
+        Value::String(s) => {
+            let result = if s.starts_with("0x") {
+                s[2..].parse::<U256>().map_err(|e| format!("{:?}", e))
+            } else {
+                U256::from_dec_str(s).map_err(|e| format!("{:?}", e))
+            };
+            result.map_err(|e| {
+                format!(
+                    "failed to parse {}: value = {}, error = {}",
+                    name, s, e
+                )
+            })
+        }
+        _ => parse_u64(value, name).map(U256::from),
+    }
+}
+
+fn parse_bool(value: &Value, name: &str) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("{} requires Boolean type", name))
+}
+
+fn parse_string(value: &Value, name: &str) -> Result<String, String> {
+    value
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("{} requires String type", name))
+}
+
+fn parse_address(s: &str) -> Result<Address, String> {
+    s.trim_start_matches("0x").parse::<Address>().map_err(|e| {
+        format!("failed to parse address: value = {}, error = {:?}", s, e)
+    })
+}
+
+fn parse_params(params: &mut CommonParams, value: Value) -> Result<(), String> {
+    for (key, value) in as_table(value, "params")? {
+        let name = key.as_str();
+        match name {
+            "account_start_nonce" => {
+                params.account_start_nonce = parse_u256(&value, name)?
+            }
+            "maximum_extra_data_size" => {
+                params.maximum_extra_data_size =
+                    parse_u64(&value, name)? as usize
+            }
+            "network_id" => params.network_id = parse_u64(&value, name)?,
+            "chain_id" => params.chain_id = parse_u64(&value, name)?,
+            "subprotocol_name" => {
+                params.subprotocol_name = parse_string(&value, name)?
+            }
+            "min_gas_limit" => params.min_gas_limit = parse_u256(&value, name)?,
+            "gas_limit_bound_divisor" => {
+                params.gas_limit_bound_divisor = parse_u256(&value, name)?
+            }
+            "registrar" => {
+                params.registrar = parse_address(&parse_string(&value, name)?)?
+            }
+            "node_permission_contract" => {
+                params.node_permission_contract =
+                    Some(parse_address(&parse_string(&value, name)?)?)
+            }
+            "max_code_size" => params.max_code_size = parse_u64(&value, name)?,
+            "max_code_size_transition" => {
+                params.max_code_size_transition = parse_u64(&value, name)?
+            }
+            "max_transaction_size" => {
+                params.max_transaction_size = parse_u64(&value, name)? as usize
+            }
+            "base_mining_reward" => {
+                params.base_mining_reward = parse_u64(&value, name)?
+            }
+            "anticone_penalty_ratio" => {
+                let ratio = parse_u64(&value, name)?;
+                if ratio == 0 {
+                    return Err(
+                        "anticone_penalty_ratio must be positive".into(),
+                    );
+                }
+                params.anticone_penalty_ratio = ratio
+            }
+            _ => return Err(format!("unknown chain parameter {}", name)),
+        }
+    }
+    Ok(())
+}
+
+fn parse_pricing(value: Value) -> Result<PricingSpec, String> {
+    let table = as_table(value, "pricing")?;
+    if table.len() != 1 {
+        return Err("pricing requires exactly one pricing scheme".into());
+    }
+    let (scheme, value) = table.into_iter().next().expect("len is 1; qed");
+    let args = as_table(value, &scheme)?;
+    let arg = |name: &str| match args.get(name) {
+        Some(value) => parse_u64(value, name).map(|v| v as usize),
+        None => Err(format!("{} pricing requires {}", scheme, name)),
+    };
+    match scheme.as_str() {
+        "linear" => Ok(PricingSpec::Linear {
+            base: arg("base")?,
+            word: arg("word")?,
+        }),
+        "modexp" => Ok(PricingSpec::Modexp {
+            divisor: arg("divisor")?,
+        }),
+        "alt_bn128_pairing" => Ok(PricingSpec::AltBn128Pairing {
+            base: arg("base")?,
+            pair: arg("pair")?,
+        }),
+        _ => Err(format!("unknown pricing scheme {}", scheme)),
+    }
+}
+
+fn parse_builtins(value: Value) -> Result<Vec<(Address, BuiltinSpec)>, String> {
+    let mut builtins = Vec::new();
+    for (key, value) in as_table(value, "builtins")? {
+        let address = parse_address(&key)?;
+        let mut table = as_table(value, &key)?;
+        let name = match table.remove("name") {
+            Some(value) => parse_string(&value, "name")?,
+            None => return Err(format!("builtin {} requires name", key)),
+        };
+        if !is_builtin_name(&name) {
+            return Err(format!("unknown builtin {}", name));
+        }
+        let pricing = match table.remove("pricing") {
+            Some(value) => parse_pricing(value)?,
+            None => return Err(format!("builtin {} requires pricing", key)),
+        };
+        let activate_at = match table.remove("activate_at") {
+            Some(value) => parse_u64(&value, "activate_at")?,
+            None => 0,
+        };
+        if let Some(unknown) = table.keys().next() {
+            return Err(format!("unknown builtin field {}", unknown));
+        }
+        builtins.push((
+            address,
+            BuiltinSpec {
+                name,
+                pricing,
+                activate_at,
+            },
+        ));
+    }
+    Ok(builtins)
+}
+
+fn parse_transitions(value: Value) -> Result<Vec<SpecTransition>, String> {
+    let array = match value {
+        Value::Array(array) => array,
+        other => {
+            return Err(format!(
+                "transitions requires Array type, found {}",
+                other.type_str()
+            ));
+        }
+    };
+
+    let mut transitions = Vec::with_capacity(array.len());
+    for value in array {
+        let mut overrides = as_table(value, "transition")?;
+        let activate_at = match overrides.remove("activate_at") {
+            Some(value) => parse_u64(&value, "activate_at")?,
+            None => return Err("transition requires activate_at".into()),
+        };
+        // Reject unknown or mistyped parameters now rather than when the
+        // transition activates.
+        let mut spec = Spec::new_spec();
+        for (name, value) in &overrides {
+            set_spec_param(&mut spec, name, value)?;
+        }
+        transitions.push(SpecTransition {
+            activate_at,
+            overrides,
+        });
+    }
+    transitions.sort_by_key(|transition| transition.activate_at);
+    Ok(transitions)
+}
+
+fn parse_pow(value: Value) -> Result<ProofOfWorkConfig, String> {
+    let mut pow_config = ProofOfWorkConfig::new(false, None);
+    for (key, value) in as_table(value, "pow")? {
+        let name = key.as_str();
+        match name {
+            "test_mode" => pow_config.test_mode = parse_bool(&value, name)?,
+            "initial_difficulty" => {
+                pow_config.initial_difficulty = parse_u64(&value, name)?
+            }
+            "block_generation_period" => {
+                pow_config.block_generation_period = parse_u64(&value, name)?
+            }
+            "difficulty_adjustment_epoch_period" => {
+                pow_config.difficulty_adjustment_epoch_period =
+                    parse_u64(&value, name)?
+            }
+            _ => return Err(format!("unknown pow parameter {}", name)),
+        }
+    }
+    Ok(pow_config)
+}
+
+fn set_spec_param(
+    spec: &mut Spec, name: &str, value: &Value,
+) -> Result<(), String> {
+    macro_rules! set_spec_fields {
+        (bool: [$($b:ident),*], usize: [$($u:ident),*]) => {
+            match name {
+                $(stringify!($b) => spec.$b = parse_bool(value, name)?,)*
+                $(stringify!($u) => {
+                    spec.$u = parse_u64(value, name)? as usize
+                })*
+                _ => return Err(format!("unknown spec parameter {}", name)),
+            }
+        };
+    }
+
+    set_spec_fields!(
+        bool: [
+            exceptional_failed_code_deposit,
+            have_delegate_call,
+            have_create2,
+            have_revert,
+            have_extcodehash,
+            have_static_call,
+            have_return_data,
+            have_bitwise_shifting,
+            no_empty,
+            kill_empty,
+            keep_unsigned_nonce
+        ],
+        usize: [
+            stack_limit,
+            max_depth,
+            exp_gas,
+            exp_byte_gas,
+            sha3_gas,
+            sha3_word_gas,
+            sload_gas,
+            sstore_set_gas,
+            sstore_reset_gas,
+            sstore_refund_gas,
+            jumpdest_gas,
+            log_gas,
+            log_data_gas,
+            log_topic_gas,
+            create_gas,
+            call_gas,
+            call_stipend,
+            call_value_transfer_gas,
+            call_new_account_gas,
+            suicide_refund_gas,
+            memory_gas,
+            quad_coeff_div,
+            create_data_gas,
+            create_data_limit,
+            tx_gas,
+            tx_create_gas,
+            tx_data_zero_gas,
+            tx_data_non_zero_gas,
+            copy_gas,
+            extcodesize_gas,
+            extcodecopy_base_gas,
+            balance_gas,
+            extcodehash_gas,
+            suicide_gas,
+            suicide_to_new_account_cost,
+            blockhash_gas
+        ]
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChainSpec;
+    use crate::builtin::PricingSpec;
+    use cfx_types::{Address, U256};
+
+    #[test]
+    fn default_chain_spec() {
+        let spec = ChainSpec::from_toml_str("").unwrap();
+        assert_eq!(spec.params.network_id, 0);
+        assert_eq!(spec.params.chain_id, 1);
+        assert_eq!(spec.builtins.len(), 8);
+        assert!(spec.pow.is_none());
+        assert!(spec.accounts.is_none());
+
+        let machine = spec.machine();
+        let mut sha256 = Address::zero();
+        sha256[19] = 2;
+        assert!(machine.builtin(&sha256, 0).is_some());
+        assert_eq!(machine.spec(1000).sload_gas, 200);
+    }
+
+    #[test]
+    fn toml_chain_spec() {
+        let spec = ChainSpec::from_toml_str(
+            r#"
+            [params]
+            network_id = 10
+            chain_id = 10
+            min_gas_limit = "0x2000"
+            base_mining_reward = 7
+            anticone_penalty_ratio = 50
+
+            [pow]
+            test_mode = true
+            initial_difficulty = 4
+
+            [accounts]
+            "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b" = "1000"
+
+            [builtins."0x0000000000000000000000000000000000000004"]
+            name = "identity"
+            activate_at = 10
+            pricing = { linear = { base = 15, word = 3 } }
+
+            [[transitions]]
+            activate_at = 200
+            have_create2 = false
+
+            [[transitions]]
+            activate_at = 100
+            sload_gas = 800
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.params.network_id, 10);
+        assert_eq!(spec.params.chain_id, 10);
+        assert_eq!(spec.params.min_gas_limit, U256::from(0x2000));
+        assert_eq!(spec.params.base_mining_reward, 7);
+        assert_eq!(spec.params.anticone_penalty_ratio, 50);
+        let pow = spec.pow.unwrap();
+        assert!(pow.test_mode);
+        assert_eq!(pow.initial_difficulty, 4);
+        let address: Address =
+            "a94f5374fce5edbc8e2a8697c15331677e6ebf0b".parse().unwrap();
+        assert_eq!(
+            spec.accounts.as_ref().unwrap().get(&address),
+            Some(&U256::from(1000))
+        );
+        assert_eq!(spec.builtins.len(), 1);
+        assert_eq!(
+            spec.builtins[0].1.pricing,
+            PricingSpec::Linear { base: 15, word: 3 }
+        );
+
+        let machine = spec.machine();
+        let mut identity = Address::zero();
+        identity[19] = 4;
+        assert!(machine.builtin(&identity, 9).is_none());
+        assert!(machine.builtin(&identity, 10).is_some());
+        assert_eq!(machine.spec(99).sload_gas, 200);
+        assert_eq!(machine.spec(100).sload_gas, 800);
+        assert!(machine.spec(100).have_create2);
+        assert_eq!(machine.spec(200).sload_gas, 800);
+        assert!(!machine.spec(200).have_create2);
+    }
+
+    #[test]
+    fn json_chain_spec() {
+        let spec = ChainSpec::from_json_str(
+            r#"{
+                "params": { "chain_id": 3 },
+                "builtins": {
+                    "0x0000000000000000000000000000000000000005": {
+                        "name": "modexp",
+                        "pricing": { "modexp": { "divisor": 20 } }
+                    }
+                },
+                "transitions": [ { "activate_at": 5, "tx_gas": 30000 } ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.params.chain_id, 3);
+        assert_eq!(
+            spec.builtins[0].1.pricing,
+            PricingSpec::Modexp { divisor: 20 }
+        );
+        assert_eq!(spec.machine().spec(5).tx_gas, 30000);
+    }
+
+    #[test]
+    fn invalid_chain_spec() {
+        assert!(ChainSpec::from_toml_str("[params]\nfoo = 1").is_err());
+        assert!(ChainSpec::from_toml_str("[params]\nchain_id = -1").is_err());
+        assert!(ChainSpec::from_toml_str(
+            "[[transitions]]\nactivate_at = 1\nsload_gas = true"
+        )
+        .is_err());
+        assert!(ChainSpec::from_toml_str(
+            "[builtins.\"0x0000000000000000000000000000000000000001\"]\n\
+             name = \"foo\"\npricing = { linear = { base = 1, word = 1 } }"
+        )
+        .is_err());
+        assert!(ChainSpec::from_json_str("{\"params\": null}").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{DEFERRED_STATE_EPOCH_COUNT, REWARD_EPOCH_COUNT};
+use cfx_types::H256;
+
+/// A trusted pivot block from which a new node syncs, instead of executing
+/// all epochs from the genesis block.
+///
+/// The node syncs the deferred state at the checkpoint, i.e. the state after
+/// the epoch `DEFERRED_STATE_EPOCH_COUNT` epochs before the checkpoint, whose
+/// root is in the checkpoint header. The rewards of the
+/// `REWARD_EPOCH_COUNT` epochs up to the state epoch are only paid in later
+/// epochs, so the blocks and receipts of these epochs are synced as well.
+/// Epochs after the state epoch are executed as usual.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub epoch_number: u64,
+    pub hash: H256,
+    /// The deferred state root in the header of the checkpoint.
+    pub state_root: H256,
+}
+
+impl Checkpoint {
+    pub fn new(
+        epoch_number: u64, hash: H256, state_root: H256,
+    ) -> Result<Self, String> {
+        let min_epoch_number = DEFERRED_STATE_EPOCH_COUNT + REWARD_EPOCH_COUNT;
+        if epoch_number < min_epoch_number {
+            return Err(format!(
+                "Checkpoint epoch number must be at least {}",
+                min_epoch_number
+            ));
+        }
+        Ok(Checkpoint {
+            epoch_number,
+            hash,
+            state_root,
+        })
+    }
+
+    /// The epoch whose state is synced. Epochs up to it are not executed.
+    pub fn state_epoch_number(&self) -> u64 {
+        self.epoch_number - DEFERRED_STATE_EPOCH_COUNT
+    }
+
+    /// The first epoch whose rewards are paid after the state epoch, so that
+    /// its blocks and receipts are needed to execute the later epochs.
+    pub fn first_reward_epoch_number(&self) -> u64 {
+        self.state_epoch_number() + 1 - REWARD_EPOCH_COUNT
+    }
+}
@@ -2,15 +2,18 @@ use crate::{
     block_data_manager::BlockDataManager,
     cache_manager::CacheId,
     consensus::{
-        ConsensusGraphInner, ANTICONE_PENALTY_RATIO, BASE_MINING_REWARD,
-        CONFLUX_TOKEN,
+        gas_price_oracle::{EpochFeeStats, GasPriceOracle},
+        ConsensusGraphInner, CONFLUX_TOKEN,
     },
     executive::{ExecutionError, Executive},
-    machine::new_machine,
+    machine::Machine,
     state::{CleanupMode, State},
     statedb::StateDb,
     storage::{state::StateTrait, state_manager::StateManagerTrait},
-    vm::{EnvInfo, Spec},
+    trace::{
+        ExecutiveTracer, ExecutiveVmTracer, NoopVmTracer, TransactionTrace,
+    },
+    vm::EnvInfo,
     vm_factory::VmFactory,
 };
 use cfx_types::{Address, H256, U256, U512};
@@ -30,6 +33,9 @@ use std::{
     thread::{self, JoinHandle},
 };
 
+/// The number of trie nodes visited by each batch of state pruning.
+const STATE_PRUNING_BATCH_SIZE: usize = 10_000;
+
 /// The struct includes all the information to compute rewards for old epochs
 #[derive(Debug)]
 pub struct RewardExecutionInfo {
@@ -42,6 +48,7 @@ pub struct RewardExecutionInfo {
 enum ExecutionTask {
     ExecuteEpoch(EpochExecutionTask),
     GetResult(GetExecutionResultTask),
+    DropStateOutside(H256),
     Stop,
 }
 
@@ -92,11 +99,17 @@ pub struct ConsensusExecutor {
 
 impl ConsensusExecutor {
     pub fn start(
-        data_man: Arc<BlockDataManager>, vm: VmFactory,
+        data_man: Arc<BlockDataManager>, vm: VmFactory, machine: Arc<Machine>,
         consensus_inner: Arc<RwLock<ConsensusGraphInner>>,
+        gas_price_oracle: Arc<GasPriceOracle>,
     ) -> Self
     {
-        let handler = Arc::new(ConsensusExecutionHandler::new(data_man, vm));
+        let handler = Arc::new(ConsensusExecutionHandler::new(
+            data_man,
+            vm,
+            machine,
+            gas_price_oracle,
+        ));
         let (sender, receiver) = channel();
 
         let executor = ConsensusExecutor {
@@ -126,6 +139,11 @@ impl ConsensusExecutor {
                                 handler.handle_epoch_execution(task)
                             },
                             None => {
+                                // Prune the states batch by batch while there
+                                // is nothing to execute.
+                                if handler.handle_state_pruning() {
+                                    continue;
+                                }
                                 debug!("No optimistic tasks to execute, block for new tasks");
                                 //  Even optimistic tasks are all finished, so we block and wait for
                                 //  new execution tasks.
@@ -185,6 +203,16 @@ impl ConsensusExecutor {
             .is_ok()
     }
 
+    /// Enqueue the pruning of states before the stable epoch `epoch_hash`.
+    /// The pruning is done by the execution thread so that it will not
+    /// interleave with the execution of epochs.
+    pub fn enqueue_drop_state_outside(&self, epoch_hash: H256) -> bool {
+        self.sender
+            .lock()
+            .send(ExecutionTask::DropStateOutside(epoch_hash))
+            .is_ok()
+    }
+
     /// Execute the epoch synchronously
     pub fn compute_epoch(&self, task: EpochExecutionTask) {
         self.handler.handle_epoch_execution(task)
@@ -196,6 +224,19 @@ impl ConsensusExecutor {
         self.handler.call_virtual(tx, epoch_id)
     }
 
+    pub fn trace_block(
+        &self, epoch_block_hashes: &Vec<H256>, block_hash: &H256,
+        transaction_index: Option<usize>, vm_trace: bool,
+    ) -> Result<Vec<TransactionTrace>, String>
+    {
+        self.handler.trace_block(
+            epoch_block_hashes,
+            block_hash,
+            transaction_index,
+            vm_trace,
+        )
+    }
+
     pub fn stop(&self) {
         self.sender
             .lock()
@@ -210,11 +251,22 @@ impl ConsensusExecutor {
 pub struct ConsensusExecutionHandler {
     data_man: Arc<BlockDataManager>,
     pub vm: VmFactory,
+    machine: Arc<Machine>,
+    gas_price_oracle: Arc<GasPriceOracle>,
 }
 
 impl ConsensusExecutionHandler {
-    pub fn new(data_man: Arc<BlockDataManager>, vm: VmFactory) -> Self {
-        ConsensusExecutionHandler { data_man, vm }
+    pub fn new(
+        data_man: Arc<BlockDataManager>, vm: VmFactory, machine: Arc<Machine>,
+        gas_price_oracle: Arc<GasPriceOracle>,
+    ) -> Self
+    {
+        ConsensusExecutionHandler {
+            data_man,
+            vm,
+            machine,
+            gas_price_oracle,
+        }
     }
 
     /// Return `false` if someting goes wrong, and we will break the working
@@ -244,6 +296,9 @@ impl ConsensusExecutionHandler {
                 self.handle_epoch_execution(task)
             }
             ExecutionTask::GetResult(task) => self.handle_get_result_task(task),
+            ExecutionTask::DropStateOutside(epoch_hash) => {
+                self.handle_drop_state_outside(epoch_hash)
+            }
             _ => {}
         }
         true
@@ -275,6 +330,29 @@ impl ConsensusExecutionHandler {
             .expect("Consensus Worker fails");
     }
 
+    fn handle_drop_state_outside(&self, epoch_hash: H256) {
+        if let Err(e) =
+            self.data_man.storage_manager.drop_state_outside(epoch_hash)
+        {
+            warn!("Fail to drop state outside {:?}: {:?}", epoch_hash, e);
+        }
+    }
+
+    /// Prune a batch of trie nodes. Returns true if pruning is not finished.
+    fn handle_state_pruning(&self) -> bool {
+        match self
+            .data_man
+            .storage_manager
+            .prune_trie_nodes(STATE_PRUNING_BATCH_SIZE)
+        {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Fail to prune trie nodes: {:?}", e);
+                false
+            }
+        }
+    }
+
     /// Compute the epoch `epoch_hash`, and skip it if already computed.
     /// After the function is called, it's assured that the state, the receipt
     /// root, and the receipts of blocks executed by this epoch exist.
@@ -301,6 +379,24 @@ impl ConsensusExecutionHandler {
             return;
         }
 
+        // A node synced from a checkpoint has no state before the state epoch
+        // of the checkpoint, and the state of the state epoch itself is synced
+        // from peers along with the receipts of the epochs before it.
+        if let Some(ref checkpoint) = self.data_man.checkpoint {
+            let height = self
+                .data_man
+                .block_header_by_hash(epoch_hash)
+                .expect("pivot header exists")
+                .height();
+            if height <= checkpoint.state_epoch_number() {
+                if self.data_man.get_receipts_root(epoch_hash).is_none() {
+                    self.restore_receipts_root(epoch_hash, epoch_block_hashes);
+                }
+                debug!("Skip execution before checkpoint {:?}", epoch_hash);
+                return;
+            }
+        }
+
         // Get blocks in this epoch after skip checking
         let epoch_blocks = self
             .data_man
@@ -368,6 +464,27 @@ impl ConsensusExecutionHandler {
         );
     }
 
+    /// Compute the receipts root of an epoch from the stored receipts of its
+    /// blocks, if all of them exist.
+    fn restore_receipts_root(
+        &self, epoch_hash: &H256, epoch_block_hashes: &Vec<H256>,
+    ) {
+        let mut epoch_receipts = Vec::with_capacity(epoch_block_hashes.len());
+        for hash in epoch_block_hashes {
+            match self
+                .data_man
+                .block_results_by_hash_with_epoch(hash, epoch_hash, true)
+            {
+                Some(result) => epoch_receipts.push(result.receipts),
+                None => return,
+            }
+        }
+        self.data_man.insert_receipts_root(
+            *epoch_hash,
+            BlockHeaderBuilder::compute_block_receipts_root(&epoch_receipts),
+        );
+    }
+
     fn process_epoch_transactions(
         &self, state: &mut State, epoch_blocks: &Vec<Arc<Block>>,
         unexecuted_transaction_addresses_lock: &Mutex<
@@ -377,11 +494,12 @@ impl ConsensusExecutionHandler {
     ) -> Vec<Arc<Vec<Receipt>>>
     {
         let pivot_block = epoch_blocks.last().expect("Epoch not empty");
-        let spec = Spec::new_spec();
-        let machine = new_machine();
         let mut epoch_receipts = Vec::with_capacity(epoch_blocks.len());
         let mut to_pending = Vec::new();
+        let mut epoch_gas_limit = U256::zero();
+        let mut epoch_gas_prices = Vec::new();
         for block in epoch_blocks.iter() {
+            epoch_gas_limit += *block.block_header.gas_limit();
             let mut receipts = Vec::new();
             debug!(
                 "process txs in block: hash={:?}, tx count={:?}",
@@ -396,12 +514,14 @@ impl ConsensusExecutionHandler {
                 gas_used: U256::zero(),
                 gas_limit: U256::from(block.block_header.gas_limit()),
             };
+            let spec = self.machine.spec(pivot_block.block_header.height());
             let mut accumulated_fee: U256 = 0.into();
-            let mut ex = Executive::new(state, &mut env, &machine, &spec);
+            let mut ex = Executive::new(state, &mut env, &self.machine, &spec);
             let mut n_invalid_nonce = 0;
             let mut n_ok = 0;
             let mut n_other = 0;
             let mut last_cumulative_gas_used = U256::zero();
+            let mut tx_execution_errors = Vec::new();
             {
                 // TODO We acquire the lock at the start to avoid acquiring it
                 // for every tx. But if the server does not need
@@ -416,6 +536,14 @@ impl ConsensusExecutionHandler {
                     let mut transaction_logs = Vec::new();
 
                     let r = ex.transact(transaction);
+                    tx_execution_errors.push(match r {
+                        Ok(ref executed) => executed
+                            .exception
+                            .as_ref()
+                            .map(|e| e.to_string())
+                            .unwrap_or_default(),
+                        Err(ref e) => e.to_string(),
+                    });
                     // TODO Store fine-grained output status in receipts.
                     // Note now NotEnoughCash has
                     // outcome_status=TRANSACTION_OUTCOME_EXCEPTION,
@@ -426,6 +554,7 @@ impl ConsensusExecutionHandler {
                             got: _,
                         })
                         | Err(ExecutionError::SenderMustExist {})
+                        | Err(ExecutionError::InvalidChainId { .. })
                         | Err(ExecutionError::Internal(_)) => {
                             warn!(
                                     "tx execution error: transaction={:?}, err={:?}",
@@ -449,6 +578,10 @@ impl ConsensusExecutionHandler {
                             last_cumulative_gas_used =
                                 executed.cumulative_gas_used;
                             n_ok += 1;
+                            epoch_gas_prices.push((
+                                *transaction.gas_price(),
+                                executed.gas_used,
+                            ));
                             trace!("tx executed successfully: transaction={:?}, result={:?}, in block {:?}", transaction, executed, block.hash());
                             accumulated_fee += executed.fee;
                             transaction_logs = executed.logs;
@@ -509,6 +642,7 @@ impl ConsensusExecutionHandler {
                 block.hash(),
                 pivot_block.hash(),
                 block_receipts.clone(),
+                Arc::new(tx_execution_errors),
                 on_local_pivot,
             );
             epoch_receipts.push(block_receipts);
@@ -522,6 +656,12 @@ impl ConsensusExecutionHandler {
             BlockHeaderBuilder::compute_block_receipts_root(&epoch_receipts),
         );
         if on_local_pivot {
+            self.gas_price_oracle.on_epoch_executed(EpochFeeStats::new(
+                pivot_block.block_header.height(),
+                pivot_block.hash(),
+                epoch_gas_limit,
+                epoch_gas_prices,
+            ));
             let parent = pivot_block.block_header.parent_hash();
             if *parent != self.data_man.genesis_block().hash() {
                 let state = self
@@ -643,7 +783,8 @@ impl ConsensusExecutionHandler {
 
             let mut reward: U512 =
                 if block.block_header.pow_quality >= difficulty {
-                    U512::from(BASE_MINING_REWARD) * U512::from(CONFLUX_TOKEN)
+                    U512::from(self.machine.params().base_mining_reward)
+                        * U512::from(CONFLUX_TOKEN)
                 } else {
                     debug!(
                         "Block {} pow_quality {} is less than difficulty {}!",
@@ -659,13 +800,15 @@ impl ConsensusExecutionHandler {
 
             if reward > 0.into() {
                 let anticone_difficulty = epoch_block_states[idx].1;
+                let penalty_ratio =
+                    U512::from(self.machine.params().anticone_penalty_ratio);
 
                 let penalty = reward * anticone_difficulty
                     / U512::from(block_difficulty)
                     * anticone_difficulty
                     / U512::from(block_difficulty)
-                    / U512::from(ANTICONE_PENALTY_RATIO)
-                    / U512::from(ANTICONE_PENALTY_RATIO);
+                    / penalty_ratio
+                    / penalty_ratio;
 
                 if penalty > reward {
                     debug!("Block {} penalty {} larger than reward {}! anticone_difficulty={}", block_hash, penalty, reward, anticone_difficulty);
@@ -722,8 +865,6 @@ impl ConsensusExecutionHandler {
     pub fn call_virtual(
         &self, tx: &SignedTransaction, epoch_id: &H256,
     ) -> Result<(Vec<u8>, U256), String> {
-        let spec = Spec::new_spec();
-        let machine = new_machine();
         let mut state = State::new(
             StateDb::new(
                 self.data_man
@@ -742,10 +883,102 @@ impl ConsensusExecutionHandler {
             gas_used: U256::zero(),
             gas_limit: tx.gas.clone(),
         };
-        let mut ex = Executive::new(&mut state, &mut env, &machine, &spec);
+        let epoch_height = self
+            .data_man
+            .block_header_by_hash(epoch_id)
+            .map_or(0, |header| header.height());
+        let spec = self.machine.spec(epoch_height);
+        let mut ex = Executive::new(&mut state, &mut env, &self.machine, &spec);
         let r = ex.transact(tx);
         trace!("Execution result {:?}", r);
         r.map(|r| (r.output, r.gas_used))
             .map_err(|e| format!("execution error: {:?}", e))
     }
+
+    /// Re-execute the epoch `epoch_block_hashes` on the state before it
+    /// without committing, and return the traces of the transactions in the
+    /// block `block_hash`. If `transaction_index` is given, only the
+    /// transaction at the index in the block is traced.
+    pub fn trace_block(
+        &self, epoch_block_hashes: &Vec<H256>, block_hash: &H256,
+        transaction_index: Option<usize>, vm_trace: bool,
+    ) -> Result<Vec<TransactionTrace>, String>
+    {
+        let epoch_blocks = self
+            .data_man
+            .blocks_by_hash_list(epoch_block_hashes, false)
+            .ok_or("Blocks of the epoch are not found")?;
+        let pivot_block = epoch_blocks.last().expect("Epoch not empty");
+        let mut state = State::new(
+            StateDb::new(
+                self.data_man
+                    .storage_manager
+                    .get_state_at(*pivot_block.block_header.parent_hash())
+                    .map_err(|e| {
+                        format!("State before the epoch is not found: {:?}", e)
+                    })?,
+            ),
+            0.into(),
+            self.vm.clone(),
+        );
+
+        for block in epoch_blocks.iter() {
+            let mut env = EnvInfo {
+                number: 0, // TODO: replace 0 with correct cardinal number
+                author: block.block_header.author().clone(),
+                timestamp: block.block_header.timestamp(),
+                difficulty: block.block_header.difficulty().clone(),
+                gas_used: U256::zero(),
+                gas_limit: U256::from(block.block_header.gas_limit()),
+            };
+            let spec = self.machine.spec(pivot_block.block_header.height());
+            let mut ex =
+                Executive::new(&mut state, &mut env, &self.machine, &spec);
+            if block.hash() != *block_hash {
+                // Transactions before the block are executed to get the
+                // state the block is executed on.
+                for transaction in &block.transactions {
+                    ex.transact(transaction).ok();
+                }
+                continue;
+            }
+
+            let mut traces = Vec::new();
+            for (idx, transaction) in block.transactions.iter().enumerate() {
+                let traced = transaction_index.map_or(true, |i| i == idx);
+                if !traced {
+                    ex.transact(transaction).ok();
+                    continue;
+                }
+                let mut tracer = ExecutiveTracer::default();
+                let (result, vm_trace) = if vm_trace {
+                    let mut vm_tracer = ExecutiveVmTracer::toplevel();
+                    let result = ex.transact_with_tracer(
+                        transaction,
+                        &mut tracer,
+                        &mut vm_tracer,
+                    );
+                    (result, vm_tracer.drain())
+                } else {
+                    let result = ex.transact_with_tracer(
+                        transaction,
+                        &mut tracer,
+                        &mut NoopVmTracer,
+                    );
+                    (result, None)
+                };
+                traces.push(TransactionTrace {
+                    transaction_hash: transaction.hash(),
+                    error: result.err().map(|e| format!("{:?}", e)),
+                    call: tracer.drain().pop(),
+                    vm_trace,
+                });
+                if transaction_index.is_some() {
+                    break;
+                }
+            }
+            return Ok(traces);
+        }
+        Err(format!("Block {:?} is not in the epoch", block_hash))
+    }
 }
@@ -0,0 +1,242 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U256};
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+
+/// The number of recent pivot epochs whose fee statistics are kept.
+pub const GAS_PRICE_ORACLE_EPOCH_WINDOW: usize = 1024;
+/// The number of recent epochs sampled to suggest a gas price.
+const GAS_PRICE_SAMPLE_EPOCHS: usize = 20;
+
+/// Fee statistics of an epoch executed on the pivot chain.
+#[derive(Debug, Clone)]
+pub struct EpochFeeStats {
+    pub epoch_number: u64,
+    pub epoch_hash: H256,
+    /// The gas used by the transactions executed in the epoch.
+    pub gas_used: U256,
+    /// The sum of gas limits of the blocks in the epoch.
+    pub gas_limit: U256,
+    /// `(gas_price, gas_used)` of the executed transactions, sorted by gas
+    /// price.
+    prices: Vec<(U256, U256)>,
+}
+
+impl EpochFeeStats {
+    pub fn new(
+        epoch_number: u64, epoch_hash: H256, gas_limit: U256,
+        mut prices: Vec<(U256, U256)>,
+    ) -> Self
+    {
+        prices.sort();
+        let gas_used = prices
+            .iter()
+            .fold(U256::zero(), |sum, (_, gas_used)| sum + *gas_used);
+        EpochFeeStats {
+            epoch_number,
+            epoch_hash,
+            gas_used,
+            gas_limit,
+            prices,
+        }
+    }
+
+    pub fn gas_used_ratio(&self) -> f64 {
+        if self.gas_limit.is_zero() {
+            return 0.0;
+        }
+        self.gas_used.low_u64() as f64 / self.gas_limit.low_u64() as f64
+    }
+
+    /// The lowest gas price such that transactions paying at most this price
+    /// used `percentile` percent of the gas of the epoch. Returns zero if no
+    /// transaction is executed in the epoch.
+    pub fn gas_price_percentile(&self, percentile: f64) -> U256 {
+        let threshold = self.gas_used.low_u64() as f64 * percentile / 100.0;
+        let mut cumulative_gas_used = 0.0;
+        for (gas_price, gas_used) in &self.prices {
+            cumulative_gas_used += gas_used.low_u64() as f64;
+            if cumulative_gas_used >= threshold {
+                return *gas_price;
+            }
+        }
+        self.prices
+            .last()
+            .map_or(U256::zero(), |(gas_price, _)| *gas_price)
+    }
+}
+
+/// The gas price percentiles and gas utilization of consecutive epochs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeHistory {
+    /// The epoch number of the first entry.
+    pub oldest_epoch: u64,
+    pub gas_used_ratio: Vec<f64>,
+    /// The requested gas price percentiles of each epoch.
+    pub gas_price: Vec<Vec<U256>>,
+}
+
+/// Tracks the fee statistics of a rolling window of recent pivot epochs. It
+/// is updated incrementally by the consensus executor, so that gas price
+/// queries do not need to load block bodies.
+pub struct GasPriceOracle {
+    window: usize,
+    epochs: RwLock<VecDeque<EpochFeeStats>>,
+}
+
+impl GasPriceOracle {
+    pub fn new(window: usize) -> Self {
+        GasPriceOracle {
+            window,
+            epochs: RwLock::new(VecDeque::with_capacity(window)),
+        }
+    }
+
+    /// Record the statistics of a newly executed pivot epoch. The
+    /// statistics of epochs with the same or larger epoch numbers are from a
+    /// previous pivot chain and are discarded.
+    pub fn on_epoch_executed(&self, stats: EpochFeeStats) {
+        let mut epochs = self.epochs.write();
+        while epochs
+            .back()
+            .map_or(false, |last| last.epoch_number >= stats.epoch_number)
+        {
+            epochs.pop_back();
+        }
+        epochs.push_back(stats);
+        while epochs.len() > self.window {
+            epochs.pop_front();
+        }
+    }
+
+    /// The median gas price of the transactions in recent epochs, or `None`
+    /// if no statistics are recorded yet.
+    pub fn gas_price(&self) -> Option<U256> {
+        let epochs = self.epochs.read();
+        let mut prices: Vec<U256> = epochs
+            .iter()
+            .rev()
+            .take(GAS_PRICE_SAMPLE_EPOCHS)
+            .flat_map(|stats| stats.prices.iter().map(|(price, _)| *price))
+            .collect();
+        if prices.is_empty() {
+            return None;
+        }
+        prices.sort();
+        Some(prices[prices.len() / 2])
+    }
+
+    /// Returns the gas used ratio and the gas price `percentiles` of at most
+    /// `epoch_count` latest consecutive epochs.
+    pub fn fee_history(
+        &self, epoch_count: usize, percentiles: &[f64],
+    ) -> Result<FeeHistory, String> {
+        let mut last_percentile = 0.0;
+        for percentile in percentiles {
+            // NaN fails every comparison, so it is rejected explicitly.
+            if !percentile.is_finite()
+                || *percentile < last_percentile
+                || *percentile > 100.0
+            {
+                return Err(format!(
+                    "Invalid percentiles {:?}: they must be increasing \
+                     values in [0, 100]",
+                    percentiles
+                ));
+            }
+            last_percentile = *percentile;
+        }
+
+        let epochs = self.epochs.read();
+        let mut selected: Vec<&EpochFeeStats> = Vec::new();
+        for stats in epochs.iter().rev().take(epoch_count) {
+            if let Some(next) = selected.last() {
+                if stats.epoch_number + 1 != next.epoch_number {
+                    break;
+                }
+            }
+            selected.push(stats);
+        }
+        selected.reverse();
+
+        Ok(FeeHistory {
+            oldest_epoch: selected.first().map_or(
+                epochs.back().map_or(0, |last| last.epoch_number + 1),
+                |stats| stats.epoch_number,
+            ),
+            gas_used_ratio: selected
+                .iter()
+                .map(|stats| stats.gas_used_ratio())
+                .collect(),
+            gas_price: selected
+                .iter()
+                .map(|stats| {
+                    percentiles
+                        .iter()
+                        .map(|p| stats.gas_price_percentile(*p))
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EpochFeeStats, GasPriceOracle};
+    use cfx_types::U256;
+
+    fn new_stats(epoch_number: u64, prices: &[(u64, u64)]) -> EpochFeeStats {
+        EpochFeeStats::new(
+            epoch_number,
+            epoch_number.into(),
+            U256::from(100_000),
+            prices
+                .iter()
+                .map(|(price, gas)| (U256::from(*price), U256::from(*gas)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_gas_price_percentile() {
+        let stats = new_stats(1, &[(30, 21000), (10, 21000), (20, 42000)]);
+        assert_eq!(stats.gas_used, U256::from(84000));
+        assert_eq!(stats.gas_used_ratio(), 0.84);
+        assert_eq!(stats.gas_price_percentile(0.0), U256::from(10));
+        assert_eq!(stats.gas_price_percentile(25.0), U256::from(10));
+        assert_eq!(stats.gas_price_percentile(50.0), U256::from(20));
+        assert_eq!(stats.gas_price_percentile(100.0), U256::from(30));
+        assert_eq!(new_stats(2, &[]).gas_price_percentile(50.0), 0.into());
+    }
+
+    #[test]
+    fn test_fee_history() {
+        let oracle = GasPriceOracle::new(3);
+        assert_eq!(oracle.gas_price(), None);
+        for epoch in 1..5 {
+            oracle.on_epoch_executed(new_stats(epoch, &[(epoch, 21000)]));
+        }
+        // A pivot chain switch at epoch 3.
+        oracle.on_epoch_executed(new_stats(3, &[(7, 21000)]));
+        assert_eq!(oracle.gas_price(), Some(U256::from(7)));
+
+        // Epoch 1 is out of the window.
+        let history = oracle.fee_history(5, &[50.0]).unwrap();
+        assert_eq!(history.oldest_epoch, 2);
+        assert_eq!(history.gas_used_ratio, vec![0.21; 2]);
+        assert_eq!(
+            history.gas_price,
+            vec![vec![U256::from(2)], vec![7.into()]]
+        );
+        assert_eq!(oracle.fee_history(1, &[]).unwrap().oldest_epoch, 3);
+        assert!(oracle.fee_history(1, &[50.0, 10.0]).is_err());
+        assert!(oracle.fee_history(1, &[101.0]).is_err());
+        assert!(oracle.fee_history(1, &[std::f64::NAN]).is_err());
+        assert!(oracle.fee_history(1, &[10.0, std::f64::NAN]).is_err());
+        assert!(oracle.fee_history(1, &[std::f64::INFINITY]).is_err());
+    }
+}
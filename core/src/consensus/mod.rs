@@ -2,22 +2,39 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+mod checkpoint;
 mod consensus_executor;
+pub mod gas_price_oracle;
+
+pub use self::checkpoint::Checkpoint;
 use super::consensus::consensus_executor::ConsensusExecutor;
 use crate::{
-    block_data_manager::BlockDataManager,
+    block_data_manager::{BlockDataManager, BlockExecutedResult},
+    bytes::Bytes,
     cache_manager::{CacheId, CacheManager},
-    consensus::consensus_executor::{EpochExecutionTask, RewardExecutionInfo},
+    consensus::{
+        consensus_executor::{EpochExecutionTask, RewardExecutionInfo},
+        gas_price_oracle::{
+            FeeHistory, GasPriceOracle, GAS_PRICE_ORACLE_EPOCH_WINDOW,
+        },
+    },
     db::COL_MISC,
+    executive::contract_address,
     ext_db::SystemDB,
     hash::KECCAK_EMPTY_LIST_RLP,
+    machine::Machine,
+    notifications::{Notification, SharedNotifications},
     pow::ProofOfWorkConfig,
     state::State,
-    statedb::StateDb,
+    statedb::{StateDb, StorageKey},
     statistics::SharedStatistics,
-    storage::{state::StateTrait, StorageManager, StorageManagerTrait},
+    storage::{
+        state::StateTrait, StorageManager, StorageManagerTrait, TrieProof,
+    },
     sync::SynchronizationGraphInner,
+    trace::TransactionTrace,
     transaction_pool::SharedTransactionPool,
+    vm::CreateContractAddress,
     vm_factory::VmFactory,
 };
 use cfx_types::{Bloom, H160, H256, U256, U512};
@@ -26,9 +43,9 @@ use parking_lot::{Mutex, RwLock};
 use primitives::{
     filter::{Filter, FilterError},
     log_entry::{LocalizedLogEntry, LogEntry},
-    receipt::Receipt,
+    receipt::{LocalizedReceipt, Receipt, TRANSACTION_OUTCOME_SUCCESS},
     transaction::Action,
-    Block, BlockHeaderBuilder, EpochNumber, SignedTransaction,
+    Account, Block, BlockHeaderBuilder, EpochNumber, SignedTransaction,
     TransactionAddress,
 };
 use rayon::prelude::*;
@@ -53,9 +70,10 @@ pub const DEFERRED_STATE_EPOCH_COUNT: u64 = 5;
 /// lead to performance downgrade
 const REWARD_EPOCH_COUNT: u64 = 12;
 const ANTICONE_PENALTY_UPPER_EPOCH_COUNT: u64 = 10;
-const ANTICONE_PENALTY_RATIO: u64 = 100;
-/// 900 Conflux tokens
-const BASE_MINING_REWARD: u64 = 900;
+/// The pivot block of a stable epoch outweighs the other children of its
+/// parent by this many times its difficulty, so that the pivot chain is not
+/// expected to switch at or before it anymore.
+pub const STABLE_EPOCH_WEIGHT_MARGIN: u64 = 100;
 /// The unit of one Conflux token: 10 ** 18
 const CONFLUX_TOKEN: u64 = 1_000_000_000_000_000_000;
 const GAS_PRICE_BLOCK_SAMPLE_SIZE: usize = 100;
@@ -123,6 +141,12 @@ pub struct ConsensusGraphInner {
     pow_config: ProofOfWorkConfig,
     pub current_difficulty: U256,
     data_man: Arc<BlockDataManager>,
+    /// The pivot chain height of the last stable epoch. It's only maintained
+    /// when state pruning is enabled.
+    stable_height: usize,
+    /// The pivot chain height of the last epoch passed to state pruning. The
+    /// states of the pivot blocks before it are pruned.
+    state_pruned_height: usize,
 }
 
 impl ConsensusGraphInner {
@@ -151,6 +175,8 @@ impl ConsensusGraphInner {
             pow_config,
             current_difficulty: pow_config.initial_difficulty.into(),
             data_man: data_man.clone(),
+            stable_height: 0,
+            state_pruned_height: 0,
         };
 
         // NOTE: Only genesis block will be first inserted into consensus graph
@@ -440,6 +466,24 @@ impl ConsensusGraphInner {
         valid
     }
 
+    /// Whether the pivot block at `height` of `pivot_chain` outweighs the
+    /// other children of its parent by `STABLE_EPOCH_WEIGHT_MARGIN` times
+    /// its difficulty.
+    fn is_stable_pivot_block(
+        &mut self, pivot_chain: &[usize], height: usize,
+    ) -> bool {
+        let pivot = pivot_chain[height];
+        let mut fork_weight = U256::zero();
+        for child in &self.arena[self.arena[pivot].parent].children {
+            if *child != pivot {
+                fork_weight += self.weight_tree.subtree_weight(*child);
+            }
+        }
+        let margin = self.arena[pivot].difficulty
+            * U256::from(STABLE_EPOCH_WEIGHT_MARGIN);
+        self.weight_tree.subtree_weight(pivot) >= fork_weight + margin
+    }
+
     pub fn compute_anticone(&mut self, me: usize) {
         let parent = self.arena[me].parent;
         debug_assert!(parent != NULL);
@@ -782,6 +826,52 @@ impl ConsensusGraphInner {
         )
     }
 
+    pub fn get_account_proof(
+        &self, address: H160, storage_keys: &Vec<H256>,
+        epoch_number: EpochNumber,
+    ) -> Result<AccountProof, String>
+    {
+        let hash = self.get_hash_from_epoch_number(epoch_number)?;
+        self.get_account_proof_by_epoch_hash(address, storage_keys, hash)
+    }
+
+    /// Same as `get_account_proof`, except that the epoch is given by the
+    /// hash of its pivot block.
+    pub fn get_account_proof_by_epoch_hash(
+        &self, address: H160, storage_keys: &Vec<H256>, hash: H256,
+    ) -> Result<AccountProof, String> {
+        let state = self
+            .data_man
+            .storage_manager
+            .get_state_at(hash)
+            .map_err(|e| format!("{}", e))?;
+        let state_root = state
+            .get_state_root()
+            .map_err(|e| format!("{}", e))?
+            .ok_or_else(|| format!("State of epoch {:?} not found", hash))?;
+
+        let (account, account_proof) = state
+            .get_with_proof(StorageKey::new_account_key(&address).as_ref())
+            .map_err(|e| format!("{}", e))?;
+        let mut storage_proofs = Vec::with_capacity(storage_keys.len());
+        for key in storage_keys {
+            let (value, proof) = state
+                .get_with_proof(
+                    StorageKey::new_storage_key(&address, key).as_ref(),
+                )
+                .map_err(|e| format!("{}", e))?;
+            storage_proofs.push((*key, value, proof));
+        }
+
+        Ok(AccountProof {
+            epoch_hash: hash,
+            state_root,
+            account,
+            account_proof,
+            storage_proofs,
+        })
+    }
+
     pub fn terminal_hashes(&self) -> Vec<H256> {
         self.terminal_hashes
             .iter()
@@ -818,6 +908,7 @@ impl ConsensusGraphInner {
     fn validate_stated_epoch(
         &self, epoch_number: &EpochNumber,
     ) -> Result<(), String> {
+        let state_pruned_height = self.state_pruned_height;
         match epoch_number {
             EpochNumber::LatestMined => {
                 return Err("Latest mined epoch is not executed".into());
@@ -827,6 +918,22 @@ impl ConsensusGraphInner {
                 if num.as_usize() > latest_state_epoch {
                     return Err(format!("Specified epoch {} is not executed, the latest state epoch is {}", num, latest_state_epoch));
                 }
+                if num.as_usize() < state_pruned_height {
+                    return Err(format!(
+                        "The state of epoch {} is pruned, the earliest state \
+                         epoch is {}",
+                        num, state_pruned_height
+                    ));
+                }
+            }
+            EpochNumber::Earliest => {
+                if state_pruned_height > 0 {
+                    return Err(format!(
+                        "The state of the earliest epoch is pruned, the \
+                         earliest state epoch is {}",
+                        state_pruned_height
+                    ));
+                }
             }
             _ => {}
         }
@@ -834,17 +941,102 @@ impl ConsensusGraphInner {
         Ok(())
     }
 
-    pub fn block_receipts_by_hash(
+    pub fn block_results_by_hash(
         &self, hash: &H256, update_cache: bool,
-    ) -> Option<Arc<Vec<Receipt>>> {
+    ) -> Option<BlockExecutedResult> {
         self.get_epoch_hash_for_block(hash).and_then(|epoch| {
             trace!("Block {} is in epoch {}", hash, epoch);
             self.data_man
                 .block_results_by_hash_with_epoch(hash, &epoch, update_cache)
-                .map(|r| r.receipts)
         })
     }
 
+    pub fn block_receipts_by_hash(
+        &self, hash: &H256, update_cache: bool,
+    ) -> Option<Arc<Vec<Receipt>>> {
+        self.block_results_by_hash(hash, update_cache)
+            .map(|r| r.receipts)
+    }
+
+    /// Return the receipts of the transactions in `block` together with the
+    /// information about where they are executed, or `None` if the block is
+    /// not executed yet.
+    pub fn block_localized_receipts(
+        &self, block: &Block, update_cache: bool,
+    ) -> Option<Vec<LocalizedReceipt>> {
+        let block_hash = block.hash();
+        let epoch_number = self.get_block_epoch_number(&block_hash)?;
+        let results = self.block_results_by_hash(&block_hash, update_cache)?;
+        let mut prior_gas_used = U256::zero();
+        let mut log_index = 0;
+        let mut localized_receipts = Vec::new();
+        for (index, (tx, receipt)) in block
+            .transactions
+            .iter()
+            .zip(results.receipts.iter())
+            .enumerate()
+        {
+            let failure_reason = results
+                .tx_execution_errors
+                .get(index)
+                .filter(|error| !error.is_empty())
+                .cloned();
+            let contract_created = match tx.action {
+                Action::Create
+                    if receipt.outcome_status
+                        == TRANSACTION_OUTCOME_SUCCESS
+                        && failure_reason.is_none() =>
+                {
+                    Some(
+                        contract_address(
+                            CreateContractAddress::FromSenderAndNonce,
+                            &tx.sender(),
+                            &tx.nonce,
+                            &tx.data,
+                        )
+                        .0,
+                    )
+                }
+                _ => None,
+            };
+            let logs = receipt
+                .logs
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, entry)| LocalizedLogEntry {
+                    entry,
+                    block_hash,
+                    transaction_hash: tx.hash(),
+                    transaction_index: index,
+                    log_index: log_index + i,
+                    transaction_log_index: i,
+                })
+                .collect();
+            log_index += receipt.logs.len();
+            localized_receipts.push(LocalizedReceipt {
+                transaction_hash: tx.hash(),
+                transaction_index: index,
+                block_hash,
+                epoch_number,
+                from: tx.sender(),
+                to: match tx.action {
+                    Action::Create => None,
+                    Action::Call(ref address) => Some(*address),
+                },
+                gas_used: receipt.gas_used.saturating_sub(prior_gas_used),
+                cumulative_gas_used: receipt.gas_used,
+                contract_created,
+                logs,
+                log_bloom: receipt.log_bloom,
+                outcome_status: receipt.outcome_status,
+                failure_reason,
+            });
+            prior_gas_used = receipt.gas_used;
+        }
+        Some(localized_receipts)
+    }
+
     pub fn get_transaction_receipt_with_address(
         &self, tx_hash: &H256,
     ) -> Option<(Receipt, TransactionAddress)> {
@@ -863,21 +1055,74 @@ impl ConsensusGraphInner {
         ))
     }
 
-    pub fn transaction_count(
-        &self, address: H160, epoch_number: EpochNumber,
-    ) -> Result<U256, String> {
+    /// Return the state after executing the given epoch.
+    fn get_state_db_by_epoch_number(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<StateDb, String> {
         self.validate_stated_epoch(&epoch_number)?;
 
         let hash = self.get_hash_from_epoch_number(epoch_number)?;
-        let state_db = StateDb::new(
-            self.data_man.storage_manager.get_state_at(hash).unwrap(),
-        );
-        let state = State::new(state_db, 0.into(), Default::default());
-        state
+        let state = self
+            .data_man
+            .storage_manager
+            .get_state_at(hash)
+            .map_err(|e| format!("Failed to get the state: {}", e))?;
+        if !state.does_exist() {
+            return Err(format!(
+                "The state of epoch {:?} does not exist",
+                hash
+            ));
+        }
+        Ok(StateDb::new(state))
+    }
+
+    fn get_state_by_epoch_number(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<State, String> {
+        Ok(State::new(
+            self.get_state_db_by_epoch_number(epoch_number)?,
+            0.into(),
+            Default::default(),
+        ))
+    }
+
+    pub fn transaction_count(
+        &self, address: H160, epoch_number: EpochNumber,
+    ) -> Result<U256, String> {
+        self.get_state_by_epoch_number(epoch_number)?
             .nonce(&address)
             .map_err(|err| format!("Get transaction count error: {:?}", err))
     }
 
+    /// Return the code of the account, which is empty if the account doesn't
+    /// exist or isn't a contract.
+    pub fn get_code(
+        &self, address: H160, epoch_number: EpochNumber,
+    ) -> Result<Bytes, String> {
+        self.get_state_by_epoch_number(epoch_number)?
+            .code(&address)
+            .map(|code| code.map_or_else(Vec::new, |code| (*code).clone()))
+            .map_err(|err| format!("Get code error: {:?}", err))
+    }
+
+    /// Return the value at the storage key of the account, which is zero if
+    /// the key is not set.
+    pub fn get_storage_at(
+        &self, address: H160, key: H256, epoch_number: EpochNumber,
+    ) -> Result<H256, String> {
+        self.get_state_by_epoch_number(epoch_number)?
+            .storage_at(&address, &key)
+            .map_err(|err| format!("Get storage error: {:?}", err))
+    }
+
+    pub fn get_account(
+        &self, address: H160, epoch_number: EpochNumber,
+    ) -> Result<Option<Account>, String> {
+        self.get_state_db_by_epoch_number(epoch_number)?
+            .get_account(&address, false)
+            .map_err(|err| format!("Get account error: {:?}", err))
+    }
+
     pub fn get_balance_validated(
         &self, address: H160, epoch_number: EpochNumber,
     ) -> Result<U256, String> {
@@ -885,6 +1130,15 @@ impl ConsensusGraphInner {
         self.get_balance(address, epoch_number)
     }
 
+    pub fn get_account_proof_validated(
+        &self, address: H160, storage_keys: &Vec<H256>,
+        epoch_number: EpochNumber,
+    ) -> Result<AccountProof, String>
+    {
+        self.validate_stated_epoch(&epoch_number)?;
+        self.get_account_proof(address, storage_keys, epoch_number)
+    }
+
     pub fn check_block_pivot_assumption(
         &self, pivot_hash: &H256, epoch: usize,
     ) -> Result<(), String> {
@@ -915,6 +1169,18 @@ impl ConsensusGraphInner {
     }
 }
 
+/// The values and proofs of an account and its storage entries, which can be
+/// verified against the state root of the epoch.
+pub struct AccountProof {
+    pub epoch_hash: H256,
+    pub state_root: H256,
+    /// The rlp of the account, or None if the account doesn't exist.
+    pub account: Option<Box<[u8]>>,
+    pub account_proof: TrieProof,
+    /// (storage key, rlp of the value, proof) for each storage key.
+    pub storage_proofs: Vec<(H256, Option<Box<[u8]>>, TrieProof)>,
+}
+
 pub struct ConsensusGraph {
     pub inner: Arc<RwLock<ConsensusGraphInner>>,
     pub txpool: SharedTransactionPool,
@@ -922,6 +1188,9 @@ pub struct ConsensusGraph {
     pub invalid_blocks: RwLock<HashSet<H256>>,
     executor: Arc<ConsensusExecutor>,
     pub statistics: SharedStatistics,
+    pub machine: Arc<Machine>,
+    notifications: SharedNotifications,
+    gas_price_oracle: Arc<GasPriceOracle>,
 }
 
 pub type SharedConsensusGraph = Arc<ConsensusGraph>;
@@ -932,7 +1201,8 @@ impl ConsensusGraph {
         vm: VmFactory, txpool: SharedTransactionPool,
         statistics: SharedStatistics, db: Arc<SystemDB>,
         cache_man: Arc<Mutex<CacheManager<CacheId>>>,
-        pow_config: ProofOfWorkConfig,
+        pow_config: ProofOfWorkConfig, machine: Arc<Machine>,
+        notifications: SharedNotifications, checkpoint: Option<Checkpoint>,
     ) -> Self
     {
         let data_man = Arc::new(BlockDataManager::new(
@@ -941,16 +1211,21 @@ impl ConsensusGraph {
             db,
             storage_manager,
             cache_man,
+            checkpoint,
         ));
         let inner =
             Arc::new(RwLock::new(ConsensusGraphInner::with_genesis_block(
                 pow_config,
                 data_man.clone(),
             )));
+        let gas_price_oracle =
+            Arc::new(GasPriceOracle::new(GAS_PRICE_ORACLE_EPOCH_WINDOW));
         let executor = Arc::new(ConsensusExecutor::start(
             data_man.clone(),
             vm,
+            machine.clone(),
             inner.clone(),
+            gas_price_oracle.clone(),
         ));
 
         ConsensusGraph {
@@ -960,6 +1235,9 @@ impl ConsensusGraph {
             invalid_blocks: RwLock::new(HashSet::new()),
             executor,
             statistics,
+            machine,
+            notifications,
+            gas_price_oracle,
         }
     }
 
@@ -1003,7 +1281,25 @@ impl ConsensusGraph {
         self.inner.read().get_block_epoch_number(hash)
     }
 
+    /// The median gas price of the transactions in recent epochs.
     pub fn gas_price(&self) -> Option<U256> {
+        self.gas_price_oracle
+            .gas_price()
+            .or_else(|| self.sample_gas_price())
+    }
+
+    /// Returns the gas used ratio and the gas price `percentiles` of at most
+    /// `epoch_count` latest executed pivot epochs.
+    pub fn fee_history(
+        &self, epoch_count: usize, percentiles: &[f64],
+    ) -> Result<FeeHistory, String> {
+        self.gas_price_oracle.fee_history(epoch_count, percentiles)
+    }
+
+    /// Sample the gas prices from the bodies of recent blocks. It is used
+    /// before the gas price oracle has seen any executed epochs, e.g. right
+    /// after a restart.
+    fn sample_gas_price(&self) -> Option<U256> {
         let inner = self.inner.read();
         let mut last_epoch_number = inner.best_epoch_number();
         let mut number_of_blocks_to_sample = GAS_PRICE_BLOCK_SAMPLE_SIZE;
@@ -1058,6 +1354,52 @@ impl ConsensusGraph {
             .get_balance_validated(address, epoch_number)
     }
 
+    pub fn get_account_proof(
+        &self, address: H160, storage_keys: &Vec<H256>,
+        epoch_number: EpochNumber,
+    ) -> Result<AccountProof, String>
+    {
+        self.inner.read().get_account_proof_validated(
+            address,
+            storage_keys,
+            epoch_number,
+        )
+    }
+
+    pub fn get_account_proof_by_epoch_hash(
+        &self, address: H160, storage_keys: &Vec<H256>, epoch_hash: H256,
+    ) -> Result<AccountProof, String> {
+        self.inner.read().get_account_proof_by_epoch_hash(
+            address,
+            storage_keys,
+            epoch_hash,
+        )
+    }
+
+    /// Return the hashes of the blocks in the epoch whose pivot block is
+    /// `epoch_hash` in execution order, and their receipts. Returns `None` if
+    /// the block is not on the pivot chain or the epoch is not executed.
+    pub fn get_epoch_receipts(
+        &self, epoch_hash: &H256,
+    ) -> Option<(Vec<H256>, Vec<Arc<Vec<Receipt>>>)> {
+        let inner = self.inner.read();
+        let index = *inner.indices.get(epoch_hash)?;
+        let epoch_number = *inner.arena[index].data.epoch_number.borrow();
+        if inner.pivot_chain.get(epoch_number) != Some(&index) {
+            return None;
+        }
+        let block_hashes = inner.get_epoch_block_hashes(index);
+        let mut receipts = Vec::with_capacity(block_hashes.len());
+        for hash in &block_hashes {
+            receipts.push(
+                self.data_man
+                    .block_results_by_hash_with_epoch(hash, epoch_hash, false)?
+                    .receipts,
+            );
+        }
+        Some((block_hashes, receipts))
+    }
+
     pub fn get_related_transactions(
         &self, address: H160, num_txs: usize, epoch_number: EpochNumber,
     ) -> Result<Vec<Arc<SignedTransaction>>, String> {
@@ -1345,6 +1687,31 @@ impl ConsensusGraph {
         self.compute_state_for_block(&hash, inner)
     }
 
+    /// Whether a block is in the past of the trusted checkpoint, which is not
+    /// inserted yet. A node synced from the checkpoint has no state before
+    /// its state epoch, so the deferred roots of these blocks are trusted.
+    /// Once the checkpoint is inserted no block can be in its past, so this
+    /// only holds during the checkpoint sync.
+    fn is_in_checkpoint_past(
+        &self, inner: &ConsensusGraphInner,
+        sync_graph: &SynchronizationGraphInner, index_in_sync_graph: usize,
+    ) -> bool
+    {
+        let checkpoint = match self.data_man.checkpoint {
+            Some(ref checkpoint) => checkpoint,
+            None => return false,
+        };
+        if inner.indices.contains_key(&checkpoint.hash) {
+            return false;
+        }
+        match sync_graph.indices.get(&checkpoint.hash) {
+            Some(checkpoint_index) => {
+                sync_graph.is_in_past(index_in_sync_graph, *checkpoint_index)
+            }
+            None => false,
+        }
+    }
+
     fn check_block_full_validity(
         &self, new: usize, block: &Block, inner: &mut ConsensusGraphInner,
         sync_graph: &SynchronizationGraphInner,
@@ -1386,9 +1753,13 @@ impl ConsensusGraph {
         // Check if the state root is correct or not
         // TODO: We may want to optimize this because now on the chain switch we
         // are going to compute state twice
-        let state_root_valid = if block.block_header.height()
-            < DEFERRED_STATE_EPOCH_COUNT
-        {
+        let state_root_valid = if self.is_in_checkpoint_past(
+            inner,
+            sync_graph,
+            my_index_in_sync_graph,
+        ) {
+            true
+        } else if block.block_header.height() < DEFERRED_STATE_EPOCH_COUNT {
             *block.block_header.deferred_state_root()
                 == inner.genesis_block_state_root
                 && *block.block_header.deferred_receipts_root()
@@ -1440,6 +1811,24 @@ impl ConsensusGraph {
                     valid = false;
                 }
                 valid
+            } else if (inner.state_pruned_height > 0
+                && block.block_header.height()
+                    <= (inner.state_pruned_height as u64
+                        + DEFERRED_STATE_EPOCH_COUNT))
+                || self.data_man.checkpoint.as_ref().map_or(
+                    false,
+                    |checkpoint| {
+                        block.block_header.height() <= checkpoint.epoch_number
+                    },
+                )
+            {
+                // The states before the state epoch of the checkpoint are not
+                // synced, and the states before the pruned height are dropped,
+                // so blocks forking before them cannot be checked.
+                warn!(
+                    "Unable to check the deferred state which is not available"
+                );
+                false
             } else {
                 // Call the expensive function to check this state root
                 let deferred_hash = inner.arena[deferred].hash;
@@ -1565,6 +1954,7 @@ impl ConsensusGraph {
             sync_inner,
         );
         inner.pivot_chain = new_pivot_chain;
+        self.txpool.set_best_epoch_number(inner.best_epoch_number());
         // Compute receipts root for the deferred block of the mining block,
         // which is not in the db
         if inner.pivot_chain.len() > DEFERRED_STATE_EPOCH_COUNT as usize {
@@ -1755,6 +2145,8 @@ impl ConsensusGraph {
             return;
         }
         debug!("Block {} is fully valid", inner.arena[me].hash);
+        self.notifications
+            .notify(Notification::NewBlockHeader(block.block_header.clone()));
 
         inner.weight_tree.make_tree(me);
         inner.weight_tree.link(inner.arena[me].parent, me);
@@ -1916,11 +2308,59 @@ impl ConsensusGraph {
             state_at += 1;
         }
 
+        // Drop the states which are older than `history_epochs` before the
+        // stable epoch, which only moves forward among the executed epochs.
+        // Pruning is batched so that it happens once every `history_epochs`
+        // epochs.
+        if let Some(history_epochs) =
+            self.data_man.storage_manager.history_epochs()
+        {
+            let history_epochs = history_epochs as usize;
+            if fork_at <= inner.stable_height {
+                warn!(
+                    "Pivot chain switched at height {} before the stable \
+                     epoch at height {}",
+                    fork_at, inner.stable_height
+                );
+                inner.stable_height = fork_at - 1;
+            }
+            while inner.stable_height + 1 < to_state_pos
+                && inner.is_stable_pivot_block(
+                    &new_pivot_chain,
+                    inner.stable_height + 1,
+                )
+            {
+                inner.stable_height += 1;
+            }
+            if inner.stable_height > history_epochs {
+                let pruned_height = inner.stable_height - history_epochs;
+                if pruned_height >= inner.state_pruned_height + history_epochs
+                {
+                    inner.state_pruned_height = pruned_height;
+                    self.executor.enqueue_drop_state_outside(
+                        inner.arena[new_pivot_chain[pruned_height]].hash,
+                    );
+                }
+            }
+        }
+
+        if fork_at < new_pivot_chain.len() {
+            self.notifications.notify(Notification::NewEpochs {
+                fork_at,
+                reorg: fork_at < inner.pivot_chain.len(),
+                epoch_hashes: new_pivot_chain[fork_at..]
+                    .iter()
+                    .map(|index| inner.arena[*index].hash)
+                    .collect(),
+            });
+        }
+
         inner.adjust_difficulty(
             *new_pivot_chain.last().expect("not empty"),
             &*sync_inner_lock.read(),
         );
         inner.pivot_chain = new_pivot_chain;
+        self.txpool.set_best_epoch_number(inner.best_epoch_number());
         inner.opt_executed_height = if to_state_pos > 0 {
             Some(to_state_pos)
         } else {
@@ -1946,20 +2386,18 @@ impl ConsensusGraph {
 
     pub fn get_transaction_info_by_hash(
         &self, hash: &H256,
-    ) -> Option<(SignedTransaction, Receipt, TransactionAddress)> {
+    ) -> Option<(SignedTransaction, LocalizedReceipt)> {
         // We need to hold the inner lock to ensure that tx_address and receipts
         // are consistent
         let inner = self.inner.read();
-        if let Some((receipt, address)) =
-            inner.get_transaction_receipt_with_address(hash)
-        {
-            let block =
-                self.data_man.block_by_hash(&address.block_hash, false)?;
-            let transaction = (*block.transactions[address.index]).clone();
-            Some((transaction, receipt, address))
-        } else {
-            None
-        }
+        let address = self.data_man.transaction_address_by_hash(hash, false)?;
+        let block = self.data_man.block_by_hash(&address.block_hash, false)?;
+        let receipt = inner
+            .block_localized_receipts(&block, false)?
+            .into_iter()
+            .nth(address.index)?;
+        let transaction = (*block.transactions[address.index]).clone();
+        Some((transaction, receipt))
     }
 
     pub fn transaction_count(
@@ -1968,6 +2406,24 @@ impl ConsensusGraph {
         self.inner.read().transaction_count(address, epoch_number)
     }
 
+    pub fn get_code(
+        &self, address: H160, epoch_number: EpochNumber,
+    ) -> Result<Bytes, String> {
+        self.inner.read().get_code(address, epoch_number)
+    }
+
+    pub fn get_storage_at(
+        &self, address: H160, key: H256, epoch_number: EpochNumber,
+    ) -> Result<H256, String> {
+        self.inner.read().get_storage_at(address, key, epoch_number)
+    }
+
+    pub fn get_account(
+        &self, address: H160, epoch_number: EpochNumber,
+    ) -> Result<Option<Account>, String> {
+        self.inner.read().get_account(address, epoch_number)
+    }
+
     pub fn best_state_block_hash(&self) -> H256 {
         self.inner.read().best_state_block_hash()
     }
@@ -2008,7 +2464,7 @@ impl ConsensusGraph {
 
             let mut blocks = Vec::new();
             for epoch_idx in from_epoch..to_epoch {
-                let epoch_hash = inner.arena[epoch_idx].hash;
+                let epoch_hash = inner.arena[inner.pivot_chain[epoch_idx]].hash;
                 for index in inner
                     .indices_in_epochs
                     .get(&inner.pivot_chain[epoch_idx])
@@ -2118,6 +2574,53 @@ impl ConsensusGraph {
         self.executor.call_virtual(tx, &epoch_id)
     }
 
+    /// Re-execute the epoch of the block and return the traces of the
+    /// transactions in the block, or of the transaction at
+    /// `transaction_index` if given.
+    pub fn trace_block(
+        &self, block_hash: &H256, transaction_index: Option<usize>,
+        vm_trace: bool,
+    ) -> Result<Vec<TransactionTrace>, String>
+    {
+        let (epoch_hash, epoch_block_hashes) = {
+            let inner = self.inner.read();
+            let epoch_number = inner
+                .get_block_epoch_number(block_hash)
+                .ok_or("Block is not found in the consensus graph")?;
+            let epoch_number = EpochNumber::Number(epoch_number.into());
+            inner.validate_stated_epoch(&epoch_number)?;
+            (
+                inner.get_hash_from_epoch_number(epoch_number.clone())?,
+                inner.block_hashes_by_epoch(epoch_number)?,
+            )
+        };
+        // The state before the epoch is available once the epoch is executed.
+        self.wait_for_block_state(&epoch_hash);
+        self.executor.trace_block(
+            &epoch_block_hashes,
+            block_hash,
+            transaction_index,
+            vm_trace,
+        )
+    }
+
+    /// Re-execute the transaction with VM tracing. Return `None` if the
+    /// transaction is not executed yet.
+    pub fn trace_transaction(
+        &self, tx_hash: &H256,
+    ) -> Result<Option<TransactionTrace>, String> {
+        let address = match self
+            .inner
+            .read()
+            .get_transaction_receipt_with_address(tx_hash)
+        {
+            Some((_, address)) => address,
+            None => return Ok(None),
+        };
+        self.trace_block(&address.block_hash, Some(address.index), true)
+            .map(|mut traces| traces.pop())
+    }
+
     /// Wait for a block's epoch is computed.
     /// Return the state_root and receipts_root
     pub fn wait_for_block_state(&self, block_hash: &H256) -> (H256, H256) {
@@ -8,6 +8,7 @@ use crate::{
     bytes::Bytes,
     machine::Machine,
     state::{CleanupMode, State, Substate},
+    trace::VmTracer,
     vm::{
         self, ActionParams, ActionValue, CallType, Context as ContextTrait,
         ContractCreateResult, CreateContractAddress, EnvInfo,
@@ -62,6 +63,7 @@ pub struct Context<'a, 'b: 'a> {
     spec: &'a Spec,
     output: OutputPolicy,
     static_flag: bool,
+    vm_tracer: &'a mut dyn VmTracer,
 }
 
 impl<'a, 'b: 'a> Context<'a, 'b> {
@@ -71,6 +73,7 @@ impl<'a, 'b: 'a> Context<'a, 'b> {
         spec: &'a Spec, depth: usize, stack_depth: usize,
         origin: &'a OriginInfo, substate: &'a mut Substate,
         output: OutputPolicy, static_flag: bool,
+        vm_tracer: &'a mut dyn VmTracer,
     ) -> Self
     {
         Context {
@@ -84,6 +87,7 @@ impl<'a, 'b: 'a> Context<'a, 'b> {
             spec,
             output,
             static_flag,
+            vm_tracer,
         }
     }
 }
@@ -324,24 +328,30 @@ impl<'a, 'b: 'a> ContextTrait for Context<'a, 'b> {
     }
 
     fn trace_next_instruction(
-        &mut self, _pc: usize, _instruction: u8, _current_gas: U256,
+        &mut self, pc: usize, instruction: u8, current_gas: U256,
     ) -> bool {
-        // TODO
-        false
+        self.vm_tracer
+            .trace_next_instruction(pc, instruction, current_gas)
     }
 
     fn trace_prepare_execute(
-        &mut self, _pc: usize, _instruction: u8, _gas_cost: U256,
-        _mem_written: Option<(usize, usize)>,
-        _store_written: Option<(U256, U256)>,
+        &mut self, pc: usize, instruction: u8, gas_cost: U256,
+        mem_written: Option<(usize, usize)>,
+        store_written: Option<(U256, U256)>,
     )
     {
-        // TODO
+        self.vm_tracer.trace_prepare_execute(
+            pc,
+            instruction,
+            gas_cost,
+            mem_written,
+            store_written,
+        )
     }
 
     fn trace_executed(
-        &mut self, _gas_used: U256, _stack_push: &[U256], _mem: &[u8],
+        &mut self, gas_left: U256, stack_push: &[U256], mem: &[u8],
     ) {
-        // TODO
+        self.vm_tracer.trace_executed(gas_left, stack_push, mem)
     }
 }
@@ -5,6 +5,7 @@
 use crate::{bytes::Bytes, statedb::Error as DbError, vm};
 use cfx_types::{Address, U256, U512};
 use primitives::LogEntry;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Executed {
@@ -69,6 +70,13 @@ pub enum ExecutionError {
         /// Amount of gas in block.
         gas: U256,
     },
+    /// Returned when transaction is signed for another chain.
+    InvalidChainId {
+        /// Chain id of this chain.
+        expected: u64,
+        /// Chain id the transaction is signed for.
+        got: u64,
+    },
     /// Returned when transaction nonce does not match state nonce.
     InvalidNonce {
         /// Nonce expected.
@@ -95,6 +103,51 @@ pub enum ExecutionError {
     TransactionMalformed(String),
 }
 
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ExecutionError::*;
+
+        let msg = match *self {
+            NotEnoughBaseGas { ref required, ref got } => format!(
+                "Not enough base gas. {} is required, but only {} paid",
+                required, got
+            ),
+            BlockGasLimitReached {
+                ref gas_limit,
+                ref gas_used,
+                ref gas,
+            } => format!(
+                "Block gas limit reached. The limit is {}, {} has already \
+                 been used, and {} more is required",
+                gas_limit, gas_used, gas
+            ),
+            InvalidChainId { ref expected, ref got } => format!(
+                "Invalid transaction chain id: expected {}, found {}",
+                expected, got
+            ),
+            InvalidNonce { ref expected, ref got } => format!(
+                "Invalid transaction nonce: expected {}, found {}",
+                expected, got
+            ),
+            NotEnoughCash { ref required, ref got } => format!(
+                "Cost of transaction exceeds sender balance. {} is required \
+                 but the sender only has {}",
+                required, got
+            ),
+            MutableCallInStaticContext => {
+                "Mutable call in static context".to_owned()
+            }
+            SenderMustExist => "Transacting from an empty account".to_owned(),
+            Internal(ref msg) => msg.clone(),
+            TransactionMalformed(ref err) => {
+                format!("Malformed transaction: {}", err)
+            }
+        };
+
+        f.write_fmt(format_args!("Transaction execution error ({}).", msg))
+    }
+}
+
 impl From<DbError> for ExecutionError {
     fn from(err: DbError) -> Self {
         ExecutionError::Internal(format!("{:?}", err))
@@ -12,6 +12,7 @@ use crate::{
     hash::keccak,
     machine::Machine,
     state::{CleanupMode, State, Substate},
+    trace::{NoopTracer, NoopVmTracer, Tracer, VmTracer},
 };
 use cfx_types::{Address, H256, U256, U512};
 use primitives::{transaction::Action, SignedTransaction};
@@ -320,7 +321,7 @@ impl<'a> CallCreateExecutive<'a> {
         state: &'any mut State<'b>, env: &'any EnvInfo, machine: &'any Machine,
         spec: &'any Spec, depth: usize, stack_depth: usize, static_flag: bool,
         origin: &'any OriginInfo, substate: &'any mut Substate,
-        output: OutputPolicy,
+        output: OutputPolicy, vm_tracer: &'any mut dyn VmTracer,
     ) -> Context<'any, 'b>
     {
         Context::new(
@@ -334,6 +335,7 @@ impl<'a> CallCreateExecutive<'a> {
             substate,
             output,
             static_flag,
+            vm_tracer,
         )
     }
 
@@ -342,7 +344,9 @@ impl<'a> CallCreateExecutive<'a> {
     /// `resume_call` or `resume_create` to continue the execution.
     pub fn exec<'b: 'a>(
         mut self, state: &mut State<'b>, substate: &mut Substate,
-    ) -> ExecutiveTrapResult<'a, FinalizationResult> {
+        vm_tracer: &mut dyn VmTracer,
+    ) -> ExecutiveTrapResult<'a, FinalizationResult>
+    {
         match self.kind {
             CallCreateExecutiveKind::Transfer(ref params) => {
                 assert!(!self.is_create);
@@ -456,6 +460,9 @@ impl<'a> CallCreateExecutive<'a> {
                 }
 
                 let origin = OriginInfo::from(&params);
+                vm_tracer.prepare_subtrace(
+                    params.code.as_ref().map_or(&[][..], |code| &code[..]),
+                );
                 let exec = self.factory.create(params, self.spec, self.depth);
 
                 let out = {
@@ -470,6 +477,7 @@ impl<'a> CallCreateExecutive<'a> {
                         &origin,
                         &mut unconfirmed_substate,
                         OutputPolicy::Return,
+                        vm_tracer,
                     );
                     match exec.exec(&mut context) {
                         Ok(val) => Ok(val.finalize(context)),
@@ -497,6 +505,7 @@ impl<'a> CallCreateExecutive<'a> {
                     }
                 };
 
+                vm_tracer.done_subtrace();
                 Self::enact_result(&res, state, substate, unconfirmed_substate);
                 Ok(res)
             }
@@ -532,6 +541,9 @@ impl<'a> CallCreateExecutive<'a> {
                 }
 
                 let origin = OriginInfo::from(&params);
+                vm_tracer.prepare_subtrace(
+                    params.code.as_ref().map_or(&[][..], |code| &code[..]),
+                );
                 let exec = self.factory.create(params, self.spec, self.depth);
 
                 let out = {
@@ -546,6 +558,7 @@ impl<'a> CallCreateExecutive<'a> {
                         &origin,
                         &mut unconfirmed_substate,
                         OutputPolicy::InitContract,
+                        vm_tracer,
                     );
                     match exec.exec(&mut context) {
                         Ok(val) => Ok(val.finalize(context)),
@@ -576,6 +589,7 @@ impl<'a> CallCreateExecutive<'a> {
                     }
                 };
 
+                vm_tracer.done_subtrace();
                 Self::enact_result(&res, state, substate, unconfirmed_substate);
                 Ok(res)
             }
@@ -590,7 +604,7 @@ impl<'a> CallCreateExecutive<'a> {
     /// Resume execution from a call trap previously trapped by `exec'.
     pub fn resume_call<'b: 'a>(
         mut self, result: vm::MessageCallResult, state: &mut State<'b>,
-        substate: &mut Substate,
+        substate: &mut Substate, vm_tracer: &mut dyn VmTracer,
     ) -> ExecutiveTrapResult<'a, FinalizationResult>
     {
         match self.kind {
@@ -617,6 +631,7 @@ impl<'a> CallCreateExecutive<'a> {
                         } else {
                             OutputPolicy::Return
                         },
+                        vm_tracer,
                     );
                     match exec.exec(&mut context) {
                         Ok(val) => Ok(val.finalize(context)),
@@ -644,6 +659,7 @@ impl<'a> CallCreateExecutive<'a> {
                     }
                 };
 
+                vm_tracer.done_subtrace();
                 Self::enact_result(&res, state, substate, unconfirmed_substate);
                 Ok(res)
             }
@@ -662,7 +678,7 @@ impl<'a> CallCreateExecutive<'a> {
     /// Resume execution from a create trap previously trapped by `exec`.
     pub fn resume_create<'b: 'a>(
         mut self, result: vm::ContractCreateResult, state: &mut State<'b>,
-        substate: &mut Substate,
+        substate: &mut Substate, vm_tracer: &mut dyn VmTracer,
     ) -> ExecutiveTrapResult<'a, FinalizationResult>
     {
         match self.kind {
@@ -689,6 +705,7 @@ impl<'a> CallCreateExecutive<'a> {
                         } else {
                             OutputPolicy::Return
                         },
+                        vm_tracer,
                     );
                     match exec.exec(&mut context) {
                         Ok(val) => Ok(val.finalize(context)),
@@ -716,6 +733,7 @@ impl<'a> CallCreateExecutive<'a> {
                     }
                 };
 
+                vm_tracer.done_subtrace();
                 Self::enact_result(&res, state, substate, unconfirmed_substate);
                 Ok(res)
             }
@@ -731,14 +749,53 @@ impl<'a> CallCreateExecutive<'a> {
         }
     }
 
+    /// Notify the tracer that the execution of this executive starts.
+    fn prepare_trace(&self, tracer: &mut dyn Tracer) {
+        match self.kind {
+            CallCreateExecutiveKind::Transfer(ref params)
+            | CallCreateExecutiveKind::CallBuiltin(ref params)
+            | CallCreateExecutiveKind::ExecCall(ref params, _) => {
+                tracer.prepare_trace_call(params)
+            }
+            CallCreateExecutiveKind::ExecCreate(ref params, _) => {
+                tracer.prepare_trace_create(params)
+            }
+            CallCreateExecutiveKind::ResumeCall(..)
+            | CallCreateExecutiveKind::ResumeCreate(..) => {
+                panic!("This executive has already been executed once.")
+            }
+        }
+    }
+
+    /// Notify the tracer that the execution of an executive finishes.
+    fn done_trace(
+        tracer: &mut dyn Tracer, result: &vm::Result<FinalizationResult>,
+    ) {
+        match result {
+            Ok(FinalizationResult {
+                gas_left,
+                return_data,
+                apply_state: true,
+            }) => tracer.done_trace(*gas_left, return_data),
+            Ok(FinalizationResult {
+                gas_left,
+                return_data,
+                apply_state: false,
+            }) => tracer.done_trace_reverted(*gas_left, return_data),
+            Err(error) => tracer.done_trace_failed(error),
+        }
+    }
+
     /// Execute and consume the current executive. This function handles resume
-    /// traps and sub-level tracing. The caller is expected to handle
-    /// current-level tracing.
+    /// traps and tracing of all levels.
     pub fn consume<'b: 'a>(
         self, state: &mut State<'b>, top_substate: &mut Substate,
-    ) -> vm::Result<FinalizationResult> {
+        tracer: &mut dyn Tracer, vm_tracer: &mut dyn VmTracer,
+    ) -> vm::Result<FinalizationResult>
+    {
+        self.prepare_trace(tracer);
         let mut last_res =
-            Some((false, self.gas, self.exec(state, top_substate)));
+            Some((false, self.gas, self.exec(state, top_substate, vm_tracer)));
 
         let mut callstack: Vec<(Option<Address>, CallCreateExecutive<'a>)> =
             Vec::new();
@@ -753,12 +810,14 @@ impl<'a> CallCreateExecutive<'a> {
                                 None => top_substate,
                             };
 
-                            last_res = Some((exec.is_create, exec.gas, exec.exec(state, parent_substate)));
+                            exec.prepare_trace(tracer);
+                            last_res = Some((exec.is_create, exec.gas, exec.exec(state, parent_substate, vm_tracer)));
                         },
                         None => panic!("When callstack only had one item and it was executed, this function would return; callstack never reaches zero item; qed"),
                     }
                 },
                 Some((is_create, _gas, Ok(val))) => {
+                    Self::done_trace(tracer, &val);
                     let current = callstack.pop();
 
                     match current {
@@ -777,6 +836,7 @@ impl<'a> CallCreateExecutive<'a> {
                                     contract_create_result,
                                     state,
                                     parent_substate,
+                                    vm_tracer,
                                 )));
                             } else {
                                 let second_last = callstack.last_mut();
@@ -789,6 +849,7 @@ impl<'a> CallCreateExecutive<'a> {
                                     into_message_call_result(val),
                                     state,
                                     parent_substate,
+                                    vm_tracer,
                                 )));
                             }
                         },
@@ -901,7 +962,8 @@ impl<'a, 'b> Executive<'a, 'b> {
 
     pub fn create_with_stack_depth(
         &mut self, params: ActionParams, substate: &mut Substate,
-        stack_depth: usize,
+        stack_depth: usize, tracer: &mut dyn Tracer,
+        vm_tracer: &mut dyn VmTracer,
     ) -> vm::Result<FinalizationResult>
     {
         let _address = params.address;
@@ -918,7 +980,7 @@ impl<'a, 'b> Executive<'a, 'b> {
             stack_depth,
             self.static_flag,
         )
-        .consume(self.state, substate);
+        .consume(self.state, substate, tracer, vm_tracer);
 
         result
     }
@@ -928,12 +990,19 @@ impl<'a, 'b> Executive<'a, 'b> {
     ) -> vm::Result<FinalizationResult> {
         println!("gas={:?}", params.gas);
 
-        self.create_with_stack_depth(params, substate, 0)
+        self.create_with_stack_depth(
+            params,
+            substate,
+            0,
+            &mut NoopTracer,
+            &mut NoopVmTracer,
+        )
     }
 
     pub fn call_with_stack_depth(
         &mut self, params: ActionParams, substate: &mut Substate,
-        stack_depth: usize,
+        stack_depth: usize, tracer: &mut dyn Tracer,
+        vm_tracer: &mut dyn VmTracer,
     ) -> vm::Result<FinalizationResult>
     {
         let _gas = params.gas;
@@ -949,7 +1018,7 @@ impl<'a, 'b> Executive<'a, 'b> {
             stack_depth,
             self.static_flag,
         )
-        .consume(self.state, substate);
+        .consume(self.state, substate, tracer, vm_tracer);
 
         result
     }
@@ -957,12 +1026,38 @@ impl<'a, 'b> Executive<'a, 'b> {
     pub fn call(
         &mut self, params: ActionParams, substate: &mut Substate,
     ) -> vm::Result<FinalizationResult> {
-        self.call_with_stack_depth(params, substate, 0)
+        self.call_with_stack_depth(
+            params,
+            substate,
+            0,
+            &mut NoopTracer,
+            &mut NoopVmTracer,
+        )
     }
 
     pub fn transact(
         &mut self, tx: &SignedTransaction,
     ) -> ExecutionResult<Executed> {
+        self.transact_with_tracer(tx, &mut NoopTracer, &mut NoopVmTracer)
+    }
+
+    /// Execute the transaction and report its call and create frames to
+    /// `tracer`, and its opcodes to `vm_tracer`.
+    pub fn transact_with_tracer(
+        &mut self, tx: &SignedTransaction, tracer: &mut dyn Tracer,
+        vm_tracer: &mut dyn VmTracer,
+    ) -> ExecutionResult<Executed>
+    {
+        // Transactions replayed from another chain are never executed.
+        // Unsigned transactions are only virtual calls from RPC.
+        let chain_id = self.machine.params().chain_id;
+        if !tx.is_unsigned() && tx.chain_id != chain_id {
+            return Err(ExecutionError::InvalidChainId {
+                expected: chain_id,
+                got: tx.chain_id,
+            });
+        }
+
         let sender = tx.sender();
         let nonce = self.state.nonce(&sender)?;
 
@@ -1069,7 +1164,13 @@ impl<'a, 'b> Executive<'a, 'b> {
                     call_type: CallType::None,
                     params_type: vm::ParamsType::Embedded,
                 };
-                let res = self.create(params, &mut substate);
+                let res = self.create_with_stack_depth(
+                    params,
+                    &mut substate,
+                    0,
+                    tracer,
+                    vm_tracer,
+                );
                 let out = match &res {
                     Ok(res) => res.return_data.to_vec(),
                     _ => Vec::new(),
@@ -1091,7 +1192,13 @@ impl<'a, 'b> Executive<'a, 'b> {
                     call_type: CallType::Call,
                     params_type: vm::ParamsType::Separate,
                 };
-                let res = self.call(params, &mut substate);
+                let res = self.call_with_stack_depth(
+                    params,
+                    &mut substate,
+                    0,
+                    tracer,
+                    vm_tracer,
+                );
                 let out = match &res {
                     Ok(res) => res.return_data.to_vec(),
                     _ => Vec::new(),
@@ -1226,6 +1333,7 @@ mod tests {
             tests::new_state_manager_for_testing, StorageManager,
             StorageManagerTrait,
         },
+        trace::{ExecutiveTracer, ExecutiveVmTracer},
     };
     use cfx_types::{Address, H256, U256, U512};
     use keylib::{Generator, Random};
@@ -1464,6 +1572,120 @@ mod tests {
         assert_eq!(gas_left, U256::from(44_752));
     }
 
+    #[test]
+    fn test_call_to_create_traces() {
+        // The same code as `test_call_to_create`.
+        let code = "7c601080600c6000396000f3006000355415600957005b60203560003555600052601d60036017f0600055".from_hex().unwrap();
+
+        let sender =
+            Address::from_str("cd1722f3947def4cf144679da39c4c32bdc35681")
+                .unwrap();
+        let address = contract_address(
+            CreateContractAddress::FromSenderAndNonce,
+            &sender,
+            &U256::zero(),
+            &[],
+        )
+        .0;
+        let mut params = ActionParams::default();
+        params.address = address.clone();
+        params.code_address = address.clone();
+        params.sender = sender.clone();
+        params.origin = sender.clone();
+        params.gas = U256::from(100_000);
+        params.code = Some(Arc::new(code.clone()));
+        params.value = ActionValue::Transfer(U256::from(100));
+        params.call_type = CallType::Call;
+
+        let storage_manager = new_state_manager_for_testing();
+        let mut state = get_state(&storage_manager, H256::from(U256::from(0)));
+        state
+            .add_balance(&sender, &U256::from(100), CleanupMode::NoEmpty)
+            .unwrap();
+        let mut info = EnvInfo::default();
+        let machine = make_byzantium_machine(5);
+        let spec = machine.spec(info.number);
+        let mut substate = Substate::new();
+        let mut tracer = ExecutiveTracer::default();
+        let mut vm_tracer = ExecutiveVmTracer::toplevel();
+
+        let FinalizationResult { gas_left, .. } = {
+            let mut ex = Executive::new(&mut state, &mut info, &machine, &spec);
+            ex.call_with_stack_depth(
+                params,
+                &mut substate,
+                0,
+                &mut tracer,
+                &mut vm_tracer,
+            )
+            .unwrap()
+        };
+        assert_eq!(gas_left, U256::from(44_752));
+
+        let traces = tracer.drain();
+        assert_eq!(traces.len(), 1);
+        let call = &traces[0];
+        assert_eq!(call.call_type, CallType::Call);
+        assert_eq!(call.from, sender);
+        assert_eq!(call.to, address);
+        assert_eq!(call.value, U256::from(100));
+        assert_eq!(call.gas_used, U256::from(100_000 - 44_752));
+        assert_eq!(call.error, None);
+        assert_eq!(call.calls.len(), 1);
+        let create = &call.calls[0];
+        assert_eq!(create.call_type, CallType::None);
+        assert_eq!(create.from, address);
+        assert_eq!(create.value, U256::from(23));
+
+        let vm_trace = vm_tracer.drain().unwrap();
+        assert_eq!(vm_trace.code, code);
+        assert_eq!(vm_trace.subs.len(), 1);
+        let create_step = vm_trace.subs[0].parent_step;
+        assert_eq!(vm_trace.operations[create_step].instruction, 0xf0);
+        assert!(vm_trace.operations.iter().all(|op| op.executed.is_some()));
+    }
+
+    #[test]
+    fn test_call_builtin_sha256() {
+        let sender =
+            Address::from_str("cd1722f3947def4cf144679da39c4c32bdc35681")
+                .unwrap();
+        let address =
+            Address::from_str("0000000000000000000000000000000000000002")
+                .unwrap();
+        let mut params = ActionParams::default();
+        params.address = address.clone();
+        params.code_address = address.clone();
+        params.sender = sender.clone();
+        params.origin = sender.clone();
+        params.gas = U256::from(100_000);
+        params.call_type = CallType::Call;
+
+        let storage_manager = new_state_manager_for_testing();
+        let mut state = get_state(&storage_manager, H256::from(U256::from(0)));
+        let mut info = EnvInfo::default();
+        let machine = make_byzantium_machine(0);
+        let spec = machine.spec(info.number);
+        let mut substate = Substate::new();
+
+        let FinalizationResult {
+            gas_left,
+            return_data,
+            ..
+        } = {
+            let mut ex = Executive::new(&mut state, &mut info, &machine, &spec);
+            ex.call(params, &mut substate).unwrap()
+        };
+
+        assert_eq!(gas_left, U256::from(99_940));
+        assert_eq!(
+            return_data.to_vec(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                .from_hex()
+                .unwrap()
+        );
+    }
+
     #[test]
     fn test_revert() {
         let factory = Factory::new(VMType::Interpreter, 1024 * 32);
@@ -1586,6 +1808,7 @@ mod tests {
     #[test]
     fn test_not_enough_cash() {
         let factory = Factory::new(VMType::Interpreter, 1024 * 32);
+        let machine = make_byzantium_machine(0);
 
         let keypair = Random.generate().unwrap();
         let t = Transaction {
@@ -1595,6 +1818,7 @@ mod tests {
             gas: U256::from(100_000),
             gas_price: U256::one(),
             nonce: U256::zero(),
+            chain_id: machine.params().chain_id,
         }
         .sign(keypair.secret());
         let sender = t.sender();
@@ -1610,7 +1834,6 @@ mod tests {
             .unwrap();
         let mut info = EnvInfo::default();
         info.gas_limit = U256::from(100_000);
-        let machine = make_byzantium_machine(0);
         let spec = machine.spec(info.number);
 
         let res = {
@@ -1628,4 +1851,62 @@ mod tests {
             _ => assert!(false, "Expected not enough cash error. {:?}", res),
         }
     }
+
+    #[test]
+    fn test_transaction_from_other_chain() {
+        let factory = Factory::new(VMType::Interpreter, 1024 * 32);
+        let machine = make_byzantium_machine(0);
+        let chain_id = machine.params().chain_id;
+
+        let keypair = Random.generate().unwrap();
+        let new_tx = |chain_id| {
+            Transaction {
+                action: Action::Call(Address::from(0x10)),
+                value: U256::from(10),
+                data: vec![],
+                gas: U256::from(21_000),
+                gas_price: U256::one(),
+                nonce: U256::zero(),
+                chain_id,
+            }
+            .sign(keypair.secret())
+        };
+        let foreign_tx = new_tx(chain_id + 1);
+        let sender = foreign_tx.sender();
+
+        let storage_manager = new_state_manager_for_testing();
+        let mut state = get_state_with_factory(
+            &storage_manager,
+            H256::from(U256::from(0)),
+            factory,
+        );
+        state
+            .add_balance(&sender, &U256::from(100_000), CleanupMode::NoEmpty)
+            .unwrap();
+        let mut info = EnvInfo::default();
+        info.gas_limit = U256::from(100_000);
+        let spec = machine.spec(info.number);
+
+        let res = {
+            let mut ex = Executive::new(&mut state, &mut info, &machine, &spec);
+            ex.transact(&foreign_tx)
+        };
+        match res {
+            Err(ExecutionError::InvalidChainId { expected, got })
+                if expected == chain_id && got == chain_id + 1 =>
+            {
+                ()
+            }
+            _ => assert!(false, "Expected invalid chain id error. {:?}", res),
+        }
+        assert_eq!(state.nonce(&sender).unwrap(), U256::zero());
+        assert_eq!(state.balance(&sender).unwrap(), U256::from(100_000));
+
+        let res = {
+            let mut ex = Executive::new(&mut state, &mut info, &machine, &spec);
+            ex.transact(&new_tx(chain_id))
+        };
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(state.nonce(&sender).unwrap(), U256::one());
+    }
 }
@@ -44,16 +44,26 @@ pub fn load_file(path: &String) -> Result<HashMap<Address, U256>, String> {
         .parse::<toml::Value>()
         .map_err(|e| format!("failed to parse toml file: {:?}", e))?;
 
+    accounts_from_toml(account_values)
+}
+
+/// Parse a table of address to balance (a decimal string).
+pub fn accounts_from_toml(
+    account_values: Value,
+) -> Result<HashMap<Address, U256>, String> {
     let mut accounts: HashMap<Address, U256> = HashMap::new();
     match account_values {
         Value::Table(table) => {
             for (key, value) in table {
-                let addr = key.parse::<Address>().map_err(|e| {
-                    format!(
-                        "failed to parse address: value = {}, error = {:?}",
-                        key, e
-                    )
-                })?;
+                let addr = key
+                    .trim_start_matches("0x")
+                    .parse::<Address>()
+                    .map_err(|e| {
+                        format!(
+                            "failed to parse address: value = {}, error = {:?}",
+                            key, e
+                        )
+                    })?;
 
                 match value {
                     Value::String(balance) => {
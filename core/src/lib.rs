@@ -38,16 +38,19 @@ extern crate rustc_hex;
 extern crate unexpected;
 
 pub mod block_data_manager;
-mod builtin;
+pub mod builtin;
 pub mod cache_config;
 pub mod cache_manager;
+pub mod chain_spec;
 pub mod consensus;
 pub mod db;
 pub mod error;
 mod evm;
 pub mod executive;
 pub mod genesis;
+pub mod light_protocol;
 pub mod machine;
+pub mod notifications;
 pub mod pow;
 pub(crate) mod snapshot;
 pub mod state;
@@ -55,6 +58,7 @@ pub mod statedb;
 pub mod statistics;
 pub mod storage;
 pub mod sync;
+pub mod trace;
 pub mod transaction_pool;
 pub mod verification;
 pub mod vm;
@@ -62,10 +66,11 @@ pub mod vm_factory;
 
 pub use crate::{
     consensus::{ConsensusGraph, SharedConsensusGraph},
+    notifications::{Notification, Notifications, SharedNotifications},
     sync::{
         BestInformation, SharedSynchronizationGraph,
         SharedSynchronizationService, SynchronizationService,
     },
     transaction_pool::{SharedTransactionPool, TransactionPool},
 };
-pub use network::PeerInfo;
+pub use network::{BannedPeer, PeerInfo, PeerSettings};
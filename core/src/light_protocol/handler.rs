@@ -0,0 +1,845 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{
+    pivot_chain::LightPivotChain,
+    query::{Query, QueryResult},
+    LIGHT_PROTOCOL_ID, LIGHT_PROTOCOL_VERSION,
+};
+use crate::{
+    bytes::Bytes,
+    consensus::{AccountProof, DEFERRED_STATE_EPOCH_COUNT},
+    sync::{Error, ErrorKind, SharedSynchronizationGraph},
+};
+use cfx_types::{H160, H256};
+use io::TimerToken;
+use message::{
+    GetBlockHeaders, GetBlockHeadersResponse, GetBlockTransactions,
+    GetBlockTransactionsResponse, GetReceipts, GetReceiptsResponse,
+    GetStateProof, GetStateProofResponse, GetTerminalBlockHashes,
+    GetTerminalBlockHashesResponse, Message, MsgId, Status,
+};
+use network::{
+    Error as NetworkError, Misbehavior, NetworkContext,
+    NetworkProtocolHandler, NetworkService, PeerId,
+};
+use parking_lot::{Mutex, RwLock};
+use primitives::{receipt::Receipt, Block, BlockHeader, EpochNumber};
+use priority_send_queue::SendQueuePriority;
+use rand::Rng;
+use rlp::Rlp;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const DEFAULT_GET_HEADERS_NUM: u64 = 1;
+const DEFAULT_GET_PARENT_HEADERS_NUM: u64 = 30;
+/// The number of peers a query is sent to before it fails.
+const MAX_QUERY_ATTEMPTS: usize = 3;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a query waits for a verified response, including retries.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(20);
+
+const SYNC_TIMER: TimerToken = 0;
+const CHECK_REQUEST_TIMER: TimerToken = 1;
+
+enum RequestKind {
+    Headers {
+        hash: H256,
+        max_blocks: u64,
+    },
+    TerminalHashes,
+    Query {
+        query: Query,
+        sender: Sender<QueryResult>,
+        attempts: usize,
+    },
+}
+
+struct InflightRequest {
+    peer: PeerId,
+    sent_at: Instant,
+    kind: RequestKind,
+}
+
+/// The handler of the light protocol on light nodes, which syncs the block
+/// header graph and sends queries for everything else to full nodes.
+pub struct LightProtocolHandler {
+    graph: SharedSynchronizationGraph,
+    /// The peers which have finished the handshake.
+    peers: RwLock<HashSet<PeerId>>,
+    next_request_id: AtomicUsize,
+    requests: Mutex<HashMap<u64, InflightRequest>>,
+    headers_in_flight: Mutex<HashSet<H256>>,
+    /// The pivot chain of the header graph, updated as new headers become
+    /// graph-ready.
+    pivot_chain: Mutex<LightPivotChain>,
+}
+
+impl LightProtocolHandler {
+    pub fn new(graph: SharedSynchronizationGraph) -> Self {
+        let pivot_chain =
+            LightPivotChain::new(&graph.data_man.genesis_block().block_header);
+        LightProtocolHandler {
+            graph,
+            peers: RwLock::new(HashSet::new()),
+            next_request_id: AtomicUsize::new(0),
+            requests: Mutex::new(HashMap::new()),
+            headers_in_flight: Mutex::new(HashSet::new()),
+            pivot_chain: Mutex::new(pivot_chain),
+        }
+    }
+
+    pub fn best_epoch_number(&self) -> u64 {
+        self.pivot_chain.lock().len() as u64 - 1
+    }
+
+    pub fn best_block_hash(&self) -> H256 {
+        self.pivot_chain.lock().best_block_hash()
+    }
+
+    /// The latest epoch whose state is committed to by a block header on the
+    /// pivot chain.
+    pub fn best_state_epoch_number(&self) -> Option<u64> {
+        self.best_epoch_number()
+            .checked_sub(DEFERRED_STATE_EPOCH_COUNT)
+    }
+
+    pub fn get_height_from_epoch_number(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<u64, String> {
+        let best_epoch_number = self.best_epoch_number();
+        Ok(match epoch_number {
+            EpochNumber::Earliest => 0,
+            EpochNumber::LatestMined => best_epoch_number,
+            EpochNumber::LatestState => {
+                self.best_state_epoch_number().ok_or_else(|| {
+                    "No state is committed by the synced headers yet."
+                        .to_owned()
+                })?
+            }
+            EpochNumber::Number(num) => {
+                let epoch_num = num.as_u64();
+                if epoch_num > best_epoch_number {
+                    return Err(
+                        "Invalid params: expected a numbers with less than \
+                         largest epoch number."
+                            .to_owned(),
+                    );
+                }
+                epoch_num
+            }
+        })
+    }
+
+    pub fn block_header_by_hash(&self, hash: &H256) -> Option<BlockHeader> {
+        self.graph.block_header_by_hash(hash)
+    }
+
+    /// Query the proof of an account and its storage entries in the state of
+    /// an epoch, which is verified against the deferred state root.
+    pub fn account_proof(
+        &self, network: &NetworkService, address: H160,
+        storage_keys: Vec<H256>, epoch_number: EpochNumber,
+    ) -> Result<AccountProof, String>
+    {
+        let epoch = self.get_height_from_epoch_number(epoch_number)?;
+        let (epoch_hash, deferred_header) = self.deferred_header(epoch)?;
+        let query = Query::StateProof {
+            epoch_hash,
+            state_root: *deferred_header.deferred_state_root(),
+            address,
+            storage_keys,
+        };
+        match self.query(network, query)? {
+            QueryResult::StateProof(proof) => Ok(proof),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Query the receipts of an epoch, which are verified against the
+    /// deferred receipts root.
+    pub fn epoch_receipts(
+        &self, network: &NetworkService, epoch_number: EpochNumber,
+    ) -> Result<(Vec<H256>, Vec<Arc<Vec<Receipt>>>), String> {
+        let epoch = self.get_height_from_epoch_number(epoch_number)?;
+        let (epoch_hash, deferred_header) = self.deferred_header(epoch)?;
+        let block_hashes = {
+            let inner = self.graph.inner.read();
+            let index = *inner
+                .indices
+                .get(&epoch_hash)
+                .ok_or_else(|| "Epoch is not in the header graph".to_owned())?;
+            inner.epoch_block_hashes(index)
+        };
+        let query = Query::Receipts {
+            epoch_hash,
+            receipts_root: *deferred_header.deferred_receipts_root(),
+            block_hashes,
+        };
+        match self.query(network, query)? {
+            QueryResult::Receipts(block_hashes, receipts) => {
+                Ok((block_hashes, receipts))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Query a block whose header is synced, with its transactions verified
+    /// against the transactions root.
+    pub fn block_by_hash(
+        &self, network: &NetworkService, hash: &H256,
+    ) -> Result<Option<Block>, String> {
+        let block_header = match self.graph.block_header_by_hash(hash) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let query = Query::BlockTransactions {
+            hash: *hash,
+            transactions_root: *block_header.transactions_root(),
+        };
+        let transactions = match self.query(network, query)? {
+            QueryResult::BlockTransactions(transactions) => transactions,
+            _ => unreachable!(),
+        };
+        Ok(Some(Block::new(block_header, transactions)))
+    }
+
+    /// The pivot block of `epoch` and the pivot block header committing to
+    /// its state and receipts.
+    fn deferred_header(
+        &self, epoch: u64,
+    ) -> Result<(H256, BlockHeader), String> {
+        let (epoch_hash, deferred_hash) = {
+            let pivot_chain = self.pivot_chain.lock();
+            let deferred_hash = pivot_chain
+                .get((epoch + DEFERRED_STATE_EPOCH_COUNT) as usize)
+                .ok_or_else(|| {
+                    format!("The state of epoch {} is not committed yet", epoch)
+                })?;
+            (pivot_chain.get(epoch as usize).unwrap(), deferred_hash)
+        };
+        let header = self
+            .graph
+            .block_header_by_hash(&deferred_hash)
+            .ok_or_else(|| "Pivot block header is missing".to_owned())?;
+        Ok((epoch_hash, header))
+    }
+
+    /// Send a query to full nodes and wait for a verified response.
+    fn query(
+        &self, network: &NetworkService, query: Query,
+    ) -> Result<QueryResult, String> {
+        let mut receiver: Option<Receiver<QueryResult>> = None;
+        network.with_context(LIGHT_PROTOCOL_ID, |io| {
+            receiver = Some(self.send_query(io, query));
+        });
+        receiver
+            .ok_or_else(|| "Light protocol is not running".to_owned())?
+            .recv_timeout(QUERY_TIMEOUT)
+            .map_err(|_| "No valid response from full nodes".to_owned())
+    }
+
+    /// Send a query to a random peer. The verified result is sent through the
+    /// returned channel, which is closed if all attempts fail.
+    pub fn send_query(
+        &self, io: &NetworkContext, query: Query,
+    ) -> Receiver<QueryResult> {
+        let (sender, receiver) = mpsc::channel();
+        self.send_query_attempt(io, query, sender, 1, None);
+        receiver
+    }
+
+    fn send_query_attempt(
+        &self, io: &NetworkContext, query: Query, sender: Sender<QueryResult>,
+        attempts: usize, failed_peer: Option<PeerId>,
+    )
+    {
+        if attempts > MAX_QUERY_ATTEMPTS {
+            debug!(
+                "Light query failed after {} attempts: {:?}",
+                MAX_QUERY_ATTEMPTS, query
+            );
+            return;
+        }
+        let peer = match self.choose_peer(failed_peer) {
+            Some(peer) => peer,
+            None => {
+                debug!("No peer to send light query {:?}", query);
+                return;
+            }
+        };
+        let kind = RequestKind::Query {
+            query,
+            sender,
+            attempts,
+        };
+        if let Err(e) = self.send_request(io, peer, kind) {
+            debug!("Error sending light query: {:?}", e);
+        }
+    }
+
+    /// Choose a random peer, avoiding `exclude` if there are other peers.
+    fn choose_peer(&self, exclude: Option<PeerId>) -> Option<PeerId> {
+        let peers = self.peers.read();
+        let mut candidates: Vec<PeerId> = peers
+            .iter()
+            .filter(|peer| Some(**peer) != exclude)
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            candidates = peers.iter().cloned().collect();
+        }
+        rand::thread_rng().choose(&candidates).cloned()
+    }
+
+    fn send_message(
+        &self, io: &NetworkContext, peer: PeerId, msg: &Message,
+    ) -> Result<(), NetworkError> {
+        let mut raw = Bytes::new();
+        raw.push(msg.msg_id().into());
+        raw.extend(msg.rlp_bytes().iter());
+        if let Err(e) = io.send(peer, raw, SendQueuePriority::High) {
+            debug!("Error sending message: {:?}", e);
+            return Err(e);
+        };
+        debug!(
+            "Send message({}) to {:?}",
+            msg.msg_id(),
+            io.get_peer_node_id(peer)
+        );
+        Ok(())
+    }
+
+    fn send_request(
+        &self, io: &NetworkContext, peer: PeerId, kind: RequestKind,
+    ) -> Result<(), NetworkError> {
+        let request_id =
+            self.next_request_id.fetch_add(1, AtomicOrdering::Relaxed) as u64;
+        let msg: Box<dyn Message> = match &kind {
+            RequestKind::Headers { hash, max_blocks } => {
+                Box::new(GetBlockHeaders {
+                    request_id: request_id.into(),
+                    hash: *hash,
+                    max_blocks: *max_blocks,
+                })
+            }
+            RequestKind::TerminalHashes => Box::new(GetTerminalBlockHashes {
+                request_id: request_id.into(),
+            }),
+            RequestKind::Query { query, .. } => match query {
+                Query::StateProof {
+                    epoch_hash,
+                    address,
+                    storage_keys,
+                    ..
+                } => Box::new(GetStateProof {
+                    request_id: request_id.into(),
+                    epoch_hash: *epoch_hash,
+                    address: *address,
+                    storage_keys: storage_keys.clone(),
+                }),
+                Query::Receipts { epoch_hash, .. } => Box::new(GetReceipts {
+                    request_id: request_id.into(),
+                    epoch_hash: *epoch_hash,
+                }),
+                Query::BlockTransactions { hash, .. } => {
+                    Box::new(GetBlockTransactions {
+                        request_id: request_id.into(),
+                        hash: *hash,
+                    })
+                }
+            },
+        };
+
+        // Register the request first, since the response may be handled by
+        // another IO thread before `send` returns.
+        self.requests.lock().insert(
+            request_id,
+            InflightRequest {
+                peer,
+                sent_at: Instant::now(),
+                kind,
+            },
+        );
+        if let Err(e) = self.send_message(io, peer, msg.as_ref()) {
+            self.requests.lock().remove(&request_id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn match_request(
+        &self, peer: PeerId, request_id: u64,
+    ) -> Result<RequestKind, Error> {
+        let mut requests = self.requests.lock();
+        match requests.get(&request_id) {
+            Some(request) if request.peer == peer => {
+                Ok(requests.remove(&request_id).unwrap().kind)
+            }
+            // The request was sent, but it timed out or was answered already.
+            None if request_id
+                < self.next_request_id.load(AtomicOrdering::Relaxed) as u64 =>
+            {
+                Err(ErrorKind::LateResponse.into())
+            }
+            _ => Err(ErrorKind::UnexpectedResponse.into()),
+        }
+    }
+
+    fn request_block_headers(
+        &self, io: &NetworkContext, peer: Option<PeerId>, hash: &H256,
+        max_blocks: u64,
+    )
+    {
+        if !self.headers_in_flight.lock().insert(*hash) {
+            return;
+        }
+        let peer = match peer.or_else(|| self.choose_peer(None)) {
+            Some(peer) => peer,
+            None => {
+                self.headers_in_flight.lock().remove(hash);
+                return;
+            }
+        };
+        let kind = RequestKind::Headers {
+            hash: *hash,
+            max_blocks,
+        };
+        if self.send_request(io, peer, kind).is_err() {
+            self.headers_in_flight.lock().remove(hash);
+        }
+    }
+
+    /// Retry a request which timed out or whose peer disconnected.
+    fn on_request_failed(&self, io: &NetworkContext, request: InflightRequest) {
+        match request.kind {
+            RequestKind::Headers { hash, max_blocks } => {
+                self.headers_in_flight.lock().remove(&hash);
+                let peer = self.choose_peer(Some(request.peer));
+                self.request_block_headers(io, peer, &hash, max_blocks);
+            }
+            // Terminal hashes are requested periodically anyway.
+            RequestKind::TerminalHashes => {}
+            RequestKind::Query {
+                query,
+                sender,
+                attempts,
+            } => self.send_query_attempt(
+                io,
+                query,
+                sender,
+                attempts + 1,
+                Some(request.peer),
+            ),
+        }
+    }
+
+    fn remove_expired_requests(&self, io: &NetworkContext) {
+        let expired: Vec<InflightRequest> = {
+            let mut requests = self.requests.lock();
+            let expired_ids: Vec<u64> = requests
+                .iter()
+                .filter(|(_, request)| {
+                    request.sent_at.elapsed() > REQUEST_TIMEOUT
+                })
+                .map(|(request_id, _)| *request_id)
+                .collect();
+            expired_ids
+                .iter()
+                .filter_map(|request_id| requests.remove(request_id))
+                .collect()
+        };
+        for request in expired {
+            debug!("Light request to peer {:?} timed out", request.peer);
+            self.on_request_failed(io, request);
+        }
+    }
+
+    fn sync_terminal_hashes(&self, io: &NetworkContext) {
+        let peers: Vec<PeerId> = self.peers.read().iter().cloned().collect();
+        for peer in peers {
+            if let Err(e) =
+                self.send_request(io, peer, RequestKind::TerminalHashes)
+            {
+                debug!("Error requesting terminal hashes: {:?}", e);
+            }
+        }
+    }
+
+    /// Add the headers which became graph-ready after inserting `inserted`
+    /// to the pivot chain, parents first.
+    fn update_pivot_chain(&self, inserted: &[H256]) {
+        let inner = self.graph.inner.read();
+        let mut pivot_chain = self.pivot_chain.lock();
+        let mut queue: VecDeque<usize> = inserted
+            .iter()
+            .filter_map(|hash| inner.indices.get(hash).cloned())
+            .collect();
+        while let Some(index) = queue.pop_front() {
+            if !inner.is_header_graph_ready(index)
+                || pivot_chain.contains(&inner.arena[index].block_header.hash())
+            {
+                continue;
+            }
+            let mut ancestors = vec![index];
+            let mut parent = inner.arena[index].parent;
+            while !pivot_chain
+                .contains(&inner.arena[parent].block_header.hash())
+            {
+                ancestors.push(parent);
+                parent = inner.arena[parent].parent;
+            }
+            for ancestor in ancestors.iter().rev() {
+                pivot_chain.insert(&inner.arena[*ancestor].block_header);
+            }
+            queue.extend(inner.arena[index].children.iter());
+            queue.extend(inner.arena[index].referrers.iter());
+        }
+    }
+
+    /// Whether the pivot chain is stable from the epoch a query is verified
+    /// in up to the block committing to it, so that a response which fails
+    /// the verification is the fault of the peer.
+    fn is_pivot_certain(&self, query: &Query) -> bool {
+        let epoch_hash = match query {
+            Query::StateProof { epoch_hash, .. }
+            | Query::Receipts { epoch_hash, .. } => epoch_hash,
+            // Transactions are verified against the header of their block.
+            Query::BlockTransactions { .. } => return true,
+        };
+        let epoch = match self.graph.block_header_by_hash(epoch_hash) {
+            Some(header) => header.height() as usize,
+            None => return false,
+        };
+        let mut pivot_chain = self.pivot_chain.lock();
+        (epoch..=epoch + DEFERRED_STATE_EPOCH_COUNT as usize)
+            .all(|height| pivot_chain.is_stable(height))
+    }
+
+    fn send_status(
+        &self, io: &NetworkContext, peer: PeerId,
+    ) -> Result<(), NetworkError> {
+        debug!("Sending status message to {:?}", peer);
+        let msg: Box<dyn Message> = Box::new(Status {
+            protocol_version: LIGHT_PROTOCOL_VERSION,
+            network_id: self.graph.network_id(),
+            genesis_hash: self.graph.genesis_hash(),
+            best_epoch: self.best_epoch_number(),
+            terminal_block_hashes: vec![self.best_block_hash()],
+        });
+        self.send_message(io, peer, msg.as_ref())
+    }
+
+    fn dispatch_message(
+        &self, io: &NetworkContext, peer: PeerId, msg_id: MsgId, rlp: Rlp,
+    ) {
+        trace!("Dispatching message: peer={:?}, msgid={:?}", peer, msg_id);
+        match msg_id {
+            MsgId::STATUS => self.on_status(io, peer, &rlp),
+            MsgId::GET_BLOCK_HEADERS_RESPONSE => {
+                self.on_block_headers_response(io, peer, &rlp)
+            }
+            MsgId::GET_TERMINAL_BLOCK_HASHES_RESPONSE => {
+                self.on_terminal_block_hashes_response(io, peer, &rlp)
+            }
+            MsgId::GET_STATE_PROOF_RESPONSE => {
+                self.on_state_proof_response(io, peer, &rlp)
+            }
+            MsgId::GET_RECEIPTS_RESPONSE => {
+                self.on_receipts_response(io, peer, &rlp)
+            }
+            MsgId::GET_BLOCK_TRANSACTIONS_RESPONSE => {
+                self.on_block_transactions_response(io, peer, &rlp)
+            }
+            _ => {
+                debug!("Unknown message: peer={:?} msgid={:?}", peer, msg_id);
+                Ok(())
+            }
+        }
+        .unwrap_or_else(|e| {
+            warn!(
+                "Error while handling message msgid={:?}, error={:?}",
+                msg_id, e
+            );
+            if let Some(misbehavior) = Self::misbehavior(&e) {
+                io.report_peer(peer, misbehavior);
+            }
+        });
+    }
+
+    /// The misbehavior of the peer which causes the error, if any.
+    fn misbehavior(e: &Error) -> Option<Misbehavior> {
+        match e.kind() {
+            ErrorKind::Invalid => Some(Misbehavior::InvalidBlock),
+            ErrorKind::InvalidProof => Some(Misbehavior::InvalidProof),
+            ErrorKind::Decoder(_) => Some(Misbehavior::MalformedMessage),
+            ErrorKind::UnexpectedResponse => {
+                Some(Misbehavior::UnsolicitedResponse)
+            }
+            ErrorKind::LateResponse => Some(Misbehavior::LateResponse),
+            _ => None,
+        }
+    }
+
+    fn on_status(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let status = rlp.as_val::<Status>()?;
+        debug!("on_status, msg=:{:?}", status);
+        let genesis_hash = self.graph.genesis_hash();
+        if genesis_hash != status.genesis_hash {
+            debug!(
+                "Peer {:?} genesis hash mismatches (ours: {:?}, theirs: {:?})",
+                peer, genesis_hash, status.genesis_hash
+            );
+            // The peer is on another chain rather than misbehaving.
+            io.disconnect_peer(peer);
+            return Ok(());
+        }
+        let network_id = self.graph.network_id();
+        if network_id != status.network_id {
+            debug!(
+                "Peer {:?} network id mismatches (ours: {}, theirs: {})",
+                peer, network_id, status.network_id
+            );
+            io.disconnect_peer(peer);
+            return Ok(());
+        }
+
+        self.peers.write().insert(peer);
+        for hash in &status.terminal_block_hashes {
+            if !self.graph.contains_block_header(hash) {
+                self.request_block_headers(
+                    io,
+                    Some(peer),
+                    hash,
+                    DEFAULT_GET_HEADERS_NUM,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn on_terminal_block_hashes_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let response = rlp.as_val::<GetTerminalBlockHashesResponse>()?;
+        debug!("on_terminal_block_hashes_response, msg=:{:?}", response);
+        match self.match_request(peer, response.request_id())? {
+            RequestKind::TerminalHashes => {}
+            _ => return Err(ErrorKind::UnexpectedResponse.into()),
+        }
+
+        for hash in &response.hashes {
+            if !self.graph.contains_block_header(hash) {
+                self.request_block_headers(
+                    io,
+                    Some(peer),
+                    hash,
+                    DEFAULT_GET_HEADERS_NUM,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn on_block_headers_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let mut response = rlp.as_val::<GetBlockHeadersResponse>()?;
+        debug!(
+            "on_block_headers_response, peer={:?} headers={}",
+            peer,
+            response.headers.len()
+        );
+        let requested_hash =
+            match self.match_request(peer, response.request_id())? {
+                RequestKind::Headers { hash, .. } => hash,
+                _ => return Err(ErrorKind::UnexpectedResponse.into()),
+            };
+        self.headers_in_flight.lock().remove(&requested_hash);
+
+        let mut inserted = Vec::new();
+        let mut dependent_hashes = Vec::new();
+        let mut misbehavior = None;
+        for header in &mut response.headers {
+            let hash = header.hash();
+            if self.graph.contains_block_header(&hash) {
+                continue;
+            }
+            if self.graph.verification_config.verify_pow(header).is_err() {
+                misbehavior = Some(Misbehavior::InvalidPow);
+                continue;
+            }
+            if self.graph.insert_block_header(header, true).0 {
+                inserted.push(hash);
+                dependent_hashes.push(*header.parent_hash());
+                dependent_hashes.extend(header.referee_hashes().iter());
+            } else if misbehavior.is_none()
+                && !self.graph.parent_or_referees_invalid(header)
+            {
+                misbehavior = Some(Misbehavior::InvalidBlock);
+            }
+        }
+        if let Some(misbehavior) = misbehavior {
+            io.report_peer(peer, misbehavior);
+        }
+
+        // Headers of the same response are inserted by now, so only the
+        // ancestors beyond them are requested.
+        for hash in &dependent_hashes {
+            if !self.graph.contains_block_header(hash) {
+                self.request_block_headers(
+                    io,
+                    Some(peer),
+                    hash,
+                    DEFAULT_GET_PARENT_HEADERS_NUM,
+                );
+            }
+        }
+
+        if !inserted.is_empty() {
+            self.update_pivot_chain(&inserted);
+        }
+        Ok(())
+    }
+
+    fn on_state_proof_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let response = rlp.as_val::<GetStateProofResponse>()?;
+        let request_id = response.request_id();
+        self.on_query_response(io, peer, request_id, |query| {
+            query.verify_state_proof(response)
+        })
+    }
+
+    fn on_receipts_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let response = rlp.as_val::<GetReceiptsResponse>()?;
+        let request_id = response.request_id();
+        self.on_query_response(io, peer, request_id, |query| {
+            query.verify_receipts(response)
+        })
+    }
+
+    fn on_block_transactions_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let response = rlp.as_val::<GetBlockTransactionsResponse>()?;
+        let request_id = response.request_id();
+        self.on_query_response(io, peer, request_id, |query| {
+            query.verify_block_transactions(response)
+        })
+    }
+
+    /// Match the response with its query and verify it. The query is sent to
+    /// another peer if the response is invalid. An invalid proof is only
+    /// blamed on the peer if the local pivot chain is certain, since the
+    /// query is built from the pivot chain of the headers.
+    fn on_query_response<F>(
+        &self, io: &NetworkContext, peer: PeerId, request_id: u64, verify: F,
+    ) -> Result<(), Error>
+    where F: FnOnce(&Query) -> Result<QueryResult, Error> {
+        let (query, sender, attempts) =
+            match self.match_request(peer, request_id)? {
+                RequestKind::Query {
+                    query,
+                    sender,
+                    attempts,
+                } => (query, sender, attempts),
+                _ => return Err(ErrorKind::UnexpectedResponse.into()),
+            };
+
+        match verify(&query) {
+            Ok(result) => {
+                // The caller may have given up waiting already.
+                sender.send(result).ok();
+                Ok(())
+            }
+            Err(e) => {
+                let pivot_certain = self.is_pivot_certain(&query);
+                self.send_query_attempt(
+                    io,
+                    query,
+                    sender,
+                    attempts + 1,
+                    Some(peer),
+                );
+                match e.kind() {
+                    ErrorKind::InvalidProof if !pivot_certain => {
+                        debug!(
+                            "Invalid proof from peer {:?} on an uncertain \
+                             pivot chain",
+                            peer
+                        );
+                        Ok(())
+                    }
+                    _ => Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl NetworkProtocolHandler for LightProtocolHandler {
+    fn initialize(&self, io: &NetworkContext) {
+        io.register_timer(SYNC_TIMER, Duration::from_secs(2))
+            .expect("Error registering light sync timer");
+        io.register_timer(CHECK_REQUEST_TIMER, Duration::from_secs(1))
+            .expect("Error registering light check request timer");
+    }
+
+    fn on_message(&self, io: &NetworkContext, peer: PeerId, raw: &[u8]) {
+        let msg_id = raw[0];
+        let rlp = Rlp::new(&raw[1..]);
+        debug!("on_message: peer={:?}, msgid={:?}", peer, msg_id);
+        self.dispatch_message(io, peer, msg_id.into(), rlp);
+    }
+
+    fn on_peer_connected(&self, io: &NetworkContext, peer: PeerId) {
+        info!("Light peer connected: peer={:?}", peer);
+        if let Err(e) = self.send_status(io, peer) {
+            debug!("Error sending status message: {:?}", e);
+            io.disconnect_peer(peer);
+        }
+    }
+
+    fn on_peer_disconnected(&self, io: &NetworkContext, peer: PeerId) {
+        info!("Light peer disconnected: peer={:?}", peer);
+        self.peers.write().remove(&peer);
+        let unfinished: Vec<InflightRequest> = {
+            let mut requests = self.requests.lock();
+            let request_ids: Vec<u64> = requests
+                .iter()
+                .filter(|(_, request)| request.peer == peer)
+                .map(|(request_id, _)| *request_id)
+                .collect();
+            request_ids
+                .iter()
+                .filter_map(|request_id| requests.remove(request_id))
+                .collect()
+        };
+        for request in unfinished {
+            self.on_request_failed(io, request);
+        }
+    }
+
+    fn on_timeout(&self, io: &NetworkContext, timer: TimerToken) {
+        trace!("Timeout: timer={:?}", timer);
+        match timer {
+            SYNC_TIMER => self.sync_terminal_hashes(io),
+            CHECK_REQUEST_TIMER => self.remove_expired_requests(io),
+            _ => warn!("Unknown timer {} triggered.", timer),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+mod handler;
+mod pivot_chain;
+mod provider;
+mod query;
+
+pub use self::{
+    handler::LightProtocolHandler,
+    provider::LightProvider,
+    query::{Query, QueryResult},
+};
+use network::ProtocolId;
+
+pub const LIGHT_PROTOCOL_ID: ProtocolId = *b"cfl";
+pub const LIGHT_PROTOCOL_VERSION: u8 = 0x01;
@@ -0,0 +1,295 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::consensus::{
+    DEFERRED_STATE_EPOCH_COUNT, STABLE_EPOCH_WEIGHT_MARGIN,
+};
+use cfx_types::{H256, U256};
+use link_cut_tree::{LinkCutTree, SignedBigNum};
+use primitives::BlockHeader;
+use std::collections::HashMap;
+
+const NULL: usize = !0;
+
+struct LightPivotNode {
+    hash: H256,
+    parent: usize,
+    height: usize,
+    children: Vec<usize>,
+    difficulty: U256,
+    /// Whether the header is known to be invalid without executing epochs,
+    /// in which case it is not added to `weight_tree` as in consensus.
+    partial_invalid: bool,
+}
+
+/// The pivot chain of the header graph on light nodes. It is maintained
+/// incrementally as `ConsensusGraph::on_new_block` does, except that light
+/// nodes can only exclude the headers which are invalid without executing
+/// the epochs, so the pivot chain is only trusted where it is stable.
+pub struct LightPivotChain {
+    arena: Vec<LightPivotNode>,
+    indices: HashMap<H256, usize>,
+    weight_tree: LinkCutTree,
+    pivot_chain: Vec<usize>,
+    genesis_state_root: H256,
+    genesis_receipts_root: H256,
+}
+
+impl LightPivotChain {
+    pub fn new(genesis: &BlockHeader) -> Self {
+        let mut pivot_chain = LightPivotChain {
+            arena: Vec::new(),
+            indices: HashMap::new(),
+            weight_tree: LinkCutTree::new(),
+            pivot_chain: Vec::new(),
+            genesis_state_root: *genesis.deferred_state_root(),
+            genesis_receipts_root: *genesis.deferred_receipts_root(),
+        };
+        let genesis_index = pivot_chain.insert_node(genesis, NULL, false);
+        pivot_chain.weight_tree.make_tree(genesis_index);
+        pivot_chain.weight_tree.update_weight(
+            genesis_index,
+            &SignedBigNum::pos(*genesis.difficulty()),
+        );
+        pivot_chain.pivot_chain.push(genesis_index);
+        pivot_chain
+    }
+
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.indices.contains_key(hash)
+    }
+
+    pub fn len(&self) -> usize { self.pivot_chain.len() }
+
+    pub fn get(&self, height: usize) -> Option<H256> {
+        self.pivot_chain
+            .get(height)
+            .map(|index| self.arena[*index].hash)
+    }
+
+    pub fn best_block_hash(&self) -> H256 {
+        let last = *self.pivot_chain.last().expect("genesis is on pivot chain");
+        self.arena[last].hash
+    }
+
+    /// Insert a header whose parent has been inserted, and update the pivot
+    /// chain. Return false if the header is known to be invalid.
+    pub fn insert(&mut self, header: &BlockHeader) -> bool {
+        let hash = header.hash();
+        if let Some(index) = self.indices.get(&hash) {
+            return !self.arena[*index].partial_invalid;
+        }
+        let parent = *self
+            .indices
+            .get(header.parent_hash())
+            .expect("parent is inserted before its children");
+        let partial_invalid = self.arena[parent].partial_invalid
+            || (header.height() < DEFERRED_STATE_EPOCH_COUNT
+                && (*header.deferred_state_root() != self.genesis_state_root
+                    || *header.deferred_receipts_root()
+                        != self.genesis_receipts_root));
+        let me = self.insert_node(header, parent, partial_invalid);
+        if partial_invalid {
+            debug!("Light header {:?} is partially invalid", hash);
+            return false;
+        }
+
+        self.arena[parent].children.push(me);
+        self.weight_tree.make_tree(me);
+        self.weight_tree.link(parent, me);
+        self.weight_tree
+            .update_weight(me, &SignedBigNum::pos(*header.difficulty()));
+
+        let last = *self.pivot_chain.last().unwrap();
+        if parent == last {
+            self.pivot_chain.push(me);
+            return true;
+        }
+        let lca = self.weight_tree.lca(last, me);
+        let fork_at = self.arena[lca].height + 1;
+        let prev = self.pivot_chain[fork_at];
+        let prev_weight = self.weight_tree.subtree_weight(prev);
+        let new = self.weight_tree.ancestor_at(me, fork_at);
+        let new_weight = self.weight_tree.subtree_weight(new);
+        if prev_weight < new_weight
+            || (prev_weight == new_weight
+                && self.arena[prev].hash < self.arena[new].hash)
+        {
+            self.pivot_chain.truncate(fork_at);
+            let mut u = new;
+            while u != NULL {
+                self.pivot_chain.push(u);
+                u = self.heaviest_child(u);
+            }
+        }
+        true
+    }
+
+    /// Whether the pivot block at `height` outweighs the other children of
+    /// its parent by `STABLE_EPOCH_WEIGHT_MARGIN` times its difficulty, as
+    /// consensus decides the stable epochs.
+    pub fn is_stable(&mut self, height: usize) -> bool {
+        let pivot = match self.pivot_chain.get(height) {
+            Some(pivot) => *pivot,
+            None => return false,
+        };
+        let parent = self.arena[pivot].parent;
+        if parent == NULL {
+            return true;
+        }
+        let mut fork_weight = U256::zero();
+        for child in &self.arena[parent].children {
+            if *child != pivot {
+                fork_weight += self.weight_tree.subtree_weight(*child);
+            }
+        }
+        let margin = self.arena[pivot].difficulty
+            * U256::from(STABLE_EPOCH_WEIGHT_MARGIN);
+        self.weight_tree.subtree_weight(pivot) >= fork_weight + margin
+    }
+
+    fn heaviest_child(&mut self, index: usize) -> usize {
+        let mut heaviest = NULL;
+        let mut heaviest_weight = U256::zero();
+        for child in &self.arena[index].children {
+            let weight = self.weight_tree.subtree_weight(*child);
+            if heaviest == NULL
+                || weight > heaviest_weight
+                || (weight == heaviest_weight
+                    && self.arena[*child].hash > self.arena[heaviest].hash)
+            {
+                heaviest = *child;
+                heaviest_weight = weight;
+            }
+        }
+        heaviest
+    }
+
+    fn insert_node(
+        &mut self, header: &BlockHeader, parent: usize, partial_invalid: bool,
+    ) -> usize {
+        let index = self.arena.len();
+        let height = if parent == NULL {
+            0
+        } else {
+            self.arena[parent].height + 1
+        };
+        self.arena.push(LightPivotNode {
+            hash: header.hash(),
+            parent,
+            height,
+            children: Vec::new(),
+            difficulty: *header.difficulty(),
+            partial_invalid,
+        });
+        self.indices.insert(header.hash(), index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LightPivotChain;
+    use crate::consensus::STABLE_EPOCH_WEIGHT_MARGIN;
+    use cfx_types::{H256, U256};
+    use primitives::{BlockHeader, BlockHeaderBuilder};
+
+    fn new_header(parent: &BlockHeader, nonce: u64) -> BlockHeader {
+        BlockHeaderBuilder::new()
+            .with_parent_hash(parent.hash())
+            .with_height(parent.height() + 1)
+            .with_deferred_state_root(*parent.deferred_state_root())
+            .with_deferred_receipts_root(*parent.deferred_receipts_root())
+            .with_difficulty(U256::one())
+            .with_nonce(nonce)
+            .build()
+    }
+
+    fn new_genesis() -> BlockHeader {
+        BlockHeaderBuilder::new()
+            .with_difficulty(U256::one())
+            .build()
+    }
+
+    #[test]
+    fn test_switch_to_heavier_fork() {
+        let genesis = new_genesis();
+        let mut pivot_chain = LightPivotChain::new(&genesis);
+        let a1 = new_header(&genesis, 1);
+        let a2 = new_header(&a1, 1);
+        assert!(pivot_chain.insert(&a1));
+        assert!(pivot_chain.insert(&a2));
+        assert_eq!(pivot_chain.len(), 3);
+        assert_eq!(pivot_chain.best_block_hash(), a2.hash());
+
+        let b1 = new_header(&genesis, 2);
+        let b2 = new_header(&b1, 2);
+        let b3 = new_header(&b2, 2);
+        assert!(pivot_chain.insert(&b1));
+        assert!(pivot_chain.insert(&b2));
+        // The forks are as heavy, so the larger hash wins.
+        let heavier = if a1.hash() > b1.hash() {
+            a1.hash()
+        } else {
+            b1.hash()
+        };
+        assert_eq!(pivot_chain.get(1), Some(heavier));
+        assert!(pivot_chain.insert(&b3));
+        assert_eq!(pivot_chain.len(), 4);
+        assert_eq!(pivot_chain.get(1), Some(b1.hash()));
+        assert_eq!(pivot_chain.best_block_hash(), b3.hash());
+        assert!(pivot_chain.contains(&a2.hash()));
+    }
+
+    #[test]
+    fn test_exclude_invalid_headers() {
+        let genesis = new_genesis();
+        let mut pivot_chain = LightPivotChain::new(&genesis);
+        let a1 = new_header(&genesis, 1);
+        assert!(pivot_chain.insert(&a1));
+
+        // A header which commits to a wrong genesis state, and its
+        // descendants, do not add weight to their fork.
+        let b1 = BlockHeaderBuilder::new()
+            .with_parent_hash(genesis.hash())
+            .with_height(1)
+            .with_deferred_state_root(H256::from(1))
+            .with_deferred_receipts_root(*genesis.deferred_receipts_root())
+            .with_difficulty(U256::one())
+            .build();
+        assert!(!pivot_chain.insert(&b1));
+        let b2 = new_header(&b1, 2);
+        let b3 = new_header(&b2, 2);
+        assert!(!pivot_chain.insert(&b2));
+        assert!(!pivot_chain.insert(&b3));
+        assert!(pivot_chain.contains(&b3.hash()));
+        assert_eq!(pivot_chain.len(), 2);
+        assert_eq!(pivot_chain.best_block_hash(), a1.hash());
+    }
+
+    #[test]
+    fn test_stable_pivot_block() {
+        let genesis = new_genesis();
+        let mut pivot_chain = LightPivotChain::new(&genesis);
+        let b1 = new_header(&genesis, 2);
+        assert!(pivot_chain.insert(&b1));
+        let a1 = new_header(&genesis, 1);
+        assert!(pivot_chain.insert(&a1));
+        let mut parent = if pivot_chain.get(1) == Some(a1.hash()) {
+            a1
+        } else {
+            b1
+        };
+        assert!(pivot_chain.is_stable(0));
+        assert!(!pivot_chain.is_stable(1));
+
+        for _ in 0..STABLE_EPOCH_WEIGHT_MARGIN {
+            let header = new_header(&parent, 0);
+            assert!(pivot_chain.insert(&header));
+            parent = header;
+        }
+        assert!(pivot_chain.is_stable(1));
+        assert!(!pivot_chain.is_stable(pivot_chain.len()));
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::LIGHT_PROTOCOL_VERSION;
+use crate::{
+    bytes::Bytes,
+    sync::{Error, ErrorKind, SharedSynchronizationGraph, MAX_HEADERS_TO_SEND},
+};
+use io::TimerToken;
+use message::{
+    GetBlockHeaders, GetBlockHeadersResponse, GetBlockTransactions,
+    GetBlockTransactionsResponse, GetReceipts, GetReceiptsResponse,
+    GetStateProof, GetStateProofResponse, GetTerminalBlockHashes,
+    GetTerminalBlockHashesResponse, Message, MsgId, Status, StorageProof,
+};
+use network::{
+    Error as NetworkError, Misbehavior, NetworkContext,
+    NetworkProtocolHandler, PeerId,
+};
+use parking_lot::RwLock;
+use priority_send_queue::SendQueuePriority;
+use rlp::Rlp;
+use std::{cmp, collections::HashSet};
+
+/// The handler of the light protocol on full nodes, which serves block
+/// headers, and state, receipts and transactions with the proofs light nodes
+/// check against block headers.
+///
+/// Requests which can't be served, e.g. for an epoch which is not executed
+/// yet, are not responded to, so that light nodes retry with other peers.
+pub struct LightProvider {
+    graph: SharedSynchronizationGraph,
+    /// The peers which have finished the handshake.
+    peers: RwLock<HashSet<PeerId>>,
+}
+
+impl LightProvider {
+    pub fn new(graph: SharedSynchronizationGraph) -> Self {
+        LightProvider {
+            graph,
+            peers: RwLock::new(HashSet::new()),
+        }
+    }
+
+    fn send_message(
+        &self, io: &NetworkContext, peer: PeerId, msg: &Message,
+    ) -> Result<(), NetworkError> {
+        let mut raw = Bytes::new();
+        raw.push(msg.msg_id().into());
+        raw.extend(msg.rlp_bytes().iter());
+        if let Err(e) = io.send(peer, raw, SendQueuePriority::Normal) {
+            debug!("Error sending message: {:?}", e);
+            return Err(e);
+        };
+        debug!(
+            "Send message({}) to {:?}",
+            msg.msg_id(),
+            io.get_peer_node_id(peer)
+        );
+        Ok(())
+    }
+
+    fn send_status(
+        &self, io: &NetworkContext, peer: PeerId,
+    ) -> Result<(), NetworkError> {
+        debug!("Sending status message to {:?}", peer);
+
+        let (_guard, best_info) = self.graph.get_best_info().into();
+
+        let msg: Box<dyn Message> = Box::new(Status {
+            protocol_version: LIGHT_PROTOCOL_VERSION,
+            network_id: self.graph.network_id(),
+            genesis_hash: self.graph.genesis_hash(),
+            best_epoch: best_info.best_epoch_number as u64,
+            terminal_block_hashes: best_info.terminal_block_hashes,
+        });
+        self.send_message(io, peer, msg.as_ref())
+    }
+
+    fn dispatch_message(
+        &self, io: &NetworkContext, peer: PeerId, msg_id: MsgId, rlp: Rlp,
+    ) {
+        trace!("Dispatching message: peer={:?}, msgid={:?}", peer, msg_id);
+        if msg_id != MsgId::STATUS && !self.peers.read().contains(&peer) {
+            warn!(
+                "Unexpected message from unrecognized peer: peer={:?} \
+                 msgid={:?}",
+                peer, msg_id
+            );
+            return;
+        }
+        match msg_id {
+            MsgId::STATUS => self.on_status(peer, &rlp),
+            MsgId::GET_BLOCK_HEADERS => {
+                self.on_get_block_headers(io, peer, &rlp)
+            }
+            MsgId::GET_TERMINAL_BLOCK_HASHES => {
+                self.on_get_terminal_block_hashes(io, peer, &rlp)
+            }
+            MsgId::GET_STATE_PROOF => self.on_get_state_proof(io, peer, &rlp),
+            MsgId::GET_RECEIPTS => self.on_get_receipts(io, peer, &rlp),
+            MsgId::GET_BLOCK_TRANSACTIONS => {
+                self.on_get_block_transactions(io, peer, &rlp)
+            }
+            _ => {
+                warn!("Unknown message: peer={:?} msgid={:?}", peer, msg_id);
+                Ok(())
+            }
+        }
+        .unwrap_or_else(|e| {
+            warn!(
+                "Error while handling message msgid={:?}, error={:?}",
+                msg_id, e
+            );
+            match e.kind() {
+                ErrorKind::Invalid => {
+                    io.report_peer(peer, Misbehavior::InvalidBlock)
+                }
+                ErrorKind::Decoder(_) => {
+                    io.report_peer(peer, Misbehavior::MalformedMessage)
+                }
+                _ => {}
+            }
+        });
+    }
+
+    fn on_status(&self, peer: PeerId, rlp: &Rlp) -> Result<(), Error> {
+        let status = rlp.as_val::<Status>()?;
+        debug!("on_status, msg=:{:?}", status);
+        let genesis_hash = self.graph.genesis_hash();
+        if genesis_hash != status.genesis_hash {
+            debug!(
+                "Peer {:?} genesis hash mismatches (ours: {:?}, theirs: {:?})",
+                peer, genesis_hash, status.genesis_hash
+            );
+            return Err(ErrorKind::Invalid.into());
+        }
+        let network_id = self.graph.network_id();
+        if network_id != status.network_id {
+            debug!(
+                "Peer {:?} network id mismatches (ours: {}, theirs: {})",
+                peer, network_id, status.network_id
+            );
+            return Err(ErrorKind::Invalid.into());
+        }
+        self.peers.write().insert(peer);
+        Ok(())
+    }
+
+    fn on_get_block_headers(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let req = rlp.as_val::<GetBlockHeaders>()?;
+        debug!("on_get_block_headers, msg=:{:?}", req);
+
+        let mut hash = req.hash;
+        let mut block_headers_resp = GetBlockHeadersResponse::default();
+        block_headers_resp.set_request_id(req.request_id());
+
+        for _n in 0..cmp::min(MAX_HEADERS_TO_SEND, req.max_blocks) {
+            let header = match self.graph.block_header_by_hash(&hash) {
+                Some(header) => header,
+                None => break,
+            };
+            let parent_hash = *header.parent_hash();
+            block_headers_resp.headers.push(header);
+            if hash == self.graph.genesis_hash() {
+                break;
+            }
+            hash = parent_hash;
+        }
+
+        let msg: Box<dyn Message> = Box::new(block_headers_resp);
+        self.send_message(io, peer, msg.as_ref())?;
+        Ok(())
+    }
+
+    fn on_get_terminal_block_hashes(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let req = rlp.as_val::<GetTerminalBlockHashes>()?;
+        debug!("on_get_terminal_block_hashes, msg=:{:?}", req);
+        let (_guard, best_info) = self.graph.get_best_info().into();
+        let msg: Box<dyn Message> = Box::new(GetTerminalBlockHashesResponse {
+            request_id: req.request_id().into(),
+            hashes: best_info.terminal_block_hashes,
+        });
+        self.send_message(io, peer, msg.as_ref())?;
+        Ok(())
+    }
+
+    fn on_get_state_proof(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let req = rlp.as_val::<GetStateProof>()?;
+        debug!("on_get_state_proof, msg=:{:?}", req);
+
+        let proof = match self.graph.consensus.get_account_proof_by_epoch_hash(
+            req.address,
+            &req.storage_keys,
+            req.epoch_hash,
+        ) {
+            Ok(proof) => proof,
+            Err(e) => {
+                debug!("Unable to serve state proof: {}", e);
+                return Ok(());
+            }
+        };
+        let storage_proofs = proof
+            .storage_proofs
+            .iter()
+            .map(|(key, value, proof)| StorageProof {
+                key: *key,
+                value: value.as_ref().map(|value| value.to_vec()),
+                proof: rlp::encode(proof).to_vec(),
+            })
+            .collect();
+        let msg: Box<dyn Message> = Box::new(GetStateProofResponse {
+            request_id: req.request_id().into(),
+            account: proof.account.as_ref().map(|account| account.to_vec()),
+            account_proof: rlp::encode(&proof.account_proof).to_vec(),
+            storage_proofs,
+        });
+        self.send_message(io, peer, msg.as_ref())?;
+        Ok(())
+    }
+
+    fn on_get_receipts(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let req = rlp.as_val::<GetReceipts>()?;
+        debug!("on_get_receipts, msg=:{:?}", req);
+
+        let (block_hashes, receipts) =
+            match self.graph.consensus.get_epoch_receipts(&req.epoch_hash) {
+                Some(epoch_receipts) => epoch_receipts,
+                None => {
+                    debug!("Unable to serve receipts of {:?}", req.epoch_hash);
+                    return Ok(());
+                }
+            };
+        let msg: Box<dyn Message> = Box::new(GetReceiptsResponse {
+            request_id: req.request_id().into(),
+            block_hashes,
+            receipts: receipts
+                .iter()
+                .map(|block_receipts| block_receipts.as_ref().clone())
+                .collect(),
+        });
+        self.send_message(io, peer, msg.as_ref())?;
+        Ok(())
+    }
+
+    fn on_get_block_transactions(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let req = rlp.as_val::<GetBlockTransactions>()?;
+        debug!("on_get_block_transactions, msg=:{:?}", req);
+
+        let block = match self.graph.block_by_hash(&req.hash) {
+            Some(block) => block,
+            None => {
+                debug!("Unable to serve transactions of {:?}", req.hash);
+                return Ok(());
+            }
+        };
+        let msg: Box<dyn Message> = Box::new(GetBlockTransactionsResponse {
+            request_id: req.request_id().into(),
+            transactions: block
+                .transactions
+                .iter()
+                .map(|tx| tx.as_ref().clone())
+                .collect(),
+        });
+        self.send_message(io, peer, msg.as_ref())?;
+        Ok(())
+    }
+}
+
+impl NetworkProtocolHandler for LightProvider {
+    fn on_message(&self, io: &NetworkContext, peer: PeerId, raw: &[u8]) {
+        let msg_id = raw[0];
+        let rlp = Rlp::new(&raw[1..]);
+        debug!("on_message: peer={:?}, msgid={:?}", peer, msg_id);
+        self.dispatch_message(io, peer, msg_id.into(), rlp);
+    }
+
+    fn on_peer_connected(&self, io: &NetworkContext, peer: PeerId) {
+        info!("Light peer connected: peer={:?}", peer);
+        if let Err(e) = self.send_status(io, peer) {
+            debug!("Error sending status message: {:?}", e);
+            io.disconnect_peer(peer);
+        }
+    }
+
+    fn on_peer_disconnected(&self, _io: &NetworkContext, peer: PeerId) {
+        info!("Light peer disconnected: peer={:?}", peer);
+        self.peers.write().remove(&peer);
+    }
+
+    fn on_timeout(&self, _io: &NetworkContext, timer: TimerToken) {
+        warn!("Unknown timer {} triggered.", timer);
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    consensus::AccountProof,
+    statedb::StorageKey,
+    storage::TrieProof,
+    sync::{Error, ErrorKind},
+};
+use cfx_types::{H160, H256};
+use message::{
+    GetBlockTransactionsResponse, GetReceiptsResponse, GetStateProofResponse,
+};
+use primitives::{
+    receipt::Receipt, Block, BlockHeaderBuilder, SignedTransaction,
+};
+use std::sync::Arc;
+
+/// A request of a light node for data which is not in block headers, along
+/// with the roots in block headers that the response is verified against.
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// An account and its storage entries in the state of an epoch.
+    StateProof {
+        epoch_hash: H256,
+        /// The deferred state root committing to the state of the epoch.
+        state_root: H256,
+        address: H160,
+        storage_keys: Vec<H256>,
+    },
+    /// The receipts of all blocks in an epoch.
+    Receipts {
+        epoch_hash: H256,
+        /// The deferred receipts root committing to the receipts of the
+        /// epoch.
+        receipts_root: H256,
+        /// The blocks of the epoch in execution order according to the
+        /// local header graph.
+        block_hashes: Vec<H256>,
+    },
+    /// The transactions of a block.
+    BlockTransactions { hash: H256, transactions_root: H256 },
+}
+
+/// The verified response of a `Query`.
+pub enum QueryResult {
+    StateProof(AccountProof),
+    /// The blocks of the epoch in execution order and their receipts.
+    Receipts(Vec<H256>, Vec<Arc<Vec<Receipt>>>),
+    BlockTransactions(Vec<Arc<SignedTransaction>>),
+}
+
+impl Query {
+    /// Check the response of a full node against the roots of the query.
+    pub fn verify_state_proof(
+        &self, response: GetStateProofResponse,
+    ) -> Result<QueryResult, Error> {
+        let (epoch_hash, state_root, address, storage_keys) = match self {
+            Query::StateProof {
+                epoch_hash,
+                state_root,
+                address,
+                storage_keys,
+            } => (epoch_hash, state_root, address, storage_keys),
+            _ => return Err(ErrorKind::UnexpectedResponse.into()),
+        };
+
+        let account_proof = rlp::decode::<TrieProof>(&response.account_proof)?;
+        if !account_proof.is_valid(
+            StorageKey::new_account_key(address).as_ref(),
+            response.account.as_ref().map(|account| &account[..]),
+            state_root,
+        ) {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+
+        if response.storage_proofs.len() != storage_keys.len() {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+        let mut storage_proofs = Vec::with_capacity(storage_keys.len());
+        for (key, storage_proof) in
+            storage_keys.iter().zip(response.storage_proofs.into_iter())
+        {
+            let proof = rlp::decode::<TrieProof>(&storage_proof.proof)?;
+            if storage_proof.key != *key
+                || !proof.is_valid(
+                    StorageKey::new_storage_key(address, key).as_ref(),
+                    storage_proof.value.as_ref().map(|value| &value[..]),
+                    state_root,
+                )
+            {
+                return Err(ErrorKind::InvalidProof.into());
+            }
+            storage_proofs.push((
+                *key,
+                storage_proof.value.map(|value| value.into_boxed_slice()),
+                proof,
+            ));
+        }
+
+        Ok(QueryResult::StateProof(AccountProof {
+            epoch_hash: *epoch_hash,
+            state_root: *state_root,
+            account: response.account.map(|account| account.into_boxed_slice()),
+            account_proof,
+            storage_proofs,
+        }))
+    }
+
+    pub fn verify_receipts(
+        &self, response: GetReceiptsResponse,
+    ) -> Result<QueryResult, Error> {
+        let (receipts_root, block_hashes) = match self {
+            Query::Receipts {
+                receipts_root,
+                block_hashes,
+                ..
+            } => (receipts_root, block_hashes),
+            _ => return Err(ErrorKind::UnexpectedResponse.into()),
+        };
+
+        // The receipts root only commits to the receipts in order, so the
+        // blocks and their order are checked against the header graph.
+        if response.block_hashes != *block_hashes
+            || response.receipts.len() != block_hashes.len()
+        {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+
+        let receipts: Vec<_> =
+            response.receipts.into_iter().map(Arc::new).collect();
+        if BlockHeaderBuilder::compute_block_receipts_root(&receipts)
+            != *receipts_root
+        {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+        Ok(QueryResult::Receipts(response.block_hashes, receipts))
+    }
+
+    pub fn verify_block_transactions(
+        &self, response: GetBlockTransactionsResponse,
+    ) -> Result<QueryResult, Error> {
+        let transactions_root = match self {
+            Query::BlockTransactions {
+                transactions_root, ..
+            } => transactions_root,
+            _ => return Err(ErrorKind::UnexpectedResponse.into()),
+        };
+
+        let transactions: Vec<_> =
+            response.transactions.into_iter().map(Arc::new).collect();
+        if Block::compute_transaction_root(&transactions) != *transactions_root
+        {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+        Ok(QueryResult::BlockTransactions(transactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Query, QueryResult};
+    use cfx_types::H256;
+    use keylib::{Generator, Random};
+    use message::{GetBlockTransactionsResponse, GetReceiptsResponse};
+    use primitives::{
+        receipt::{Receipt, TRANSACTION_OUTCOME_SUCCESS},
+        Block, BlockHeaderBuilder, Transaction,
+    };
+    use std::sync::Arc;
+
+    fn receipts_query(
+        block_hashes: &Vec<H256>, receipts: &Vec<Vec<Receipt>>,
+    ) -> Query {
+        let receipts =
+            receipts.iter().cloned().map(Arc::new).collect::<Vec<_>>();
+        Query::Receipts {
+            epoch_hash: block_hashes[1],
+            receipts_root: BlockHeaderBuilder::compute_block_receipts_root(
+                &receipts,
+            ),
+            block_hashes: block_hashes.clone(),
+        }
+    }
+
+    #[test]
+    fn test_verify_receipts() {
+        let block_hashes = vec![H256::from(1), H256::from(2)];
+        let receipt =
+            Receipt::new(TRANSACTION_OUTCOME_SUCCESS, 21000.into(), vec![]);
+        let receipts = vec![vec![receipt], vec![]];
+        let query = receipts_query(&block_hashes, &receipts);
+
+        let response = GetReceiptsResponse {
+            request_id: 0.into(),
+            block_hashes: block_hashes.clone(),
+            receipts: receipts.clone(),
+        };
+        match query.verify_receipts(response) {
+            Ok(QueryResult::Receipts(hashes, verified)) => {
+                assert_eq!(hashes, block_hashes);
+                assert_eq!(verified.len(), 2);
+                assert_eq!(verified[0].as_ref(), &receipts[0]);
+            }
+            _ => panic!("valid receipts are rejected"),
+        }
+
+        // Receipts which are not committed to by the root.
+        let mut forged = receipts.clone();
+        forged[0][0].gas_used = 0.into();
+        let response = GetReceiptsResponse {
+            request_id: 0.into(),
+            block_hashes: block_hashes.clone(),
+            receipts: forged,
+        };
+        assert!(query.verify_receipts(response).is_err());
+
+        // Blocks which are not in the epoch.
+        let response = GetReceiptsResponse {
+            request_id: 0.into(),
+            block_hashes: vec![block_hashes[0], block_hashes[0]],
+            receipts: receipts.clone(),
+        };
+        assert!(query.verify_receipts(response).is_err());
+
+        // Blocks of the epoch which are not in execution order.
+        let response = GetReceiptsResponse {
+            request_id: 0.into(),
+            block_hashes: vec![block_hashes[1], block_hashes[0]],
+            receipts,
+        };
+        assert!(query.verify_receipts(response).is_err());
+    }
+
+    #[test]
+    fn test_verify_block_transactions() {
+        let keypair = Random.generate().unwrap();
+        let transaction = Transaction {
+            nonce: 0.into(),
+            gas_price: 1.into(),
+            gas: 21000.into(),
+            value: 100.into(),
+            ..Default::default()
+        }
+        .sign(keypair.secret());
+        let transactions = vec![Arc::new(transaction.clone())];
+        let query = Query::BlockTransactions {
+            hash: H256::from(1),
+            transactions_root: Block::compute_transaction_root(&transactions),
+        };
+
+        let response = GetBlockTransactionsResponse {
+            request_id: 0.into(),
+            transactions: vec![transaction.clone()],
+        };
+        match query.verify_block_transactions(response) {
+            Ok(QueryResult::BlockTransactions(verified)) => {
+                assert_eq!(verified, transactions);
+            }
+            _ => panic!("valid transactions are rejected"),
+        }
+
+        let response = GetBlockTransactionsResponse {
+            request_id: 0.into(),
+            transactions: vec![],
+        };
+        assert!(query.verify_block_transactions(response).is_err());
+    }
+}
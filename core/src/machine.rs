@@ -2,19 +2,20 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use super::builtin::Builtin;
+use super::builtin::{Builtin, BuiltinSpec, PricingSpec};
 use crate::vm::Spec;
 use cfx_types::{Address, U256};
 use primitives::CardinalNumber;
 use std::{collections::BTreeMap, sync::Arc};
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct CommonParams {
     /// Account start nonce.
     pub account_start_nonce: U256,
     /// Maximum size of extra data.
     pub maximum_extra_data_size: usize,
-    /// Network id.
+    /// Network id exchanged in status messages. Peers with another network
+    /// id are disconnected.
     pub network_id: u64,
     /// Chain id.
     pub chain_id: u64,
@@ -34,14 +35,19 @@ pub struct CommonParams {
     pub max_code_size_transition: CardinalNumber,
     /// Maximum size of transaction's RLP payload.
     pub max_transaction_size: usize,
+    /// Mining reward of a block in Conflux tokens.
+    pub base_mining_reward: u64,
+    /// The reward of a block is penalized by the square of its anticone
+    /// difficulty divided by its own difficulty and by this ratio.
+    pub anticone_penalty_ratio: u64,
 }
 
 impl CommonParams {
-    fn common_params() -> Self {
+    pub fn common_params() -> Self {
         CommonParams {
             account_start_nonce: 0x00.into(),
             maximum_extra_data_size: 0x20,
-            network_id: 0x1,
+            network_id: 0x0,
             chain_id: 0x1,
             subprotocol_name: "cfx".into(),
             min_gas_limit: 0x1387.into(),
@@ -51,6 +57,8 @@ impl CommonParams {
             max_code_size: 24576,
             max_code_size_transition: 0,
             max_transaction_size: 300 * 1024,
+            base_mining_reward: 900,
+            anticone_penalty_ratio: 100,
         }
     }
 }
@@ -97,9 +105,65 @@ impl Machine {
 }
 
 pub fn new_machine() -> Machine {
+    new_machine_with_builtins(
+        CommonParams::common_params(),
+        default_builtin_specs(),
+    )
+}
+
+pub fn new_machine_with_builtins(
+    params: CommonParams, builtin_specs: Vec<(Address, BuiltinSpec)>,
+) -> Machine {
+    let builtins = builtin_specs
+        .into_iter()
+        .map(|(address, spec)| (address, Builtin::from(spec)))
+        .collect();
     Machine {
-        params: CommonParams::common_params(),
-        builtins: Arc::new(BTreeMap::new()),
+        params,
+        builtins: Arc::new(builtins),
         spec_rules: None,
     }
 }
+
+fn builtin_address(index: u8) -> Address {
+    let mut address = Address::zero();
+    address[19] = index;
+    address
+}
+
+/// The standard precompiled contracts at addresses 0x1 to 0x8, priced as in
+/// Ethereum Byzantium.
+pub fn default_builtin_specs() -> Vec<(Address, BuiltinSpec)> {
+    let linear = |name: &str, base: usize, word: usize| BuiltinSpec {
+        name: name.into(),
+        pricing: PricingSpec::Linear { base, word },
+        activate_at: 0,
+    };
+    vec![
+        (builtin_address(1), linear("ecrecover", 3000, 0)),
+        (builtin_address(2), linear("sha256", 60, 12)),
+        (builtin_address(3), linear("ripemd160", 600, 120)),
+        (builtin_address(4), linear("identity", 15, 3)),
+        (
+            builtin_address(5),
+            BuiltinSpec {
+                name: "modexp".into(),
+                pricing: PricingSpec::Modexp { divisor: 20 },
+                activate_at: 0,
+            },
+        ),
+        (builtin_address(6), linear("alt_bn128_add", 500, 0)),
+        (builtin_address(7), linear("alt_bn128_mul", 40000, 0)),
+        (
+            builtin_address(8),
+            BuiltinSpec {
+                name: "alt_bn128_pairing".into(),
+                pricing: PricingSpec::AltBn128Pairing {
+                    base: 100000,
+                    pair: 80000,
+                },
+                activate_at: 0,
+            },
+        ),
+    ]
+}
@@ -0,0 +1,86 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use parking_lot::Mutex;
+use primitives::BlockHeader;
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc,
+};
+
+/// Events of the consensus graph and the transaction pool.
+#[derive(Clone, Debug)]
+pub enum Notification {
+    /// A fully valid block is inserted into the consensus graph.
+    NewBlockHeader(BlockHeader),
+    /// The pivot chain is changed from epoch `fork_at`, and the epochs from
+    /// `fork_at` on are now `epoch_hashes`. If `reorg` is true, the epochs
+    /// from `fork_at` on in the previous pivot chain are reverted.
+    NewEpochs {
+        fork_at: usize,
+        reorg: bool,
+        epoch_hashes: Vec<H256>,
+    },
+    /// Transactions are newly inserted into the transaction pool.
+    NewPendingTransactions(Vec<H256>),
+}
+
+/// Broadcasts notifications to all subscribers. A subscriber is removed once
+/// its receiver is dropped.
+pub struct Notifications {
+    subscribers: Mutex<Vec<Sender<Notification>>>,
+}
+
+pub type SharedNotifications = Arc<Notifications>;
+
+impl Notifications {
+    pub fn new() -> Self {
+        Notifications {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<Notification> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    /// The notification is sent without blocking, so it's safe to notify
+    /// while holding locks.
+    pub fn notify(&self, notification: Notification) {
+        self.subscribers
+            .lock()
+            .retain(|sender| sender.send(notification.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Notification, Notifications};
+
+    #[test]
+    fn test_notify_subscribers() {
+        let notifications = Notifications::new();
+        let receiver1 = notifications.subscribe();
+        let receiver2 = notifications.subscribe();
+
+        notifications
+            .notify(Notification::NewPendingTransactions(vec![1.into()]));
+        for receiver in &[&receiver1, &receiver2] {
+            match receiver.try_recv() {
+                Ok(Notification::NewPendingTransactions(hashes)) => {
+                    assert_eq!(hashes, vec![1.into()])
+                }
+                _ => panic!("Expected NewPendingTransactions"),
+            }
+        }
+
+        drop(receiver1);
+        notifications.notify(Notification::NewPendingTransactions(vec![]));
+        assert_eq!(notifications.subscribers.lock().len(), 1);
+        assert!(receiver2.try_recv().is_ok());
+    }
+}
@@ -3,3 +3,4 @@
 // See http://www.gnu.org/licenses/
 
 pub(self) mod snapshot;
+pub(self) mod snapshot_builder;
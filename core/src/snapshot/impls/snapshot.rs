@@ -3,9 +3,129 @@
 // See http://www.gnu.org/licenses/
 
 use super::super::snapshot::*;
+use crate::{
+    hash::keccak,
+    storage::{ErrorKind, Result},
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use cfx_types::H256;
+use rlp::*;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
 
-use std::path::Path;
+impl SnapshotChunk {
+    pub fn hash(&self) -> H256 { keccak(rlp::encode(self)) }
+}
+
+impl Encodable for SnapshotManifest {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.epoch_id)
+            .append(&self.state_root)
+            .append_list(&self.chunk_hashes);
+    }
+}
+
+impl Decodable for SnapshotManifest {
+    fn decode(rlp: &Rlp) -> ::std::result::Result<Self, DecoderError> {
+        Ok(SnapshotManifest {
+            epoch_id: rlp.val_at(0)?,
+            state_root: rlp.val_at(1)?,
+            chunk_hashes: rlp.list_at(2)?,
+        })
+    }
+}
+
+impl Encodable for SnapshotChunk {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(self.key_values.len());
+        for (key, value) in &self.key_values {
+            s.begin_list(2).append(key).append(value);
+        }
+    }
+}
+
+impl Decodable for SnapshotChunk {
+    fn decode(rlp: &Rlp) -> ::std::result::Result<Self, DecoderError> {
+        let mut key_values = Vec::with_capacity(rlp.item_count()?);
+        for key_value in rlp.iter() {
+            key_values.push((key_value.val_at(0)?, key_value.val_at(1)?));
+        }
+        Ok(SnapshotChunk { key_values })
+    }
+}
+
+impl Snapshot {
+    fn write_record<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+        writer.write_u32::<BigEndian>(bytes.len() as u32)?;
+        writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Returns None at the end of file.
+    fn read_record<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+        let len = match reader.read_u32::<BigEndian>() {
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut bytes = vec![0; len as usize];
+        reader.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+}
 
 impl SnapshotTrait for Snapshot {
-    fn from_file(_path: &Path) -> Self { unimplemented!() }
+    fn from_file(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let manifest: SnapshotManifest = match Self::read_record(&mut reader)? {
+            Some(bytes) => rlp::decode(&bytes)?,
+            None => return Err(DecoderError::RlpIsTooShort.into()),
+        };
+        let mut chunks = Vec::with_capacity(manifest.chunk_hashes.len());
+        while let Some(bytes) = Self::read_record(&mut reader)? {
+            chunks.push(rlp::decode(&bytes)?);
+        }
+
+        let snapshot = Snapshot { manifest, chunks };
+        snapshot.verify_chunks()?;
+        Ok(snapshot)
+    }
+
+    fn write_to_file(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        Self::write_record(&mut writer, &rlp::encode(&self.manifest))?;
+        for chunk in &self.chunks {
+            Self::write_record(&mut writer, &rlp::encode(chunk))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn verify_chunks(&self) -> Result<()> {
+        if self.chunks.len() != self.manifest.chunk_hashes.len() {
+            return Err(ErrorKind::SnapshotChunkHashMismatch(
+                self.chunks.len().min(self.manifest.chunk_hashes.len()),
+            )
+            .into());
+        }
+        for (index, (chunk, hash)) in self
+            .chunks
+            .iter()
+            .zip(self.manifest.chunk_hashes.iter())
+            .enumerate()
+        {
+            if chunk.hash() != *hash {
+                return Err(ErrorKind::SnapshotChunkHashMismatch(index).into());
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,66 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::super::{snapshot::*, snapshot_builder::*};
+use crate::storage::MerkleHash;
+use primitives::EpochId;
+use std::mem;
+
+impl SnapshotBuilder {
+    pub fn new(epoch_id: EpochId, state_root: MerkleHash) -> Self {
+        Self::new_with_chunk_size(
+            epoch_id,
+            state_root,
+            DEFAULT_SNAPSHOT_CHUNK_SIZE,
+        )
+    }
+
+    pub fn new_with_chunk_size(
+        epoch_id: EpochId, state_root: MerkleHash, chunk_size: usize,
+    ) -> Self {
+        Self {
+            epoch_id,
+            state_root,
+            chunk_size,
+            chunks: Vec::new(),
+            current_chunk: Default::default(),
+            current_chunk_size: 0,
+        }
+    }
+
+    fn close_current_chunk(&mut self) {
+        if !self.current_chunk.key_values.is_empty() {
+            self.chunks.push(mem::replace(
+                &mut self.current_chunk,
+                Default::default(),
+            ));
+            self.current_chunk_size = 0;
+        }
+    }
+}
+
+impl SnapshotBuilderTrait for SnapshotBuilder {
+    fn add_key_value(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.current_chunk_size += key.len() + value.len();
+        self.current_chunk.key_values.push((key, value));
+        if self.current_chunk_size >= self.chunk_size {
+            self.close_current_chunk();
+        }
+    }
+
+    fn build(mut self) -> Snapshot {
+        self.close_current_chunk();
+        let chunk_hashes =
+            self.chunks.iter().map(|chunk| chunk.hash()).collect();
+
+        Snapshot {
+            manifest: SnapshotManifest {
+                epoch_id: self.epoch_id,
+                state_root: self.state_root,
+                chunk_hashes,
+            },
+            chunks: self.chunks,
+        }
+    }
+}
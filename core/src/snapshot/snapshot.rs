@@ -2,17 +2,45 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+use crate::storage::{MerkleHash, Result};
+use cfx_types::H256;
+use primitives::EpochId;
 use std::path::Path;
 
+/// The manifest describes the state at an epoch, and the hash of every chunk
+/// in order, so that chunks can be verified independently.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotManifest {
+    pub epoch_id: EpochId,
+    pub state_root: MerkleHash,
+    pub chunk_hashes: Vec<H256>,
+}
+
+/// A chunk of key-values of the state. Keys are storage access keys, values
+/// are the raw values in storage, i.e. rlp of account, storage value, or code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnapshotChunk {
+    pub key_values: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
 // Conflux snapshot wire-format.
+//
+// The file is a sequence of records, each of which is the rlp bytes prefixed
+// by its length as big-endian u32. The first record is the manifest, the rest
+// are the chunks.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Snapshot {
-    // TODO(yz): implement.
+    pub manifest: SnapshotManifest,
+    pub chunks: Vec<SnapshotChunk>,
 }
 
 // The trait is created to separate the implementation to another file, and the
 // concrete struct is put into inner mod, because the implementation is
 // anticipated to be too complex to present in the same file of the API.
 // TODO(yz): check if this is the best way to organize code for this library.
-pub trait SnapshotTrait {
-    fn from_file(path: &Path) -> Self;
+pub trait SnapshotTrait: Sized {
+    fn from_file(path: &Path) -> Result<Self>;
+    fn write_to_file(&self, path: &Path) -> Result<()>;
+    /// Check that the chunks match the chunk hashes in manifest.
+    fn verify_chunks(&self) -> Result<()>;
 }
@@ -2,14 +2,25 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use super::snapshot::Snapshot;
+use super::snapshot::{Snapshot, SnapshotChunk};
+use crate::storage::MerkleHash;
+use primitives::EpochId;
+
+/// The total size of keys and values after which a chunk is closed.
+pub const DEFAULT_SNAPSHOT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
 // Build snapshot
-#[allow(dead_code)]
-pub struct SnapshotBuilder {}
+pub struct SnapshotBuilder {
+    pub(super) epoch_id: EpochId,
+    pub(super) state_root: MerkleHash,
+    pub(super) chunk_size: usize,
+    pub(super) chunks: Vec<SnapshotChunk>,
+    pub(super) current_chunk: SnapshotChunk,
+    pub(super) current_chunk_size: usize,
+}
 
-trait SnapshotBuilderTrait {
-    // TODO(yz): methods to add content into snapshot.
+pub trait SnapshotBuilderTrait {
+    fn add_key_value(&mut self, key: Vec<u8>, value: Vec<u8>);
 
-    fn build() -> Snapshot;
+    fn build(self) -> Snapshot;
 }
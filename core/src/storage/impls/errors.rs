@@ -2,6 +2,8 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+use super::multi_version_merkle_patricia_trie::merkle_patricia_trie::MerkleHash;
+use primitives::EpochId;
 use std::{io, num};
 
 error_chain! {
@@ -52,9 +54,42 @@ error_chain! {
             display("Too many nodes."),
         }
 
+        UncommittedTrieNode {
+            description("Committed trie node refers to an uncommitted node."),
+            display("Committed trie node refers to an uncommitted node."),
+        }
+
         StateCommitWithoutMerkleHash {
             description("State commit called before computing Merkle hash."),
             display("State commit called before computing Merkle hash."),
         }
+
+        StateNotFound(epoch_id: EpochId) {
+            description("State not found."),
+            display("State not found at epoch {:?}.", epoch_id),
+        }
+
+        SnapshotChunkHashMismatch(index: usize) {
+            description("Snapshot chunk doesn't match its hash in manifest."),
+            display(
+                "Snapshot chunk {} doesn't match its hash in manifest.",
+                index
+            ),
+        }
+
+        SnapshotStateRootMismatch(expected: MerkleHash, actual: MerkleHash) {
+            description("State root of snapshot doesn't match manifest."),
+            display(
+                "State root of snapshot doesn't match manifest, expected \
+                 {:?}, got {:?}.",
+                expected,
+                actual
+            ),
+        }
+
+        InvalidTrieProof {
+            description("Invalid trie proof."),
+            display("Invalid trie proof."),
+        }
     }
 }
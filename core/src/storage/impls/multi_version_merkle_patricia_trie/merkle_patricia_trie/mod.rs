@@ -11,6 +11,7 @@ pub mod mpt_value;
 pub mod node_ref;
 pub mod subtrie_visitor;
 pub mod trie_node;
+pub mod trie_proof;
 
 #[cfg(test)]
 mod tests;
@@ -25,4 +26,5 @@ pub use self::{
     node_ref::{NodeRefDeltaMpt, NodeRefDeltaMptCompact},
     subtrie_visitor::SubTrieVisitor,
     trie_node::TrieNode,
+    trie_proof::{TrieProof, TrieProofNode},
 };
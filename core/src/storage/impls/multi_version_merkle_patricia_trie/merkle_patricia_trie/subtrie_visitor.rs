@@ -127,31 +127,9 @@ impl<'trie> SubTrieVisitor<'trie> {
                     Ok(Some(trie_node.merkle_hash))
                 } else {
                     let maybe_value = trie_node.value_clone().into_option();
-                    let merkles = match trie_node
-                        .children_table
-                        .get_children_count()
-                    {
-                        0 => None,
-                        _ => {
-                            let mut merkles = ChildrenMerkleTable::default();
-                            let children_table =
-                                trie_node.children_table.clone();
-                            drop(trie_node);
-                            for (i, maybe_node_ref) in
-                                children_table.iter_non_skip()
-                            {
-                                merkles[i as usize] = match maybe_node_ref {
-                                    None => super::merkle::MERKLE_NULL_NODE,
-                                    Some(node_ref) => self
-                                        .trie_ref
-                                        .get_merkle(Some((*node_ref).into()))?
-                                        .unwrap(),
-                                };
-                            }
-
-                            Some(merkles)
-                        }
-                    };
+                    let children_table = trie_node.children_table.clone();
+                    drop(trie_node);
+                    let merkles = self.get_children_merkles(&children_table)?;
 
                     Ok(Some(compute_node_merkle(
                         merkles.as_ref(),
@@ -162,6 +140,111 @@ impl<'trie> SubTrieVisitor<'trie> {
         }
     }
 
+    /// The trie node which holds the children_table must not be borrowed,
+    /// because loading the children locks the cache manager.
+    fn get_children_merkles(
+        &self, children_table: &ChildrenTableDeltaMpt,
+    ) -> Result<MaybeMerkleTable> {
+        match children_table.get_children_count() {
+            0 => Ok(None),
+            _ => {
+                let mut merkles = ChildrenMerkleTable::default();
+                for (i, maybe_node_ref) in children_table.iter_non_skip() {
+                    merkles[i as usize] = match maybe_node_ref {
+                        None => MERKLE_NULL_NODE,
+                        Some(node_ref) => self
+                            .trie_ref
+                            .get_merkle(Some((*node_ref).into()))?
+                            .unwrap(),
+                    };
+                }
+
+                Ok(Some(merkles))
+            }
+        }
+    }
+
+    /// Returns the value of the key, and the proof which consists of the trie
+    /// nodes from the subtree root to the node where the lookup stops.
+    pub fn get_with_proof(
+        &self, key: KeyPart,
+    ) -> Result<(Option<Box<[u8]>>, TrieProof)> {
+        let node_memory_manager = self.node_memory_manager();
+        let allocator = node_memory_manager.get_allocator();
+        let cache_manager = node_memory_manager.get_cache_manager();
+        let mut node_ref = self.root.node_ref.clone();
+        let mut key = key;
+
+        let mut proof_nodes = vec![];
+        loop {
+            let trie_node = node_memory_manager
+                .node_as_ref_with_cache_manager(
+                    &allocator,
+                    node_ref,
+                    cache_manager,
+                    &mut false,
+                )?;
+            let (path, path_end_mask) = {
+                let path = trie_node.compressed_path_ref();
+                (path.path_slice().to_vec(), path.end_mask())
+            };
+            let value = trie_node.value_clone().into_option();
+            let children_table = trie_node.children_table.clone();
+            let walk_stop = trie_node.walk::<Read>(key);
+            drop(trie_node);
+
+            proof_nodes.push(TrieProofNode {
+                path,
+                path_end_mask,
+                children_merkles: self.get_children_merkles(&children_table)?,
+                value: value.clone(),
+            });
+            match walk_stop {
+                WalkStop::Arrived => {
+                    return Ok((value, TrieProof::new(proof_nodes)));
+                }
+                WalkStop::Descent {
+                    key_remaining,
+                    child_index: _,
+                    child_node,
+                } => {
+                    node_ref = child_node;
+                    key = key_remaining;
+                }
+                _ => {
+                    return Ok((None, TrieProof::new(proof_nodes)));
+                }
+            }
+        }
+    }
+
+    /// Returns all key-values in the subtree. The keys include the compressed
+    /// path of the subtree root.
+    pub fn iterate_all(mut self) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        let node_memory_manager = self.node_memory_manager();
+        let allocator = node_memory_manager.get_allocator();
+        let mut node_cow = self.root.take();
+
+        let trie_node_ref =
+            node_cow.get_trie_node(node_memory_manager, &allocator)?;
+        let key_prefix = {
+            let path = trie_node_ref.compressed_path_ref();
+            CompressedPathRaw::new(path.path_slice(), path.end_mask())
+        };
+        let trie_node = GuardedValue::take(trie_node_ref);
+
+        let mut values = vec![];
+        node_cow.iterate_internal(
+            self.owned_node_set.get_ref(),
+            self.get_trie_ref(),
+            trie_node,
+            key_prefix,
+            &mut values,
+        )?;
+
+        Ok(values)
+    }
+
     /// The visitor can only be used once to modify.
     /// Returns (deleted value, is root node replaced, the current root node for
     /// the subtree).
@@ -686,6 +769,7 @@ use super::{
         return_after_use::ReturnAfterUse,
         MultiVersionMerklePatriciaTrie,
     },
+    children_table::ChildrenTableDeltaMpt,
     merkle::*,
     trie_node::{access_mode::*, *},
     *,
@@ -652,6 +652,14 @@ impl<CacheAlgoDataT: CacheAlgoDataTrait> TrieNode<CacheAlgoDataT> {
         unsafe { unreachable_unchecked() }
     }
 
+    /// Returns references to all children, e.g. to walk the whole trie.
+    pub fn get_children(&self) -> Vec<NodeRefDeltaMpt> {
+        self.children_table
+            .iter()
+            .map(|(_, node_ref)| (*node_ref).into())
+            .collect()
+    }
+
     fn get_child(&self, child_index: u8) -> Option<NodeRefDeltaMptCompact> {
         self.children_table.get_child(child_index)
     }
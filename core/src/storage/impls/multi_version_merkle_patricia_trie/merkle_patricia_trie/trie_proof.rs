@@ -0,0 +1,219 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// A trie node on the path of a key lookup, with everything needed to compute
+/// its merkle hash.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrieProofNode {
+    pub path: Vec<u8>,
+    pub path_end_mask: u8,
+    pub children_merkles: MaybeMerkleTable,
+    pub value: Option<Box<[u8]>>,
+}
+
+/// The trie nodes visited by a key lookup, from the root to the node where
+/// the lookup stops.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrieProof {
+    nodes: Vec<TrieProofNode>,
+}
+
+enum ProofWalkStop<'key> {
+    Arrived,
+    Descent {
+        key_remaining: KeyPart<'key>,
+        child_index: u8,
+    },
+    NotFound,
+}
+
+impl TrieProofNode {
+    pub fn compressed_path_ref(&self) -> CompressedPathRef {
+        CompressedPathRef {
+            path_slice: &self.path,
+            end_mask: self.path_end_mask,
+        }
+    }
+
+    pub fn get_merkle(&self) -> MerkleHash {
+        compute_merkle(
+            self.compressed_path_ref(),
+            self.children_merkles.as_ref(),
+            self.value.as_ref().map(|value| &**value),
+        )
+    }
+
+    /// Same as TrieNode::walk::<Read>, except that it only tells the child
+    /// index to descent into.
+    fn walk<'key>(&self, key: KeyPart<'key>) -> ProofWalkStop<'key> {
+        let path_slice = self.path.as_slice();
+        let memcmp_len = min(
+            path_slice
+                .len()
+                .saturating_sub((self.path_end_mask != 0) as usize),
+            key.len(),
+        );
+
+        if path_slice[0..memcmp_len] != key[0..memcmp_len] {
+            return ProofWalkStop::NotFound;
+        }
+        if key.len() == memcmp_len {
+            if path_slice.len() > memcmp_len {
+                ProofWalkStop::NotFound
+            } else {
+                ProofWalkStop::Arrived
+            }
+        } else if path_slice.len() == memcmp_len {
+            ProofWalkStop::Descent {
+                key_remaining: &key[memcmp_len..],
+                child_index: CompressedPathRaw::first_nibble(key[memcmp_len]),
+            }
+        } else if CompressedPathRaw::first_nibble(
+            path_slice[memcmp_len] ^ key[memcmp_len],
+        ) != 0
+        {
+            ProofWalkStop::NotFound
+        } else {
+            ProofWalkStop::Descent {
+                key_remaining: &key[memcmp_len + 1..],
+                child_index: CompressedPathRaw::second_nibble(key[memcmp_len]),
+            }
+        }
+    }
+}
+
+impl TrieProof {
+    pub fn new(nodes: Vec<TrieProofNode>) -> Self { Self { nodes } }
+
+    pub fn get_nodes(&self) -> &Vec<TrieProofNode> { &self.nodes }
+
+    /// Verify the proof against the state root without accessing storage.
+    /// Returns the value of the key, or None if the proof shows that the key
+    /// doesn't exist.
+    pub fn verify(
+        &self, key: KeyPart, root: &MerkleHash,
+    ) -> Result<Option<&[u8]>> {
+        if self.nodes.is_empty() {
+            // Only an empty state has no trie node.
+            return if *root == MERKLE_NULL_NODE {
+                Ok(None)
+            } else {
+                Err(ErrorKind::InvalidTrieProof.into())
+            };
+        }
+
+        let mut expected_merkle = *root;
+        let mut key = key;
+        let last = self.nodes.len() - 1;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.get_merkle() != expected_merkle {
+                return Err(ErrorKind::InvalidTrieProof.into());
+            }
+            let maybe_proven = match node.walk(key) {
+                ProofWalkStop::Arrived => {
+                    Some(node.value.as_ref().map(|value| &**value))
+                }
+                ProofWalkStop::NotFound => Some(None),
+                ProofWalkStop::Descent {
+                    key_remaining,
+                    child_index,
+                } => {
+                    let child_merkle = match &node.children_merkles {
+                        Some(merkles) => merkles[child_index as usize],
+                        None => MERKLE_NULL_NODE,
+                    };
+                    if child_merkle == MERKLE_NULL_NODE {
+                        Some(None)
+                    } else {
+                        expected_merkle = child_merkle;
+                        key = key_remaining;
+                        None
+                    }
+                }
+            };
+            match maybe_proven {
+                // The lookup stops at this node, which must be the last.
+                Some(value) => {
+                    return if i == last {
+                        Ok(value)
+                    } else {
+                        Err(ErrorKind::InvalidTrieProof.into())
+                    };
+                }
+                None => {}
+            }
+        }
+
+        // The lookup needs more nodes than provided.
+        Err(ErrorKind::InvalidTrieProof.into())
+    }
+
+    /// Check if the proof shows that the key maps to value (or doesn't exist
+    /// for None) in the state with the root.
+    pub fn is_valid(
+        &self, key: KeyPart, value: Option<&[u8]>, root: &MerkleHash,
+    ) -> bool {
+        match self.verify(key, root) {
+            Ok(proven_value) => proven_value == value,
+            Err(_) => false,
+        }
+    }
+}
+
+impl Encodable for TrieProofNode {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        // Format: [ compressed_path, children_merkles ([] or [*16]), value ]
+        s.begin_list(3).append(&self.compressed_path_ref());
+        match &self.children_merkles {
+            Some(merkles) => {
+                s.append_list(&merkles[..]);
+            }
+            None => {
+                s.begin_list(0);
+            }
+        }
+        s.append(&self.value.as_ref().map(|value| &**value));
+    }
+}
+
+impl Decodable for TrieProofNode {
+    fn decode(rlp: &Rlp) -> ::std::result::Result<Self, DecoderError> {
+        let compressed_path: CompressedPathRaw = rlp.val_at(0)?;
+        let merkles = rlp.list_at::<MerkleHash>(1)?;
+        let children_merkles = match merkles.len() {
+            0 => None,
+            CHILDREN_COUNT => {
+                let mut table = ChildrenMerkleTable::default();
+                table.copy_from_slice(&merkles);
+                Some(table)
+            }
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        };
+
+        Ok(TrieProofNode {
+            path: compressed_path.path_slice().to_vec(),
+            path_end_mask: compressed_path.end_mask(),
+            children_merkles,
+            value: rlp
+                .val_at::<Option<Vec<u8>>>(2)?
+                .map(|value| value.into_boxed_slice()),
+        })
+    }
+}
+
+impl Encodable for TrieProof {
+    fn rlp_append(&self, s: &mut RlpStream) { s.append_list(&self.nodes); }
+}
+
+impl Decodable for TrieProof {
+    fn decode(rlp: &Rlp) -> ::std::result::Result<Self, DecoderError> {
+        Ok(TrieProof {
+            nodes: rlp.as_list()?,
+        })
+    }
+}
+
+use super::{super::super::errors::*, merkle::*, trie_node::KeyPart, *};
+use rlp::*;
+use std::cmp::min;
@@ -52,6 +52,10 @@ impl MultiVersionMerklePatriciaTrie {
         self.root_by_version.write().insert(epoch_id, root);
     }
 
+    pub fn remove_epoch_root(&self, epoch_id: &EpochId) {
+        self.root_by_version.write().remove(epoch_id);
+    }
+
     pub fn loaded_root_at_epoch(
         &self, epoch_id: EpochId, db_key: DeltaMptDbKey,
     ) -> NodeRefDeltaMpt {
@@ -24,6 +24,23 @@ impl<'a> State<'a> {
             dirty: false,
         }
     }
+
+    /// Returns all key-values of the state, e.g. for snapshot.
+    pub fn get_all_key_values(&self) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        // Iteration won't create any new nodes so it's fine to pass an empty
+        // owned_node_set.
+        let mut empty_owned_node_set: Option<OwnedNodeSet> =
+            Some(Default::default());
+        match self.get_root_node() {
+            None => Ok(vec![]),
+            Some(root_node) => SubTrieVisitor::new(
+                self.delta_trie,
+                root_node,
+                &mut empty_owned_node_set,
+            )
+            .iterate_all(),
+        }
+    }
 }
 
 impl<'a> Drop for State<'a> {
@@ -70,6 +87,24 @@ impl<'a> StateTrait for State<'a> {
         }
     }
 
+    fn get_with_proof(
+        &self, access_key: &[u8],
+    ) -> Result<(Option<Box<[u8]>>, TrieProof)> {
+        // Get won't create any new nodes so it's fine to pass an empty
+        // owned_node_set.
+        let mut empty_owned_node_set: Option<OwnedNodeSet> =
+            Some(Default::default());
+        match self.get_root_node() {
+            None => Ok((None, TrieProof::default())),
+            Some(root_node) => SubTrieVisitor::new(
+                self.delta_trie,
+                root_node,
+                &mut empty_owned_node_set,
+            )
+            .get_with_proof(access_key),
+        }
+    }
+
     fn set(&mut self, access_key: &[u8], value: &[u8]) -> Result<()> {
         self.pre_modification();
 
@@ -290,12 +325,8 @@ impl<'a> State<'a> {
 
                 commit_transaction.transaction.put(
                     COL_DELTA_TRIE,
-                    [
-                        "state_root_db_key_for_epoch_id_".as_bytes(),
-                        epoch_id.as_ref(),
-                    ]
-                    .concat()
-                    .as_slice(),
+                    StateManager::state_root_db_key_for_epoch_id(&epoch_id)
+                        .as_slice(),
                     db_key.to_string().as_bytes(),
                 );
 
@@ -3,7 +3,10 @@
 // See http://www.gnu.org/licenses/
 
 pub use super::super::super::db::COL_DELTA_TRIE;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+const STATE_ROOT_DB_KEY_PREFIX: &[u8] = b"state_root_db_key_for_epoch_id_";
+const PRUNED_STATE_DB_KEY_PREFIX: &[u8] = b"pruned_state_for_epoch_id_";
 
 #[derive(Default)]
 pub struct AtomicCommit {
@@ -15,11 +18,27 @@ pub struct AtomicCommitTransaction<'a> {
     pub transaction: DBTransaction,
 }
 
+/// The trie nodes which are older than the stable state root, and no longer
+/// reachable from the remaining states, are deleted batch by batch so that
+/// pruning doesn't hold up the execution of epochs.
+struct PruningPass {
+    stable_root_db_key: RowNumberUnderlyingType,
+    /// The trie nodes to visit to mark the reachable ones.
+    to_visit: Vec<RowNumberUnderlyingType>,
+    reachable: HashSet<RowNumberUnderlyingType>,
+    /// The db key to continue scanning the trie nodes from.
+    next_key: Vec<u8>,
+}
+
 pub struct StateManager {
     delta_trie: MultiVersionMerklePatriciaTrie,
     pub db: Arc<SystemDB>,
     commit_lock: Mutex<AtomicCommit>,
+    history_epochs: Option<u64>,
+    pruning: Mutex<Option<PruningPass>>,
     pub number_commited_nodes: AtomicUsize,
+    pub number_pruned_nodes: AtomicUsize,
+    pub number_pruned_epochs: AtomicUsize,
 }
 
 impl StateManager {
@@ -34,20 +53,37 @@ impl StateManager {
         }
     }
 
+    pub(super) fn state_root_db_key_for_epoch_id(
+        epoch_id: &EpochId,
+    ) -> Vec<u8> {
+        [STATE_ROOT_DB_KEY_PREFIX, epoch_id.as_ref()].concat()
+    }
+
+    fn pruned_state_db_key_for_epoch_id(epoch_id: &EpochId) -> Vec<u8> {
+        [PRUNED_STATE_DB_KEY_PREFIX, epoch_id.as_ref()].concat()
+    }
+
+    fn is_state_pruned(&self, epoch_id: EpochId) -> Result<bool> {
+        if self.history_epochs.is_none() {
+            return Ok(false);
+        }
+        Ok(self
+            .db
+            .key_value()
+            .get(
+                COL_DELTA_TRIE,
+                Self::pruned_state_db_key_for_epoch_id(&epoch_id).as_slice(),
+            )?
+            .is_some())
+    }
+
     fn load_state_root_node_ref_from_db(
         &self, epoch_id: EpochId,
     ) -> Result<Option<NodeRefDeltaMpt>> {
-        let db_key_result = Self::parse_row_number(
-            self.db.key_value().get(
-                COL_DELTA_TRIE,
-                [
-                    "state_root_db_key_for_epoch_id_".as_bytes(),
-                    epoch_id.as_ref(),
-                ]
-                .concat()
-                .as_slice(),
-            ),
-        )?;
+        let db_key_result = Self::parse_row_number(self.db.key_value().get(
+            COL_DELTA_TRIE,
+            Self::state_root_db_key_for_epoch_id(&epoch_id).as_slice(),
+        ))?;
         match db_key_result {
             Some(db_key) => {
                 Ok(Some(self.delta_trie.loaded_root_at_epoch(epoch_id, db_key)))
@@ -106,6 +142,8 @@ impl StateManager {
         debug!("Storage conf {:?}", conf);
 
         Self {
+            history_epochs: conf.history_epochs,
+            pruning: Mutex::new(None),
             delta_trie: MultiVersionMerklePatriciaTrie::new(
                 db.key_value().clone(),
                 conf,
@@ -115,6 +153,8 @@ impl StateManager {
                 row_number: RowNumber { value: row_number },
             }),
             number_commited_nodes: Default::default(),
+            number_pruned_nodes: Default::default(),
+            number_pruned_epochs: Default::default(),
         }
     }
 
@@ -159,6 +199,140 @@ impl StateManager {
             "number of nodes committed to db {}",
             self.number_commited_nodes.load(Ordering::Relaxed),
         );
+        info!(
+            "number of nodes pruned from db {}, number of epochs pruned {}",
+            self.number_pruned_nodes.load(Ordering::Relaxed),
+            self.number_pruned_epochs.load(Ordering::Relaxed),
+        );
+    }
+
+    /// The number of epochs of state to keep before the stable epoch, or None
+    /// if state pruning is disabled.
+    pub fn history_epochs(&self) -> Option<u64> { self.history_epochs }
+
+    /// Returns all (epoch_id, root db key) pairs committed to db.
+    fn load_epoch_roots_from_db(
+        &self,
+    ) -> Result<Vec<(EpochId, RowNumberUnderlyingType)>> {
+        let mut epoch_roots = vec![];
+        for (key, value) in self
+            .db
+            .key_value()
+            .iter_from_prefix(COL_DELTA_TRIE, STATE_ROOT_DB_KEY_PREFIX)
+            .take_while(|(key, _)| key.starts_with(STATE_ROOT_DB_KEY_PREFIX))
+        {
+            let epoch_id =
+                EpochId::from_slice(&key[STATE_ROOT_DB_KEY_PREFIX.len()..]);
+            let db_key = str::from_utf8(&value)
+                .map_err(|_| Error::from(ErrorKind::MPTInvalidValue))?
+                .parse::<RowNumberUnderlyingType>()?;
+            epoch_roots.push((epoch_id, db_key));
+        }
+        Ok(epoch_roots)
+    }
+
+    /// Mark at most `max_nodes` trie nodes reachable from the remaining
+    /// states. The nodes are read from db directly so that pruning doesn't
+    /// pollute the cache.
+    fn mark_reachable_nodes(
+        &self, pass: &mut PruningPass, max_nodes: usize,
+    ) -> Result<()> {
+        for _ in 0..max_nodes {
+            let db_key = match pass.to_visit.pop() {
+                Some(db_key) => db_key,
+                None => break,
+            };
+            if !pass.reachable.insert(db_key) {
+                continue;
+            }
+            let rlp_bytes = match self
+                .db
+                .key_value()
+                .get(COL_DELTA_TRIE, db_key.to_string().as_bytes())?
+            {
+                Some(rlp_bytes) => rlp_bytes,
+                None => return Err(ErrorKind::MPTKeyNotFound.into()),
+            };
+            let trie_node: TrieNodeDeltaMpt = rlp::decode(&rlp_bytes)?;
+            for child in trie_node.get_children() {
+                match child {
+                    NodeRefDeltaMpt::Committed { db_key } => {
+                        pass.to_visit.push(db_key)
+                    }
+                    NodeRefDeltaMpt::Dirty { .. } => {
+                        return Err(ErrorKind::UncommittedTrieNode.into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the unreachable trie nodes older than the stable state root
+    /// among the next `max_nodes` trie nodes in db. Returns true when all
+    /// trie nodes are scanned.
+    fn delete_unreachable_nodes(
+        &self, pass: &mut PruningPass, max_nodes: usize,
+    ) -> Result<bool> {
+        // Hold the commit lock so that no trie node is committed during
+        // pruning.
+        let mut commit_transaction = self.start_commit();
+
+        let start_key = pass.next_key.clone();
+        let mut scanned = 0;
+        let mut pruned_nodes = 0;
+        let mut finished = true;
+        for (key, _) in self
+            .db
+            .key_value()
+            .iter_from_prefix(COL_DELTA_TRIE, &start_key)
+        {
+            // Trie nodes are keyed by their row numbers in decimal, which
+            // are ordered before the other keys.
+            let db_key = match str::from_utf8(&key)
+                .ok()
+                .and_then(|key| key.parse::<RowNumberUnderlyingType>().ok())
+            {
+                Some(db_key) => db_key,
+                None => break,
+            };
+            if scanned == max_nodes {
+                pass.next_key = key.to_vec();
+                finished = false;
+                break;
+            }
+            scanned += 1;
+            if db_key < pass.stable_root_db_key
+                && !pass.reachable.contains(&db_key)
+            {
+                commit_transaction.transaction.delete(COL_DELTA_TRIE, &key);
+                pruned_nodes += 1;
+            }
+        }
+        self.db.key_value().write(commit_transaction.transaction)?;
+        self.number_pruned_nodes
+            .fetch_add(pruned_nodes, Ordering::Relaxed);
+        Ok(finished)
+    }
+
+    fn load_snapshot_into_state(
+        state: &mut State, snapshot: &Snapshot,
+    ) -> Result<()> {
+        for chunk in &snapshot.chunks {
+            for (key, value) in &chunk.key_values {
+                state.set(key, value)?;
+            }
+        }
+
+        let state_root = state.compute_state_root()?;
+        if state_root != snapshot.manifest.state_root {
+            return Err(ErrorKind::SnapshotStateRootMismatch(
+                snapshot.manifest.state_root,
+                state_root,
+            )
+            .into());
+        }
+        Ok(())
     }
 
     pub fn state_exists(&self, epoch_id: EpochId) -> bool {
@@ -172,31 +346,166 @@ impl StateManager {
 }
 
 impl StateManagerTrait for StateManager {
-    fn from_snapshot(snapshot: &Snapshot) -> Self { unimplemented!() }
+    fn from_snapshot(
+        db: Arc<SystemDB>, conf: StorageConfiguration, snapshot: &Snapshot,
+    ) -> Result<Self> {
+        let manager = Self::new(db, conf);
+        manager.restore_snapshot(snapshot)?;
+        Ok(manager)
+    }
 
-    fn make_snapshot(&self, epoch_id: EpochId) -> Snapshot { unimplemented!() }
+    fn restore_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        snapshot.verify_chunks()?;
+
+        let mut state = self.get_state_at(H256::default())?;
+        match Self::load_snapshot_into_state(&mut state, snapshot) {
+            Ok(()) => state.commit(snapshot.manifest.epoch_id),
+            Err(e) => {
+                state.revert();
+                Err(e)
+            }
+        }
+    }
+
+    fn make_snapshot(&self, epoch_id: EpochId) -> Result<Snapshot> {
+        let state = self.get_state_at(epoch_id)?;
+        let state_root = match state.get_state_root()? {
+            Some(state_root) => state_root,
+            None => return Err(ErrorKind::StateNotFound(epoch_id).into()),
+        };
+
+        let mut builder = SnapshotBuilder::new(epoch_id, state_root);
+        for (key, value) in state.get_all_key_values()? {
+            builder.add_key_value(key, value.into_vec());
+        }
+
+        Ok(builder.build())
+    }
 
     fn get_state_at(&self, epoch_id: EpochId) -> Result<State> {
         // FIXME: only allow existing epoch id and H256::Default().
-        Ok(State::new(self, self.get_state_root_node_ref(epoch_id)?))
+        let root_node = self.get_state_root_node_ref(epoch_id)?;
+        if root_node.is_none() && self.is_state_pruned(epoch_id)? {
+            return Err(ErrorKind::StateNotFound(epoch_id).into());
+        }
+        Ok(State::new(self, root_node))
     }
 
     fn contains_state(&self, epoch_id: EpochId) -> bool {
-        self.get_state_at(epoch_id).unwrap().does_exist()
+        self.get_state_at(epoch_id)
+            .map(|state| state.does_exist())
+            .unwrap_or(false)
     }
 
-    fn drop_state_outside(&self, epoch_id: EpochId) { unimplemented!() }
+    /// A child node is always committed before its parent, so that the db
+    /// key of any trie node of a state committed before the stable epoch is
+    /// smaller than the db key of the stable state root. Therefore states
+    /// whose root is older than the stable state root are dropped, and only
+    /// trie nodes older than the stable state root can become unreachable.
+    ///
+    /// The caller must make sure that no state older than the stable epoch is
+    /// in use, e.g. as the base state of an epoch being executed.
+    fn drop_state_outside(&self, epoch_id: EpochId) -> Result<()> {
+        let stable_root_db_key = match self.get_state_root_node_ref(epoch_id)? {
+            Some(NodeRefDeltaMpt::Committed { db_key }) => db_key,
+            _ => return Err(ErrorKind::StateNotFound(epoch_id).into()),
+        };
+
+        let mut remaining_roots = vec![];
+        let mut dropped_epochs = vec![];
+        for (epoch, root_db_key) in self.load_epoch_roots_from_db()? {
+            if root_db_key >= stable_root_db_key {
+                remaining_roots.push(root_db_key);
+            } else {
+                dropped_epochs.push(epoch);
+            }
+        }
+
+        let mut commit_transaction = self.start_commit();
+        for epoch in &dropped_epochs {
+            commit_transaction.transaction.delete(
+                COL_DELTA_TRIE,
+                Self::state_root_db_key_for_epoch_id(epoch).as_slice(),
+            );
+            commit_transaction.transaction.put(
+                COL_DELTA_TRIE,
+                Self::pruned_state_db_key_for_epoch_id(epoch).as_slice(),
+                &[],
+            );
+        }
+        self.db.key_value().write(commit_transaction.transaction)?;
+
+        for epoch in &dropped_epochs {
+            self.delta_trie.remove_epoch_root(epoch);
+        }
+        self.number_pruned_epochs
+            .fetch_add(dropped_epochs.len(), Ordering::Relaxed);
+        info!(
+            "Dropped the states of {} epochs before stable epoch {:?}",
+            dropped_epochs.len(),
+            epoch_id
+        );
+
+        // A pass in progress is continued with the new stable state root.
+        let mut pruning = self.pruning.lock().unwrap();
+        let next_key = match pruning.take() {
+            Some(pass) => pass.next_key,
+            None => Vec::new(),
+        };
+        *pruning = Some(PruningPass {
+            stable_root_db_key,
+            to_visit: remaining_roots,
+            reachable: HashSet::new(),
+            next_key,
+        });
+        Ok(())
+    }
+
+    fn prune_trie_nodes(&self, max_nodes: usize) -> Result<bool> {
+        let mut pruning = self.pruning.lock().unwrap();
+        let result = match *pruning {
+            Some(ref mut pass) => {
+                if pass.to_visit.is_empty() {
+                    self.delete_unreachable_nodes(pass, max_nodes)
+                } else {
+                    self.mark_reachable_nodes(pass, max_nodes).map(|_| false)
+                }
+            }
+            None => return Ok(false),
+        };
+        match result {
+            Ok(false) => Ok(true),
+            Ok(true) => {
+                *pruning = None;
+                info!(
+                    "Pruned trie nodes, {} trie nodes pruned in total",
+                    self.number_pruned_nodes.load(Ordering::Relaxed)
+                );
+                Ok(false)
+            }
+            Err(e) => {
+                *pruning = None;
+                Err(e)
+            }
+        }
+    }
 }
 
 use super::{
     super::{state::*, state_manager::*},
     errors::*,
     multi_version_merkle_patricia_trie::{
-        merkle_patricia_trie::NodeRefDeltaMpt, row_number::*, *,
+        merkle_patricia_trie::NodeRefDeltaMpt,
+        node_memory_manager::TrieNodeDeltaMpt, row_number::*, *,
     },
 };
 use crate::{
-    ext_db::SystemDB, snapshot::snapshot::Snapshot, statedb::StorageKey,
+    ext_db::SystemDB,
+    snapshot::{
+        snapshot::{Snapshot, SnapshotTrait},
+        snapshot_builder::{SnapshotBuilder, SnapshotBuilderTrait},
+    },
+    statedb::StorageKey,
 };
 use cfx_types::{Address, H256, U256};
 use kvdb::{DBTransaction, DBValue};
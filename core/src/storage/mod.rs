@@ -17,7 +17,8 @@ pub use self::{
         defaults,
         errors::{Error, ErrorKind, Result},
         multi_version_merkle_patricia_trie::{
-            guarded_value::GuardedValue, merkle_patricia_trie::MerkleHash,
+            guarded_value::GuardedValue,
+            merkle_patricia_trie::{MerkleHash, TrieProof, TrieProofNode},
         },
     },
     state::{State as Storage, StateTrait as StorageTrait},
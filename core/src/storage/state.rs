@@ -25,6 +25,12 @@ pub trait StateTrait {
 
     // Actions.
     fn get(&self, access_key: &[u8]) -> Result<Option<Box<[u8]>>>;
+    /// Get the value together with the proof which can be verified against
+    /// the state root by TrieProof::verify. The state root must have been
+    /// computed.
+    fn get_with_proof(
+        &self, access_key: &[u8],
+    ) -> Result<(Option<Box<[u8]>>, TrieProof)>;
     fn set(&mut self, access_key: &[u8], value: &[u8]) -> Result<()>;
     fn delete(&mut self, access_key: &[u8]) -> Result<Option<Box<[u8]>>>;
     // Delete everything prefixed by access_key and return deleted key value
@@ -40,12 +46,12 @@ pub trait StateTrait {
     fn get_state_root(&self) -> Result<Option<MerkleHash>>;
     fn commit(&mut self, epoch: EpochId) -> Result<()>;
     fn revert(&mut self);
-
-    // TODO(yz): verifiable proof related methods.
 }
 
 use super::impls::{
     errors::*,
-    multi_version_merkle_patricia_trie::merkle_patricia_trie::MerkleHash,
+    multi_version_merkle_patricia_trie::merkle_patricia_trie::{
+        MerkleHash, TrieProof,
+    },
 };
 use primitives::EpochId;
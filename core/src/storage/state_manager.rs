@@ -3,7 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 use super::{impls::errors::*, state::State};
-use crate::snapshot::snapshot::Snapshot;
+use crate::{ext_db::SystemDB, snapshot::snapshot::Snapshot};
 use primitives::EpochId;
 use std::sync::Arc;
 
@@ -18,15 +18,30 @@ pub type SharedStateManager = Arc<StateManager>;
 // anticipated to be too complex to present in the same file of the API.
 // TODO(yz): check if this is the best way to organize code for this library.
 pub trait StateManagerTrait {
-    fn from_snapshot(snapshot: &Snapshot) -> Self;
-    fn make_snapshot(&self, epoch_id: EpochId) -> Snapshot;
+    /// Rebuild the state in the snapshot, and check the state root against
+    /// the manifest before committing it at the snapshot epoch.
+    fn from_snapshot(
+        db: Arc<SystemDB>, conf: StorageConfiguration, snapshot: &Snapshot,
+    ) -> Result<Self>
+    where Self: Sized;
+    /// Load the state in the snapshot into this manager, and check the state
+    /// root against the manifest before committing it at the snapshot epoch.
+    fn restore_snapshot(&self, snapshot: &Snapshot) -> Result<()>;
+    fn make_snapshot(&self, epoch_id: EpochId) -> Result<Snapshot>;
     /// Even for non-existing the method returns a State because we need a way
     /// to create the genesis State. However there should be a special
-    /// epoch_id to create the genesis State.
+    /// epoch_id to create the genesis State. Returns `StateNotFound` if the
+    /// state is pruned.
     //  TODO(yz): special epoch_id for empty state.
     fn get_state_at(&self, epoch_id: EpochId) -> Result<State>;
     fn contains_state(&self, epoch_id: EpochId) -> bool;
-    fn drop_state_outside(&self, epoch_id: EpochId);
+    /// Drop the states committed before the stable epoch. The trie nodes which
+    /// are no longer reachable from the remaining states are deleted by
+    /// `prune_trie_nodes` afterwards.
+    fn drop_state_outside(&self, epoch_id: EpochId) -> Result<()>;
+    /// Continue pruning the unreachable trie nodes, visiting at most
+    /// `max_nodes` trie nodes. Returns true if pruning is not finished.
+    fn prune_trie_nodes(&self, max_nodes: usize) -> Result<bool>;
 }
 
 #[derive(Debug)]
@@ -36,4 +51,7 @@ pub struct StorageConfiguration {
     pub idle_size: u32,
     pub node_map_size: u32,
     pub recent_lfu_factor: f64,
+    /// The number of epochs of state to keep before the stable epoch. None
+    /// disables state pruning.
+    pub history_epochs: Option<u64>,
 }
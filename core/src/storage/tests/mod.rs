@@ -2,14 +2,25 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+#[cfg(test)]
+mod proof;
+#[cfg(test)]
+mod pruning;
+#[cfg(test)]
+mod snapshot;
 #[cfg(test)]
 mod state;
 
 use super::state_manager::StateManager;
 use crate::{ext_db::SystemDB, storage::state_manager::StorageConfiguration};
 use elastic_array::ElasticArray128;
-use kvdb::{DBTransaction, KeyValueDB};
-use std::{io::Result, sync::Arc};
+use kvdb::{DBOp, DBTransaction, DBValue, KeyValueDB};
+use parking_lot::RwLock;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Result,
+    sync::Arc,
+};
 
 #[derive(Default)]
 pub struct FakeDbForStateTest {}
@@ -48,15 +59,116 @@ impl KeyValueDB for FakeDbForStateTest {
     fn restore(&self, new_db: &str) -> Result<()> { unimplemented!() }
 }
 
+/// A key-value db which keeps everything in memory, for tests which read back
+/// what is written to db.
+#[derive(Default)]
+pub struct InMemoryDbForStateTest {
+    columns: RwLock<HashMap<Option<u32>, BTreeMap<Vec<u8>, DBValue>>>,
+}
+
+impl InMemoryDbForStateTest {
+    fn collect_key_values<'a, I>(key_values: I) -> Vec<(Box<[u8]>, Box<[u8]>)>
+    where I: Iterator<Item = (&'a Vec<u8>, &'a DBValue)> {
+        key_values
+            .map(|(key, value)| {
+                (
+                    key.clone().into_boxed_slice(),
+                    value.to_vec().into_boxed_slice(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl KeyValueDB for InMemoryDbForStateTest {
+    fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+        Ok(self
+            .columns
+            .read()
+            .get(&col)
+            .and_then(|column| column.get(key).cloned()))
+    }
+
+    fn get_by_prefix(
+        &self, col: Option<u32>, prefix: &[u8],
+    ) -> Option<Box<[u8]>> {
+        self.columns.read().get(&col).and_then(|column| {
+            column
+                .range(prefix.to_vec()..)
+                .next()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(_, value)| value.to_vec().into_boxed_slice())
+        })
+    }
+
+    fn write_buffered(&self, transaction: DBTransaction) {
+        let mut columns = self.columns.write();
+        for op in transaction.ops {
+            match op {
+                DBOp::Insert { col, key, value } => {
+                    columns.entry(col).or_default().insert(key.to_vec(), value);
+                }
+                DBOp::Delete { col, key } => {
+                    if let Some(column) = columns.get_mut(&col) {
+                        column.remove(&*key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// No-op
+    fn flush(&self) -> Result<()> { Ok(()) }
+
+    fn iter<'a>(
+        &'a self, col: Option<u32>,
+    ) -> Box<Iterator<Item = (Box<[u8]>, Box<[u8]>)>> {
+        let key_values = match self.columns.read().get(&col) {
+            Some(column) => Self::collect_key_values(column.iter()),
+            None => vec![],
+        };
+        Box::new(key_values.into_iter())
+    }
+
+    /// Like rocksdb, the iteration starts from prefix and doesn't stop at the
+    /// end of the prefix.
+    fn iter_from_prefix<'a>(
+        &'a self, col: Option<u32>, prefix: &'a [u8],
+    ) -> Box<Iterator<Item = (Box<[u8]>, Box<[u8]>)>> {
+        let key_values = match self.columns.read().get(&col) {
+            Some(column) => {
+                Self::collect_key_values(column.range(prefix.to_vec()..))
+            }
+            None => vec![],
+        };
+        Box::new(key_values.into_iter())
+    }
+
+    fn restore(&self, new_db: &str) -> Result<()> { unimplemented!() }
+}
+
+pub fn new_storage_configuration_for_testing() -> StorageConfiguration {
+    StorageConfiguration {
+        cache_start_size: 1_000_000,
+        cache_size: 20_000_000,
+        idle_size: 200_000,
+        node_map_size: 20_000_000,
+        recent_lfu_factor: 4.0,
+        history_epochs: None,
+    }
+}
+
+pub fn new_system_db_for_testing() -> Arc<SystemDB> {
+    Arc::new(SystemDB::new(Arc::new(FakeDbForStateTest::default())))
+}
+
+pub fn new_in_memory_system_db_for_testing() -> Arc<SystemDB> {
+    Arc::new(SystemDB::new(Arc::new(InMemoryDbForStateTest::default())))
+}
+
 pub fn new_state_manager_for_testing() -> StateManager {
     StateManager::new(
-        Arc::new(SystemDB::new(Arc::new(FakeDbForStateTest::default()))),
-        StorageConfiguration {
-            cache_start_size: 1_000_000,
-            cache_size: 20_000_000,
-            idle_size: 200_000,
-            node_map_size: 20_000_000,
-            recent_lfu_factor: 4.0,
-        },
+        new_system_db_for_testing(),
+        new_storage_configuration_for_testing(),
     )
 }
@@ -0,0 +1,110 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+const NUMBER_OF_KEYS: usize = 10000;
+
+fn epoch_id_for_testing() -> EpochId {
+    let mut epoch_id = H256::default();
+    epoch_id[0] = 1;
+    epoch_id
+}
+
+#[test]
+fn test_get_with_proof() {
+    let mut rng = ChaChaRng::from_seed([123; 32]);
+    let keys: Vec<[u8; 4]> = (0..NUMBER_OF_KEYS).map(|_| rng.gen()).collect();
+
+    let state_manager = new_state_manager_for_testing();
+    let mut state = state_manager.get_state_at(H256::default()).unwrap();
+    for key in &keys {
+        state.set(key, key).expect("Failed to insert key.");
+    }
+    let state_root = state.compute_state_root().unwrap();
+    state.commit(epoch_id_for_testing()).unwrap();
+
+    let state = state_manager.get_state_at(epoch_id_for_testing()).unwrap();
+    for key in &keys {
+        let (value, proof) = state.get_with_proof(key).unwrap();
+        assert_eq!(value.as_ref().map(|v| &**v), Some(&key[..]));
+        assert_eq!(proof.verify(key, &state_root).unwrap(), Some(&key[..]));
+        assert!(proof.is_valid(key, Some(&key[..]), &state_root));
+        assert!(!proof.is_valid(key, None, &state_root));
+        assert!(!proof.is_valid(key, Some(&key[..]), &H256::default()));
+    }
+
+    // Proof of non-existence.
+    let mut key_absent: [u8; 4] = rng.gen();
+    while keys.contains(&key_absent) {
+        key_absent = rng.gen();
+    }
+    let (value, proof) = state.get_with_proof(&key_absent).unwrap();
+    assert_eq!(value, None);
+    assert_eq!(proof.verify(&key_absent, &state_root).unwrap(), None);
+    assert!(!proof.is_valid(&key_absent, Some(&key_absent[..]), &state_root));
+}
+
+#[test]
+fn test_tampered_proof() {
+    let keys: Vec<[u8; 4]> = (0..100u8).map(|i| [i, i, i, i]).collect();
+
+    let state_manager = new_state_manager_for_testing();
+    let mut state = state_manager.get_state_at(H256::default()).unwrap();
+    for key in &keys {
+        state.set(key, key).expect("Failed to insert key.");
+    }
+    let state_root = state.compute_state_root().unwrap();
+    state.commit(epoch_id_for_testing()).unwrap();
+
+    let state = state_manager.get_state_at(epoch_id_for_testing()).unwrap();
+    let (_, proof) = state.get_with_proof(&keys[1]).unwrap();
+    assert!(proof.is_valid(&keys[1], Some(&keys[1][..]), &state_root));
+
+    // The proof can't be used for another key.
+    assert!(proof.verify(&keys[2], &state_root).is_err());
+
+    // Modified value.
+    let mut nodes = proof.get_nodes().clone();
+    nodes.last_mut().unwrap().value = Some(vec![0; 4].into_boxed_slice());
+    assert!(TrieProof::new(nodes).verify(&keys[1], &state_root).is_err());
+
+    // Truncated proof.
+    let mut nodes = proof.get_nodes().clone();
+    nodes.pop();
+    assert!(TrieProof::new(nodes).verify(&keys[1], &state_root).is_err());
+}
+
+#[test]
+fn test_proof_encode_decode() {
+    let state_manager = new_state_manager_for_testing();
+    let mut state = state_manager.get_state_at(H256::default()).unwrap();
+    for i in 0..100u8 {
+        state.set(&[i, 1], &[i]).expect("Failed to insert key.");
+    }
+    let state_root = state.compute_state_root().unwrap();
+    state.commit(epoch_id_for_testing()).unwrap();
+
+    let state = state_manager.get_state_at(epoch_id_for_testing()).unwrap();
+    let (_, proof) = state.get_with_proof(&[7, 1]).unwrap();
+    let decoded: TrieProof = rlp::decode(&rlp::encode(&proof)).unwrap();
+    assert_eq!(decoded, proof);
+    assert!(decoded.is_valid(&[7, 1], Some(&[7][..]), &state_root));
+}
+
+#[test]
+fn test_empty_state_proof() {
+    let state_manager = new_state_manager_for_testing();
+    let state = state_manager.get_state_at(H256::default()).unwrap();
+    let (value, proof) = state.get_with_proof(&[1, 2, 3, 4]).unwrap();
+    assert_eq!(value, None);
+    assert!(proof.is_valid(&[1, 2, 3, 4], None, &KECCAK_EMPTY));
+}
+
+use super::{
+    super::{state::*, state_manager::*, TrieProof},
+    new_state_manager_for_testing,
+};
+use crate::hash::KECCAK_EMPTY;
+use cfx_types::H256;
+use primitives::EpochId;
+use rand::{ChaChaRng, Rng, SeedableRng};
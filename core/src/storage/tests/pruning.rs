@@ -0,0 +1,150 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+const NUMBER_OF_KEYS: usize = 1000;
+
+fn epoch_id_for_testing(n: u8) -> EpochId {
+    let mut epoch_id = H256::default();
+    epoch_id[0] = n;
+    epoch_id
+}
+
+/// Commit a new state on top of parent_epoch_id where every key maps to
+/// value.
+fn commit_epoch(
+    state_manager: &StateManager, parent_epoch_id: EpochId, epoch_id: EpochId,
+    keys: &Vec<[u8; 4]>, value: &[u8],
+)
+{
+    let mut state = state_manager.get_state_at(parent_epoch_id).unwrap();
+    for key in keys {
+        state.set(key, value).expect("Failed to insert key.");
+    }
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+}
+
+fn assert_state_values(
+    state_manager: &StateManager, epoch_id: EpochId, keys: &Vec<[u8; 4]>,
+    value: &[u8],
+)
+{
+    let state = state_manager.get_state_at(epoch_id).unwrap();
+    for key in keys {
+        let got = state
+            .get(key)
+            .expect("Failed to get key.")
+            .expect("Failed to get key");
+        assert_eq!(value, got.as_ref());
+    }
+}
+
+#[test]
+fn test_drop_state_outside() {
+    let mut rng = ChaChaRng::from_seed([123; 32]);
+    let keys: Vec<[u8; 4]> = (0..NUMBER_OF_KEYS).map(|_| rng.gen()).collect();
+    let epoch_1 = epoch_id_for_testing(1);
+    let epoch_2 = epoch_id_for_testing(2);
+    let epoch_3 = epoch_id_for_testing(3);
+
+    let db = new_in_memory_system_db_for_testing();
+    let state_manager =
+        StateManager::new(db.clone(), new_storage_configuration_for_testing());
+    commit_epoch(&state_manager, H256::default(), epoch_1, &keys, b"1");
+    commit_epoch(&state_manager, epoch_1, epoch_2, &keys, b"2");
+    commit_epoch(&state_manager, epoch_2, epoch_3, &keys, b"3");
+
+    state_manager.drop_state_outside(epoch_2).unwrap();
+    assert!(!state_manager.contains_state(epoch_1));
+    assert!(state_manager.contains_state(epoch_2));
+    assert!(state_manager.contains_state(epoch_3));
+    while state_manager.prune_trie_nodes(100).unwrap() {}
+    assert!(state_manager.number_pruned_nodes.load(Ordering::Relaxed) > 0);
+    assert_eq!(
+        state_manager.number_pruned_epochs.load(Ordering::Relaxed),
+        1
+    );
+
+    // The remaining states are complete in db.
+    let reloaded_manager =
+        StateManager::new(db, new_storage_configuration_for_testing());
+    assert!(!reloaded_manager.contains_state(epoch_1));
+    assert_state_values(&reloaded_manager, epoch_2, &keys, b"2");
+    assert_state_values(&reloaded_manager, epoch_3, &keys, b"3");
+}
+
+#[test]
+fn test_pruned_state_not_found() {
+    let keys: Vec<[u8; 4]> = vec![[1; 4], [2; 4]];
+    let epoch_1 = epoch_id_for_testing(1);
+    let epoch_2 = epoch_id_for_testing(2);
+
+    let mut conf = new_storage_configuration_for_testing();
+    conf.history_epochs = Some(1);
+    let state_manager =
+        StateManager::new(new_in_memory_system_db_for_testing(), conf);
+    commit_epoch(&state_manager, H256::default(), epoch_1, &keys, b"1");
+    commit_epoch(&state_manager, epoch_1, epoch_2, &keys, b"2");
+
+    state_manager.drop_state_outside(epoch_2).unwrap();
+    assert!(!state_manager.contains_state(epoch_1));
+    assert!(state_manager.get_state_at(epoch_1).is_err());
+    // The states which never existed are still available for commit.
+    assert!(state_manager.get_state_at(epoch_id_for_testing(3)).is_ok());
+}
+
+#[test]
+fn test_prune_trie_nodes_in_batches() {
+    let mut rng = ChaChaRng::from_seed([123; 32]);
+    let keys: Vec<[u8; 4]> = (0..NUMBER_OF_KEYS).map(|_| rng.gen()).collect();
+    let epoch_1 = epoch_id_for_testing(1);
+    let epoch_2 = epoch_id_for_testing(2);
+    let epoch_3 = epoch_id_for_testing(3);
+    let epoch_4 = epoch_id_for_testing(4);
+
+    let db = new_in_memory_system_db_for_testing();
+    let state_manager =
+        StateManager::new(db.clone(), new_storage_configuration_for_testing());
+    commit_epoch(&state_manager, H256::default(), epoch_1, &keys, b"1");
+    commit_epoch(&state_manager, epoch_1, epoch_2, &keys, b"2");
+
+    // States committed between the batches are kept.
+    state_manager.drop_state_outside(epoch_2).unwrap();
+    assert!(state_manager.prune_trie_nodes(10).unwrap());
+    commit_epoch(&state_manager, epoch_2, epoch_3, &keys, b"3");
+    while state_manager.prune_trie_nodes(10).unwrap() {}
+    assert!(!state_manager.prune_trie_nodes(10).unwrap());
+
+    // A new pass continues with the new stable epoch.
+    commit_epoch(&state_manager, epoch_3, epoch_4, &keys, b"4");
+    state_manager.drop_state_outside(epoch_3).unwrap();
+    while state_manager.prune_trie_nodes(10).unwrap() {}
+
+    let reloaded_manager =
+        StateManager::new(db, new_storage_configuration_for_testing());
+    assert!(!reloaded_manager.contains_state(epoch_2));
+    assert_state_values(&reloaded_manager, epoch_3, &keys, b"3");
+    assert_state_values(&reloaded_manager, epoch_4, &keys, b"4");
+}
+
+#[test]
+fn test_drop_state_outside_unknown_epoch() {
+    let state_manager = StateManager::new(
+        new_in_memory_system_db_for_testing(),
+        new_storage_configuration_for_testing(),
+    );
+    assert!(state_manager
+        .drop_state_outside(epoch_id_for_testing(1))
+        .is_err());
+}
+
+use super::{
+    super::{state::*, state_manager::*},
+    new_in_memory_system_db_for_testing,
+    new_storage_configuration_for_testing,
+};
+use cfx_types::H256;
+use primitives::EpochId;
+use rand::{ChaChaRng, Rng, SeedableRng};
+use std::sync::atomic::Ordering;
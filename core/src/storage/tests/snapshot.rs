@@ -0,0 +1,156 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+const NUMBER_OF_KEYS: usize = 10000;
+
+fn new_state_with_keys(
+    state_manager: &StateManager, epoch_id: EpochId,
+) -> Vec<[u8; 4]> {
+    let mut rng = ChaChaRng::from_seed([123; 32]);
+    let keys: Vec<[u8; 4]> = (0..NUMBER_OF_KEYS).map(|_| rng.gen()).collect();
+
+    let mut state = state_manager.get_state_at(H256::default()).unwrap();
+    for key in &keys {
+        state.set(key, key).expect("Failed to insert key.");
+    }
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+
+    keys
+}
+
+fn epoch_id_for_testing() -> EpochId {
+    let mut epoch_id = H256::default();
+    epoch_id[0] = 1;
+    epoch_id
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let epoch_id = epoch_id_for_testing();
+    let state_manager = new_state_manager_for_testing();
+    let keys = new_state_with_keys(&state_manager, epoch_id);
+    let state_root = state_manager
+        .get_state_at(epoch_id)
+        .unwrap()
+        .get_state_root()
+        .unwrap()
+        .unwrap();
+
+    let snapshot = state_manager.make_snapshot(epoch_id).unwrap();
+    assert_eq!(snapshot.manifest.epoch_id, epoch_id);
+    assert_eq!(snapshot.manifest.state_root, state_root);
+
+    let restored_manager = StateManager::from_snapshot(
+        new_system_db_for_testing(),
+        new_storage_configuration_for_testing(),
+        &snapshot,
+    )
+    .unwrap();
+    let restored_state = restored_manager.get_state_at(epoch_id).unwrap();
+    assert_eq!(restored_state.get_state_root().unwrap(), Some(state_root));
+    for key in &keys {
+        let value = restored_state
+            .get(key)
+            .expect("Failed to get key.")
+            .expect("Failed to get key");
+        assert_eq!(key, value.as_ref());
+    }
+}
+
+#[test]
+fn test_snapshot_file_round_trip() {
+    let epoch_id = epoch_id_for_testing();
+    let state_manager = new_state_manager_for_testing();
+    new_state_with_keys(&state_manager, epoch_id);
+    let snapshot = state_manager.make_snapshot(epoch_id).unwrap();
+
+    let path = env::temp_dir().join("conflux_test_snapshot_file_round_trip");
+    snapshot.write_to_file(&path).unwrap();
+    let loaded = Snapshot::from_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(snapshot, loaded);
+}
+
+#[test]
+fn test_snapshot_chunking() {
+    let mut builder = SnapshotBuilder::new_with_chunk_size(
+        epoch_id_for_testing(),
+        H256::default(),
+        16,
+    );
+    for i in 0..10u8 {
+        builder.add_key_value(vec![i; 4], vec![i; 4]);
+    }
+    let snapshot = builder.build();
+
+    assert_eq!(snapshot.chunks.len(), 5);
+    assert_eq!(snapshot.manifest.chunk_hashes.len(), 5);
+    snapshot.verify_chunks().unwrap();
+}
+
+#[test]
+fn test_snapshot_tampered() {
+    let epoch_id = epoch_id_for_testing();
+    let state_manager = new_state_manager_for_testing();
+    new_state_with_keys(&state_manager, epoch_id);
+
+    let mut snapshot = state_manager.make_snapshot(epoch_id).unwrap();
+    snapshot.chunks[0].key_values[0].1 = vec![0; 4];
+    assert!(snapshot.verify_chunks().is_err());
+
+    // Chunk hashes are consistent, however the state root is not.
+    snapshot.manifest.chunk_hashes[0] = snapshot.chunks[0].hash();
+    snapshot.verify_chunks().unwrap();
+    assert!(StateManager::from_snapshot(
+        new_system_db_for_testing(),
+        new_storage_configuration_for_testing(),
+        &snapshot,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_snapshot_restore_into_existing_manager() {
+    let epoch_id = epoch_id_for_testing();
+    let state_manager = new_state_manager_for_testing();
+    let keys = new_state_with_keys(&state_manager, epoch_id);
+    let snapshot = state_manager.make_snapshot(epoch_id).unwrap();
+
+    // The manager of a syncing node already has the genesis state.
+    let restored_manager = new_state_manager_for_testing();
+    let mut genesis_id = H256::default();
+    genesis_id[0] = 2;
+    let mut genesis_state =
+        restored_manager.get_state_at(H256::default()).unwrap();
+    genesis_state.set(&[0; 4], &[0; 4]).unwrap();
+    genesis_state.compute_state_root().unwrap();
+    genesis_state.commit(genesis_id).unwrap();
+
+    restored_manager.restore_snapshot(&snapshot).unwrap();
+    let restored_state = restored_manager.get_state_at(epoch_id).unwrap();
+    assert_eq!(
+        restored_state.get_state_root().unwrap(),
+        Some(snapshot.manifest.state_root)
+    );
+    assert_eq!(
+        restored_state.get(&keys[0]).unwrap().unwrap().as_ref(),
+        &keys[0][..]
+    );
+}
+
+use super::{
+    super::{state::*, state_manager::*},
+    new_state_manager_for_testing, new_storage_configuration_for_testing,
+    new_system_db_for_testing,
+};
+use crate::snapshot::{
+    snapshot::{Snapshot, SnapshotTrait},
+    snapshot_builder::{SnapshotBuilder, SnapshotBuilderTrait},
+};
+use cfx_types::H256;
+use primitives::EpochId;
+use rand::{ChaChaRng, Rng, SeedableRng};
+use std::{env, fs};
@@ -30,6 +30,16 @@ error_chain! {
             display("Unexpected response"),
         }
 
+        LateResponse {
+            description("Response to a timed out request"),
+            display("Late response"),
+        }
+
+        InvalidProof {
+            description("Invalid proof"),
+            display("Invalid proof"),
+        }
+
         Useless {
             description("Useless block"),
             display("Useless block"),
@@ -3,8 +3,10 @@
 // See http://www.gnu.org/licenses/
 
 mod error;
+mod synchronization_checkpoint;
 mod synchronization_graph;
 mod synchronization_protocol_handler;
+mod synchronization_scheduler;
 mod synchronization_service;
 mod synchronization_state;
 
@@ -17,7 +19,7 @@ pub use self::{
     },
     synchronization_protocol_handler::{
         ProtocolConfiguration, SynchronizationProtocolHandler,
-        SYNCHRONIZATION_PROTOCOL_VERSION,
+        MAX_HEADERS_TO_SEND, SYNCHRONIZATION_PROTOCOL_VERSION,
     },
     synchronization_service::{
         SharedSynchronizationService, SynchronizationService,
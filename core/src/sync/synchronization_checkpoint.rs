@@ -0,0 +1,747 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{Error, ErrorKind, SynchronizationGraphInner};
+use crate::{
+    consensus::{Checkpoint, DEFERRED_STATE_EPOCH_COUNT},
+    snapshot::snapshot::{Snapshot, SnapshotChunk, SnapshotManifest},
+};
+use cfx_types::H256;
+use network::PeerId;
+use primitives::{receipt::Receipt, BlockHeaderBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// The number of state chunk requests in flight.
+const MAX_STATE_CHUNKS_IN_FLIGHT: usize = 8;
+/// The number of peers asked for the chunk hashes of the state. The chunk
+/// hashes sent by most of them are adopted.
+const STATE_MANIFEST_PEERS: usize = 3;
+
+/// What to do with the body of a block whose header is received while
+/// syncing from a checkpoint.
+#[derive(Debug, PartialEq)]
+pub enum CheckpointBlockBody {
+    /// The block is in the past of all epochs whose blocks are needed, so it
+    /// is inserted without its body.
+    Skip,
+    Download,
+    /// The block may be in an epoch which is executed, so its body is not
+    /// downloaded until the state and receipts are synced.
+    Defer,
+}
+
+/// The receipts of an epoch whose rewards are paid after the state epoch of
+/// the checkpoint.
+struct EpochReceiptsTarget {
+    /// The blocks of the epoch in execution order, according to the header
+    /// graph.
+    block_hashes: Vec<H256>,
+    /// The deferred receipts root in the header of the pivot block
+    /// `DEFERRED_STATE_EPOCH_COUNT` epochs later.
+    receipts_root: H256,
+}
+
+/// The chunk hashes of the state sent by some peers.
+struct ManifestCandidate {
+    peers: HashSet<PeerId>,
+    /// The first chunk, which is sent along with the chunk hashes.
+    first_chunk: SnapshotChunk,
+}
+
+/// The position of the checkpoint in the header graph.
+struct CheckpointLocation {
+    /// The pivot block of the state epoch.
+    state_epoch_hash: H256,
+    /// The sync graph index of the pivot block before the first reward
+    /// epoch. Blocks in its past are not needed.
+    boundary_index: usize,
+}
+
+/// Syncs the state at a trusted checkpoint from peers.
+///
+/// Once all block headers are received, the checkpoint is located in the
+/// header graph, which gives the pivot blocks of the epochs before it. The
+/// state of the state epoch is then requested in chunks. The chunk hashes
+/// are requested from several peers first, and the ones sent by most peers
+/// are adopted. Each chunk is verified against them, and the whole state is
+/// verified against the state root of the checkpoint when all chunks are
+/// received. Only then are the peers which sent wrong chunk hashes known.
+/// The receipts of the reward epochs are requested as well, and verified
+/// against the deferred receipts roots of the checkpoint ancestors.
+///
+/// Block bodies in the past of the reward epochs are skipped. The bodies of
+/// blocks from the checkpoint on are deferred until the sync is completed, so
+/// that no epoch after the state epoch is executed before that.
+pub struct CheckpointSync {
+    checkpoint: Checkpoint,
+    location: Option<CheckpointLocation>,
+    /// The peers asked for the chunk hashes, which have not responded yet.
+    manifest_requests: HashSet<PeerId>,
+    manifest_candidates: HashMap<Vec<H256>, ManifestCandidate>,
+    /// The adopted chunk hashes, and the peers which sent them. Chunks are
+    /// only requested from these peers.
+    manifest: Option<(Vec<H256>, HashSet<PeerId>)>,
+    /// The chunk hashes which failed to restore the state root.
+    rejected_manifests: HashSet<Vec<H256>>,
+    chunks: HashMap<u64, SnapshotChunk>,
+    chunks_in_flight: HashSet<u64>,
+    state_synced: bool,
+    /// The reward epochs whose receipts are not received yet, by epoch hash.
+    receipts: HashMap<H256, EpochReceiptsTarget>,
+    receipts_in_flight: HashSet<H256>,
+    deferred_blocks: Vec<(u64, H256)>,
+}
+
+impl CheckpointSync {
+    pub fn new(checkpoint: Checkpoint) -> Self {
+        CheckpointSync {
+            checkpoint,
+            location: None,
+            manifest_requests: HashSet::new(),
+            manifest_candidates: HashMap::new(),
+            manifest: None,
+            rejected_manifests: HashSet::new(),
+            chunks: HashMap::new(),
+            chunks_in_flight: HashSet::new(),
+            state_synced: false,
+            receipts: HashMap::new(),
+            receipts_in_flight: HashSet::new(),
+            deferred_blocks: Vec::new(),
+        }
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint { &self.checkpoint }
+
+    pub fn is_located(&self) -> bool { self.location.is_some() }
+
+    pub fn state_epoch_hash(&self) -> Option<H256> {
+        self.location
+            .as_ref()
+            .map(|location| location.state_epoch_hash)
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.is_located() && self.state_synced && self.receipts.is_empty()
+    }
+
+    /// Find the checkpoint and its ancestors in the header graph, and set up
+    /// the state and receipts to sync. Returns false if the headers are not
+    /// received yet, or an error if the checkpoint header does not match the
+    /// configuration.
+    pub fn locate(
+        &mut self, graph: &SynchronizationGraphInner,
+    ) -> Result<bool, String> {
+        let index = match graph.indices.get(&self.checkpoint.hash) {
+            Some(index) => *index,
+            None => return Ok(false),
+        };
+        if !graph.is_header_graph_ready(index) {
+            return Ok(false);
+        }
+
+        let header = &graph.arena[index].block_header;
+        if header.height() != self.checkpoint.epoch_number
+            || *header.deferred_state_root() != self.checkpoint.state_root
+        {
+            return Err(format!(
+                "Checkpoint {:?} does not match its header {:?}",
+                self.checkpoint, header
+            ));
+        }
+
+        // The pivot blocks from the boundary to the checkpoint, by height.
+        let boundary_height = self.checkpoint.first_reward_epoch_number() - 1;
+        let mut pivots = Vec::new();
+        let mut cur = index;
+        loop {
+            pivots.push(cur);
+            if graph.arena[cur].block_header.height() == boundary_height {
+                break;
+            }
+            cur = graph.arena[cur].parent;
+        }
+        pivots.reverse();
+        let pivot_at =
+            |height: u64| pivots[(height - boundary_height) as usize];
+
+        self.receipts.clear();
+        for epoch in self.checkpoint.first_reward_epoch_number()
+            ..=self.checkpoint.state_epoch_number()
+        {
+            let pivot = pivot_at(epoch);
+            let epoch_hash = graph.arena[pivot].block_header.hash();
+            let block_hashes = graph.epoch_block_hashes(pivot);
+            let receipts_root = *graph.arena
+                [pivot_at(epoch + DEFERRED_STATE_EPOCH_COUNT)]
+            .block_header
+            .deferred_receipts_root();
+            self.receipts.insert(
+                epoch_hash,
+                EpochReceiptsTarget {
+                    block_hashes,
+                    receipts_root,
+                },
+            );
+        }
+
+        let state_epoch_hash = graph.arena
+            [pivot_at(self.checkpoint.state_epoch_number())]
+        .block_header
+        .hash();
+        info!(
+            "Located checkpoint {:?}, syncing the state of epoch {:?}",
+            self.checkpoint, state_epoch_hash
+        );
+        self.location = Some(CheckpointLocation {
+            state_epoch_hash,
+            boundary_index: pivots[0],
+        });
+        Ok(true)
+    }
+
+    /// Decide what to do with the body of a block in the header graph.
+    pub fn block_body(
+        &self, graph: &SynchronizationGraphInner, hash: &H256,
+    ) -> CheckpointBlockBody {
+        let location = match self.location {
+            Some(ref location) => location,
+            None => return CheckpointBlockBody::Defer,
+        };
+        let index = match graph.indices.get(hash) {
+            Some(index) => *index,
+            None => return CheckpointBlockBody::Defer,
+        };
+        if graph.is_in_past(index, location.boundary_index) {
+            CheckpointBlockBody::Skip
+        } else if !(self.state_synced && self.receipts.is_empty())
+            && graph.arena[index].block_header.height()
+                >= self.checkpoint.epoch_number
+        {
+            CheckpointBlockBody::Defer
+        } else {
+            CheckpointBlockBody::Download
+        }
+    }
+
+    pub fn defer_blocks(&mut self, blocks: Vec<(u64, H256)>) {
+        self.deferred_blocks.extend(blocks);
+    }
+
+    pub fn take_deferred_blocks(&mut self) -> Vec<(u64, H256)> {
+        std::mem::replace(&mut self.deferred_blocks, Vec::new())
+    }
+
+    /// Returns the state chunks to request, and the peers to request them
+    /// from.
+    pub fn next_chunk_requests(
+        &mut self, peers: &[PeerId],
+    ) -> Vec<(PeerId, u64)> {
+        if self.state_synced || !self.is_located() || peers.is_empty() {
+            return Vec::new();
+        }
+
+        let manifest_peers: Vec<PeerId> = match self.manifest {
+            Some((_, ref manifest_peers)) => peers
+                .iter()
+                .filter(|peer| manifest_peers.contains(peer))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        if self.manifest.is_some() && manifest_peers.is_empty() {
+            // The peers which sent the chunk hashes are gone, so the chunk
+            // hashes are requested again.
+            self.manifest = None;
+            self.chunks.clear();
+            self.chunks_in_flight.clear();
+        }
+
+        let missing: Vec<u64> = match self.manifest {
+            Some((ref chunk_hashes, _)) => (0..chunk_hashes.len() as u64)
+                .filter(|index| !self.chunks.contains_key(index))
+                .collect(),
+            None => {
+                if !self.manifest_requests.is_empty() {
+                    return Vec::new();
+                }
+                // The first chunk is sent along with the chunk hashes.
+                let requests: Vec<(PeerId, u64)> = peers
+                    .iter()
+                    .take(STATE_MANIFEST_PEERS)
+                    .map(|peer| (*peer, 0))
+                    .collect();
+                self.manifest_requests
+                    .extend(requests.iter().map(|(peer, _)| *peer));
+                return requests;
+            }
+        };
+        let mut requests = Vec::new();
+        for index in missing {
+            if self.chunks_in_flight.len() >= MAX_STATE_CHUNKS_IN_FLIGHT {
+                break;
+            }
+            if self.chunks_in_flight.insert(index) {
+                let peer =
+                    manifest_peers[index as usize % manifest_peers.len()];
+                requests.push((peer, index));
+            }
+        }
+        requests
+    }
+
+    /// Verify a state chunk against the chunk hashes sent along with it.
+    /// Returns the snapshot of the state once all chunks are received.
+    ///
+    /// Chunk hashes different from the adopted ones are not an error, since
+    /// it is not known which are right until the state is restored.
+    pub fn on_chunk_response(
+        &mut self, peer: PeerId, index: u64, chunk_hashes: Vec<H256>,
+        chunk: SnapshotChunk,
+    ) -> Result<Option<Snapshot>, Error>
+    {
+        let manifest_response =
+            index == 0 && self.manifest_requests.remove(&peer);
+        if !manifest_response {
+            self.chunks_in_flight.remove(&index);
+        }
+        if self.state_synced || !self.is_located() {
+            return Ok(None);
+        }
+
+        if chunk_hashes.get(index as usize) != Some(&chunk.hash())
+            || self.rejected_manifests.contains(&chunk_hashes)
+        {
+            if manifest_response {
+                self.adopt_manifest();
+            }
+            return Err(ErrorKind::InvalidProof.into());
+        }
+
+        if manifest_response {
+            self.manifest_candidates
+                .entry(chunk_hashes)
+                .or_insert_with(|| ManifestCandidate {
+                    peers: HashSet::new(),
+                    first_chunk: chunk,
+                })
+                .peers
+                .insert(peer);
+            self.adopt_manifest();
+        } else {
+            match self.manifest {
+                Some((ref adopted, _)) if *adopted == chunk_hashes => {
+                    self.chunks.insert(index, chunk);
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(self.take_snapshot())
+    }
+
+    /// Called when a chunk request fails. Returns the snapshot of the state
+    /// if all chunks are received, which happens when the last request for
+    /// the chunk hashes fails after the others returned all chunks.
+    pub fn on_chunk_failure(
+        &mut self, peer: PeerId, index: u64,
+    ) -> Option<Snapshot> {
+        if index == 0 && self.manifest_requests.remove(&peer) {
+            self.adopt_manifest();
+            self.take_snapshot()
+        } else {
+            self.chunks_in_flight.remove(&index);
+            None
+        }
+    }
+
+    /// Adopt the chunk hashes sent by most peers, once all peers asked for
+    /// them have responded.
+    fn adopt_manifest(&mut self) {
+        if self.manifest.is_some() || !self.manifest_requests.is_empty() {
+            return;
+        }
+        let chunk_hashes = self
+            .manifest_candidates
+            .iter()
+            .max_by_key(|(_, candidate)| candidate.peers.len())
+            .map(|(chunk_hashes, _)| chunk_hashes.clone());
+        if let Some(chunk_hashes) = chunk_hashes {
+            let candidate = self
+                .manifest_candidates
+                .remove(&chunk_hashes)
+                .expect("exists");
+            self.chunks.clear();
+            self.chunks.insert(0, candidate.first_chunk);
+            self.manifest = Some((chunk_hashes, candidate.peers));
+        }
+        self.manifest_candidates.clear();
+    }
+
+    fn take_snapshot(&mut self) -> Option<Snapshot> {
+        let state_epoch_hash = self.state_epoch_hash()?;
+        let chunk_hashes = match self.manifest {
+            Some((ref chunk_hashes, _))
+                if self.chunks.len() == chunk_hashes.len() =>
+            {
+                chunk_hashes.clone()
+            }
+            _ => return None,
+        };
+        let chunks = (0..chunk_hashes.len() as u64)
+            .map(|index| self.chunks.remove(&index).expect("all received"))
+            .collect();
+        Some(Snapshot {
+            manifest: SnapshotManifest {
+                epoch_id: state_epoch_hash,
+                state_root: self.checkpoint.state_root,
+                chunk_hashes,
+            },
+            chunks,
+        })
+    }
+
+    /// Called after the snapshot is loaded into the storage. If it fails,
+    /// the adopted chunk hashes are wrong and the state is synced again.
+    /// Returns the peers which sent the wrong chunk hashes in that case.
+    pub fn on_state_restored(&mut self, success: bool) -> Vec<PeerId> {
+        self.chunks.clear();
+        self.chunks_in_flight.clear();
+        let manifest = self.manifest.take();
+        if success {
+            self.state_synced = true;
+            return Vec::new();
+        }
+        match manifest {
+            Some((chunk_hashes, peers)) => {
+                self.rejected_manifests.insert(chunk_hashes);
+                peers.into_iter().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the epochs whose receipts are to be requested.
+    pub fn next_receipts_requests(&mut self) -> Vec<H256> {
+        let mut requests = Vec::new();
+        for epoch_hash in self.receipts.keys() {
+            if self.receipts_in_flight.insert(*epoch_hash) {
+                requests.push(*epoch_hash);
+            }
+        }
+        requests
+    }
+
+    /// Drop the epochs whose receipts already exist, e.g. after a restart.
+    pub fn retain_receipts<F>(&mut self, is_missing: F)
+    where F: Fn(&H256, &[H256]) -> bool {
+        self.receipts.retain(|epoch_hash, target| {
+            is_missing(epoch_hash, &target.block_hashes)
+        });
+    }
+
+    /// Verify the receipts of an epoch. Returns the receipts root and the
+    /// receipts of each block.
+    pub fn on_receipts_response(
+        &mut self, epoch_hash: &H256, block_hashes: Vec<H256>,
+        receipts: Vec<Vec<Receipt>>,
+    ) -> Result<Option<(H256, Vec<(H256, Arc<Vec<Receipt>>)>)>, Error>
+    {
+        self.receipts_in_flight.remove(epoch_hash);
+        let target = match self.receipts.get(epoch_hash) {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+
+        // The receipts root only commits to the receipts in order, so the
+        // blocks and their order are checked against the header graph.
+        if block_hashes != target.block_hashes
+            || receipts.len() != block_hashes.len()
+        {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+        let receipts: Vec<_> = receipts.into_iter().map(Arc::new).collect();
+        let receipts_root =
+            BlockHeaderBuilder::compute_block_receipts_root(&receipts);
+        if receipts_root != target.receipts_root {
+            return Err(ErrorKind::InvalidProof.into());
+        }
+
+        self.receipts.remove(epoch_hash);
+        Ok(Some((
+            receipts_root,
+            block_hashes.into_iter().zip(receipts.into_iter()).collect(),
+        )))
+    }
+
+    pub fn on_receipts_failure(&mut self, epoch_hash: &H256) {
+        self.receipts_in_flight.remove(epoch_hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckpointLocation, CheckpointSync, EpochReceiptsTarget};
+    use crate::{
+        consensus::Checkpoint,
+        snapshot::{
+            snapshot::{SnapshotChunk, SnapshotTrait},
+            snapshot_builder::{SnapshotBuilder, SnapshotBuilderTrait},
+        },
+    };
+    use cfx_types::H256;
+    use primitives::{receipt::Receipt, BlockHeaderBuilder};
+    use std::sync::Arc;
+
+    fn located_sync() -> CheckpointSync {
+        let mut sync = CheckpointSync::new(
+            Checkpoint::new(100, H256::from(1), H256::from(2)).unwrap(),
+        );
+        sync.location = Some(CheckpointLocation {
+            state_epoch_hash: H256::from(3),
+            boundary_index: 0,
+        });
+        sync
+    }
+
+    fn chunks(count: u8) -> Vec<SnapshotChunk> {
+        let mut builder = SnapshotBuilder::new_with_chunk_size(
+            H256::from(3),
+            H256::from(2),
+            8,
+        );
+        for i in 0..count {
+            builder.add_key_value(vec![i; 4], vec![i; 4]);
+        }
+        builder.build().chunks
+    }
+
+    #[test]
+    fn test_state_chunks_are_verified() {
+        let mut sync = located_sync();
+        let chunks = chunks(3);
+        let chunk_hashes: Vec<H256> =
+            chunks.iter().map(|chunk| chunk.hash()).collect();
+
+        // The chunk hashes are requested from several peers first.
+        assert_eq!(
+            sync.next_chunk_requests(&[1, 2, 3, 4]),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert!(sync.next_chunk_requests(&[1, 2, 3, 4]).is_empty());
+        for peer in 1..=2 {
+            assert!(sync
+                .on_chunk_response(
+                    peer,
+                    0,
+                    chunk_hashes.clone(),
+                    chunks[0].clone()
+                )
+                .unwrap()
+                .is_none());
+        }
+        assert!(sync.next_chunk_requests(&[1, 2, 3, 4]).is_empty());
+        assert!(sync.on_chunk_failure(3, 0).is_none());
+
+        // The other chunks are only requested from the peers which sent the
+        // adopted chunk hashes.
+        assert_eq!(
+            sync.next_chunk_requests(&[1, 2, 3, 4]),
+            vec![(2, 1), (1, 2)]
+        );
+
+        // A chunk not matching its hash is rejected and requested again.
+        assert!(sync
+            .on_chunk_response(2, 1, chunk_hashes.clone(), chunks[2].clone())
+            .is_err());
+        assert_eq!(sync.next_chunk_requests(&[1, 3]), vec![(1, 1)]);
+
+        assert!(sync
+            .on_chunk_response(1, 2, chunk_hashes.clone(), chunks[2].clone())
+            .unwrap()
+            .is_none());
+        let snapshot = sync
+            .on_chunk_response(1, 1, chunk_hashes.clone(), chunks[1].clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.manifest.epoch_id, H256::from(3));
+        assert_eq!(snapshot.manifest.state_root, H256::from(2));
+        assert_eq!(snapshot.chunks, chunks);
+        snapshot.verify_chunks().unwrap();
+
+        // A snapshot with wrong chunk hashes is synced again.
+        let mut peers = sync.on_state_restored(false);
+        peers.sort();
+        assert_eq!(peers, vec![1, 2]);
+        assert_eq!(sync.next_chunk_requests(&[2]), vec![(2, 0)]);
+        assert!(!sync.is_completed());
+    }
+
+    #[test]
+    fn test_poisoned_state_manifest() {
+        let mut sync = located_sync();
+        let chunks = chunks(3);
+        let chunk_hashes: Vec<H256> =
+            chunks.iter().map(|chunk| chunk.hash()).collect();
+        // Chunk hashes which are consistent with the chunk sent along with
+        // them, but not with the state root.
+        let poisoned_chunk = SnapshotChunk {
+            key_values: vec![(vec![9; 4], vec![9; 4])],
+        };
+        let mut poisoned_hashes = chunk_hashes.clone();
+        poisoned_hashes[0] = poisoned_chunk.hash();
+
+        // The first response is poisoned, but the chunk hashes sent by most
+        // peers are adopted, and the poisoned peer is not penalized yet.
+        assert_eq!(
+            sync.next_chunk_requests(&[1, 2, 3]),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert!(sync
+            .on_chunk_response(
+                1,
+                0,
+                poisoned_hashes.clone(),
+                poisoned_chunk.clone()
+            )
+            .unwrap()
+            .is_none());
+        for peer in 2..=3 {
+            assert!(sync
+                .on_chunk_response(
+                    peer,
+                    0,
+                    chunk_hashes.clone(),
+                    chunks[0].clone()
+                )
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(sync.next_chunk_requests(&[1, 2, 3]), vec![(3, 1), (2, 2)]);
+        // Chunks sent along with other chunk hashes are ignored.
+        assert!(sync
+            .on_chunk_response(3, 1, poisoned_hashes.clone(), chunks[1].clone())
+            .unwrap()
+            .is_none());
+        assert_eq!(sync.next_chunk_requests(&[1, 2, 3]), vec![(3, 1)]);
+        assert!(sync
+            .on_chunk_response(3, 1, chunk_hashes.clone(), chunks[1].clone())
+            .unwrap()
+            .is_none());
+        let snapshot = sync
+            .on_chunk_response(2, 2, chunk_hashes.clone(), chunks[2].clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.manifest.chunk_hashes, chunk_hashes);
+        assert_eq!(snapshot.chunks, chunks);
+        snapshot.verify_chunks().unwrap();
+
+        // If only the poisoned peer responds, its chunk hashes are adopted,
+        // and it is only known to be wrong once the state fails to restore.
+        let mut sync = located_sync();
+        sync.next_chunk_requests(&[1, 2, 3]);
+        assert!(sync.on_chunk_failure(2, 0).is_none());
+        assert!(sync.on_chunk_failure(3, 0).is_none());
+        assert!(sync
+            .on_chunk_response(
+                1,
+                0,
+                poisoned_hashes.clone(),
+                poisoned_chunk.clone()
+            )
+            .unwrap()
+            .is_none());
+        assert_eq!(sync.next_chunk_requests(&[1, 2, 3]), vec![(1, 1), (1, 2)]);
+        assert!(sync
+            .on_chunk_response(1, 1, poisoned_hashes.clone(), chunks[1].clone())
+            .unwrap()
+            .is_none());
+        let snapshot = sync
+            .on_chunk_response(1, 2, poisoned_hashes.clone(), chunks[2].clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.manifest.chunk_hashes, poisoned_hashes);
+        assert_eq!(sync.on_state_restored(false), vec![1]);
+
+        // The rejected chunk hashes are then provably wrong.
+        assert_eq!(
+            sync.next_chunk_requests(&[1, 2, 3]),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert!(sync
+            .on_chunk_response(1, 0, poisoned_hashes, poisoned_chunk)
+            .is_err());
+        for peer in 2..=3 {
+            assert!(sync
+                .on_chunk_response(
+                    peer,
+                    0,
+                    chunk_hashes.clone(),
+                    chunks[0].clone()
+                )
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(sync.next_chunk_requests(&[1, 2, 3]), vec![(3, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn test_receipts_are_verified() {
+        let mut sync = located_sync();
+        let receipts =
+            vec![vec![Receipt::new(0, 21000.into(), Vec::new())], Vec::new()];
+        let receipts_root = BlockHeaderBuilder::compute_block_receipts_root(
+            &receipts.iter().cloned().map(Arc::new).collect(),
+        );
+        sync.receipts.insert(
+            H256::from(10),
+            EpochReceiptsTarget {
+                block_hashes: vec![H256::from(11), H256::from(10)],
+                receipts_root,
+            },
+        );
+        sync.on_state_restored(true);
+        assert!(!sync.is_completed());
+        assert_eq!(sync.next_receipts_requests(), vec![H256::from(10)]);
+        assert!(sync.next_receipts_requests().is_empty());
+
+        // The blocks of the epoch must match the header graph.
+        assert!(sync
+            .on_receipts_response(
+                &H256::from(10),
+                vec![H256::from(12), H256::from(10)],
+                receipts.clone(),
+            )
+            .is_err());
+        // The receipts root does not commit to the blocks, so the blocks
+        // must be in execution order as well.
+        assert!(sync
+            .on_receipts_response(
+                &H256::from(10),
+                vec![H256::from(10), H256::from(11)],
+                receipts.clone(),
+            )
+            .is_err());
+        // The receipts must match the receipts root.
+        assert!(sync
+            .on_receipts_response(
+                &H256::from(10),
+                vec![H256::from(11), H256::from(10)],
+                vec![Vec::new(), Vec::new()],
+            )
+            .is_err());
+        let (root, block_receipts) = sync
+            .on_receipts_response(
+                &H256::from(10),
+                vec![H256::from(11), H256::from(10)],
+                receipts.clone(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(root, receipts_root);
+        assert_eq!(block_receipts[0].0, H256::from(11));
+        assert!(sync.is_completed());
+    }
+}
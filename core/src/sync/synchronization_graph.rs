@@ -10,7 +10,7 @@ use crate::{
     },
     db::COL_MISC,
     error::{BlockError, Error, ErrorKind},
-    machine::new_machine,
+    machine::Machine,
     pow::ProofOfWorkConfig,
     statistics::SharedStatistics,
     storage::GuardedValue,
@@ -110,12 +110,15 @@ pub struct SynchronizationGraphInner {
     children_by_hash: HashMap<H256, Vec<usize>>,
     referrers_by_hash: HashMap<H256, Vec<usize>>,
     pow_config: ProofOfWorkConfig,
+    machine: Arc<Machine>,
 }
 
 impl SynchronizationGraphInner {
     pub fn with_genesis_block(
         genesis_header: Arc<BlockHeader>, pow_config: ProofOfWorkConfig,
-    ) -> Self {
+        machine: Arc<Machine>,
+    ) -> Self
+    {
         let mut inner = SynchronizationGraphInner {
             arena: Slab::new(),
             indices: HashMap::new(),
@@ -123,6 +126,7 @@ impl SynchronizationGraphInner {
             children_by_hash: HashMap::new(),
             referrers_by_hash: HashMap::new(),
             pow_config,
+            machine,
         };
         inner.genesis_block_index = inner.insert(genesis_header);
         debug!(
@@ -254,6 +258,10 @@ impl SynchronizationGraphInner {
                 >= BLOCK_HEADER_PARENTAL_TREE_READY
     }
 
+    pub fn is_header_graph_ready(&self, index: usize) -> bool {
+        self.arena[index].graph_status >= BLOCK_HEADER_GRAPH_READY
+    }
+
     pub fn new_to_be_header_graph_ready(&self, index: usize) -> bool {
         let ref node_me = self.arena[index];
         if node_me.graph_status >= BLOCK_HEADER_GRAPH_READY {
@@ -291,6 +299,61 @@ impl SynchronizationGraphInner {
             })
     }
 
+    /// The hashes of the blocks in the epoch of `pivot` in execution order,
+    /// which is the topological order with ties broken by block hash, as in
+    /// `ConsensusGraphInner::topological_sort`.
+    pub fn epoch_block_hashes(&self, pivot: usize) -> Vec<H256> {
+        let mut index_set =
+            self.arena[pivot].blockset_in_own_view_of_epoch.clone();
+        index_set.insert(pivot);
+
+        let mut num_incoming_edges = HashMap::new();
+        for me in &index_set {
+            num_incoming_edges.entry(*me).or_insert(0);
+            let parent = self.arena[*me].parent;
+            if index_set.contains(&parent) {
+                *num_incoming_edges.entry(parent).or_insert(0) += 1;
+            }
+            for referee in &self.arena[*me].referees {
+                if index_set.contains(referee) {
+                    *num_incoming_edges.entry(*referee).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut candidates: HashSet<usize> = index_set
+            .iter()
+            .filter(|me| num_incoming_edges[*me] == 0)
+            .cloned()
+            .collect();
+        let mut reversed_hashes = Vec::new();
+        while !candidates.is_empty() {
+            let me = candidates
+                .iter()
+                .max_by_key(|index| self.arena[**index].block_header.hash())
+                .cloned()
+                .unwrap();
+            candidates.remove(&me);
+            reversed_hashes.push(self.arena[me].block_header.hash());
+
+            let parent = self.arena[me].parent;
+            let referees = &self.arena[me].referees;
+            for dependency in referees.iter().chain(Some(&parent)) {
+                if index_set.contains(dependency) {
+                    let count = num_incoming_edges
+                        .get_mut(dependency)
+                        .expect("in index set");
+                    *count -= 1;
+                    if *count == 0 {
+                        candidates.insert(*dependency);
+                    }
+                }
+            }
+        }
+        reversed_hashes.reverse();
+        reversed_hashes
+    }
+
     fn collect_blockset_in_own_view_of_epoch(&mut self, pivot: usize) {
         let mut queue = VecDeque::new();
         for referee in &self.arena[pivot].referees {
@@ -360,9 +423,8 @@ impl SynchronizationGraphInner {
             })));
         }
 
-        let machine = new_machine();
-        let gas_limit_divisor = machine.params().gas_limit_bound_divisor;
-        let min_gas_limit = machine.params().min_gas_limit;
+        let gas_limit_divisor = self.machine.params().gas_limit_bound_divisor;
+        let min_gas_limit = self.machine.params().min_gas_limit;
         let parent_gas_limit = *self.arena[parent].block_header.gas_limit();
         let gas_lower = max(
             parent_gas_limit - parent_gas_limit / gas_limit_divisor,
@@ -526,6 +588,7 @@ impl SynchronizationGraph {
             SynchronizationGraphInner::with_genesis_block(
                 Arc::new(data_man.genesis_block().block_header.clone()),
                 pow_config,
+                consensus.machine.clone(),
             ),
         ));
         let mut sync_graph = SynchronizationGraph {
@@ -743,6 +806,11 @@ impl SynchronizationGraph {
 
     pub fn genesis_hash(&self) -> H256 { self.data_man.genesis_block().hash() }
 
+    /// The network id of the chain spec, which peers must agree on.
+    pub fn network_id(&self) -> u64 {
+        self.consensus.machine.params().network_id
+    }
+
     pub fn contains_block_header(&self, hash: &H256) -> bool {
         self.inner.read().indices.contains_key(hash)
     }
@@ -757,7 +825,7 @@ impl SynchronizationGraph {
         self.cache_man.lock().note_used(CacheId::CompactBlock(hash));
     }
 
-    fn parent_or_referees_invalid(&self, header: &BlockHeader) -> bool {
+    pub fn parent_or_referees_invalid(&self, header: &BlockHeader) -> bool {
         self.consensus.verified_invalid(header.parent_hash())
             || header
                 .referee_hashes()
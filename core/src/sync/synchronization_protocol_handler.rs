@@ -3,26 +3,37 @@
 // See http://www.gnu.org/licenses/
 
 use super::{
-    super::transaction_pool::SharedTransactionPool, random, Error, ErrorKind,
+    super::transaction_pool::SharedTransactionPool,
+    random,
+    synchronization_checkpoint::{CheckpointBlockBody, CheckpointSync},
+    synchronization_scheduler::BlockDownloadScheduler,
+    Error, ErrorKind,
     SharedSynchronizationGraph, SynchronizationGraph, SynchronizationPeerState,
     SynchronizationState,
 };
 use crate::{
-    bytes::Bytes, consensus::SharedConsensusGraph, pow::ProofOfWorkConfig,
+    bytes::Bytes,
+    consensus::SharedConsensusGraph,
+    hash::KECCAK_EMPTY_LIST_RLP,
+    pow::ProofOfWorkConfig,
+    snapshot::snapshot::{Snapshot, SnapshotChunk},
+    storage::StorageManagerTrait,
 };
 use cfx_types::H256;
 use io::TimerToken;
 use message::{
     GetBlockHeaders, GetBlockHeadersResponse, GetBlockTxn, GetBlockTxnResponse,
     GetBlocks, GetBlocksResponse, GetBlocksWithPublicResponse,
-    GetCompactBlocks, GetCompactBlocksResponse, GetTerminalBlockHashes,
-    GetTerminalBlockHashesResponse, GetTransactions, GetTransactionsResponse,
-    Message, MsgId, NewBlock, NewBlockHashes, Status, TransIndex,
-    TransactionDigests, TransactionPropagationControl, Transactions,
+    GetCompactBlocks, GetCompactBlocksResponse, GetReceipts,
+    GetReceiptsResponse, GetStateChunk, GetStateChunkResponse,
+    GetTerminalBlockHashes, GetTerminalBlockHashesResponse, GetTransactions,
+    GetTransactionsResponse, Message, MsgId, NewBlock, NewBlockHashes, Status,
+    TransIndex, TransactionDigests, TransactionPropagationControl,
+    Transactions,
 };
 use network::{
     throttling::THROTTLING_SERVICE, Error as NetworkError, HandlerWorkType,
-    NetworkContext, NetworkProtocolHandler, PeerId,
+    Misbehavior, NetworkContext, NetworkProtocolHandler, PeerId,
 };
 use parking_lot::{Mutex, RwLock};
 use rand::Rng;
@@ -35,7 +46,8 @@ use crate::{
     verification::VerificationConfig,
 };
 use primitives::{
-    Block, SignedTransaction, TransactionWithSignature, TxPropagateId,
+    Block, BlockHeader, EpochNumber, SignedTransaction,
+    TransactionWithSignature, TxPropagateId,
 };
 use priority_send_queue::SendQueuePriority;
 use rlp::DecoderError;
@@ -48,6 +60,7 @@ use std::{
         mpsc::channel,
         Arc,
     },
+    thread,
     time::{Duration, Instant},
 };
 use threadpool::ThreadPool;
@@ -71,9 +84,18 @@ const CHECK_REQUEST_TIMER: TimerToken = 1;
 const BLOCK_CACHE_GC_TIMER: TimerToken = 2;
 const CHECK_CATCH_UP_MODE_TIMER: TimerToken = 3;
 const LOG_STATISTIC_TIMER: TimerToken = 4;
+const LOCAL_TRANSACTIONS_TIMER: TimerToken = 5;
 
 const MAX_TXS_BYTES_TO_PROPAGATE: usize = 1024 * 1024; // 1MB
 
+/// The state of a pivot epoch is only served to peers when at least this
+/// many epochs are after it, so that the snapshot is not built for epochs
+/// which may be reverted.
+const STATE_SNAPSHOT_STABLE_EPOCH_COUNT: usize = 10;
+/// The minimum interval between building state snapshots of different
+/// epochs.
+const STATE_SNAPSHOT_BUILD_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Eq, PartialEq, PartialOrd, Ord)]
 enum WaitingRequest {
     Header(H256),
@@ -85,11 +107,23 @@ enum SyncHandlerWorkType {
     RecoverPublic = 1,
 }
 
+/// The state snapshot served to peers, whose chunks are requested one by
+/// one. It is built in a separate thread, one at a time.
+#[derive(Default)]
+struct StateSnapshotCache {
+    snapshot: Option<Arc<Snapshot>>,
+    /// The epoch whose snapshot is being built.
+    building: Option<H256>,
+    last_build: Option<Instant>,
+}
+
 struct RecoverPublicTask {
     blocks: Vec<Block>,
     requested: HashSet<H256>,
     failed_peer: PeerId,
     compact: bool,
+    /// Whether the blocks are requested by the block download scheduler.
+    scheduled: bool,
 }
 
 pub struct SynchronizationProtocolHandler {
@@ -102,9 +136,16 @@ pub struct SynchronizationProtocolHandler {
     block_request_waittime: Mutex<HashMap<H256, Duration>>,
     waiting_requests: Mutex<BinaryHeap<(Instant, WaitingRequest)>>,
     requests_queue: Mutex<BinaryHeap<Arc<TimedSyncRequests>>>,
+    // Block bodies to download in catch-up mode
+    block_download_scheduler: Mutex<BlockDownloadScheduler>,
 
     // Worker task queue for recover public
     recover_public_queue: Mutex<VecDeque<RecoverPublicTask>>,
+
+    // Sync from the trusted checkpoint, until it is completed
+    checkpoint_sync: Mutex<Option<CheckpointSync>>,
+    // The state snapshot served to peers
+    state_snapshot: Arc<Mutex<StateSnapshotCache>>,
 }
 
 pub struct ProtocolConfiguration {
@@ -154,6 +195,9 @@ impl TimedSyncRequests {
             | RequestMessage::Compact(_)
             | RequestMessage::BlockTxn(_) => conf.blocks_request_timeout,
             RequestMessage::Transactions(_) => conf.transaction_request_timeout,
+            RequestMessage::StateChunk(_) | RequestMessage::Receipts(_) => {
+                conf.blocks_request_timeout
+            }
             _ => Duration::default(),
         };
         TimedSyncRequests::new(peer_id, timeout, request_id)
@@ -193,6 +237,11 @@ impl SynchronizationProtocolHandler {
         let sent_transaction_window_size =
             protocol_config.tx_maintained_for_peer_timeout.as_millis()
                 / protocol_config.send_tx_period.as_millis();
+        let checkpoint_sync = consensus_graph
+            .data_man
+            .checkpoint
+            .clone()
+            .map(CheckpointSync::new);
 
         SynchronizationProtocolHandler {
             protocol_config,
@@ -213,7 +262,10 @@ impl SynchronizationProtocolHandler {
             block_request_waittime: Default::default(),
             waiting_requests: Default::default(),
             requests_queue: Default::default(),
+            block_download_scheduler: Mutex::new(BlockDownloadScheduler::new()),
             recover_public_queue: Mutex::new(VecDeque::new()),
+            checkpoint_sync: Mutex::new(checkpoint_sync),
+            state_snapshot: Default::default(),
         }
     }
 
@@ -308,6 +360,14 @@ impl SynchronizationProtocolHandler {
             MsgId::GET_TRANSACTIONS_RESPONSE => {
                 self.on_get_transactions_response(io, peer, &rlp)
             }
+            MsgId::GET_STATE_CHUNK => self.on_get_state_chunk(io, peer, &rlp),
+            MsgId::GET_STATE_CHUNK_RESPONSE => {
+                self.on_state_chunk_response(io, peer, &rlp)
+            }
+            MsgId::GET_RECEIPTS => self.on_get_receipts(io, peer, &rlp),
+            MsgId::GET_RECEIPTS_RESPONSE => {
+                self.on_receipts_response(io, peer, &rlp)
+            }
             _ => {
                 warn!("Unknown message: peer={:?} msgid={:?}", peer, msg_id);
                 Ok(())
@@ -318,9 +378,26 @@ impl SynchronizationProtocolHandler {
                 "Error while handling message msgid={:?}, error={:?}",
                 msg_id, e
             );
+            if let Some(misbehavior) = Self::misbehavior(&e) {
+                io.report_peer(peer, misbehavior);
+            }
         });
     }
 
+    /// The misbehavior of the peer which causes the error, if any.
+    fn misbehavior(e: &Error) -> Option<Misbehavior> {
+        match e.kind() {
+            ErrorKind::Invalid => Some(Misbehavior::InvalidBlock),
+            ErrorKind::Decoder(_) => Some(Misbehavior::MalformedMessage),
+            ErrorKind::UnexpectedResponse => {
+                Some(Misbehavior::UnsolicitedResponse)
+            }
+            ErrorKind::LateResponse => Some(Misbehavior::LateResponse),
+            ErrorKind::InvalidProof => Some(Misbehavior::InvalidProof),
+            _ => None,
+        }
+    }
+
     fn on_get_compact_blocks(
         &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
     ) -> Result<(), Error> {
@@ -336,6 +413,12 @@ impl SynchronizationProtocolHandler {
         for hash in &req.hashes {
             if let Some(compact_block) = self.graph.compact_block_by_hash(hash)
             {
+                if Self::is_placeholder_block(
+                    &compact_block.block_header,
+                    compact_block.tx_short_ids.len(),
+                ) {
+                    continue;
+                }
                 if (compact_blocks.len() as u64) < MAX_HEADERS_TO_SEND {
                     compact_blocks.push(compact_block);
                 }
@@ -458,6 +541,7 @@ impl SynchronizationProtocolHandler {
             requested_blocks,
             peer,
             true,
+            false,
         );
 
         // Request full block if reconstruction fails
@@ -894,6 +978,12 @@ impl SynchronizationProtocolHandler {
             let mut packet_size_left = MAX_PACKET_SIZE;
             for hash in req.hashes.iter() {
                 if let Some(block) = self.graph.block_by_hash(hash) {
+                    if Self::is_placeholder_block(
+                        &block.block_header,
+                        block.transactions.len(),
+                    ) {
+                        continue;
+                    }
                     if packet_size_left
                         >= block.approximated_rlp_size_with_public()
                     {
@@ -941,6 +1031,12 @@ impl SynchronizationProtocolHandler {
             let mut packet_size_left = MAX_PACKET_SIZE;
             for hash in req.hashes.iter() {
                 if let Some(block) = self.graph.block_by_hash(hash) {
+                    if Self::is_placeholder_block(
+                        &block.block_header,
+                        block.transactions.len(),
+                    ) {
+                        continue;
+                    }
                     if packet_size_left >= block.approximated_rlp_size() {
                         packet_size_left -= block.approximated_rlp_size();
                         let block = block.as_ref().clone();
@@ -985,6 +1081,502 @@ impl SynchronizationProtocolHandler {
         Ok(())
     }
 
+    fn on_get_state_chunk(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        if !self.syn.read().peers.contains_key(&peer) {
+            warn!(
+                "Unexpected message from unrecognized peer: peer={:?} \
+                 msg=GET_STATE_CHUNK",
+                peer
+            );
+            return Ok(());
+        }
+
+        let req = rlp.as_val::<GetStateChunk>()?;
+        debug!("on_get_state_chunk, msg=:{:?}", req);
+
+        // An empty response tells that the state is not available.
+        let mut resp = GetStateChunkResponse::default();
+        resp.set_request_id(req.request_id());
+        if let Some(snapshot) = self.state_snapshot(&req.epoch_hash) {
+            if let Some(chunk) = snapshot.chunks.get(req.chunk_index as usize)
+            {
+                resp.chunk_hashes = snapshot.manifest.chunk_hashes.clone();
+                resp.key_values = chunk.key_values.clone();
+            }
+        }
+        self.send_message(io, peer, &resp, SendQueuePriority::Normal)?;
+        Ok(())
+    }
+
+    /// The snapshot of the state at `epoch_hash`, if it is built already.
+    /// Otherwise the snapshot is built in a separate thread if the epoch is
+    /// stable on the pivot chain, and no other snapshot is built recently.
+    fn state_snapshot(&self, epoch_hash: &H256) -> Option<Arc<Snapshot>> {
+        let mut cache = self.state_snapshot.lock();
+        if let Some(ref snapshot) = cache.snapshot {
+            if snapshot.manifest.epoch_id == *epoch_hash {
+                return Some(snapshot.clone());
+            }
+        }
+        if cache.building.is_some() {
+            return None;
+        }
+        if let Some(last_build) = cache.last_build {
+            if last_build.elapsed() < STATE_SNAPSHOT_BUILD_INTERVAL {
+                return None;
+            }
+        }
+
+        let consensus = &self.graph.consensus;
+        let is_stable = match consensus.get_block_epoch_number(epoch_hash) {
+            Some(epoch_number) => {
+                epoch_number + STATE_SNAPSHOT_STABLE_EPOCH_COUNT
+                    <= consensus.best_epoch_number()
+                    && consensus
+                        .get_hash_from_epoch_number(EpochNumber::Number(
+                            epoch_number.into(),
+                        ))
+                        .ok()
+                        == Some(*epoch_hash)
+            }
+            None => false,
+        };
+        let storage_manager = self.graph.data_man.storage_manager.clone();
+        if !is_stable || !storage_manager.contains_state(*epoch_hash) {
+            debug!("Unable to serve the state of {:?}", epoch_hash);
+            return None;
+        }
+
+        cache.building = Some(*epoch_hash);
+        cache.last_build = Some(Instant::now());
+        let state_snapshot = self.state_snapshot.clone();
+        let epoch_hash = *epoch_hash;
+        let spawned = thread::Builder::new()
+            .name("State Snapshot Builder".into())
+            .spawn(move || {
+                let snapshot = match storage_manager.make_snapshot(epoch_hash)
+                {
+                    Ok(snapshot) => Some(Arc::new(snapshot)),
+                    Err(e) => {
+                        warn!(
+                            "Failed to make snapshot of {:?}: {:?}",
+                            epoch_hash, e
+                        );
+                        None
+                    }
+                };
+                let mut cache = state_snapshot.lock();
+                cache.building = None;
+                if snapshot.is_some() {
+                    cache.snapshot = snapshot;
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to build the state snapshot: {:?}", e);
+            cache.building = None;
+        }
+        None
+    }
+
+    fn on_state_chunk_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let resp = rlp.as_val::<GetStateChunkResponse>()?;
+        debug!(
+            "on_state_chunk_response, request_id={} key_values={}",
+            resp.request_id(),
+            resp.key_values.len()
+        );
+        let req = self.match_request(io, peer, resp.request_id())?;
+        let chunk_index = match req {
+            RequestMessage::StateChunk(request) => request.chunk_index,
+            _ => {
+                warn!(
+                    "Get response not matching the request! req={:?}, \
+                     resp={:?}",
+                    req, resp
+                );
+                return Err(ErrorKind::UnexpectedResponse.into());
+            }
+        };
+
+        if resp.chunk_hashes.is_empty() {
+            self.on_state_chunk_failure(io, peer, chunk_index);
+            self.progress_checkpoint_sync(io);
+            return Ok(());
+        }
+        let result = match *self.checkpoint_sync.lock() {
+            Some(ref mut checkpoint_sync) => checkpoint_sync.on_chunk_response(
+                peer,
+                chunk_index,
+                resp.chunk_hashes,
+                SnapshotChunk {
+                    key_values: resp.key_values,
+                },
+            ),
+            None => Ok(None),
+        };
+        if let Ok(Some(ref snapshot)) = result {
+            self.restore_checkpoint_state(io, snapshot);
+        }
+        self.progress_checkpoint_sync(io);
+        result.map(|_| ())
+    }
+
+    fn on_state_chunk_failure(
+        &self, io: &NetworkContext, peer: PeerId, chunk_index: u64,
+    ) {
+        let snapshot = match *self.checkpoint_sync.lock() {
+            Some(ref mut checkpoint_sync) => {
+                checkpoint_sync.on_chunk_failure(peer, chunk_index)
+            }
+            None => None,
+        };
+        if let Some(ref snapshot) = snapshot {
+            self.restore_checkpoint_state(io, snapshot);
+        }
+    }
+
+    /// Load the state synced from peers into the storage.
+    fn restore_checkpoint_state(
+        &self, io: &NetworkContext, snapshot: &Snapshot,
+    ) {
+        info!(
+            "Restoring the state of epoch {:?} from {} chunks",
+            snapshot.manifest.epoch_id,
+            snapshot.chunks.len()
+        );
+        let result = self
+            .graph
+            .data_man
+            .storage_manager
+            .restore_snapshot(snapshot);
+        if let Err(ref e) = result {
+            warn!("Failed to restore the state at checkpoint: {:?}", e);
+        }
+        let invalid_peers = match *self.checkpoint_sync.lock() {
+            Some(ref mut checkpoint_sync) => {
+                checkpoint_sync.on_state_restored(result.is_ok())
+            }
+            None => Vec::new(),
+        };
+        for peer in invalid_peers {
+            io.report_peer(peer, Misbehavior::InvalidProof);
+        }
+    }
+
+    fn on_get_receipts(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        if !self.syn.read().peers.contains_key(&peer) {
+            warn!(
+                "Unexpected message from unrecognized peer: peer={:?} \
+                 msg=GET_RECEIPTS",
+                peer
+            );
+            return Ok(());
+        }
+
+        let req = rlp.as_val::<GetReceipts>()?;
+        debug!("on_get_receipts, msg=:{:?}", req);
+
+        // An empty response tells that the receipts are not available.
+        let mut resp = GetReceiptsResponse::default();
+        resp.set_request_id(req.request_id());
+        if let Some((block_hashes, receipts)) =
+            self.graph.consensus.get_epoch_receipts(&req.epoch_hash)
+        {
+            resp.block_hashes = block_hashes;
+            resp.receipts = receipts
+                .iter()
+                .map(|block_receipts| block_receipts.as_ref().clone())
+                .collect();
+        }
+        self.send_message(io, peer, &resp, SendQueuePriority::Normal)?;
+        Ok(())
+    }
+
+    fn on_receipts_response(
+        &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
+    ) -> Result<(), Error> {
+        let resp = rlp.as_val::<GetReceiptsResponse>()?;
+        debug!("on_receipts_response, msg=:{:?}", resp);
+        let req = self.match_request(io, peer, resp.request_id())?;
+        let epoch_hash = match req {
+            RequestMessage::Receipts(request) => request.epoch_hash,
+            _ => {
+                warn!(
+                    "Get response not matching the request! req={:?}, \
+                     resp={:?}",
+                    req, resp
+                );
+                return Err(ErrorKind::UnexpectedResponse.into());
+            }
+        };
+
+        let result = match *self.checkpoint_sync.lock() {
+            Some(ref mut checkpoint_sync) => {
+                if resp.block_hashes.is_empty() {
+                    checkpoint_sync.on_receipts_failure(&epoch_hash);
+                    Ok(None)
+                } else {
+                    checkpoint_sync.on_receipts_response(
+                        &epoch_hash,
+                        resp.block_hashes,
+                        resp.receipts,
+                    )
+                }
+            }
+            None => Ok(None),
+        };
+        if let Ok(Some((ref receipts_root, ref block_receipts))) = result {
+            for (hash, receipts) in block_receipts {
+                self.graph.data_man.insert_block_results_to_kv(
+                    *hash,
+                    epoch_hash,
+                    receipts.clone(),
+                    Arc::new(vec![String::new(); receipts.len()]),
+                    true,
+                );
+            }
+            self.graph
+                .data_man
+                .insert_receipts_root(epoch_hash, *receipts_root);
+        }
+        self.progress_checkpoint_sync(io);
+        result.map(|_| ())
+    }
+
+    /// Drive the sync from the trusted checkpoint: locate the checkpoint
+    /// once its header graph is ready, insert or download the bodies of
+    /// received headers, and request the state and receipts. When all are
+    /// synced, the deferred block bodies are downloaded and the sync ends.
+    fn progress_checkpoint_sync(&self, io: &NetworkContext) {
+        let peers: Vec<PeerId> =
+            self.syn.read().peers.keys().cloned().collect();
+        let mut placeholders = Vec::new();
+        let mut downloads = Vec::new();
+        let (state_epoch_hash, chunk_requests, receipts_requests) = {
+            let mut checkpoint_sync = self.checkpoint_sync.lock();
+            let located = match *checkpoint_sync {
+                Some(ref mut checkpoint_sync) => {
+                    if checkpoint_sync.is_located() {
+                        Ok(true)
+                    } else {
+                        let located =
+                            checkpoint_sync.locate(&*self.graph.inner.read());
+                        if let Ok(true) = located {
+                            self.skip_synced_checkpoint_data(checkpoint_sync);
+                        }
+                        located
+                    }
+                }
+                None => return,
+            };
+            match located {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    // The block bodies are synced from the genesis instead.
+                    error!("Stopped syncing from the checkpoint: {}", e);
+                    let deferred = checkpoint_sync
+                        .take()
+                        .expect("checked above")
+                        .take_deferred_blocks();
+                    drop(checkpoint_sync);
+                    self.schedule_block_downloads(io, deferred);
+                    return;
+                }
+            }
+
+            let completed = match *checkpoint_sync {
+                Some(ref mut checkpoint_sync) => {
+                    let mut deferred = Vec::new();
+                    {
+                        let inner = self.graph.inner.read();
+                        for (height, hash) in
+                            checkpoint_sync.take_deferred_blocks()
+                        {
+                            match checkpoint_sync.block_body(&*inner, &hash) {
+                                CheckpointBlockBody::Skip => {
+                                    placeholders.push(hash)
+                                }
+                                CheckpointBlockBody::Download => {
+                                    downloads.push((height, hash))
+                                }
+                                CheckpointBlockBody::Defer => {
+                                    deferred.push((height, hash))
+                                }
+                            }
+                        }
+                    }
+                    checkpoint_sync.defer_blocks(deferred);
+                    checkpoint_sync.is_completed()
+                }
+                None => return,
+            };
+
+            if completed {
+                let mut checkpoint_sync =
+                    checkpoint_sync.take().expect("checked above");
+                info!(
+                    "Synced from checkpoint {:?}",
+                    checkpoint_sync.checkpoint()
+                );
+                downloads.extend(checkpoint_sync.take_deferred_blocks());
+                (None, Vec::new(), Vec::new())
+            } else {
+                let checkpoint_sync =
+                    checkpoint_sync.as_mut().expect("checked above");
+                (
+                    checkpoint_sync.state_epoch_hash(),
+                    checkpoint_sync.next_chunk_requests(&peers),
+                    checkpoint_sync.next_receipts_requests(),
+                )
+            }
+        };
+
+        // Blocks in the past of the reward epochs are not executed, so they
+        // are inserted without their bodies.
+        for hash in placeholders {
+            if let Some(header) = self.graph.block_header_by_hash(&hash) {
+                self.graph.insert_block(
+                    Block::new(header, Vec::new()),
+                    false,
+                    true,
+                    false,
+                );
+            }
+        }
+        self.schedule_block_downloads(io, downloads);
+
+        if let Some(epoch_hash) = state_epoch_hash {
+            for (peer, chunk_index) in chunk_requests {
+                if self
+                    .request_state_chunk(io, peer, epoch_hash, chunk_index)
+                    .is_err()
+                {
+                    self.on_state_chunk_failure(io, peer, chunk_index);
+                }
+            }
+        }
+        for epoch_hash in receipts_requests {
+            let peer = self.syn.read().get_random_peer(&HashSet::new());
+            let sent = match peer {
+                Some(peer) => {
+                    self.request_receipts(io, peer, epoch_hash).is_ok()
+                }
+                None => false,
+            };
+            if !sent {
+                if let Some(ref mut checkpoint_sync) =
+                    *self.checkpoint_sync.lock()
+                {
+                    checkpoint_sync.on_receipts_failure(&epoch_hash);
+                }
+            }
+        }
+    }
+
+    /// Skip the state and receipts which are already in the storage, e.g.
+    /// when the node restarts during or after the checkpoint sync.
+    fn skip_synced_checkpoint_data(
+        &self, checkpoint_sync: &mut CheckpointSync,
+    ) {
+        let data_man = &self.graph.data_man;
+        if let Some(state_epoch_hash) = checkpoint_sync.state_epoch_hash() {
+            if data_man.storage_manager.contains_state(state_epoch_hash) {
+                checkpoint_sync.on_state_restored(true);
+            }
+        }
+        checkpoint_sync.retain_receipts(|epoch_hash, block_hashes| {
+            !block_hashes.iter().all(|hash| {
+                data_man
+                    .block_results_by_hash_with_epoch(hash, epoch_hash, false)
+                    .is_some()
+            })
+        });
+    }
+
+    fn request_state_chunk(
+        &self, io: &NetworkContext, peer_id: PeerId, epoch_hash: H256,
+        chunk_index: u64,
+    ) -> Result<(), Error>
+    {
+        match self.send_request(
+            io,
+            peer_id,
+            Box::new(RequestMessage::StateChunk(GetStateChunk {
+                request_id: 0.into(),
+                epoch_hash,
+                chunk_index,
+            })),
+            SendQueuePriority::Normal,
+        ) {
+            Ok(timed_req) => {
+                if let Some(timed_req) = timed_req {
+                    debug!(
+                        "Requesting state chunk {} of {:?} from {:?} \
+                         request_id={}",
+                        chunk_index, epoch_hash, peer_id, timed_req.request_id
+                    );
+                    self.requests_queue.lock().push(timed_req);
+                } else {
+                    debug!(
+                        "State chunk request is added in pending queue. peer \
+                         {}",
+                        peer_id
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn request_receipts(
+        &self, io: &NetworkContext, peer_id: PeerId, epoch_hash: H256,
+    ) -> Result<(), Error> {
+        match self.send_request(
+            io,
+            peer_id,
+            Box::new(RequestMessage::Receipts(GetReceipts {
+                request_id: 0.into(),
+                epoch_hash,
+            })),
+            SendQueuePriority::Normal,
+        ) {
+            Ok(timed_req) => {
+                if let Some(timed_req) = timed_req {
+                    debug!(
+                        "Requesting receipts of {:?} from {:?} request_id={}",
+                        epoch_hash, peer_id, timed_req.request_id
+                    );
+                    self.requests_queue.lock().push(timed_req);
+                } else {
+                    debug!(
+                        "Receipts request is added in pending queue. peer {}",
+                        peer_id
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether a block with `transaction_count` transactions was inserted
+    /// without its body when syncing from a checkpoint. Such blocks are not
+    /// served to peers.
+    fn is_placeholder_block(
+        header: &BlockHeader, transaction_count: usize,
+    ) -> bool {
+        transaction_count == 0
+            && *header.transactions_root() != KECCAK_EMPTY_LIST_RLP
+    }
+
     fn on_get_terminal_block_hashes(
         &self, io: &NetworkContext, peer: PeerId, rlp: &Rlp,
     ) -> Result<(), Error> {
@@ -1050,7 +1642,18 @@ impl SynchronizationProtocolHandler {
                 "Peer {:?} genesis hash mismatches (ours: {:?}, theirs: {:?})",
                 peer, genesis_hash, status.genesis_hash
             );
-            return Err(ErrorKind::Invalid.into());
+            // The peer is on another chain rather than misbehaving.
+            io.disconnect_peer(peer);
+            return Ok(());
+        }
+        let network_id = self.graph.network_id();
+        if network_id != status.network_id {
+            debug!(
+                "Peer {:?} network id mismatches (ours: {}, theirs: {})",
+                peer, network_id, status.network_id
+            );
+            io.disconnect_peer(peer);
+            return Ok(());
         }
 
         let mut requests_vec = Vec::with_capacity(
@@ -1145,18 +1748,26 @@ impl SynchronizationProtocolHandler {
         let mut need_to_relay = Vec::new();
 
         let mut responsed = false;
+        let mut misbehavior = None;
         for header in &mut block_headers.headers {
             let hash = header.hash();
             if hash == req_hash {
                 responsed = true;
             }
 
+            if !self.graph.contains_block_header(&hash)
+                && self.graph.verification_config.verify_pow(header).is_err()
+            {
+                misbehavior = Some(Misbehavior::InvalidPow);
+                continue;
+            }
+
             let res = self.graph.insert_block_header(header, true);
 
             if res.0 {
                 // Valid block based on header
                 if !self.graph.contains_block(&hash) {
-                    hashes.push(hash);
+                    hashes.push((header.height(), hash));
                 }
 
                 need_to_relay.extend(res.1);
@@ -1164,8 +1775,17 @@ impl SynchronizationProtocolHandler {
                 for referee in header.referee_hashes() {
                     dependent_hashes.push(*referee);
                 }
+            } else if misbehavior.is_none()
+                && !self.graph.parent_or_referees_invalid(header)
+            {
+                // Only blame the peer for headers which are invalid on their
+                // own, since it may not know their ancestors are invalid.
+                misbehavior = Some(Misbehavior::InvalidBlock);
             }
         }
+        if let Some(misbehavior) = misbehavior {
+            io.report_peer(peer, misbehavior);
+        }
 
         {
             let mut headers_in_flight = self.headers_in_flight.lock();
@@ -1207,11 +1827,20 @@ impl SynchronizationProtocolHandler {
                 let num = if *past_hash == parent_hash {
                     let current_height =
                         self.graph.consensus.best_epoch_number() as u64;
+                    // In catch-up mode, the whole header graph is built
+                    // before bodies are downloaded, so fetch as many
+                    // ancestors as a peer is willing to send.
+                    let max_parent_headers = if self.syn.read().catch_up_mode
+                    {
+                        MAX_HEADERS_TO_SEND
+                    } else {
+                        DEFAULT_GET_PARENT_HEADERS_NUM
+                    };
                     // Without fork, we only need to request missing blocks
                     // since current_height
                     if parent_height > current_height {
                         cmp::min(
-                            DEFAULT_GET_PARENT_HEADERS_NUM,
+                            max_parent_headers,
                             parent_height - current_height,
                         )
                     } else {
@@ -1226,14 +1855,27 @@ impl SynchronizationProtocolHandler {
 
         let catch_up_mode = self.syn.read().catch_up_mode;
 
-        if !hashes.is_empty() {
-            // FIXME: This is a naive strategy. Need to
-            // make it more sophisticated.
-            if catch_up_mode {
-                self.request_blocks(io, Some(peer), hashes);
-            } else {
-                self.request_compact_block(io, Some(peer), hashes);
+        let syncing_checkpoint = match *self.checkpoint_sync.lock() {
+            Some(ref mut checkpoint_sync) => {
+                checkpoint_sync.defer_blocks(hashes.clone());
+                true
             }
+            None => false,
+        };
+        if syncing_checkpoint {
+            // Which bodies to download depends on where the checkpoint is in
+            // the header graph.
+            self.progress_checkpoint_sync(io);
+        } else if catch_up_mode {
+            // Bodies are downloaded from all peers once the headers are
+            // complete, so this is called even if `hashes` is empty.
+            self.schedule_block_downloads(io, hashes);
+        } else if !hashes.is_empty() {
+            self.request_compact_block(
+                io,
+                Some(peer),
+                hashes.into_iter().map(|(_, hash)| hash).collect(),
+            );
         }
 
         if !need_to_relay.is_empty() && !catch_up_mode {
@@ -1273,6 +1915,14 @@ impl SynchronizationProtocolHandler {
                 return Err(ErrorKind::UnexpectedResponse.into());
             }
         };
+        let received: HashSet<H256> =
+            blocks.blocks.iter().map(|b| b.hash()).collect();
+        let scheduled = self.block_download_scheduler.lock().on_response(
+            peer,
+            &req_hashes_vec,
+            &received,
+            Instant::now(),
+        );
         let requested_blocks: HashSet<H256> =
             req_hashes_vec.into_iter().collect();
         self.dispatch_recover_public_task(
@@ -1281,6 +1931,7 @@ impl SynchronizationProtocolHandler {
             requested_blocks,
             peer,
             false,
+            scheduled,
         );
 
         Ok(())
@@ -1307,6 +1958,14 @@ impl SynchronizationProtocolHandler {
                 return Err(ErrorKind::UnexpectedResponse.into());
             }
         };
+        let received: HashSet<H256> =
+            blocks.blocks.iter().map(|b| b.hash()).collect();
+        let scheduled = self.block_download_scheduler.lock().on_response(
+            peer,
+            &req_hashes_vec,
+            &received,
+            Instant::now(),
+        );
         let requested_blocks: HashSet<H256> =
             req_hashes_vec.into_iter().collect();
 
@@ -1316,6 +1975,7 @@ impl SynchronizationProtocolHandler {
             requested_blocks,
             peer,
             false,
+            scheduled,
         );
 
         Ok(())
@@ -1395,6 +2055,19 @@ impl SynchronizationProtocolHandler {
                     task.requested.into_iter().collect(),
                 );
             }
+        } else if task.scheduled {
+            // Blocks missing in the response are already rescheduled, so
+            // this only reschedules the blocks which fail to be inserted.
+            let blocks = task
+                .requested
+                .iter()
+                .filter_map(|hash| {
+                    self.graph
+                        .block_header_by_hash(hash)
+                        .map(|header| (header.height(), *hash))
+                })
+                .collect();
+            self.schedule_block_downloads(io, blocks);
         } else {
             // Request missing blocks from another random peer
             if !task.requested.is_empty() {
@@ -1462,6 +2135,13 @@ impl SynchronizationProtocolHandler {
                 );
                 if res.0 {
                     need_to_relay.extend(res.1);
+                } else if self
+                    .graph
+                    .parent_or_referees_invalid(&block.block_header)
+                {
+                    // The sender may not know that an ancestor is invalid,
+                    // so the block is dropped without blaming it.
+                    return Err(Error::from_kind(ErrorKind::Useless));
                 } else {
                     return Err(Error::from_kind(ErrorKind::Invalid));
                 }
@@ -1608,7 +2288,7 @@ impl SynchronizationProtocolHandler {
 
         let msg: Box<dyn Message> = Box::new(Status {
             protocol_version: SYNCHRONIZATION_PROTOCOL_VERSION,
-            network_id: 0x0,
+            network_id: self.graph.network_id(),
             genesis_hash: self.graph.genesis_hash(),
             best_epoch: best_info.best_epoch_number as u64,
             terminal_block_hashes: best_info.terminal_block_hashes,
@@ -2091,6 +2771,9 @@ impl SynchronizationProtocolHandler {
                 }
             }
             Ok(removed_req)
+        } else if request_id < peer_info.next_request_id {
+            // The request was sent, but it timed out or was answered already.
+            Err(ErrorKind::LateResponse.into())
         } else {
             Err(ErrorKind::UnexpectedResponse.into())
         }
@@ -2327,7 +3010,7 @@ impl SynchronizationProtocolHandler {
                 Ok(request) => {
                     // TODO may have better choice than random peer
                     debug!("Timeout request: {:?}", request);
-                    self.send_request_again(request, io);
+                    self.send_request_again(request, sync_req.peer_id, io);
                 }
                 Err(e) => {
                     debug!("Timeout a removed request err={:?}", e);
@@ -2395,7 +3078,11 @@ impl SynchronizationProtocolHandler {
         }
     }
 
-    fn send_request_again(&self, request: RequestMessage, io: &NetworkContext) {
+    fn send_request_again(
+        &self, request: RequestMessage, failed_peer: PeerId,
+        io: &NetworkContext,
+    )
+    {
         let chosen_peer = self.syn.read().get_random_peer(&HashSet::new());
         match request {
             RequestMessage::Headers(get_headers) => {
@@ -2407,7 +3094,15 @@ impl SynchronizationProtocolHandler {
                 );
             }
             RequestMessage::Blocks(get_blocks) => {
-                self.request_blocks(io, chosen_peer, get_blocks.hashes);
+                if self.block_download_scheduler.lock().on_failure(
+                    failed_peer,
+                    &get_blocks.hashes,
+                    Instant::now(),
+                ) {
+                    self.schedule_block_downloads(io, Vec::new());
+                } else {
+                    self.request_blocks(io, chosen_peer, get_blocks.hashes);
+                }
             }
             RequestMessage::Compact(get_compact) => {
                 {
@@ -2423,6 +3118,23 @@ impl SynchronizationProtocolHandler {
                 hashes.push(blocktxn.block_hash);
                 self.request_blocks(io, chosen_peer, hashes);
             }
+            RequestMessage::StateChunk(get_chunk) => {
+                self.on_state_chunk_failure(
+                    io,
+                    failed_peer,
+                    get_chunk.chunk_index,
+                );
+                self.progress_checkpoint_sync(io);
+            }
+            RequestMessage::Receipts(get_receipts) => {
+                if let Some(ref mut checkpoint_sync) =
+                    *self.checkpoint_sync.lock()
+                {
+                    checkpoint_sync
+                        .on_receipts_failure(&get_receipts.epoch_hash);
+                }
+                self.progress_checkpoint_sync(io);
+            }
             _ => {}
         }
     }
@@ -2707,6 +3419,7 @@ impl SynchronizationProtocolHandler {
     fn dispatch_recover_public_task(
         &self, io: &NetworkContext, blocks: Vec<Block>,
         requested: HashSet<H256>, failed_peer: PeerId, compact: bool,
+        scheduled: bool,
     )
     {
         self.recover_public_queue
@@ -2716,11 +3429,54 @@ impl SynchronizationProtocolHandler {
                 requested,
                 failed_peer,
                 compact,
+                scheduled,
             });
 
         io.dispatch_work(SyncHandlerWorkType::RecoverPublic as HandlerWorkType);
     }
 
+    /// Add `blocks`, as (height, hash), to the block download scheduler and
+    /// request the scheduled ranges from all peers. While block headers are
+    /// still in flight, blocks are only queued, so that bodies are
+    /// downloaded after the header graph is built up to the terminals of
+    /// peers.
+    fn schedule_block_downloads(
+        &self, io: &NetworkContext, blocks: Vec<(u64, H256)>,
+    ) {
+        let headers_complete = self.headers_in_flight.lock().is_empty();
+        let (peers, catch_up_mode) = {
+            let syn = self.syn.read();
+            (
+                syn.peers.keys().cloned().collect::<Vec<_>>(),
+                syn.catch_up_mode,
+            )
+        };
+        let assignments = {
+            let mut scheduler = self.block_download_scheduler.lock();
+            scheduler.enqueue(blocks);
+            if !headers_complete {
+                return;
+            }
+            scheduler.assign(&peers, Instant::now(), |hash| {
+                !self.graph.contains_block(hash)
+            })
+        };
+
+        let with_public = self.request_block_need_public(catch_up_mode);
+        for (peer, hashes) in assignments {
+            if self
+                .request_blocks_unchecked(io, peer, &hashes, with_public)
+                .is_err()
+            {
+                self.block_download_scheduler.lock().on_failure(
+                    peer,
+                    &hashes,
+                    Instant::now(),
+                );
+            }
+        }
+    }
+
     fn request_block_need_public(&self, catch_up_mode: bool) -> bool {
         catch_up_mode && self.protocol_config.request_block_with_public
     }
@@ -2747,6 +3503,11 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
         .expect("Error registering check_catch_up_mode timer");
         io.register_timer(LOG_STATISTIC_TIMER, Duration::from_millis(5000))
             .expect("Error registering log_statistics timer");
+        io.register_timer(
+            LOCAL_TRANSACTIONS_TIMER,
+            Duration::from_millis(5000),
+        )
+        .expect("Error registering local_transactions timer");
     }
 
     fn on_message(&self, io: &NetworkContext, peer: PeerId, raw: &[u8]) {
@@ -2838,8 +3599,11 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
                 _ => {}
             }
 
-            self.send_request_again(*request, io);
+            self.send_request_again(*request, peer, io);
         }
+
+        self.block_download_scheduler.lock().remove_peer(peer);
+        self.schedule_block_downloads(io, Vec::new());
     }
 
     fn on_timeout(&self, io: &NetworkContext, timer: TimerToken) {
@@ -2851,6 +3615,8 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
             }
             CHECK_REQUEST_TIMER => {
                 self.remove_expired_flying_request(io);
+                self.schedule_block_downloads(io, Vec::new());
+                self.progress_checkpoint_sync(io);
             }
             BLOCK_CACHE_GC_TIMER => {
                 self.block_cache_gc();
@@ -2861,6 +3627,9 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
             LOG_STATISTIC_TIMER => {
                 self.log_statistics();
             }
+            LOCAL_TRANSACTIONS_TIMER => {
+                self.get_transaction_pool().flush_local_transactions();
+            }
             _ => warn!("Unknown timer {} triggered.", timer),
         }
     }
@@ -0,0 +1,423 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::synchronization_protocol_handler::MAX_BLOCKS_TO_SEND;
+use cfx_types::H256;
+use network::PeerId;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// The number of block ranges a peer may have in flight, so that the next
+/// range is queued at the peer while the current one is being sent.
+const MAX_RANGES_PER_PEER: usize = 2;
+/// The size of the first range assigned to a peer, before its throughput is
+/// measured.
+const INITIAL_RANGE_SIZE: usize = 16;
+const MIN_RANGE_SIZE: usize = 4;
+/// Ranges are sized so that a peer is expected to send one in this time.
+const TARGET_RANGE_DURATION: Duration = Duration::from_secs(2);
+/// The assumed latency of a peer before it is measured.
+const DEFAULT_LATENCY: Duration = Duration::from_secs(1);
+/// A range which is not received after this many times the expected duration
+/// may be assigned to another idle peer as well.
+const SLOW_RANGE_FACTOR: u32 = 2;
+/// The weight of a new sample in the moving averages of peer statistics.
+const SAMPLE_WEIGHT: f64 = 0.3;
+
+struct AssignedRange {
+    blocks: Vec<(u64, H256)>,
+    sent_at: Instant,
+    /// After the deadline, the range may be stolen by an idle peer.
+    deadline: Instant,
+    stolen: bool,
+}
+
+#[derive(Default)]
+struct PeerDownloadState {
+    /// Moving average of the time from sending a request to receiving the
+    /// response.
+    latency: Option<Duration>,
+    /// Moving average of the blocks received per second.
+    throughput: Option<f64>,
+    ranges: Vec<AssignedRange>,
+}
+
+impl PeerDownloadState {
+    fn range_size(&self) -> usize {
+        match self.throughput {
+            Some(throughput) => {
+                let size = throughput * duration_as_secs(TARGET_RANGE_DURATION);
+                (size as usize)
+                    .max(MIN_RANGE_SIZE)
+                    .min(MAX_BLOCKS_TO_SEND as usize)
+            }
+            None => INITIAL_RANGE_SIZE,
+        }
+    }
+
+    /// The expected time to receive a range of `blocks` blocks.
+    fn expected_duration(&self, blocks: usize) -> Duration {
+        let latency = self.latency.unwrap_or(DEFAULT_LATENCY);
+        let transfer = match self.throughput {
+            Some(throughput) if throughput > 0.0 => {
+                duration_from_secs(blocks as f64 / throughput)
+            }
+            _ => DEFAULT_LATENCY,
+        };
+        latency + transfer
+    }
+
+    fn take_range(&mut self, requested: &[H256]) -> Option<AssignedRange> {
+        let index = self.ranges.iter().position(|range| {
+            range.blocks.len() == requested.len()
+                && range
+                    .blocks
+                    .iter()
+                    .zip(requested.iter())
+                    .all(|((_, hash), requested)| hash == requested)
+        })?;
+        Some(self.ranges.remove(index))
+    }
+
+    fn add_sample(&mut self, elapsed: Duration, received: usize) {
+        let elapsed = duration_as_secs(elapsed);
+        let throughput = received as f64 / elapsed.max(0.001);
+        self.throughput = Some(match self.throughput {
+            Some(average) => moving_average(average, throughput),
+            None => throughput,
+        });
+        self.latency = Some(match self.latency {
+            Some(average) => duration_from_secs(moving_average(
+                duration_as_secs(average),
+                elapsed,
+            )),
+            None => duration_from_secs(elapsed),
+        });
+    }
+}
+
+fn moving_average(average: f64, sample: f64) -> f64 {
+    average * (1.0 - SAMPLE_WEIGHT) + sample * SAMPLE_WEIGHT
+}
+
+fn duration_as_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+fn duration_from_secs(secs: f64) -> Duration {
+    Duration::from_millis((secs * 1000.0) as u64)
+}
+
+/// Schedules the download of block bodies whose headers are already in the
+/// graph. Blocks are split into ranges ordered by height and spread across
+/// all peers. Each peer gets ranges sized by its measured throughput, and
+/// peers with the shortest expected response time get the lowest ranges,
+/// which are needed first to insert the rest. Ranges of failing peers are
+/// reassigned, and ranges of slow peers are also sent to idle peers.
+pub struct BlockDownloadScheduler {
+    /// Blocks which are not assigned to any peer, as (height, hash).
+    pending: BTreeSet<(u64, H256)>,
+    /// Blocks which are pending or assigned.
+    tracked: HashSet<H256>,
+    peers: HashMap<PeerId, PeerDownloadState>,
+}
+
+impl BlockDownloadScheduler {
+    pub fn new() -> Self {
+        BlockDownloadScheduler {
+            pending: BTreeSet::new(),
+            tracked: HashSet::new(),
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn pending_len(&self) -> usize { self.pending.len() }
+
+    /// Add blocks to download, as (height, hash). Blocks which are already
+    /// scheduled are ignored.
+    pub fn enqueue(&mut self, blocks: Vec<(u64, H256)>) {
+        for (height, hash) in blocks {
+            if self.tracked.insert(hash) {
+                self.pending.insert((height, hash));
+            }
+        }
+    }
+
+    /// Assign pending blocks to the peers with free capacity. Blocks for
+    /// which `is_needed` returns false, e.g. because they are received from
+    /// other requests, are dropped. Returns the ranges to request from each
+    /// peer.
+    pub fn assign<F>(
+        &mut self, peers: &[PeerId], now: Instant, is_needed: F,
+    ) -> Vec<(PeerId, Vec<H256>)>
+    where F: Fn(&H256) -> bool {
+        for peer in peers {
+            self.peers.entry(*peer).or_insert_with(Default::default);
+        }
+        let mut ordered_peers = peers.to_vec();
+        ordered_peers.sort_by_key(|peer| {
+            let state = &self.peers[peer];
+            state.expected_duration(state.range_size())
+        });
+
+        let mut assignments = Vec::new();
+        for peer in ordered_peers {
+            while self.peers[&peer].ranges.len() < MAX_RANGES_PER_PEER {
+                let size = self.peers[&peer].range_size();
+                let mut blocks = self.take_pending(size, &is_needed);
+                if blocks.is_empty() {
+                    blocks = match self.steal_slow_range(peer, now) {
+                        Some(blocks) => blocks,
+                        None => break,
+                    };
+                }
+
+                let state = self.peers.get_mut(&peer).expect("peer added");
+                let expected = state.expected_duration(blocks.len());
+                assignments.push((
+                    peer,
+                    blocks.iter().map(|(_, hash)| *hash).collect(),
+                ));
+                state.ranges.push(AssignedRange {
+                    blocks,
+                    sent_at: now,
+                    deadline: now + expected * SLOW_RANGE_FACTOR,
+                    stolen: false,
+                });
+            }
+        }
+        assignments
+    }
+
+    fn take_pending<F>(
+        &mut self, size: usize, is_needed: &F,
+    ) -> Vec<(u64, H256)>
+    where F: Fn(&H256) -> bool {
+        let mut blocks = Vec::with_capacity(size);
+        while blocks.len() < size {
+            let block = match self.pending.iter().next() {
+                Some(block) => *block,
+                None => break,
+            };
+            self.pending.remove(&block);
+            if is_needed(&block.1) {
+                blocks.push(block);
+            } else {
+                self.tracked.remove(&block.1);
+            }
+        }
+        blocks
+    }
+
+    /// Take the most overdue range of another peer for `thief`. The range
+    /// stays assigned to its peer, whichever response comes first is used.
+    fn steal_slow_range(
+        &mut self, thief: PeerId, now: Instant,
+    ) -> Option<Vec<(u64, H256)>> {
+        let range = self
+            .peers
+            .iter_mut()
+            .filter(|(peer, _)| **peer != thief)
+            .flat_map(|(_, state)| state.ranges.iter_mut())
+            .filter(|range| !range.stolen && range.deadline < now)
+            .min_by_key(|range| range.deadline)?;
+        range.stolen = true;
+        debug!(
+            "Reassign slow block range starting at {:?} to peer {}",
+            range.blocks[0].1, thief
+        );
+        Some(range.blocks.clone())
+    }
+
+    /// Update the statistics of `peer` with the response of a range request.
+    /// Requested blocks which are not received are rescheduled. Returns
+    /// false if the request is not scheduled by `self`.
+    pub fn on_response(
+        &mut self, peer: PeerId, requested: &[H256], received: &HashSet<H256>,
+        now: Instant,
+    ) -> bool
+    {
+        let state = match self.peers.get_mut(&peer) {
+            Some(state) => state,
+            None => return false,
+        };
+        let range = match state.take_range(requested) {
+            Some(range) => range,
+            None => return false,
+        };
+        let received_count = range
+            .blocks
+            .iter()
+            .filter(|(_, hash)| received.contains(hash))
+            .count();
+        state.add_sample(now.duration_since(range.sent_at), received_count);
+
+        for block in range.blocks {
+            if !received.contains(&block.1) {
+                self.pending.insert(block);
+            } else {
+                self.tracked.remove(&block.1);
+            }
+        }
+        true
+    }
+
+    /// Reschedule the blocks of a failed range request, and penalize the
+    /// peer as if it sent nothing. Returns false if the request is not
+    /// scheduled by `self`.
+    pub fn on_failure(
+        &mut self, peer: PeerId, requested: &[H256], now: Instant,
+    ) -> bool {
+        let state = match self.peers.get_mut(&peer) {
+            Some(state) => state,
+            None => return false,
+        };
+        let range = match state.take_range(requested) {
+            Some(range) => range,
+            None => return false,
+        };
+        state.add_sample(now.duration_since(range.sent_at), 0);
+        self.pending.extend(range.blocks);
+        true
+    }
+
+    /// Reschedule all ranges of a disconnected peer.
+    pub fn remove_peer(&mut self, peer: PeerId) {
+        if let Some(state) = self.peers.remove(&peer) {
+            for range in state.ranges {
+                self.pending.extend(range.blocks);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockDownloadScheduler, INITIAL_RANGE_SIZE};
+    use cfx_types::H256;
+    use std::{
+        collections::HashSet,
+        time::{Duration, Instant},
+    };
+
+    fn blocks(count: u64) -> Vec<(u64, H256)> {
+        (1..=count)
+            .map(|height| (height, H256::from(height)))
+            .collect()
+    }
+
+    #[test]
+    fn test_ranges_spread_across_peers() {
+        let mut scheduler = BlockDownloadScheduler::new();
+        scheduler.enqueue(blocks(100));
+        scheduler.enqueue(blocks(10));
+        assert_eq!(scheduler.pending_len(), 100);
+
+        let assignments =
+            scheduler.assign(&[1, 2, 3], Instant::now(), |_| true);
+        // Each peer gets two ranges of the initial size.
+        assert_eq!(assignments.len(), 6);
+        let mut assigned = HashSet::new();
+        for (_, hashes) in &assignments {
+            assert_eq!(hashes.len(), INITIAL_RANGE_SIZE);
+            for hash in hashes {
+                assert!(assigned.insert(*hash));
+            }
+        }
+        // The lowest blocks are assigned first.
+        assert_eq!(assignments[0].1[0], H256::from(1));
+        assert_eq!(scheduler.pending_len(), 100 - 6 * INITIAL_RANGE_SIZE);
+
+        // No more capacity until responses arrive.
+        assert!(scheduler
+            .assign(&[1, 2, 3], Instant::now(), |_| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_faster_peer_gets_larger_ranges() {
+        let mut scheduler = BlockDownloadScheduler::new();
+        scheduler.enqueue(blocks(1000));
+        let start = Instant::now();
+        let assignments = scheduler.assign(&[1, 2], start, |_| true);
+        for (peer, hashes) in &assignments {
+            let received = hashes.iter().cloned().collect();
+            // Peer 1 responds in 100ms and peer 2 in 2s.
+            let elapsed = if *peer == 1 { 100 } else { 2000 };
+            let now = start + Duration::from_millis(elapsed);
+            assert!(scheduler.on_response(*peer, hashes, &received, now));
+        }
+
+        let assignments =
+            scheduler.assign(&[1, 2], start + Duration::from_secs(3), |_| true);
+        let size = |peer| {
+            assignments
+                .iter()
+                .find(|(p, _)| *p == peer)
+                .map(|(_, hashes)| hashes.len())
+                .unwrap()
+        };
+        assert!(size(1) > size(2));
+        // The faster peer gets the lowest range.
+        assert_eq!(assignments[0].0, 1);
+    }
+
+    #[test]
+    fn test_failed_range_is_reassigned() {
+        let mut scheduler = BlockDownloadScheduler::new();
+        scheduler.enqueue(blocks(INITIAL_RANGE_SIZE as u64));
+        let now = Instant::now();
+        let assignments = scheduler.assign(&[1], now, |_| true);
+        assert_eq!(assignments.len(), 1);
+        let requested = assignments[0].1.clone();
+
+        // Half of the blocks are missing in the response.
+        let received: HashSet<H256> = requested[..INITIAL_RANGE_SIZE / 2]
+            .iter()
+            .cloned()
+            .collect();
+        assert!(scheduler.on_response(1, &requested, &received, now));
+        assert_eq!(scheduler.pending_len(), INITIAL_RANGE_SIZE / 2);
+
+        let assignments = scheduler.assign(&[2], now, |_| true);
+        assert_eq!(
+            assignments[0].1,
+            requested[INITIAL_RANGE_SIZE / 2..].to_vec()
+        );
+        assert!(scheduler.on_failure(2, &assignments[0].1, now));
+        assert!(!scheduler.on_failure(2, &assignments[0].1, now));
+        assert_eq!(scheduler.pending_len(), INITIAL_RANGE_SIZE / 2);
+
+        // Blocks received by other means are dropped.
+        assert!(scheduler.assign(&[3], now, |_| false).is_empty());
+        assert_eq!(scheduler.pending_len(), 0);
+    }
+
+    #[test]
+    fn test_slow_range_is_stolen() {
+        let mut scheduler = BlockDownloadScheduler::new();
+        scheduler.enqueue(blocks(INITIAL_RANGE_SIZE as u64));
+        let now = Instant::now();
+        let slow = scheduler.assign(&[1], now, |_| true);
+        assert_eq!(slow.len(), 1);
+
+        // The range is not overdue yet.
+        assert!(scheduler.assign(&[2], now, |_| true).is_empty());
+
+        let later = now + Duration::from_secs(60);
+        let stolen = scheduler.assign(&[2], later, |_| true);
+        assert_eq!(stolen.len(), 1);
+        assert_eq!(stolen[0], (2, slow[0].1.clone()));
+        // A range is only stolen once.
+        assert!(scheduler.assign(&[3], later, |_| true).is_empty());
+
+        // Both requests can be matched with their responses.
+        let received = slow[0].1.iter().cloned().collect();
+        assert!(scheduler.on_response(2, &slow[0].1, &received, later));
+        assert!(scheduler.on_response(1, &slow[0].1, &received, later));
+        assert_eq!(scheduler.pending_len(), 0);
+    }
+}
@@ -7,23 +7,33 @@ use super::{
     SYNCHRONIZATION_PROTOCOL_VERSION,
 };
 use crate::{
-    consensus::SharedConsensusGraph, pow::ProofOfWorkConfig,
+    consensus::{AccountProof, SharedConsensusGraph},
+    light_protocol::{
+        LightProtocolHandler, LightProvider, LIGHT_PROTOCOL_ID,
+        LIGHT_PROTOCOL_VERSION,
+    },
+    pow::ProofOfWorkConfig,
     sync::synchronization_protocol_handler::ProtocolConfiguration,
     verification::VerificationConfig,
 };
-use cfx_types::H256;
+use cfx_types::{H160, H256};
 use keylib::KeyPair;
 use network::{
     node_table::{NodeEntry, NodeId},
-    Error as NetworkError, NetworkService, PeerInfo, ProtocolId,
+    BannedPeer, Error as NetworkError, NetworkService, PeerInfo, PeerSettings,
+    ProtocolId,
 };
-use primitives::Block;
+use primitives::{receipt::Receipt, Block, EpochNumber};
 use std::sync::Arc;
 
 pub struct SynchronizationService {
     network: NetworkService,
     protocol_handler: Arc<SynchronizationProtocolHandler>,
     protocol: ProtocolId,
+    /// Serves light nodes when running as a full node.
+    light_provider: Arc<LightProvider>,
+    /// Set when running as a light node.
+    light_handler: Option<Arc<LightProtocolHandler>>,
 }
 
 impl SynchronizationService {
@@ -42,10 +52,16 @@ impl SynchronizationService {
             fast_recover,
         ));
 
+        let light_provider = Arc::new(LightProvider::new(
+            sync_handler.get_synchronization_graph(),
+        ));
+
         SynchronizationService {
             network,
             protocol_handler: sync_handler,
             protocol: *b"cfx",
+            light_provider,
+            light_handler: None,
         }
     }
 
@@ -66,9 +82,70 @@ impl SynchronizationService {
             self.protocol,
             &[SYNCHRONIZATION_PROTOCOL_VERSION],
         )?;
+        self.network.register_protocol(
+            self.light_provider.clone(),
+            LIGHT_PROTOCOL_ID,
+            &[LIGHT_PROTOCOL_VERSION],
+        )?;
+        Ok(())
+    }
+
+    /// Start as a light node, which only syncs block headers and queries
+    /// full nodes for everything else.
+    pub fn start_light(&mut self) -> Result<(), Error> {
+        let light_handler = Arc::new(LightProtocolHandler::new(
+            self.get_synchronization_graph(),
+        ));
+        self.network.start()?;
+        self.network.register_protocol(
+            light_handler.clone(),
+            LIGHT_PROTOCOL_ID,
+            &[LIGHT_PROTOCOL_VERSION],
+        )?;
+        self.light_handler = Some(light_handler);
         Ok(())
     }
 
+    pub fn is_light_node(&self) -> bool { self.light_handler.is_some() }
+
+    pub fn light_handler(&self) -> Option<&Arc<LightProtocolHandler>> {
+        self.light_handler.as_ref()
+    }
+
+    pub fn light_account_proof(
+        &self, address: H160, storage_keys: Vec<H256>,
+        epoch_number: EpochNumber,
+    ) -> Result<AccountProof, String>
+    {
+        self.expect_light_handler()?.account_proof(
+            &self.network,
+            address,
+            storage_keys,
+            epoch_number,
+        )
+    }
+
+    pub fn light_epoch_receipts(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<(Vec<H256>, Vec<Arc<Vec<Receipt>>>), String> {
+        self.expect_light_handler()?
+            .epoch_receipts(&self.network, epoch_number)
+    }
+
+    pub fn light_block_by_hash(
+        &self, hash: &H256,
+    ) -> Result<Option<Block>, String> {
+        self.expect_light_handler()?
+            .block_by_hash(&self.network, hash)
+    }
+
+    fn expect_light_handler(&self) -> Result<&LightProtocolHandler, String> {
+        self.light_handler
+            .as_ref()
+            .map(|handler| handler.as_ref())
+            .ok_or_else(|| "Not running as a light node".to_owned())
+    }
+
     pub fn announce_new_blocks(&self, hashes: &[H256]) {
         self.network.with_context(self.protocol, |io| {
             self.protocol_handler.announce_new_blocks(io, hashes);
@@ -103,6 +180,44 @@ impl SynchronizationService {
         self.network.get_peer_info().unwrap()
     }
 
+    pub fn banned_peers(&self) -> Result<Vec<BannedPeer>, NetworkError> {
+        self.network.banned_peers()
+    }
+
+    pub fn clear_bans(&self) -> Result<(), NetworkError> {
+        self.network.clear_bans()
+    }
+
+    pub fn add_reserved_peer(&self, url: &str) -> Result<(), NetworkError> {
+        self.network.add_reserved_peer(url)
+    }
+
+    pub fn remove_reserved_peer(
+        &self, id: &NodeId,
+    ) -> Result<bool, NetworkError> {
+        self.network.remove_reserved_peer(id)
+    }
+
+    pub fn set_ip_filter(&self, filter: &str) -> Result<(), NetworkError> {
+        self.network.set_ip_filter(filter)
+    }
+
+    pub fn set_max_peers(
+        &self, outgoing: u32, incoming: u32,
+    ) -> Result<(), NetworkError> {
+        self.network.set_max_peers(outgoing, incoming)
+    }
+
+    pub fn set_reserved_only(
+        &self, reserved_only: bool,
+    ) -> Result<(), NetworkError> {
+        self.network.set_reserved_only(reserved_only)
+    }
+
+    pub fn peer_settings(&self) -> Result<PeerSettings, NetworkError> {
+        self.network.peer_settings()
+    }
+
     pub fn sign_challenge(
         &self, challenge: Vec<u8>,
     ) -> Result<Vec<u8>, NetworkError> {
@@ -4,8 +4,9 @@
 
 use cfx_types::H256;
 use message::{
-    GetBlockHeaders, GetBlockTxn, GetBlocks, GetCompactBlocks,
-    GetTerminalBlockHashes, GetTransactions, Message, TransIndex,
+    GetBlockHeaders, GetBlockTxn, GetBlocks, GetCompactBlocks, GetReceipts,
+    GetStateChunk, GetTerminalBlockHashes, GetTransactions, Message,
+    TransIndex,
 };
 use network::PeerId;
 //use slab::Slab;
@@ -33,6 +34,8 @@ pub enum RequestMessage {
     BlockTxn(GetBlockTxn),
     Terminals(GetTerminalBlockHashes),
     Transactions(GetTransactions),
+    StateChunk(GetStateChunk),
+    Receipts(GetReceipts),
 }
 
 impl RequestMessage {
@@ -56,6 +59,12 @@ impl RequestMessage {
             RequestMessage::Transactions(ref mut msg) => {
                 msg.set_request_id(request_id)
             }
+            RequestMessage::StateChunk(ref mut msg) => {
+                msg.set_request_id(request_id)
+            }
+            RequestMessage::Receipts(ref mut msg) => {
+                msg.set_request_id(request_id)
+            }
         }
     }
 
@@ -67,6 +76,8 @@ impl RequestMessage {
             RequestMessage::BlockTxn(ref msg) => msg,
             RequestMessage::Terminals(ref msg) => msg,
             RequestMessage::Transactions(ref msg) => msg,
+            RequestMessage::StateChunk(ref msg) => msg,
+            RequestMessage::Receipts(ref msg) => msg,
         }
     }
 }
@@ -0,0 +1,224 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{
+    CallTrace, MemoryDiff, StorageDiff, Tracer, VmExecutedOperation,
+    VmOperation, VmTrace, VmTracer,
+};
+use crate::vm::{ActionParams, Error as VmError};
+use cfx_types::U256;
+
+/// Tracer which records the tree of call and create frames.
+#[derive(Default)]
+pub struct ExecutiveTracer {
+    /// Frames which are not finished yet, the innermost last.
+    frames: Vec<CallTrace>,
+    /// Finished outermost frames.
+    traces: Vec<CallTrace>,
+}
+
+impl ExecutiveTracer {
+    /// Returns the finished outermost frames.
+    pub fn drain(self) -> Vec<CallTrace> { self.traces }
+
+    fn finish(&mut self, gas_left: U256, output: &[u8], error: Option<String>) {
+        let mut call = self
+            .frames
+            .pop()
+            .expect("done_trace is called after prepare_trace; qed");
+        call.gas_used = call.gas.saturating_sub(gas_left);
+        call.output = output.to_vec();
+        call.error = error;
+        match self.frames.last_mut() {
+            Some(parent) => parent.calls.push(call),
+            None => self.traces.push(call),
+        }
+    }
+}
+
+impl Tracer for ExecutiveTracer {
+    fn prepare_trace_call(&mut self, params: &ActionParams) {
+        self.frames.push(CallTrace::new_call(params));
+    }
+
+    fn prepare_trace_create(&mut self, params: &ActionParams) {
+        self.frames.push(CallTrace::new_create(params));
+    }
+
+    fn done_trace(&mut self, gas_left: U256, output: &[u8]) {
+        self.finish(gas_left, output, None);
+    }
+
+    fn done_trace_reverted(&mut self, gas_left: U256, output: &[u8]) {
+        self.finish(gas_left, output, Some(VmError::Reverted.to_string()));
+    }
+
+    fn done_trace_failed(&mut self, error: &VmError) {
+        self.finish(U256::zero(), &[], Some(error.to_string()));
+    }
+}
+
+/// VM tracer which records every executed opcode.
+pub struct ExecutiveVmTracer {
+    /// Frames which are not finished yet, the innermost last. The first one
+    /// is a placeholder holding the outermost frames.
+    frames: Vec<VmTrace>,
+    last_mem_written: Option<(usize, usize)>,
+    last_store_written: Option<(U256, U256)>,
+}
+
+impl ExecutiveVmTracer {
+    /// Create a tracer for the execution of a transaction.
+    pub fn toplevel() -> Self {
+        ExecutiveVmTracer {
+            frames: vec![VmTrace::default()],
+            last_mem_written: None,
+            last_store_written: None,
+        }
+    }
+
+    /// Returns the trace of the outermost frame, or `None` if no code is
+    /// executed.
+    pub fn drain(mut self) -> Option<VmTrace> {
+        self.frames.truncate(1);
+        self.frames
+            .pop()
+            .and_then(|mut toplevel| toplevel.subs.pop())
+    }
+
+    fn current(&mut self) -> &mut VmTrace {
+        self.frames
+            .last_mut()
+            .expect("The toplevel frame is never popped; qed")
+    }
+}
+
+impl VmTracer for ExecutiveVmTracer {
+    fn trace_next_instruction(
+        &mut self, _pc: usize, _instruction: u8, _current_gas: U256,
+    ) -> bool {
+        true
+    }
+
+    fn trace_prepare_execute(
+        &mut self, pc: usize, instruction: u8, gas_cost: U256,
+        mem_written: Option<(usize, usize)>,
+        store_written: Option<(U256, U256)>,
+    )
+    {
+        self.last_mem_written = mem_written;
+        self.last_store_written = store_written;
+        self.current().operations.push(VmOperation {
+            pc,
+            instruction,
+            gas_cost,
+            executed: None,
+        });
+    }
+
+    fn trace_executed(
+        &mut self, gas_left: U256, stack_push: &[U256], mem: &[u8],
+    ) {
+        let mem_diff =
+            self.last_mem_written.take().and_then(|(offset, size)| {
+                let end = offset.checked_add(size)?;
+                mem.get(offset..end).map(|data| MemoryDiff {
+                    offset,
+                    data: data.to_vec(),
+                })
+            });
+        let store_diff = self
+            .last_store_written
+            .take()
+            .map(|(location, value)| StorageDiff { location, value });
+        if let Some(operation) = self.current().operations.last_mut() {
+            operation.executed = Some(VmExecutedOperation {
+                gas_left,
+                stack_push: stack_push.to_vec(),
+                mem_diff,
+                store_diff,
+            });
+        }
+    }
+
+    fn prepare_subtrace(&mut self, code: &[u8]) {
+        let parent_step = self.current().operations.len().saturating_sub(1);
+        self.frames.push(VmTrace {
+            parent_step,
+            code: code.to_vec(),
+            operations: Vec::new(),
+            subs: Vec::new(),
+        });
+    }
+
+    fn done_subtrace(&mut self) {
+        if self.frames.len() > 1 {
+            let sub = self.frames.pop().expect("len > 1; qed");
+            self.current().subs.push(sub);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{Tracer, VmTracer},
+        ExecutiveTracer, ExecutiveVmTracer,
+    };
+    use crate::vm::{ActionParams, CallType, Error as VmError};
+    use cfx_types::U256;
+
+    #[test]
+    fn test_call_tree() {
+        let mut params = ActionParams::default();
+        params.gas = 100.into();
+        params.call_type = CallType::Call;
+
+        let mut tracer = ExecutiveTracer::default();
+        tracer.prepare_trace_call(&params);
+        params.gas = 50.into();
+        tracer.prepare_trace_create(&params);
+        tracer.done_trace_failed(&VmError::OutOfGas);
+        params.gas = 20.into();
+        tracer.prepare_trace_call(&params);
+        tracer.done_trace_reverted(15.into(), &[1]);
+        tracer.done_trace(30.into(), &[2]);
+
+        let traces = tracer.drain();
+        assert_eq!(traces.len(), 1);
+        let call = &traces[0];
+        assert_eq!(call.gas_used, U256::from(70));
+        assert_eq!(call.output, vec![2]);
+        assert_eq!(call.error, None);
+        assert_eq!(call.calls.len(), 2);
+        assert_eq!(call.calls[0].call_type, CallType::None);
+        assert_eq!(call.calls[0].gas_used, U256::from(50));
+        assert_eq!(call.calls[0].error, Some(VmError::OutOfGas.to_string()));
+        assert_eq!(call.calls[1].gas_used, U256::from(5));
+        assert_eq!(call.calls[1].error, Some(VmError::Reverted.to_string()));
+    }
+
+    #[test]
+    fn test_vm_trace() {
+        let mut tracer = ExecutiveVmTracer::toplevel();
+        tracer.prepare_subtrace(&[0x60, 0x00]);
+        tracer.trace_prepare_execute(0, 0x52, 3.into(), Some((0, 2)), None);
+        tracer.trace_executed(97.into(), &[], &[1, 2, 3]);
+        tracer.trace_prepare_execute(1, 0xf1, 40.into(), None, None);
+        tracer.prepare_subtrace(&[0x00]);
+        tracer.done_subtrace();
+        tracer.trace_executed(50.into(), &[1.into()], &[]);
+        tracer.done_subtrace();
+
+        let trace = tracer.drain().unwrap();
+        assert_eq!(trace.code, vec![0x60, 0x00]);
+        assert_eq!(trace.operations.len(), 2);
+        let executed = trace.operations[0].executed.as_ref().unwrap();
+        assert_eq!(executed.mem_diff.as_ref().unwrap().data, vec![1, 2]);
+        assert_eq!(trace.subs.len(), 1);
+        assert_eq!(trace.subs[0].parent_step, 1);
+        let executed = trace.operations[1].executed.as_ref().unwrap();
+        assert_eq!(executed.stack_push, vec![U256::from(1)]);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Tracing of transaction execution.
+//!
+//! A `Tracer` is notified when every call or create frame starts and
+//! finishes, and a `VmTracer` is notified on every executed opcode. The
+//! default execution path uses the no-op tracers, so that tracing costs
+//! nothing unless a transaction is re-executed for debugging.
+
+mod executive_tracer;
+mod noop_tracer;
+mod types;
+
+pub use self::{
+    executive_tracer::{ExecutiveTracer, ExecutiveVmTracer},
+    noop_tracer::{NoopTracer, NoopVmTracer},
+    types::{
+        CallTrace, MemoryDiff, StorageDiff, TransactionTrace,
+        VmExecutedOperation, VmOperation, VmTrace,
+    },
+};
+
+use crate::vm::{ActionParams, Error as VmError};
+use cfx_types::U256;
+
+/// Receives the call and create frames of an execution.
+///
+/// Every `prepare_trace_*` is matched by exactly one `done_trace*` call, and
+/// the frames spawned by a frame are finished before the frame itself.
+pub trait Tracer {
+    /// A message call frame, including a transfer or a builtin call, starts.
+    fn prepare_trace_call(&mut self, params: &ActionParams);
+
+    /// A contract creation frame starts.
+    fn prepare_trace_create(&mut self, params: &ActionParams);
+
+    /// The current frame finishes successfully. `output` is the return data of
+    /// a call, or the code of the created contract.
+    fn done_trace(&mut self, gas_left: U256, output: &[u8]);
+
+    /// The current frame is reverted by `REVERT`.
+    fn done_trace_reverted(&mut self, gas_left: U256, output: &[u8]);
+
+    /// The current frame fails and consumes all its gas.
+    fn done_trace_failed(&mut self, error: &VmError);
+}
+
+/// Receives the opcodes executed by the VM.
+///
+/// The hooks of the VM are forwarded through `vm::Context`. The frames which
+/// run code are wrapped by `prepare_subtrace` and `done_subtrace`.
+pub trait VmTracer {
+    /// Decide if the instruction at `pc` and the following ones should be
+    /// traced.
+    fn trace_next_instruction(
+        &mut self, pc: usize, instruction: u8, current_gas: U256,
+    ) -> bool;
+
+    /// An instruction is about to be executed. `mem_written` is the memory
+    /// range `(offset, size)` and `store_written` is the storage
+    /// `(key, value)` which the instruction writes.
+    fn trace_prepare_execute(
+        &mut self, pc: usize, instruction: u8, gas_cost: U256,
+        mem_written: Option<(usize, usize)>,
+        store_written: Option<(U256, U256)>,
+    );
+
+    /// The last prepared instruction is executed.
+    fn trace_executed(
+        &mut self, gas_left: U256, stack_push: &[U256], mem: &[u8],
+    );
+
+    /// The code of a new frame starts executing.
+    fn prepare_subtrace(&mut self, code: &[u8]);
+
+    /// The frame of the last `prepare_subtrace` finishes.
+    fn done_subtrace(&mut self);
+}
@@ -0,0 +1,50 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{Tracer, VmTracer};
+use crate::vm::{ActionParams, Error as VmError};
+use cfx_types::U256;
+
+/// Tracer which ignores all frames.
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {
+    fn prepare_trace_call(&mut self, _params: &ActionParams) {}
+
+    fn prepare_trace_create(&mut self, _params: &ActionParams) {}
+
+    fn done_trace(&mut self, _gas_left: U256, _output: &[u8]) {}
+
+    fn done_trace_reverted(&mut self, _gas_left: U256, _output: &[u8]) {}
+
+    fn done_trace_failed(&mut self, _error: &VmError) {}
+}
+
+/// VM tracer which stops the VM from tracing at the first instruction.
+pub struct NoopVmTracer;
+
+impl VmTracer for NoopVmTracer {
+    fn trace_next_instruction(
+        &mut self, _pc: usize, _instruction: u8, _current_gas: U256,
+    ) -> bool {
+        false
+    }
+
+    fn trace_prepare_execute(
+        &mut self, _pc: usize, _instruction: u8, _gas_cost: U256,
+        _mem_written: Option<(usize, usize)>,
+        _store_written: Option<(U256, U256)>,
+    )
+    {
+    }
+
+    fn trace_executed(
+        &mut self, _gas_left: U256, _stack_push: &[U256], _mem: &[u8],
+    ) {
+    }
+
+    fn prepare_subtrace(&mut self, _code: &[u8]) {}
+
+    fn done_subtrace(&mut self) {}
+}
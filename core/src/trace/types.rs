@@ -0,0 +1,124 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    bytes::Bytes,
+    vm::{ActionParams, CallType},
+};
+use cfx_types::{Address, H256, U256};
+
+/// A call or create frame, together with the frames it spawned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallTrace {
+    /// The type of the call, or `CallType::None` for a contract creation.
+    pub call_type: CallType,
+    pub from: Address,
+    /// The callee, or the address of the created contract.
+    pub to: Address,
+    pub value: U256,
+    /// Gas provided to the frame.
+    pub gas: U256,
+    /// Call data, or the init code of the created contract.
+    pub input: Bytes,
+    pub gas_used: U256,
+    /// Return data, or the code of the created contract.
+    pub output: Bytes,
+    /// The reason why the frame failed or `None` if it succeeded.
+    pub error: Option<String>,
+    /// Frames spawned by this frame in execution order.
+    pub calls: Vec<CallTrace>,
+}
+
+impl CallTrace {
+    pub fn new_call(params: &ActionParams) -> Self {
+        Self::new(
+            params,
+            params.call_type.clone(),
+            params.data.clone().unwrap_or_default(),
+        )
+    }
+
+    pub fn new_create(params: &ActionParams) -> Self {
+        Self::new(
+            params,
+            CallType::None,
+            params
+                .code
+                .as_ref()
+                .map_or(Vec::new(), |code| code.to_vec()),
+        )
+    }
+
+    fn new(params: &ActionParams, call_type: CallType, input: Bytes) -> Self {
+        CallTrace {
+            call_type,
+            from: params.sender,
+            to: params.address,
+            value: params.value.value(),
+            gas: params.gas,
+            input,
+            gas_used: U256::zero(),
+            output: Bytes::new(),
+            error: None,
+            calls: Vec::new(),
+        }
+    }
+}
+
+/// The opcodes executed in a frame, together with the frames it spawned.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VmTrace {
+    /// The index of the operation in the parent frame which spawned this
+    /// frame.
+    pub parent_step: usize,
+    pub code: Bytes,
+    pub operations: Vec<VmOperation>,
+    /// Frames spawned by this frame in execution order.
+    pub subs: Vec<VmTrace>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmOperation {
+    pub pc: usize,
+    pub instruction: u8,
+    pub gas_cost: U256,
+    /// The result of the instruction, or `None` if it failed.
+    pub executed: Option<VmExecutedOperation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmExecutedOperation {
+    pub gas_left: U256,
+    /// Items pushed onto the stack, the top item last.
+    pub stack_push: Vec<U256>,
+    pub mem_diff: Option<MemoryDiff>,
+    pub store_diff: Option<StorageDiff>,
+}
+
+/// Memory written by an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDiff {
+    pub offset: usize,
+    pub data: Bytes,
+}
+
+/// Storage written by an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageDiff {
+    pub location: U256,
+    pub value: U256,
+}
+
+/// The traces of re-executing a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionTrace {
+    pub transaction_hash: H256,
+    /// The error if the transaction is not executed at all, e.g. because of
+    /// an invalid nonce. Errors inside the VM are in the call trace instead.
+    pub error: Option<String>,
+    /// The outermost frame, or `None` if the transaction is not executed.
+    pub call: Option<CallTrace>,
+    /// Only present if VM tracing is requested.
+    pub vm_trace: Option<VmTrace>,
+}
@@ -14,25 +14,33 @@ pub use self::impls::TreapMap;
 use self::ready::Readiness;
 use crate::{
     cache_manager::{CacheId, CacheManager},
+    db::COL_MISC,
     executive,
+    ext_db::SystemDB,
+    machine::Machine,
+    notifications::{Notification, SharedNotifications},
     pow::WORKER_COMPUTATION_PARALLELISM,
     state::State,
     statedb::StateDb,
     storage::{Storage, StorageManager, StorageManagerTrait},
-    vm,
 };
 use cfx_types::{Address, H256, H512, U256, U512};
 use parking_lot::{Mutex, RwLock};
 use primitives::{
-    Account, Action, EpochId, SignedTransaction, TransactionAddress,
-    TransactionWithSignature,
+    transaction::TransactionError, Account, Action, EpochId, SignedTransaction,
+    TransactionAddress, TransactionWithSignature,
 };
 use rlp::*;
 use std::{
     cmp::{min, Ordering},
-    collections::{hash_map::HashMap, HashSet},
+    collections::{hash_map::HashMap, BTreeSet, HashSet},
     ops::DerefMut,
-    sync::{mpsc::channel, Arc},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        mpsc::channel,
+        Arc,
+    },
 };
 use threadpool::ThreadPool;
 
@@ -42,6 +50,76 @@ pub const DEFAULT_MAX_BLOCK_GAS_LIMIT: u64 = 30_000 * 100_000;
 
 pub const FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET: u32 = 2000;
 
+/// The database key in `COL_MISC` of the local transactions journal.
+const LOCAL_TRANSACTIONS_KEY: &[u8] = b"local_transactions";
+
+pub const DEFAULT_TX_POOL_CAPACITY: usize = 500_000;
+/// A transaction replaces the one of the same sender and nonce only if its
+/// gas price is higher by at least this percentage.
+pub const DEFAULT_MIN_REPLACE_GAS_PRICE_BUMP: u64 = 10;
+pub const DEFAULT_MAX_TRANSACTIONS_PER_SENDER: usize = 1000;
+
+/// Which transactions are evicted to make room for new ones when the pool is
+/// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the transaction with the lowest gas price, and a new
+    /// transaction is rejected if its gas price is not higher.
+    LowestGasPrice,
+    /// Evict the transaction which entered the pool first.
+    Oldest,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowest_gas_price" => Ok(EvictionPolicy::LowestGasPrice),
+            "oldest" => Ok(EvictionPolicy::Oldest),
+            _ => Err(format!("Invalid eviction policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TxPoolConfig {
+    /// The maximum number of transactions in the pool.
+    pub capacity: usize,
+    /// The minimum gas price increase in percent to replace a transaction.
+    pub min_replace_gas_price_bump: u64,
+    /// The maximum number of transactions of a sender in the pool.
+    pub max_txs_per_sender: usize,
+    pub eviction_policy: EvictionPolicy,
+    /// The senders whose transactions are never evicted and are packed
+    /// before others.
+    pub local_senders: Vec<Address>,
+}
+
+impl Default for TxPoolConfig {
+    fn default() -> Self {
+        TxPoolConfig {
+            capacity: DEFAULT_TX_POOL_CAPACITY,
+            min_replace_gas_price_bump: DEFAULT_MIN_REPLACE_GAS_PRICE_BUMP,
+            max_txs_per_sender: DEFAULT_MAX_TRANSACTIONS_PER_SENDER,
+            eviction_policy: EvictionPolicy::LowestGasPrice,
+            local_senders: Vec::new(),
+        }
+    }
+}
+
+impl TxPoolConfig {
+    /// Returns true if `new_tx` is priced high enough to replace `old_tx`.
+    pub fn is_replaceable(
+        &self, old_tx: &SignedTransaction, new_tx: &SignedTransaction,
+    ) -> bool {
+        let bump = U512::from(100 + self.min_replace_gas_price_bump);
+        new_tx.gas_price > old_tx.gas_price
+            && U512::from(new_tx.gas_price) * U512::from(100)
+                >= U512::from(old_tx.gas_price) * bump
+    }
+}
+
 pub struct AccountCache<'storage> {
     pub accounts: HashMap<Address, Account>,
     pub storage: StateDb<'storage>,
@@ -161,6 +239,18 @@ impl NoncePool {
             .and_then(|bucket| bucket.get(nonce))
             .map(|tx| tx.clone())
     }
+
+    /// The number of transactions of the sender.
+    fn count(&self, addr: &Address) -> usize {
+        self.buckets.get(addr).map_or(0, |bucket| bucket.len())
+    }
+
+    /// The transactions of the sender.
+    fn get_all(&self, addr: &Address) -> Vec<Arc<SignedTransaction>> {
+        self.buckets.get(addr).map_or(Vec::new(), |bucket| {
+            bucket.values().map(|tx| tx.clone()).collect()
+        })
+    }
 }
 
 struct PendingTransactionPool {
@@ -289,6 +379,14 @@ impl ReadyTransactionPool {
 pub struct TransactionPoolInner {
     pending_transactions: PendingTransactionPool,
     ready_transactions: ReadyTransactionPool,
+    /// Transactions ordered by which to evict first. The key is the gas price
+    /// (or zero if evicting the oldest ones) and the arrival sequence number.
+    eviction_index: BTreeSet<(U256, u64, H256)>,
+    /// The keys in `eviction_index` of transactions.
+    eviction_keys: HashMap<H256, (U256, u64)>,
+    next_arrival: u64,
+    /// Transactions of these senders are not in `eviction_index`.
+    local_senders: HashSet<Address>,
 }
 
 impl TransactionPoolInner {
@@ -296,6 +394,10 @@ impl TransactionPoolInner {
         TransactionPoolInner {
             pending_transactions: PendingTransactionPool::new(),
             ready_transactions: ReadyTransactionPool::new(),
+            eviction_index: BTreeSet::new(),
+            eviction_keys: HashMap::new(),
+            next_arrival: 0,
+            local_senders: HashSet::new(),
         }
     }
 
@@ -308,10 +410,125 @@ impl TransactionPoolInner {
             .get(tx_hash)
             .or_else(|| self.pending_transactions.get_by_hash(tx_hash))
     }
+
+    fn get_by_nonce(
+        &self, address: &Address, nonce: &U256,
+    ) -> Option<Arc<SignedTransaction>> {
+        self.ready_transactions
+            .get_by_nonce(address, nonce)
+            .or_else(|| self.pending_transactions.get(address, nonce))
+    }
+
+    /// The number of transactions of the sender in the pool.
+    fn sender_count(&self, address: &Address) -> usize {
+        self.ready_transactions.nonce_pool.count(address)
+            + self.pending_transactions.nonce_pool.count(address)
+    }
+
+    /// The transactions of the sender in the pool.
+    fn sender_transactions(
+        &self, address: &Address,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = self.ready_transactions.nonce_pool.get_all(address);
+        txs.extend(self.pending_transactions.nonce_pool.get_all(address));
+        txs
+    }
+
+    fn track(&mut self, tx: &SignedTransaction, policy: EvictionPolicy) {
+        if self.local_senders.contains(&tx.sender) {
+            return;
+        }
+        let price = match policy {
+            EvictionPolicy::LowestGasPrice => tx.gas_price,
+            EvictionPolicy::Oldest => U256::zero(),
+        };
+        let key = (price, self.next_arrival);
+        self.next_arrival += 1;
+        if let Some((price, arrival)) =
+            self.eviction_keys.insert(tx.hash(), key)
+        {
+            self.eviction_index.remove(&(price, arrival, tx.hash()));
+        }
+        self.eviction_index.insert((key.0, key.1, tx.hash()));
+    }
+
+    fn untrack(&mut self, tx_hash: &H256) {
+        if let Some((price, arrival)) = self.eviction_keys.remove(tx_hash) {
+            self.eviction_index.remove(&(price, arrival, *tx_hash));
+        }
+    }
+
+    /// Exempt the transactions of the sender from eviction. Returns false
+    /// if the sender is already local.
+    fn add_local_sender(&mut self, address: Address) -> bool {
+        if !self.local_senders.insert(address) {
+            return false;
+        }
+        for tx in self.sender_transactions(&address) {
+            self.untrack(&tx.hash());
+        }
+        true
+    }
+
+    /// Make the transactions of the sender evictable again. Returns false if
+    /// the sender is not local.
+    fn remove_local_sender(
+        &mut self, address: &Address, policy: EvictionPolicy,
+    ) -> bool {
+        if !self.local_senders.remove(address) {
+            return false;
+        }
+        for tx in self.sender_transactions(address) {
+            self.track(&tx, policy);
+        }
+        true
+    }
+
+    /// Remove the transaction from the pool. The ready transactions of the
+    /// same sender with higher nonces are moved back to the pending pool, as
+    /// they can not be packed until the nonce gap is filled.
+    fn remove(&mut self, tx_hash: &H256) -> Option<Arc<SignedTransaction>> {
+        self.untrack(tx_hash);
+        if let Some(tx) = self.ready_transactions.remove(tx_hash) {
+            let mut nonce = tx.nonce + 1;
+            while let Some(next) =
+                self.ready_transactions.get_by_nonce(&tx.sender, &nonce)
+            {
+                self.ready_transactions.remove(&next.hash());
+                self.pending_transactions.insert(next);
+                nonce += 1.into();
+            }
+            return Some(tx);
+        }
+        let tx = self.pending_transactions.get_by_hash(tx_hash)?;
+        self.pending_transactions.remove(&tx.sender, &tx.nonce)
+    }
+
+    /// The gas price used to order the next transaction to evict.
+    fn lowest_eviction_price(&self) -> Option<U256> {
+        self.eviction_index
+            .iter()
+            .next()
+            .map(|(price, _, _)| *price)
+    }
+
+    /// Remove the first transaction in the eviction order from the pool. The
+    /// later nonces of its sender are demoted like in `remove`.
+    fn evict(&mut self) -> Option<Arc<SignedTransaction>> {
+        while let Some(key) = self.eviction_index.iter().next().cloned() {
+            let (_, _, tx_hash) = key;
+            self.eviction_index.remove(&key);
+            self.eviction_keys.remove(&tx_hash);
+            if let Some(tx) = self.remove(&tx_hash) {
+                return Some(tx);
+            }
+        }
+        None
+    }
 }
 
 pub struct TransactionPool {
-    capacity: usize,
+    config: TxPoolConfig,
     inner: RwLock<TransactionPoolInner>,
     storage_manager: Arc<StorageManager>,
     pub transaction_pubkey_cache: RwLock<HashMap<H256, Arc<SignedTransaction>>>,
@@ -319,35 +536,58 @@ pub struct TransactionPool {
         Mutex<HashMap<H256, HashSet<TransactionAddress>>>,
     pub worker_pool: Arc<Mutex<ThreadPool>>,
     cache_man: Arc<Mutex<CacheManager<CacheId>>>,
-    spec: vm::Spec,
+    machine: Arc<Machine>,
+    notifications: SharedNotifications,
+    /// Hashes of the transactions submitted to this node through RPC, which
+    /// are journaled to the database so that they survive restarts.
+    local_transactions: Mutex<HashSet<H256>>,
+    /// Whether local transactions are added since the last journal flush.
+    local_transactions_dirty: AtomicBool,
+    db: Arc<SystemDB>,
+    /// The best epoch number of the consensus graph, which selects the spec
+    /// to verify transactions with.
+    best_epoch_number: AtomicUsize,
 }
 
 pub type SharedTransactionPool = Arc<TransactionPool>;
 
 impl TransactionPool {
-    pub fn with_capacity(
-        capacity: usize, storage_manager: Arc<StorageManager>,
-        worker_pool: Arc<Mutex<ThreadPool>>,
-        cache_man: Arc<Mutex<CacheManager<CacheId>>>,
+    pub fn new(
+        config: TxPoolConfig, storage_manager: Arc<StorageManager>,
+        db: Arc<SystemDB>, worker_pool: Arc<Mutex<ThreadPool>>,
+        cache_man: Arc<Mutex<CacheManager<CacheId>>>, machine: Arc<Machine>,
+        notifications: SharedNotifications,
     ) -> Self
     {
+        // TODO Cache capacity should be set seperately
+        let transaction_pubkey_cache =
+            RwLock::new(HashMap::with_capacity(config.capacity));
+        let mut inner = TransactionPoolInner::new();
+        inner.local_senders.extend(config.local_senders.iter());
         TransactionPool {
-            capacity,
-            inner: RwLock::new(TransactionPoolInner::new()),
+            config,
+            inner: RwLock::new(inner),
             storage_manager,
-            // TODO Cache capacity should be set seperately
-            transaction_pubkey_cache: RwLock::new(HashMap::with_capacity(
-                capacity,
-            )),
+            transaction_pubkey_cache,
             unexecuted_transaction_addresses: Mutex::new(HashMap::new()),
             worker_pool,
             cache_man,
-            spec: vm::Spec::new_spec(),
+            machine,
+            notifications,
+            local_transactions: Mutex::new(HashSet::new()),
+            local_transactions_dirty: AtomicBool::new(false),
+            db,
+            best_epoch_number: AtomicUsize::new(0),
         }
     }
 
     pub fn len(&self) -> usize { self.inner.read().len() }
 
+    pub fn set_best_epoch_number(&self, best_epoch_number: usize) {
+        self.best_epoch_number
+            .store(best_epoch_number, AtomicOrdering::Relaxed);
+    }
+
     pub fn get_transaction(
         &self, tx_hash: &H256,
     ) -> Option<Arc<SignedTransaction>> {
@@ -511,6 +751,13 @@ impl TransactionPool {
             }
         }
 
+        if !passed_transaction.is_empty() {
+            self.notifications
+                .notify(Notification::NewPendingTransactions(
+                    passed_transaction,
+                ));
+        }
+
         transactions
             .iter()
             .map(|tx| {
@@ -523,6 +770,93 @@ impl TransactionPool {
             .collect()
     }
 
+    /// Insert transactions submitted to this node, and journal them so that
+    /// they are restored after restarts. The journal is written by the next
+    /// `flush_local_transactions`.
+    pub fn insert_new_local_transactions(
+        &self, latest_epoch: EpochId,
+        transactions: &Vec<TransactionWithSignature>,
+    ) -> Vec<Result<H256, String>>
+    {
+        let results = self.insert_new_transactions(latest_epoch, transactions);
+        self.local_transactions
+            .lock()
+            .extend(results.iter().filter_map(|r| r.as_ref().ok()));
+        self.local_transactions_dirty
+            .store(true, AtomicOrdering::Relaxed);
+        results
+    }
+
+    /// Write the local transactions which are still in the pool to the
+    /// journal, if any of them is added or has left the pool since the last
+    /// flush. It's called periodically so that sending transactions does not
+    /// rewrite the journal every time.
+    pub fn flush_local_transactions(&self) {
+        // The journal is written without holding the pool lock, and the lock
+        // of local transactions keeps the flushes in order.
+        let mut local_transactions = self.local_transactions.lock();
+        let transactions: Vec<_> = {
+            let inner = self.inner.read();
+            let len = local_transactions.len();
+            local_transactions.retain(|hash| inner.get(hash).is_some());
+            let dirty = self
+                .local_transactions_dirty
+                .swap(false, AtomicOrdering::Relaxed);
+            if !dirty && local_transactions.len() == len {
+                return;
+            }
+            local_transactions
+                .iter()
+                .filter_map(|hash| inner.get(hash))
+                .collect()
+        };
+
+        let mut rlp_stream = RlpStream::new_list(transactions.len());
+        for tx in &transactions {
+            rlp_stream.append::<TransactionWithSignature>(&**tx);
+        }
+        let mut dbops = self.db.key_value().transaction();
+        dbops.put(COL_MISC, LOCAL_TRANSACTIONS_KEY, &rlp_stream.drain());
+        self.db.key_value().write(dbops).expect("db error");
+    }
+
+    /// Re-verify and insert the journaled local transactions. Returns the
+    /// number of restored transactions.
+    pub fn restore_local_transactions(&self, latest_epoch: EpochId) -> usize {
+        let journal = match self
+            .db
+            .key_value()
+            .get(COL_MISC, LOCAL_TRANSACTIONS_KEY)
+            .expect(
+                "Low-level database error when fetching local transactions. \
+                 Some issue with disk?",
+            )
+        {
+            Some(journal) => journal,
+            None => return 0,
+        };
+        let transactions: Vec<TransactionWithSignature> =
+            match Rlp::new(&journal).as_list() {
+                Ok(transactions) => transactions,
+                Err(e) => {
+                    warn!("Failed to decode local transactions: {:?}", e);
+                    return 0;
+                }
+            };
+
+        let restored = self
+            .insert_new_local_transactions(latest_epoch, &transactions)
+            .iter()
+            .filter(|r| r.is_ok())
+            .count();
+        info!(
+            "Restored {} of {} local transactions",
+            restored,
+            transactions.len()
+        );
+        restored
+    }
+
     // verify transactions based on the rules that
     // have nothing to do with readiness
     pub fn verify_transaction(
@@ -542,11 +876,32 @@ impl TransactionPool {
             ));
         }
 
+        // check transaction chain id against replay from other chains
+        let chain_id = self.machine.params().chain_id;
+        if transaction.chain_id != chain_id {
+            warn!(
+                "Transaction {:?} discarded due to chain id mismatch: {} != {}",
+                transaction.hash(),
+                transaction.chain_id,
+                chain_id
+            );
+            return Err(format!(
+                "{:?}",
+                TransactionError::ChainIdMismatch {
+                    expected: chain_id,
+                    got: transaction.chain_id,
+                }
+            ));
+        }
+
         // check transaction intrinsic gas
+        let spec = self
+            .machine
+            .spec(self.best_epoch_number.load(AtomicOrdering::Relaxed) as u64);
         let tx_intrinsic_gas = executive::Executive::gas_required_for(
             transaction.action == Action::Create,
             &transaction.data,
-            &self.spec,
+            &spec,
         );
         if transaction.gas < (tx_intrinsic_gas as usize).into() {
             debug!(
@@ -602,12 +957,51 @@ impl TransactionPool {
         let mut inner = self.inner.write();
         let inner = inner.deref_mut();
 
-        if self.capacity <= inner.len() {
-            warn!("Transaction discarded due to insufficient txpool capacity: {:?}", transaction.hash());
-            return Err(format!("Transaction discarded due to insufficient txpool capacity: {:?}", transaction.hash()));
+        let readiness = account_cache.is_ready(&transaction);
+        let replaced = match readiness {
+            Readiness::Ready | Readiness::Future => {
+                self.make_room_without_lock(inner, &transaction)?
+            }
+            _ => None,
+        };
+
+        // The replaced transaction may be ready while `transaction` is not,
+        // so it is dropped first instead of being overwritten in place.
+        if let Some(ref replaced) = replaced {
+            inner.remove(&replaced.hash());
         }
 
-        match account_cache.is_ready(&transaction) {
+        let result = self.add_by_readiness_without_lock(
+            inner,
+            account_cache,
+            readiness,
+            transaction.clone(),
+        );
+        if result.is_ok() {
+            inner.track(&transaction, self.config.eviction_policy);
+        } else if let Some(replaced) = replaced {
+            if self
+                .add_by_readiness_without_lock(
+                    inner,
+                    account_cache,
+                    readiness,
+                    replaced.clone(),
+                )
+                .is_ok()
+            {
+                inner.track(&replaced, self.config.eviction_policy);
+            }
+        }
+        result
+    }
+
+    fn add_by_readiness_without_lock(
+        &self, inner: &mut TransactionPoolInner,
+        account_cache: &mut AccountCache, readiness: Readiness,
+        transaction: Arc<SignedTransaction>,
+    ) -> Result<(), String>
+    {
+        match readiness {
             Readiness::Ready => {
                 let account =
                     account_cache.accounts.get_mut(&transaction.sender);
@@ -689,6 +1083,82 @@ impl TransactionPool {
         }
     }
 
+    /// Check if `transaction` is allowed into the pool and evict a
+    /// transaction if the pool is full. Returns the transaction of the same
+    /// sender and nonce which `transaction` replaces.
+    fn make_room_without_lock(
+        &self, inner: &mut TransactionPoolInner,
+        transaction: &SignedTransaction,
+    ) -> Result<Option<Arc<SignedTransaction>>, String>
+    {
+        let sender = &transaction.sender;
+        if let Some(old_tx) = inner.get_by_nonce(sender, &transaction.nonce) {
+            if old_tx.hash() == transaction.hash() {
+                return Err(format!("{:?}", TransactionError::AlreadyImported));
+            }
+            if !self.config.is_replaceable(&old_tx, transaction) {
+                debug!(
+                    "Transaction {:?} discarded due to gas price {} too low \
+                     to replace {:?}",
+                    transaction.hash(),
+                    transaction.gas_price,
+                    old_tx.hash()
+                );
+                return Err(format!(
+                    "{:?}",
+                    TransactionError::TooCheapToReplace
+                ));
+            }
+            return Ok(Some(old_tx));
+        }
+
+        if inner.sender_count(sender) >= self.config.max_txs_per_sender {
+            debug!(
+                "Transaction {:?} discarded due to too many transactions \
+                 of sender {:?}",
+                transaction.hash(),
+                sender
+            );
+            return Err(format!("{:?}", TransactionError::LimitReached));
+        }
+
+        let is_local = inner.local_senders.contains(sender);
+        while inner.len() >= self.config.capacity {
+            if !is_local
+                && self.config.eviction_policy == EvictionPolicy::LowestGasPrice
+                && inner
+                    .lowest_eviction_price()
+                    .map_or(true, |price| transaction.gas_price <= price)
+            {
+                warn!(
+                    "Transaction discarded due to insufficient txpool \
+                     capacity: {:?}",
+                    transaction.hash()
+                );
+                return Err(format!("{:?}", TransactionError::LimitReached));
+            }
+            match inner.evict() {
+                Some(evicted) => debug!(
+                    "Transaction {:?} evicted for {:?}",
+                    evicted.hash(),
+                    transaction.hash()
+                ),
+                None => {
+                    warn!(
+                        "Transaction discarded due to insufficient txpool \
+                         capacity: {:?}",
+                        transaction.hash()
+                    );
+                    return Err(format!(
+                        "{:?}",
+                        TransactionError::LimitReached
+                    ));
+                }
+            }
+        }
+        Ok(None)
+    }
+
     pub fn add_ready(&self, transaction: Arc<SignedTransaction>) -> bool {
         let mut inner = self.inner.write();
         let inner = inner.deref_mut();
@@ -752,6 +1222,7 @@ impl TransactionPool {
     ) -> Option<Arc<SignedTransaction>>
     {
         let hash = transaction.hash();
+        inner.untrack(&hash);
         inner.ready_transactions.remove(&hash)
     }
 
@@ -773,9 +1244,13 @@ impl TransactionPool {
         transaction: &SignedTransaction,
     ) -> Option<Arc<SignedTransaction>>
     {
-        inner
+        let removed = inner
             .pending_transactions
-            .remove(&transaction.sender, &transaction.nonce)
+            .remove(&transaction.sender, &transaction.nonce);
+        if let Some(ref tx) = removed {
+            inner.untrack(&tx.hash());
+        }
+        removed
     }
 
     /// pack at most num_txs transactions randomly
@@ -800,7 +1275,42 @@ impl TransactionPool {
 
         let mut big_tx_resample_times_limit = 10;
 
-        'out: while let Some(tx) = inner.ready_transactions.pop() {
+        // Transactions of local senders are packed first, in nonce order.
+        let local_senders: Vec<Address> =
+            inner.local_senders.iter().cloned().collect();
+        for sender in local_senders {
+            let nonce = match state.nonce(&sender) {
+                Ok(nonce) => nonce_map.entry(sender).or_insert(nonce),
+                Err(_) => continue,
+            };
+            while packed_transactions.len() < num_txs {
+                let tx =
+                    match inner.ready_transactions.get_by_nonce(&sender, nonce)
+                    {
+                        Some(tx) => tx,
+                        None => break,
+                    };
+                let tx_size = tx.rlp_size();
+                if block_gas_limit - total_tx_gas_limit < *tx.gas_limit()
+                    || block_size_limit - total_tx_size < tx_size
+                {
+                    break;
+                }
+                inner.ready_transactions.remove(&tx.hash());
+
+                total_tx_gas_limit += *tx.gas_limit();
+                total_tx_size += tx_size;
+
+                *nonce += 1.into();
+                packed_transactions.push(tx);
+            }
+        }
+
+        'out: while packed_transactions.len() < num_txs {
+            let tx = match inner.ready_transactions.pop() {
+                Some(tx) => tx,
+                None => break,
+            };
             let sender = tx.sender;
             let nonce_entry = nonce_map.entry(sender);
 
@@ -821,7 +1331,10 @@ impl TransactionPool {
                     .entry(sender)
                     .or_insert(HashMap::new())
                     .insert(tx.nonce, tx);
-            } else if tx.nonce == *nonce {
+            } else if tx.nonce < *nonce {
+                // The transaction is already executed and dropped.
+                inner.untrack(&tx.hash());
+            } else {
                 let tx_size = tx.rlp_size();
                 if block_gas_limit - total_tx_gas_limit < *tx.gas_limit()
                     || block_size_limit - total_tx_size < tx_size
@@ -997,14 +1510,79 @@ impl TransactionPool {
 
         (ready_txs, pending_txs)
     }
+
+    /// Drop the transaction from the pool. Returns the dropped transaction.
+    pub fn remove_transaction(
+        &self, tx_hash: &H256,
+    ) -> Option<Arc<SignedTransaction>> {
+        let removed = self.inner.write().remove(tx_hash);
+        if removed.is_some() {
+            self.flush_local_transactions();
+        }
+        removed
+    }
+
+    /// Drop all transactions of the sender from the pool. Returns the number
+    /// of dropped transactions.
+    pub fn remove_sender_transactions(&self, address: &Address) -> usize {
+        let removed = {
+            let mut inner = self.inner.write();
+            let txs = inner.sender_transactions(address);
+            txs.iter()
+                .filter(|tx| inner.remove(&tx.hash()).is_some())
+                .count()
+        };
+        if removed > 0 {
+            self.flush_local_transactions();
+        }
+        removed
+    }
+
+    /// Mark the sender as local, so that its transactions are never evicted
+    /// and are packed first. Returns false if it is already local.
+    pub fn add_local_sender(&self, address: Address) -> bool {
+        self.inner.write().add_local_sender(address)
+    }
+
+    /// Returns false if the sender is not local.
+    pub fn remove_local_sender(&self, address: &Address) -> bool {
+        self.inner
+            .write()
+            .remove_local_sender(address, self.config.eviction_policy)
+    }
+
+    pub fn local_senders(&self) -> Vec<Address> {
+        self.inner.read().local_senders.iter().cloned().collect()
+    }
 }
 
 #[cfg(test)]
 mod tests2 {
+    use super::{
+        AccountCache, EvictionPolicy, TransactionPool, TxPoolConfig,
+        DEFAULT_MAX_BLOCK_GAS_LIMIT,
+    };
+    use crate::{
+        cache_manager::CacheManager,
+        machine::new_machine,
+        notifications::Notifications,
+        storage::{
+            tests::{
+                new_in_memory_system_db_for_testing,
+                new_storage_configuration_for_testing,
+            },
+            StorageManager, StorageManagerTrait,
+        },
+    };
     use cfx_types::{Address, U256};
     use keylib::{Generator, KeyPair, Random};
-    use primitives::{Action, SignedTransaction, Transaction};
-    use std::sync::Arc;
+    use parking_lot::Mutex;
+    use primitives::{
+        transaction::TransactionError, Action, EpochId, SignedTransaction,
+        Transaction,
+    };
+    use std::{collections::HashMap, sync::Arc};
+    use threadpool::ThreadPool;
 
     fn new_test_tx(
         sender: &KeyPair, nonce: usize, gas_price: usize, value: usize,
@@ -1017,11 +1595,57 @@ mod tests2 {
                 action: Action::Call(Address::random()),
                 value: U256::from(value),
                 data: Vec::new(),
+                chain_id: 0,
             }
             .sign(sender.secret()),
         )
     }
 
+    const TEST_BALANCE: u64 = 1_000_000_000;
+
+    /// A pool over a genesis state where every sender has `TEST_BALANCE`.
+    fn new_test_pool(
+        config: TxPoolConfig, senders: &[&KeyPair],
+    ) -> (TransactionPool, EpochId) {
+        let db = new_in_memory_system_db_for_testing();
+        let storage_manager = Arc::new(StorageManager::new(
+            db.clone(),
+            new_storage_configuration_for_testing(),
+        ));
+        let accounts: HashMap<_, _> = senders
+            .iter()
+            .map(|sender| (sender.address(), U256::from(TEST_BALANCE)))
+            .collect();
+        let genesis = storage_manager.initialize(
+            accounts,
+            DEFAULT_MAX_BLOCK_GAS_LIMIT.into(),
+            Address::default(),
+        );
+        let pool = TransactionPool::new(
+            config,
+            storage_manager,
+            db,
+            Arc::new(Mutex::new(ThreadPool::new(1))),
+            Arc::new(Mutex::new(CacheManager::new(1 << 20, 1 << 21, 1 << 10))),
+            Arc::new(new_machine()),
+            Arc::new(Notifications::new()),
+        );
+        (pool, genesis.hash())
+    }
+
+    /// A new pool over the database and states of `pool`, as after a restart.
+    fn restart_test_pool(pool: &TransactionPool) -> TransactionPool {
+        TransactionPool::new(
+            TxPoolConfig::default(),
+            pool.storage_manager.clone(),
+            pool.db.clone(),
+            Arc::new(Mutex::new(ThreadPool::new(1))),
+            Arc::new(Mutex::new(CacheManager::new(1 << 20, 1 << 21, 1 << 10))),
+            Arc::new(new_machine()),
+            Arc::new(Notifications::new()),
+        )
+    }
+
     #[test]
     fn test_nonce_pool_new_added() {
         let mut nonce_pool = super::NoncePool::new();
@@ -1165,4 +1789,298 @@ mod tests2 {
         assert_eq!(pool.get(&tx.hash()), None);
         assert_eq!(pool.get(&tx3.hash()), Some(tx3.clone()));
     }
+
+    #[test]
+    fn test_replace_gas_price_bump() {
+        let sender = Random.generate().unwrap();
+        let tx = new_test_tx(&sender, 5, 100, 100);
+
+        let mut config = super::TxPoolConfig::default();
+        assert_eq!(config.min_replace_gas_price_bump, 10);
+        let too_cheap = new_test_tx(&sender, 5, 109, 100);
+        assert!(!config.is_replaceable(&tx, &too_cheap));
+        assert!(config.is_replaceable(&tx, &new_test_tx(&sender, 5, 110, 100)));
+
+        config.min_replace_gas_price_bump = 0;
+        let same_price = new_test_tx(&sender, 5, 100, 100);
+        assert!(!config.is_replaceable(&tx, &same_price));
+        assert!(config.is_replaceable(&tx, &new_test_tx(&sender, 5, 101, 100)));
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        for policy in &[
+            super::EvictionPolicy::LowestGasPrice,
+            super::EvictionPolicy::Oldest,
+        ] {
+            let mut inner = super::TransactionPoolInner::new();
+            let sender = Random.generate().unwrap();
+            let txs: Vec<_> = [5, 3, 8]
+                .iter()
+                .enumerate()
+                .map(|(nonce, price)| new_test_tx(&sender, nonce, *price, 100))
+                .collect();
+            for tx in &txs {
+                assert!(inner.ready_transactions.insert(tx.clone()));
+                inner.track(tx, *policy);
+            }
+            // An untracked transaction is not evicted.
+            let pending_tx = new_test_tx(&sender, 10, 1, 100);
+            assert!(inner.pending_transactions.insert(pending_tx.clone()));
+            assert_eq!(inner.len(), 4);
+
+            let expected = match policy {
+                super::EvictionPolicy::LowestGasPrice => [1, 0, 2],
+                super::EvictionPolicy::Oldest => [0, 1, 2],
+            };
+            for index in expected.iter() {
+                assert_eq!(inner.evict(), Some(txs[*index].clone()));
+            }
+            assert_eq!(inner.evict(), None);
+            assert_eq!(inner.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_local_sender_not_evicted() {
+        let policy = super::EvictionPolicy::LowestGasPrice;
+        let mut inner = super::TransactionPoolInner::new();
+        let local = Random.generate().unwrap();
+        let remote = Random.generate().unwrap();
+        let local_tx = new_test_tx(&local, 0, 1, 100);
+        let remote_tx = new_test_tx(&remote, 0, 5, 100);
+        for tx in &[local_tx.clone(), remote_tx.clone()] {
+            assert!(inner.ready_transactions.insert(tx.clone()));
+            inner.track(tx, policy);
+        }
+
+        assert!(inner.add_local_sender(local.address()));
+        assert!(!inner.add_local_sender(local.address()));
+        assert_eq!(inner.evict(), Some(remote_tx));
+        assert_eq!(inner.evict(), None);
+
+        assert!(inner.remove_local_sender(&local.address(), policy));
+        assert_eq!(inner.evict(), Some(local_tx));
+    }
+
+    #[test]
+    fn test_remove_demotes_later_nonces() {
+        let mut inner = super::TransactionPoolInner::new();
+        let sender = Random.generate().unwrap();
+        let txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&sender, nonce, 1, 100))
+            .collect();
+        for tx in &txs {
+            assert!(inner.ready_transactions.insert(tx.clone()));
+        }
+
+        assert_eq!(inner.remove(&txs[1].hash()), Some(txs[1].clone()));
+        assert_eq!(inner.remove(&txs[1].hash()), None);
+        assert_eq!(inner.ready_transactions.len(), 1);
+        assert_eq!(inner.pending_transactions.len(), 1);
+        assert_eq!(inner.get(&txs[2].hash()), Some(txs[2].clone()));
+        assert_eq!(inner.sender_transactions(&sender.address()).len(), 2);
+    }
+
+    #[test]
+    fn test_add_with_readiness_replacement() {
+        let sender = Random.generate().unwrap();
+        let (pool, genesis) =
+            new_test_pool(TxPoolConfig::default(), &[&sender]);
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        let tx0 = new_test_tx(&sender, 0, 10, 100);
+        let tx1 = new_test_tx(&sender, 1, 10, 100);
+        assert!(pool
+            .add_with_readiness(&mut account_cache, tx0.clone())
+            .is_ok());
+        assert!(pool
+            .add_with_readiness(&mut account_cache, tx1.clone())
+            .is_ok());
+        assert_eq!(pool.stats(), (2, 0));
+
+        // The gas price bump is required to replace a transaction.
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        let cheap = new_test_tx(&sender, 0, 10, 200);
+        assert_eq!(
+            pool.add_with_readiness(&mut account_cache, cheap),
+            Err("TooCheapToReplace".into())
+        );
+        let bumped = new_test_tx(&sender, 0, 11, 100);
+        assert!(pool
+            .add_with_readiness(&mut account_cache, bumped.clone())
+            .is_ok());
+        assert_eq!(pool.stats(), (2, 0));
+        assert_eq!(pool.get_transaction(&tx0.hash()), None);
+        assert!(!pool.inner.read().eviction_keys.contains_key(&tx0.hash()));
+
+        // A replacement the sender can not afford is pending, and the ready
+        // transaction it replaces is dropped with the later nonces demoted.
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        let unaffordable = new_test_tx(&sender, 0, 100_000, 100);
+        assert!(pool
+            .add_with_readiness(&mut account_cache, unaffordable.clone())
+            .is_ok());
+        assert_eq!(pool.stats(), (0, 2));
+        assert_eq!(pool.get_transaction(&bumped.hash()), None);
+        assert_eq!(pool.get_transaction(&tx1.hash()), Some(tx1));
+        let inner = pool.inner.read();
+        assert!(!inner.eviction_keys.contains_key(&bumped.hash()));
+        assert!(inner.eviction_keys.contains_key(&unaffordable.hash()));
+    }
+
+    #[test]
+    fn test_add_with_readiness_sender_quota() {
+        let sender = Random.generate().unwrap();
+        let mut config = TxPoolConfig::default();
+        config.max_txs_per_sender = 2;
+        let (pool, genesis) = new_test_pool(config, &[&sender]);
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        for nonce in 0..2 {
+            let tx = new_test_tx(&sender, nonce, 10, 100);
+            assert!(pool.add_with_readiness(&mut account_cache, tx).is_ok());
+        }
+        let tx = new_test_tx(&sender, 2, 10, 100);
+        assert_eq!(
+            pool.add_with_readiness(&mut account_cache, tx),
+            Err("LimitReached".into())
+        );
+
+        // Replacing a transaction does not count against the quota.
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        let tx = new_test_tx(&sender, 0, 20, 100);
+        assert!(pool.add_with_readiness(&mut account_cache, tx).is_ok());
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_add_with_readiness_eviction() {
+        let senders: Vec<_> =
+            (0..4).map(|_| Random.generate().unwrap()).collect();
+        let mut config = TxPoolConfig::default();
+        config.capacity = 3;
+        config.eviction_policy = EvictionPolicy::LowestGasPrice;
+        let (pool, genesis) =
+            new_test_pool(config, &senders.iter().collect::<Vec<_>>());
+        let state = pool.storage_manager.get_state_at(genesis).unwrap();
+        let mut account_cache = AccountCache::new(state);
+        let a0 = new_test_tx(&senders[0], 0, 1, 100);
+        let a1 = new_test_tx(&senders[0], 1, 5, 100);
+        let b0 = new_test_tx(&senders[1], 0, 3, 100);
+        for tx in &[a0.clone(), a1.clone(), b0.clone()] {
+            assert!(pool
+                .add_with_readiness(&mut account_cache, tx.clone())
+                .is_ok());
+        }
+
+        // The cheapest transaction is evicted and the later nonce of its
+        // sender can no longer be packed.
+        let c0 = new_test_tx(&senders[2], 0, 4, 100);
+        assert!(pool.add_with_readiness(&mut account_cache, c0).is_ok());
+        assert_eq!(pool.get_transaction(&a0.hash()), None);
+        assert_eq!(pool.get_transaction(&a1.hash()), Some(a1));
+        assert_eq!(pool.stats(), (2, 1));
+
+        // A transaction not pricier than all the others is rejected.
+        let d0 = new_test_tx(&senders[3], 0, 3, 100);
+        assert_eq!(
+            pool.add_with_readiness(&mut account_cache, d0),
+            Err("LimitReached".into())
+        );
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.get_transaction(&b0.hash()), Some(b0));
+    }
+
+    #[test]
+    fn test_chain_id_mismatch_rejected() {
+        let sender = Random.generate().unwrap();
+        let (pool, genesis) =
+            new_test_pool(TxPoolConfig::default(), &[&sender]);
+        let chain_id = pool.machine.params().chain_id;
+        let new_tx = |chain_id| {
+            Transaction {
+                nonce: U256::zero(),
+                gas_price: U256::from(10),
+                gas: U256::from(50000),
+                action: Action::Call(Address::random()),
+                value: U256::from(100),
+                data: Vec::new(),
+                chain_id,
+            }
+            .sign(sender.secret())
+            .transaction
+        };
+
+        let other_chain_tx = new_tx(chain_id + 1);
+        assert_eq!(
+            pool.insert_new_transactions(genesis, &vec![other_chain_tx]),
+            vec![Err(format!(
+                "{:?}",
+                TransactionError::ChainIdMismatch {
+                    expected: chain_id,
+                    got: chain_id + 1,
+                }
+            ))]
+        );
+        assert_eq!(pool.len(), 0);
+
+        let tx = new_tx(chain_id);
+        assert_eq!(
+            pool.insert_new_transactions(genesis, &vec![tx.clone()]),
+            vec![Ok(tx.hash())]
+        );
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_local_transactions_journal_round_trip() {
+        let sender = Random.generate().unwrap();
+        let (pool, genesis) =
+            new_test_pool(TxPoolConfig::default(), &[&sender]);
+        let chain_id = pool.machine.params().chain_id;
+        let new_tx = |nonce: usize| {
+            Transaction {
+                nonce: U256::from(nonce),
+                gas_price: U256::from(10),
+                gas: U256::from(50000),
+                action: Action::Call(Address::random()),
+                value: U256::from(100),
+                data: Vec::new(),
+                chain_id,
+            }
+            .sign(sender.secret())
+            .transaction
+        };
+        let tx0 = new_tx(0);
+        let tx1 = new_tx(1);
+        assert_eq!(
+            pool.insert_new_local_transactions(
+                genesis,
+                &vec![tx0.clone(), tx1.clone()]
+            ),
+            vec![Ok(tx0.hash()), Ok(tx1.hash())]
+        );
+
+        // Nothing is journaled until the journal is flushed.
+        assert_eq!(
+            restart_test_pool(&pool).restore_local_transactions(genesis),
+            0
+        );
+        pool.flush_local_transactions();
+        let restarted = restart_test_pool(&pool);
+        assert_eq!(restarted.restore_local_transactions(genesis), 2);
+        assert!(restarted.get_transaction(&tx0.hash()).is_some());
+        assert!(restarted.get_transaction(&tx1.hash()).is_some());
+
+        // Transactions which left the pool are pruned from the journal.
+        assert!(pool.remove_transaction(&tx1.hash()).is_some());
+        let restarted = restart_test_pool(&pool);
+        assert_eq!(restarted.restore_local_transactions(genesis), 1);
+        assert!(restarted.get_transaction(&tx0.hash()).is_some());
+        assert!(restarted.get_transaction(&tx1.hash()).is_none());
+    }
 }
@@ -105,6 +105,7 @@ fn next_signed_transaction(rng: &mut ChaChaRng) -> SignedTransaction {
             value: next_u256(rng),
             action: Action::Call(0.into()),
             data: vec![],
+            chain_id: 0,
         }
         .with_signature(Signature::default()),
     )
@@ -0,0 +1,97 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{Message, MsgId, RequestId};
+use cfx_types::H256;
+use primitives::SignedTransaction;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::ops::{Deref, DerefMut};
+
+#[derive(Debug, PartialEq)]
+pub struct GetBlockTransactions {
+    pub request_id: RequestId,
+    pub hash: H256,
+}
+
+impl Message for GetBlockTransactions {
+    fn msg_id(&self) -> MsgId { MsgId::GET_BLOCK_TRANSACTIONS }
+}
+
+impl Deref for GetBlockTransactions {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetBlockTransactions {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetBlockTransactions {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(2)
+            .append(&self.request_id)
+            .append(&self.hash);
+    }
+}
+
+impl Decodable for GetBlockTransactions {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetBlockTransactions {
+            request_id: rlp.val_at(0)?,
+            hash: rlp.val_at(1)?,
+        })
+    }
+}
+
+/// The transactions of a block along with their senders, which are committed
+/// to by the transactions root of the block header.
+#[derive(Debug, PartialEq, Default)]
+pub struct GetBlockTransactionsResponse {
+    pub request_id: RequestId,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+impl Message for GetBlockTransactionsResponse {
+    fn msg_id(&self) -> MsgId { MsgId::GET_BLOCK_TRANSACTIONS_RESPONSE }
+
+    fn is_size_sensitive(&self) -> bool { self.transactions.len() > 1 }
+}
+
+impl Deref for GetBlockTransactionsResponse {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetBlockTransactionsResponse {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetBlockTransactionsResponse {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(2)
+            .append(&self.request_id)
+            .append_list(&self.transactions);
+    }
+}
+
+impl Decodable for GetBlockTransactionsResponse {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetBlockTransactionsResponse {
+            request_id: rlp.val_at(0)?,
+            transactions: rlp.list_at(1)?,
+        })
+    }
+}
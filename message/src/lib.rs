@@ -10,6 +10,7 @@ extern crate rlp;
 mod blockbodies;
 mod blockheaders;
 mod blocks;
+mod blocktransactions;
 mod blocktxn;
 mod cmpctblocks;
 mod getblockbodies;
@@ -22,6 +23,9 @@ mod getterminalblockhashes;
 mod message;
 mod newblock;
 mod newblockhashes;
+mod receipts;
+mod statechunk;
+mod stateproof;
 mod status;
 mod terminalblockhashes;
 mod transactions;
@@ -30,6 +34,7 @@ pub use crate::{
     blockbodies::GetBlockBodiesResponse,
     blockheaders::GetBlockHeadersResponse,
     blocks::{GetBlocksResponse, GetBlocksWithPublicResponse},
+    blocktransactions::{GetBlockTransactions, GetBlockTransactionsResponse},
     blocktxn::GetBlockTxnResponse,
     cmpctblocks::GetCompactBlocksResponse,
     getblockbodies::GetBlockBodies,
@@ -42,6 +47,9 @@ pub use crate::{
     message::{Message, MsgId, RequestId},
     newblock::NewBlock,
     newblockhashes::NewBlockHashes,
+    receipts::{GetReceipts, GetReceiptsResponse},
+    statechunk::{GetStateChunk, GetStateChunkResponse},
+    stateproof::{GetStateProof, GetStateProofResponse, StorageProof},
     status::Status,
     terminalblockhashes::GetTerminalBlockHashesResponse,
     transactions::{
@@ -42,6 +42,18 @@ build_msgid! {
     TRANSACTION_DIGESTS = 0x14
     GET_TRANSACTIONS = 0x15
     GET_TRANSACTIONS_RESPONSE = 0x16
+
+    // Messages of the light protocol, which full nodes serve to light nodes.
+    GET_STATE_PROOF = 0x17
+    GET_STATE_PROOF_RESPONSE = 0x18
+    GET_RECEIPTS = 0x19
+    GET_RECEIPTS_RESPONSE = 0x1a
+    GET_BLOCK_TRANSACTIONS = 0x1b
+    GET_BLOCK_TRANSACTIONS_RESPONSE = 0x1c
+
+    // Messages to sync the state at a trusted checkpoint.
+    GET_STATE_CHUNK = 0x1d
+    GET_STATE_CHUNK_RESPONSE = 0x1e
 }
 
 impl From<u8> for MsgId {
@@ -0,0 +1,110 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{Message, MsgId, RequestId};
+use cfx_types::H256;
+use primitives::receipt::Receipt;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::ops::{Deref, DerefMut};
+
+/// Request the receipts of all blocks in the epoch whose pivot block is
+/// `epoch_hash`.
+#[derive(Debug, PartialEq)]
+pub struct GetReceipts {
+    pub request_id: RequestId,
+    pub epoch_hash: H256,
+}
+
+impl Message for GetReceipts {
+    fn msg_id(&self) -> MsgId { MsgId::GET_RECEIPTS }
+}
+
+impl Deref for GetReceipts {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetReceipts {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetReceipts {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(2)
+            .append(&self.request_id)
+            .append(&self.epoch_hash);
+    }
+}
+
+impl Decodable for GetReceipts {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetReceipts {
+            request_id: rlp.val_at(0)?,
+            epoch_hash: rlp.val_at(1)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct GetReceiptsResponse {
+    pub request_id: RequestId,
+    /// The blocks of the epoch in execution order.
+    pub block_hashes: Vec<H256>,
+    /// The receipts of each block in `block_hashes`.
+    pub receipts: Vec<Vec<Receipt>>,
+}
+
+impl Message for GetReceiptsResponse {
+    fn msg_id(&self) -> MsgId { MsgId::GET_RECEIPTS_RESPONSE }
+
+    fn is_size_sensitive(&self) -> bool { self.receipts.len() > 1 }
+}
+
+impl Deref for GetReceiptsResponse {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetReceiptsResponse {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetReceiptsResponse {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(3)
+            .append(&self.request_id)
+            .append_list(&self.block_hashes)
+            .begin_list(self.receipts.len());
+        for block_receipts in &self.receipts {
+            stream.append_list(block_receipts);
+        }
+    }
+}
+
+impl Decodable for GetReceiptsResponse {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let receipts = rlp
+            .at(2)?
+            .iter()
+            .map(|block_receipts| block_receipts.as_list())
+            .collect::<Result<_, _>>()?;
+        Ok(GetReceiptsResponse {
+            request_id: rlp.val_at(0)?,
+            block_hashes: rlp.list_at(1)?,
+            receipts,
+        })
+    }
+}
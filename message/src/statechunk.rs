@@ -0,0 +1,114 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{Message, MsgId, RequestId};
+use cfx_bytes::Bytes;
+use cfx_types::H256;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::ops::{Deref, DerefMut};
+
+/// Request a chunk of the key-values of the state of the epoch whose pivot
+/// block is `epoch_hash`, used to sync the state at a checkpoint.
+#[derive(Debug, PartialEq)]
+pub struct GetStateChunk {
+    pub request_id: RequestId,
+    pub epoch_hash: H256,
+    pub chunk_index: u64,
+}
+
+impl Message for GetStateChunk {
+    fn msg_id(&self) -> MsgId { MsgId::GET_STATE_CHUNK }
+}
+
+impl Deref for GetStateChunk {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetStateChunk {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetStateChunk {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(3)
+            .append(&self.request_id)
+            .append(&self.epoch_hash)
+            .append(&self.chunk_index);
+    }
+}
+
+impl Decodable for GetStateChunk {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetStateChunk {
+            request_id: rlp.val_at(0)?,
+            epoch_hash: rlp.val_at(1)?,
+            chunk_index: rlp.val_at(2)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct GetStateChunkResponse {
+    pub request_id: RequestId,
+    /// The hashes of all chunks of the state in order, which are the keccak
+    /// of the rlp of `key_values` of each chunk.
+    pub chunk_hashes: Vec<H256>,
+    /// The storage access keys and raw values in the requested chunk.
+    pub key_values: Vec<(Bytes, Bytes)>,
+}
+
+impl Message for GetStateChunkResponse {
+    fn msg_id(&self) -> MsgId { MsgId::GET_STATE_CHUNK_RESPONSE }
+
+    fn is_size_sensitive(&self) -> bool { !self.key_values.is_empty() }
+}
+
+impl Deref for GetStateChunkResponse {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetStateChunkResponse {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetStateChunkResponse {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(3)
+            .append(&self.request_id)
+            .append_list(&self.chunk_hashes)
+            .begin_list(self.key_values.len());
+        for (key, value) in &self.key_values {
+            stream.begin_list(2).append(key).append(value);
+        }
+    }
+}
+
+impl Decodable for GetStateChunkResponse {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let key_values = rlp
+            .at(2)?
+            .iter()
+            .map(|key_value| Ok((key_value.val_at(0)?, key_value.val_at(1)?)))
+            .collect::<Result<_, DecoderError>>()?;
+        Ok(GetStateChunkResponse {
+            request_id: rlp.val_at(0)?,
+            chunk_hashes: rlp.list_at(1)?,
+            key_values,
+        })
+    }
+}
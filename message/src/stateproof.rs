@@ -0,0 +1,142 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{Message, MsgId, RequestId};
+use cfx_bytes::Bytes;
+use cfx_types::{H160, H256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::ops::{Deref, DerefMut};
+
+/// Request the proof of an account and its storage entries in the state of
+/// the epoch whose pivot block is `epoch_hash`.
+#[derive(Debug, PartialEq)]
+pub struct GetStateProof {
+    pub request_id: RequestId,
+    pub epoch_hash: H256,
+    pub address: H160,
+    pub storage_keys: Vec<H256>,
+}
+
+impl Message for GetStateProof {
+    fn msg_id(&self) -> MsgId { MsgId::GET_STATE_PROOF }
+}
+
+impl Deref for GetStateProof {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetStateProof {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetStateProof {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(4)
+            .append(&self.request_id)
+            .append(&self.epoch_hash)
+            .append(&self.address)
+            .append_list(&self.storage_keys);
+    }
+}
+
+impl Decodable for GetStateProof {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetStateProof {
+            request_id: rlp.val_at(0)?,
+            epoch_hash: rlp.val_at(1)?,
+            address: rlp.val_at(2)?,
+            storage_keys: rlp.list_at(3)?,
+        })
+    }
+}
+
+/// The value and proof of a storage entry. The proof is the rlp of the trie
+/// nodes on the path of the key.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StorageProof {
+    pub key: H256,
+    pub value: Option<Bytes>,
+    pub proof: Bytes,
+}
+
+impl Encodable for StorageProof {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(3)
+            .append(&self.key)
+            .append(&self.value)
+            .append(&self.proof);
+    }
+}
+
+impl Decodable for StorageProof {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(StorageProof {
+            key: rlp.val_at(0)?,
+            value: rlp.val_at(1)?,
+            proof: rlp.val_at(2)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct GetStateProofResponse {
+    pub request_id: RequestId,
+    /// The rlp of the account, or None if the account doesn't exist.
+    pub account: Option<Bytes>,
+    /// The rlp of the trie nodes on the path of the account key.
+    pub account_proof: Bytes,
+    pub storage_proofs: Vec<StorageProof>,
+}
+
+impl Message for GetStateProofResponse {
+    fn msg_id(&self) -> MsgId { MsgId::GET_STATE_PROOF_RESPONSE }
+}
+
+impl Deref for GetStateProofResponse {
+    type Target = RequestId;
+
+    fn deref(&self) -> &Self::Target { &self.request_id }
+}
+
+impl DerefMut for GetStateProofResponse {
+    fn deref_mut(&mut self) -> &mut RequestId { &mut self.request_id }
+}
+
+impl Encodable for GetStateProofResponse {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(4)
+            .append(&self.request_id)
+            .append(&self.account)
+            .append(&self.account_proof)
+            .append_list(&self.storage_proofs);
+    }
+}
+
+impl Decodable for GetStateProofResponse {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetStateProofResponse {
+            request_id: rlp.val_at(0)?,
+            account: rlp.val_at(1)?,
+            account_proof: rlp.val_at(2)?,
+            storage_proofs: rlp.list_at(3)?,
+        })
+    }
+}
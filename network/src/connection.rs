@@ -3,6 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
+    encryption::{FrameCipher, SEAL_OVERHEAD},
     io::{IoContext, StreamToken},
     throttling::THROTTLING_SERVICE,
 };
@@ -56,6 +57,29 @@ pub trait PacketSizer {
     fn packet_size(_: &Bytes) -> usize;
 }
 
+/// A frame in the send queue.
+struct SendItem {
+    data: Vec<u8>,
+    /// The number of bytes already written.
+    pos: usize,
+    /// Whether the frame is to be encrypted. Frames are encrypted right
+    /// before they are written, so that they are encrypted in the order they
+    /// are sent, regardless of priorities.
+    seal: bool,
+}
+
+impl SendItem {
+    /// The number of bytes to write.
+    fn remaining(&self) -> usize {
+        let len = if self.seal {
+            self.data.len() + SEAL_OVERHEAD
+        } else {
+            self.data.len()
+        };
+        len.saturating_sub(self.pos)
+    }
+}
+
 /// This information is to measure the congestion situation of network.
 #[allow(dead_code)]
 pub struct SendQueueStatus {
@@ -66,9 +90,12 @@ pub struct GenericConnection<Socket: GenericSocket, Sizer: PacketSizer> {
     token: StreamToken,
     socket: Socket,
     recv_buf: Bytes,
-    send_queue: PrioritySendQueue<(Vec<u8>, usize)>,
+    send_queue: PrioritySendQueue<SendItem>,
     interest: Ready,
     registered: AtomicBool,
+    /// Ciphers of sent and received frames once the session is encrypted.
+    egress_cipher: Option<FrameCipher>,
+    ingress_cipher: Option<FrameCipher>,
     phantom: PhantomData<Sizer>,
 }
 
@@ -77,10 +104,9 @@ impl<Socket: GenericSocket, Sizer: PacketSizer> Drop
 {
     fn drop(&mut self) {
         let mut service = THROTTLING_SERVICE.write();
-        while let Some(((packet, pos), priority)) = self.send_queue.pop_front()
-        {
-            if pos < packet.len() {
-                service.on_dequeue(packet.len() - pos);
+        while let Some((item, priority)) = self.send_queue.pop_front() {
+            if item.remaining() > 0 {
+                service.on_dequeue(item.remaining());
                 if priority == SendQueuePriority::High {
                     decr_high_priority_packets();
                 }
@@ -92,7 +118,7 @@ impl<Socket: GenericSocket, Sizer: PacketSizer> Drop
 impl<Socket: GenericSocket, Sizer: PacketSizer>
     GenericConnection<Socket, Sizer>
 {
-    pub fn readable(&mut self) -> io::Result<Option<Bytes>> {
+    pub fn readable(&mut self) -> Result<Option<Bytes>, Error> {
         let mut buf: [u8; 1024] = [0; 1024];
         loop {
             match self.socket.read(&mut buf) {
@@ -110,7 +136,7 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
                 Err(e) => {
                     if e.kind() != io::ErrorKind::WouldBlock {
                         debug!("Failed to read socket data, token = {}, err = {:?}", self.token, e);
-                        return Err(e);
+                        return Err(e.into());
                     }
                     break;
                 }
@@ -122,7 +148,11 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
             Ok(None)
         } else {
             trace!("Packet received, token = {}, size = {}", self.token, size);
-            Ok(Some(self.recv_buf.split_to(size)))
+            let packet = self.recv_buf.split_to(size);
+            match self.ingress_cipher {
+                Some(ref mut cipher) => Ok(Some(cipher.open(&packet)?.into())),
+                None => Ok(Some(packet)),
+            }
         }
     }
 
@@ -147,8 +177,14 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
             }
             None => return Ok(WriteStatus::Complete),
         };
-        let len = buf.0.len();
-        let pos = buf.1;
+        if buf.seal {
+            if let Some(ref mut cipher) = self.egress_cipher {
+                cipher.seal(&mut buf.data)?;
+            }
+            buf.seal = false;
+        }
+        let len = buf.data.len();
+        let pos = buf.pos;
         if pos >= len {
             error!(
                 "Unexpected connection data, token = {}, len = {}, pos = {}",
@@ -157,7 +193,7 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
             return Ok(WriteStatus::Complete);
         }
 
-        let size = self.socket.write(&buf.0[pos..])?;
+        let size = self.socket.write(&buf.data[pos..])?;
 
         trace!(
             "Succeed to send socket data, token = {}, size = {}",
@@ -167,7 +203,7 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
         THROTTLING_SERVICE.write().on_dequeue(size);
 
         if pos + size < len {
-            buf.1 += size;
+            buf.pos += size;
             Ok(WriteStatus::Ongoing)
         } else {
             trace!("Packet sent, token = {}, size = {}", self.token, len);
@@ -205,9 +241,13 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
                 self.token,
                 data.len()
             );
-            THROTTLING_SERVICE.write().on_enqueue(data.len())?;
-            let message = data.to_vec();
-            self.send_queue.push_back((message, 0), priority);
+            let item = SendItem {
+                data: data.to_vec(),
+                pos: 0,
+                seal: self.egress_cipher.is_some(),
+            };
+            THROTTLING_SERVICE.write().on_enqueue(item.remaining())?;
+            self.send_queue.push_back(item, priority);
             if priority == SendQueuePriority::High {
                 incr_high_priority_packets();
             }
@@ -223,6 +263,18 @@ impl<Socket: GenericSocket, Sizer: PacketSizer>
     }
 
     pub fn is_sending(&self) -> bool { self.interest.is_writable() }
+
+    /// Encrypt the frames sent and require the received frames to be
+    /// encrypted from now on. Frames already in the send queue are sent
+    /// unencrypted.
+    pub fn enable_encryption(
+        &mut self, egress_cipher: FrameCipher, ingress_cipher: FrameCipher,
+    ) {
+        self.egress_cipher = Some(egress_cipher);
+        self.ingress_cipher = Some(ingress_cipher);
+    }
+
+    pub fn is_encrypted(&self) -> bool { self.egress_cipher.is_some() }
 }
 
 pub type Connection<Sizer> = GenericConnection<TcpStream, Sizer>;
@@ -236,6 +288,8 @@ impl<Sizer: PacketSizer> Connection<Sizer> {
             send_queue: PrioritySendQueue::new(),
             interest: Ready::hup() | Ready::readable(),
             registered: AtomicBool::new(false),
+            egress_cipher: None,
+            ingress_cipher: None,
             phantom: PhantomData,
         }
     }
@@ -405,6 +459,8 @@ mod tests {
                 recv_buf: Bytes::new(),
                 interest: Ready::hup() | Ready::readable(),
                 registered: AtomicBool::new(false),
+                egress_cipher: None,
+                ingress_cipher: None,
                 phantom: PhantomData,
             }
         }
@@ -426,7 +482,11 @@ mod tests {
     fn connection_write_is_buffered() {
         let mut connection = TestConnection::new();
         connection.socket = TestSocket::with_buf(1024);
-        let data = (vec![0; 10240], 0);
+        let data = SendItem {
+            data: vec![0; 10240],
+            pos: 0,
+            seal: false,
+        };
         connection
             .send_queue
             .push_back(data, SendQueuePriority::High);
@@ -88,6 +88,10 @@ impl Discovery {
         }
     }
 
+    pub fn set_ip_filter(&mut self, ip_filter: IpFilter) {
+        self.ip_filter = ip_filter;
+    }
+
     fn is_allowed(&self, entry: &NodeEntry) -> bool {
         entry.endpoint.is_allowed(&self.ip_filter) && entry.id != self.id
     }
@@ -0,0 +1,285 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Encryption of session frames.
+//!
+//! Each side of a session generates an ephemeral key pair and a nonce, and
+//! sends them in its HELLO packet, which is signed by the node key. Once both
+//! HELLO packets are exchanged, the ECDH shared secret of the ephemeral keys
+//! is used to derive a pair of AES-256-CTR keys and HMAC-SHA256 keys, one for
+//! each direction. Every later frame is encrypted and carries a MAC over its
+//! header, ciphertext and sequence number.
+//!
+//! Peers which do not send the handshake in HELLO are legacy peers, and the
+//! session with them stays unencrypted unless encryption is required.
+
+use crate::{connection::MAX_PAYLOAD_SIZE, hash::keccak, Error, ErrorKind};
+use bytes::BufMut;
+use cfx_types::H256;
+use keylib::{crypto::ecdh, Generator, KeyPair, Public, Random};
+use parity_crypto::{aes::AesCtr256, hmac};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::cmp::min;
+
+/// The latest frame encryption version. Version 0 means no encryption.
+pub const ENCRYPTION_VERSION: u8 = 1;
+/// The packet id of encrypted frames.
+pub const PACKET_SEALED: u8 = 0x81;
+const MAC_LENGTH: usize = 32;
+/// The number of bytes encryption adds to a frame.
+pub const SEAL_OVERHEAD: usize = 1 + MAC_LENGTH;
+
+/// The handshake parameters sent in HELLO packets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handshake {
+    pub version: u8,
+    pub public: Public,
+    pub nonce: H256,
+}
+
+impl Encodable for Handshake {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.version)
+            .append(&self.public)
+            .append(&self.nonce);
+    }
+}
+
+impl Decodable for Handshake {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(Handshake {
+            version: rlp.val_at(0)?,
+            public: rlp.val_at(1)?,
+            nonce: rlp.val_at(2)?,
+        })
+    }
+}
+
+/// The local side of the handshake of a session.
+pub struct LocalHandshake {
+    keys: KeyPair,
+    nonce: H256,
+}
+
+impl LocalHandshake {
+    pub fn new() -> Result<Self, Error> {
+        Ok(LocalHandshake {
+            keys: Random.generate()?,
+            nonce: H256::random(),
+        })
+    }
+
+    pub fn handshake(&self) -> Handshake {
+        Handshake {
+            version: ENCRYPTION_VERSION,
+            public: *self.keys.public(),
+            nonce: self.nonce,
+        }
+    }
+
+    /// The encryption version both sides support, or `None` if the session
+    /// is not encrypted.
+    pub fn negotiate(&self, remote: &Handshake) -> Option<u8> {
+        match min(ENCRYPTION_VERSION, remote.version) {
+            0 => None,
+            version => Some(version),
+        }
+    }
+
+    /// Derive the egress and ingress ciphers from the handshake of the
+    /// remote peer.
+    pub fn agree(
+        &self, remote: &Handshake,
+    ) -> Result<(FrameCipher, FrameCipher), Error> {
+        if remote.nonce == self.nonce {
+            bail!(ErrorKind::Auth);
+        }
+        let shared = ecdh::agree(self.keys.secret(), &remote.public)?;
+        let egress = FrameCipher::new(&shared[..], &self.nonce, &remote.nonce)?;
+        let ingress =
+            FrameCipher::new(&shared[..], &remote.nonce, &self.nonce)?;
+        Ok((egress, ingress))
+    }
+}
+
+/// Encrypts or decrypts the frames in one direction of a session.
+///
+/// A plain frame is `size (3 bytes) | packet id | payload`, and a sealed frame
+/// is `size (3 bytes) | PACKET_SEALED | encrypted packet id and payload | MAC`.
+pub struct FrameCipher {
+    cipher: AesCtr256,
+    mac_key: H256,
+    /// The number of frames sealed or opened, which is covered by the MAC so
+    /// that frames can not be replayed or reordered.
+    sequence: u64,
+}
+
+impl FrameCipher {
+    /// `sender_nonce` is the nonce of the side which seals the frames.
+    fn new(
+        shared: &[u8], sender_nonce: &H256, receiver_nonce: &H256,
+    ) -> Result<Self, Error> {
+        let mut material = Vec::with_capacity(32 * 3);
+        material.extend_from_slice(shared);
+        material.extend_from_slice(&sender_nonce[..]);
+        material.extend_from_slice(&receiver_nonce[..]);
+        let secret = keccak(&material);
+
+        // The keys are unique for each session and direction, so a zero IV
+        // is safe.
+        let aes_key = keccak(&[&secret[..], b"aes"].concat());
+        let cipher = AesCtr256::new(&aes_key[..], &[0u8; 16])
+            .map_err(|_| Error::from(ErrorKind::Auth))?;
+        Ok(FrameCipher {
+            cipher,
+            mac_key: keccak(&[&secret[..], b"mac"].concat()),
+            sequence: 0,
+        })
+    }
+
+    fn mac(&self, data: &[u8]) -> Vec<u8> {
+        let mut message = Vec::with_capacity(8 + data.len());
+        message.put_u64_be(self.sequence);
+        message.extend_from_slice(data);
+        let key = hmac::SigKey::sha256(&self.mac_key[..]);
+        hmac::sign(&key, &message).to_vec()
+    }
+
+    /// Encrypt a plain frame in place.
+    pub fn seal(&mut self, frame: &mut Vec<u8>) -> Result<(), Error> {
+        if frame.len() <= 3 {
+            bail!(ErrorKind::BadProtocol);
+        }
+        let mut body = frame.split_off(3);
+        self.cipher
+            .encrypt(&mut body)
+            .map_err(|_| Error::from(ErrorKind::Auth))?;
+
+        let size = 1 + body.len() + MAC_LENGTH;
+        if size > MAX_PAYLOAD_SIZE {
+            bail!(ErrorKind::OversizedPacket);
+        }
+        frame.clear();
+        frame.reserve(3 + size);
+        frame.put_uint_le(size as u64, 3);
+        frame.push(PACKET_SEALED);
+        frame.extend_from_slice(&body);
+        let mac = self.mac(&frame[..]);
+        frame.extend_from_slice(&mac);
+        self.sequence += 1;
+        Ok(())
+    }
+
+    /// Verify and decrypt a sealed frame into a plain frame.
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        if frame.len() <= 4 + MAC_LENGTH || frame[3] != PACKET_SEALED {
+            debug!("Unencrypted frame on an encrypted session");
+            bail!(ErrorKind::BadProtocol);
+        }
+        let (data, mac) = frame.split_at(frame.len() - MAC_LENGTH);
+        let mut message = Vec::with_capacity(8 + data.len());
+        message.put_u64_be(self.sequence);
+        message.extend_from_slice(data);
+        let key = hmac::VerifyKey::sha256(&self.mac_key[..]);
+        if !hmac::verify(&key, &message, mac) {
+            debug!("Frame MAC mismatch, sequence = {}", self.sequence);
+            bail!(ErrorKind::Auth);
+        }
+
+        let mut body = data[4..].to_vec();
+        self.cipher
+            .decrypt(&mut body)
+            .map_err(|_| Error::from(ErrorKind::Auth))?;
+        self.sequence += 1;
+
+        let mut plain = Vec::with_capacity(3 + body.len());
+        plain.put_uint_le(body.len() as u64, 3);
+        plain.extend_from_slice(&body);
+        Ok(plain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type CipherPair = (FrameCipher, FrameCipher);
+
+    fn cipher_pairs() -> (CipherPair, CipherPair) {
+        let a = LocalHandshake::new().unwrap();
+        let b = LocalHandshake::new().unwrap();
+        assert_eq!(a.negotiate(&b.handshake()), Some(ENCRYPTION_VERSION));
+        (
+            a.agree(&b.handshake()).unwrap(),
+            b.agree(&a.handshake()).unwrap(),
+        )
+    }
+
+    fn plain_frame(packet_id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.put_uint_le(payload.len() as u64 + 1, 3);
+        frame.push(packet_id);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_handshake_rlp() {
+        let handshake = LocalHandshake::new().unwrap().handshake();
+        let encoded = rlp::encode(&handshake);
+        assert_eq!(rlp::decode::<Handshake>(&encoded).unwrap(), handshake);
+    }
+
+    #[test]
+    fn test_negotiate_legacy_version() {
+        let local = LocalHandshake::new().unwrap();
+        let mut remote = LocalHandshake::new().unwrap().handshake();
+        remote.version = 0;
+        assert_eq!(local.negotiate(&remote), None);
+        remote.version = ENCRYPTION_VERSION + 1;
+        assert_eq!(local.negotiate(&remote), Some(ENCRYPTION_VERSION));
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let ((mut a_egress, mut a_ingress), (mut b_egress, mut b_ingress)) =
+            cipher_pairs();
+        for i in 0..3u8 {
+            let frame = plain_frame(0x10, &[i; 100]);
+            let mut sealed = frame.clone();
+            a_egress.seal(&mut sealed).unwrap();
+            assert_eq!(sealed.len(), frame.len() + SEAL_OVERHEAD);
+            assert_eq!(sealed[3], PACKET_SEALED);
+            assert!(sealed.windows(100).all(|w| w != &frame[4..]));
+            assert_eq!(b_ingress.open(&sealed).unwrap(), frame);
+        }
+
+        let frame = plain_frame(0x02, &[]);
+        let mut sealed = frame.clone();
+        b_egress.seal(&mut sealed).unwrap();
+        assert_eq!(a_ingress.open(&sealed).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_open_rejects_tampered_frames() {
+        let ((mut egress, _), (_, mut ingress)) = cipher_pairs();
+        let frame = plain_frame(0x10, b"payload");
+
+        let mut tampered = frame.clone();
+        egress.seal(&mut tampered).unwrap();
+        tampered[5] ^= 1;
+        assert!(ingress.open(&tampered).is_err());
+
+        // Plain frames are rejected.
+        assert!(ingress.open(&frame).is_err());
+
+        // Replayed frames are rejected, as the sequence number changed.
+        let ((mut egress, _), (_, mut ingress)) = cipher_pairs();
+        let mut sealed = frame.clone();
+        egress.seal(&mut sealed).unwrap();
+        assert!(ingress.open(&sealed).is_ok());
+        assert!(ingress.open(&sealed).is_err());
+    }
+}
@@ -5,6 +5,7 @@
 use crate::io::IoError;
 use keylib;
 use rlp;
+use snappy;
 use std::{fmt, io, net};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -14,6 +15,10 @@ pub enum DisconnectReason {
     WrongEndpointInfo,
     IpLimited,
     UpdateNodeIdFailed,
+    EncryptionRequired,
+    Banned,
+    TooManyPeers,
+    NotReserved,
     Unknown,
 }
 
@@ -25,6 +30,10 @@ impl DisconnectReason {
             2 => DisconnectReason::WrongEndpointInfo,
             3 => DisconnectReason::IpLimited,
             4 => DisconnectReason::UpdateNodeIdFailed,
+            5 => DisconnectReason::EncryptionRequired,
+            6 => DisconnectReason::Banned,
+            7 => DisconnectReason::TooManyPeers,
+            8 => DisconnectReason::NotReserved,
             _ => DisconnectReason::Unknown,
         }
     }
@@ -38,6 +47,10 @@ impl fmt::Display for DisconnectReason {
             DisconnectReason::WrongEndpointInfo => "wrong node id",
             DisconnectReason::IpLimited => "IP limited",
             DisconnectReason::UpdateNodeIdFailed => "Update node id failed",
+            DisconnectReason::EncryptionRequired => "encryption required",
+            DisconnectReason::Banned => "banned",
+            DisconnectReason::TooManyPeers => "too many peers",
+            DisconnectReason::NotReserved => "not a reserved peer",
             DisconnectReason::Unknown => "unknown",
         };
 
@@ -142,6 +155,10 @@ impl From<rlp::DecoderError> for Error {
     fn from(_err: rlp::DecoderError) -> Self { ErrorKind::Decoder.into() }
 }
 
+impl From<snappy::InvalidInput> for Error {
+    fn from(_err: snappy::InvalidInput) -> Self { ErrorKind::Decoder.into() }
+}
+
 impl From<keylib::Error> for Error {
     fn from(_err: keylib::Error) -> Self { ErrorKind::Auth.into() }
 }
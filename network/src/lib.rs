@@ -23,6 +23,8 @@ extern crate igd;
 extern crate keccak_hash as hash;
 extern crate keylib;
 extern crate libc;
+extern crate parity_crypto;
+extern crate parity_snappy as snappy;
 extern crate parity_path;
 extern crate rand;
 #[macro_use]
@@ -38,10 +40,13 @@ pub type PeerId = usize;
 
 mod connection;
 mod discovery;
+mod encryption;
 mod error;
 mod ip_utils;
 mod node_database;
 pub mod node_table;
+mod peer_reputation;
+mod peer_settings;
 mod service;
 mod session;
 mod session_manager;
@@ -49,12 +54,15 @@ pub mod throttling;
 
 pub use crate::{
     error::{DisconnectReason, Error, ErrorKind, ThrottlingReason},
+    peer_reputation::{BannedPeer, Misbehavior},
+    peer_settings::PeerSettings,
     service::NetworkService,
 };
 pub use io::TimerToken;
 
 use crate::{
     node_table::NodeId,
+    peer_reputation::DEFAULT_BAN_DURATION,
     service::{
         DEFAULT_CONNECTION_LIFETIME_FOR_PROMOTION,
         DEFAULT_DISCOVERY_REFRESH_TIMEOUT, DEFAULT_DISCOVERY_ROUND_TIMEOUT,
@@ -68,6 +76,7 @@ use priority_send_queue::SendQueuePriority;
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use std::{
     cmp::Ordering,
+    fmt,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     str::{self, FromStr},
     sync::Arc,
@@ -117,6 +126,12 @@ pub struct NetworkConfiguration {
     pub test_mode: bool,
     /// Maximum number of P2P nodes per IP address.
     pub nodes_per_ip: usize,
+    /// Disconnect peers which do not support session encryption.
+    pub require_encryption: bool,
+    /// Compress large user packets sent to peers supporting compression.
+    pub compression_enabled: bool,
+    /// Duration of the ban of misbehaving peers
+    pub ban_duration: Duration,
 }
 
 impl Default for NetworkConfiguration {
@@ -149,6 +164,9 @@ impl NetworkConfiguration {
                 DEFAULT_CONNECTION_LIFETIME_FOR_PROMOTION,
             test_mode: false,
             nodes_per_ip: 1,
+            require_encryption: false,
+            compression_enabled: true,
+            ban_duration: DEFAULT_BAN_DURATION,
         }
     }
 
@@ -222,6 +240,10 @@ pub trait NetworkContext {
 
     fn disconnect_peer(&self, peer: PeerId);
 
+    /// Penalize the peer for its misbehavior, which may disconnect and ban
+    /// the peer.
+    fn report_peer(&self, peer: PeerId, misbehavior: Misbehavior);
+
     /// Register a new IO timer. 'IoHandler::timeout' will be called with the
     /// token.
     fn register_timer(
@@ -332,6 +354,26 @@ impl IpFilter {
     }
 }
 
+impl fmt::Display for IpFilter {
+    /// Format the filter in the form accepted by `IpFilter::parse`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let predefined = match self.predefined {
+            AllowIP::All => "all",
+            AllowIP::Private => "private",
+            AllowIP::Public => "public",
+            AllowIP::None => "none",
+        };
+        write!(f, "{}", predefined)?;
+        for network in &self.custom_allow {
+            write!(f, " {}", network)?;
+        }
+        for network in &self.custom_block {
+            write!(f, " -{}", network)?;
+        }
+        Ok(())
+    }
+}
+
 /// IP fiter
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AllowIP {
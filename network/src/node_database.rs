@@ -4,24 +4,30 @@
 
 use crate::{
     node_table::{Node, NodeContact, NodeEntry, NodeId, NodeTable},
+    peer_reputation::{BannedPeer, Misbehavior, PeerReputation},
     IpFilter,
 };
 use io::StreamToken;
 use std::{collections::HashMap, net::IpAddr, time::Duration};
 
 /// Node database maintains all P2P nodes in trusted and untrusted node tables,
-/// and support to limit the number of nodes for the same IP address.
+/// and support to limit the number of nodes for the same IP address. It also
+/// tracks the reputation of nodes and the banned nodes.
 pub struct NodeDatabase {
     trusted_nodes: NodeTable,
     untrusted_nodes: NodeTable,
     ip_limit: NodeIpLimit,
+    reputation: PeerReputation,
 }
 
 impl NodeDatabase {
-    pub fn new(path: Option<String>, nodes_per_ip: usize) -> Self {
+    pub fn new(
+        path: Option<String>, nodes_per_ip: usize, ban_duration: Duration,
+    ) -> Self {
         let trusted_nodes = NodeTable::new(path.clone(), true);
         let untrusted_nodes = NodeTable::new(path.clone(), false);
         let mut ip_limit = NodeIpLimit::new(nodes_per_ip);
+        let reputation = PeerReputation::new(path, ban_duration);
 
         ip_limit.init(&trusted_nodes);
         ip_limit.init(&untrusted_nodes);
@@ -30,6 +36,7 @@ impl NodeDatabase {
             trusted_nodes,
             untrusted_nodes,
             ip_limit,
+            reputation,
         }
     }
 
@@ -173,6 +180,32 @@ impl NodeDatabase {
         }
     }
 
+    /// Penalize the specified node for its misbehavior. Returns true if the
+    /// node is banned because of it, in which case it is also marked as
+    /// failure.
+    pub fn note_misbehavior(
+        &mut self, id: &NodeId, ip: IpAddr, misbehavior: Misbehavior,
+    ) -> bool {
+        let banned = self.reputation.note_misbehavior(id, ip, misbehavior);
+        if banned {
+            self.note_failure(id, true, false);
+        }
+        banned
+    }
+
+    /// Whether connections with the node id or IP address are refused.
+    pub fn is_banned(&self, id: Option<&NodeId>, ip: &IpAddr) -> bool {
+        self.reputation.is_ip_banned(ip)
+            || id.map_or(false, |id| self.reputation.is_node_banned(id))
+    }
+
+    pub fn banned_peers(&self) -> Vec<BannedPeer> {
+        self.reputation.banned_peers()
+    }
+
+    /// Lift all bans.
+    pub fn clear_bans(&mut self) { self.reputation.clear(); }
+
     /// Get node from trusted and/or untrusted node table for the specified id.
     pub fn get(&self, id: &NodeId, trusted_only: bool) -> Option<&Node> {
         self.trusted_nodes.get(id).or_else(|| {
@@ -196,8 +229,11 @@ impl NodeDatabase {
         self.trusted_nodes.sample_node_ids(count, filter)
     }
 
-    /// Persist trust and untrusted node tables and clear all useless nodes.
+    /// Persist trust and untrusted node tables and banned nodes, and clear all
+    /// useless nodes.
     pub fn save(&mut self) {
+        self.reputation.save();
+
         self.trusted_nodes.save();
         self.trusted_nodes.clear_useless();
 
@@ -531,11 +567,13 @@ mod tests {
             super::{InsertResult, NodeDatabase},
             new_entry,
         };
-        use crate::node_table::NodeId;
+        use crate::{
+            node_table::NodeId, peer_reputation::DEFAULT_BAN_DURATION,
+        };
 
         #[test]
         fn test_insert_with_token() {
-            let mut db = NodeDatabase::new(None, 1);
+            let mut db = NodeDatabase::new(None, 1, DEFAULT_BAN_DURATION);
 
             // add a trusted node
             let entry = new_entry(None, "127.0.0.1:999");
@@ -576,7 +614,7 @@ mod tests {
 
         #[test]
         fn test_insert_with_promotion() {
-            let mut db = NodeDatabase::new(None, 1);
+            let mut db = NodeDatabase::new(None, 1, DEFAULT_BAN_DURATION);
 
             // add untrusted node
             let entry = new_entry(None, "127.0.0.1:999");
@@ -594,7 +632,7 @@ mod tests {
 
         #[test]
         fn test_insert_trusted() {
-            let mut db = NodeDatabase::new(None, 1);
+            let mut db = NodeDatabase::new(None, 1, DEFAULT_BAN_DURATION);
 
             // new added
             let entry = new_entry(None, "127.0.0.1:999");
@@ -623,7 +661,7 @@ mod tests {
 
         #[test]
         fn test_remove() {
-            let mut db = NodeDatabase::new(None, 1);
+            let mut db = NodeDatabase::new(None, 1, DEFAULT_BAN_DURATION);
 
             // add trusted node
             let entry1 = new_entry(None, "127.0.0.1:999");
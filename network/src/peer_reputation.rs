@@ -0,0 +1,385 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::node_table::NodeId;
+use std::{
+    collections::HashMap,
+    fs,
+    hash::Hash,
+    net::IpAddr,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A peer is banned once its penalty score reaches the threshold.
+const BAN_SCORE_THRESHOLD: f64 = 100.0;
+/// The time for a penalty score to decay to half.
+const PENALTY_HALF_LIFE_SECS: f64 = 600.0;
+/// Scores which have decayed below this are dropped.
+const MIN_SCORE: f64 = 1.0;
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+const BANNED_PEERS_FILE: &str = "banned_peers.json";
+
+/// Misbehaviors of peers which are penalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehavior {
+    /// Sent an invalid block or block header.
+    InvalidBlock,
+    /// Sent a block header with invalid proof of work.
+    InvalidPow,
+    /// Sent a message which can not be decoded.
+    MalformedMessage,
+    /// Sent a response which does not match any request.
+    UnsolicitedResponse,
+    /// Sent a response to a request which timed out already.
+    LateResponse,
+    /// Sent a response which does not match the roots in block headers.
+    InvalidProof,
+}
+
+impl Misbehavior {
+    fn penalty(&self) -> f64 {
+        match self {
+            Misbehavior::InvalidPow => 100.0,
+            Misbehavior::InvalidBlock => 50.0,
+            Misbehavior::MalformedMessage => 50.0,
+            Misbehavior::InvalidProof => 50.0,
+            Misbehavior::UnsolicitedResponse => 5.0,
+            // Honest peers may be slow sometimes, so only the peers which
+            // keep responding late are banned.
+            Misbehavior::LateResponse => 2.0,
+        }
+    }
+
+    /// Whether the misbehavior counts against the IP address of the peer
+    /// besides its node id.
+    fn penalizes_ip(&self) -> bool {
+        match self {
+            Misbehavior::LateResponse => false,
+            _ => true,
+        }
+    }
+}
+
+/// A banned node id or IP address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BannedPeer {
+    pub node_id: Option<NodeId>,
+    pub ip: Option<IpAddr>,
+    /// The time the ban expires, in seconds since the Unix epoch.
+    pub until: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Score {
+    value: f64,
+    updated: Instant,
+}
+
+impl Score {
+    /// The score decays exponentially over time.
+    fn decayed(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated);
+        let elapsed_secs = elapsed.as_secs() as f64
+            + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+        self.value * 0.5f64.powf(elapsed_secs / PENALTY_HALF_LIFE_SECS)
+    }
+}
+
+/// Penalty scores of peers by node id and IP address, and the peers
+/// temporarily banned because of their scores. The bans are persisted along
+/// with the node tables.
+pub struct PeerReputation {
+    path: Option<String>,
+    ban_duration: Duration,
+    node_scores: HashMap<NodeId, Score>,
+    ip_scores: HashMap<IpAddr, Score>,
+    /// The time bans expire, in seconds since the Unix epoch.
+    banned_nodes: HashMap<NodeId, u64>,
+    banned_ips: HashMap<IpAddr, u64>,
+}
+
+impl PeerReputation {
+    pub fn new(path: Option<String>, ban_duration: Duration) -> Self {
+        let mut reputation = PeerReputation {
+            path,
+            ban_duration,
+            node_scores: HashMap::new(),
+            ip_scores: HashMap::new(),
+            banned_nodes: HashMap::new(),
+            banned_ips: HashMap::new(),
+        };
+        reputation.load_from_file();
+        reputation
+    }
+
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Add the penalty to the score of `key` and returns the new score.
+    fn add_penalty<K: Hash + Eq>(
+        scores: &mut HashMap<K, Score>, key: K, penalty: f64, now: Instant,
+    ) -> f64 {
+        let score = scores.entry(key).or_insert(Score {
+            value: 0.0,
+            updated: now,
+        });
+        score.value = score.decayed(now) + penalty;
+        score.updated = now;
+        score.value
+    }
+
+    /// Penalize a peer for its misbehavior. Returns true if the peer is
+    /// banned because of it. Loopback addresses are only banned by node id,
+    /// since all local nodes share them.
+    pub fn note_misbehavior(
+        &mut self, id: &NodeId, ip: IpAddr, misbehavior: Misbehavior,
+    ) -> bool {
+        let now = Instant::now();
+        let penalty = misbehavior.penalty();
+        let until = Self::now_secs() + self.ban_duration.as_secs();
+        let mut banned = false;
+
+        if Self::add_penalty(&mut self.node_scores, *id, penalty, now)
+            >= BAN_SCORE_THRESHOLD
+        {
+            self.node_scores.remove(id);
+            self.banned_nodes.insert(*id, until);
+            banned = true;
+        }
+
+        if !ip.is_loopback()
+            && misbehavior.penalizes_ip()
+            && Self::add_penalty(&mut self.ip_scores, ip, penalty, now)
+                >= BAN_SCORE_THRESHOLD
+        {
+            self.ip_scores.remove(&ip);
+            self.banned_ips.insert(ip, until);
+            banned = true;
+        }
+
+        banned
+    }
+
+    pub fn is_node_banned(&self, id: &NodeId) -> bool {
+        self.banned_nodes
+            .get(id)
+            .map_or(false, |until| *until > Self::now_secs())
+    }
+
+    pub fn is_ip_banned(&self, ip: &IpAddr) -> bool {
+        self.banned_ips
+            .get(ip)
+            .map_or(false, |until| *until > Self::now_secs())
+    }
+
+    /// Returns the bans which are not expired yet.
+    pub fn banned_peers(&self) -> Vec<BannedPeer> {
+        let now = Self::now_secs();
+        let nodes = self.banned_nodes.iter().map(|(id, until)| BannedPeer {
+            node_id: Some(*id),
+            ip: None,
+            until: *until,
+        });
+        let ips = self.banned_ips.iter().map(|(ip, until)| BannedPeer {
+            node_id: None,
+            ip: Some(*ip),
+            until: *until,
+        });
+        nodes.chain(ips).filter(|ban| ban.until > now).collect()
+    }
+
+    /// Lift all bans and reset all penalty scores.
+    pub fn clear(&mut self) {
+        self.node_scores.clear();
+        self.ip_scores.clear();
+        self.banned_nodes.clear();
+        self.banned_ips.clear();
+    }
+
+    /// Drop the scores which have decayed below `MIN_SCORE`, so that the
+    /// scores of peers which stopped misbehaving do not pile up.
+    fn prune_scores(&mut self, now: Instant) {
+        self.node_scores
+            .retain(|_, score| score.decayed(now) >= MIN_SCORE);
+        self.ip_scores
+            .retain(|_, score| score.decayed(now) >= MIN_SCORE);
+    }
+
+    fn file_path(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|path| {
+            let mut path = PathBuf::from(path);
+            path.push(BANNED_PEERS_FILE);
+            path
+        })
+    }
+
+    fn load_from_file(&mut self) {
+        let path = match self.file_path() {
+            Some(path) => path,
+            None => return,
+        };
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("Error opening banned peers file: {:?}", e);
+                return;
+            }
+        };
+        let banned: Vec<BannedPeer> = match serde_json::from_reader(file) {
+            Ok(banned) => banned,
+            Err(e) => {
+                warn!("Error reading banned peers file: {:?}", e);
+                return;
+            }
+        };
+        for ban in banned {
+            if let Some(id) = ban.node_id {
+                self.banned_nodes.insert(id, ban.until);
+            }
+            if let Some(ip) = ban.ip {
+                self.banned_ips.insert(ip, ban.until);
+            }
+        }
+    }
+
+    /// Remove the expired bans and the decayed scores, and save the bans to
+    /// the banned peers file.
+    pub fn save(&mut self) {
+        let now = Self::now_secs();
+        self.banned_nodes.retain(|_, until| *until > now);
+        self.banned_ips.retain(|_, until| *until > now);
+        self.prune_scores(Instant::now());
+
+        let path = match self.file_path() {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                warn!("Error creating banned peers directory: {:?}", e);
+                return;
+            }
+        }
+        match fs::File::create(&path) {
+            Ok(file) => {
+                if let Err(e) =
+                    serde_json::to_writer_pretty(file, &self.banned_peers())
+                {
+                    warn!("Error writing banned peers file: {:?}", e);
+                }
+            }
+            Err(e) => {
+                warn!("Error creating banned peers file: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_ban_on_misbehavior() {
+        let mut reputation = PeerReputation::new(None, DEFAULT_BAN_DURATION);
+        let id = NodeId::random();
+        let ip = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+        assert!(!reputation.note_misbehavior(
+            &id,
+            ip,
+            Misbehavior::InvalidBlock
+        ));
+        assert!(!reputation.is_node_banned(&id));
+        assert!(reputation.note_misbehavior(
+            &id,
+            ip,
+            Misbehavior::MalformedMessage
+        ));
+        assert!(reputation.is_node_banned(&id));
+        assert!(reputation.is_ip_banned(&ip));
+        assert_eq!(reputation.banned_peers().len(), 2);
+
+        assert!(!reputation.is_node_banned(&NodeId::random()));
+
+        reputation.clear();
+        assert!(!reputation.is_node_banned(&id));
+        assert!(!reputation.is_ip_banned(&ip));
+        assert!(reputation.banned_peers().is_empty());
+    }
+
+    #[test]
+    fn test_loopback_not_banned_by_ip() {
+        let mut reputation = PeerReputation::new(None, DEFAULT_BAN_DURATION);
+        let id = NodeId::random();
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        assert!(reputation.note_misbehavior(&id, ip, Misbehavior::InvalidPow));
+        assert!(reputation.is_node_banned(&id));
+        assert!(!reputation.is_ip_banned(&ip));
+    }
+
+    #[test]
+    fn test_penalty_decay() {
+        let now = Instant::now();
+        let score = Score {
+            value: 80.0,
+            updated: now,
+        };
+        assert_eq!(score.decayed(now), 80.0);
+        let later = now + Duration::from_secs(PENALTY_HALF_LIFE_SECS as u64);
+        assert!((score.decayed(later) - 40.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_late_response_not_banned_by_ip() {
+        let mut reputation = PeerReputation::new(None, DEFAULT_BAN_DURATION);
+        let id = NodeId::random();
+        let ip = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        for _ in 0..40 {
+            assert!(!reputation.note_misbehavior(
+                &id,
+                ip,
+                Misbehavior::LateResponse
+            ));
+        }
+        assert!((0..20).any(|_| reputation.note_misbehavior(
+            &id,
+            ip,
+            Misbehavior::LateResponse
+        )));
+        assert!(reputation.is_node_banned(&id));
+        assert!(!reputation.is_ip_banned(&ip));
+    }
+
+    #[test]
+    fn test_prune_decayed_scores() {
+        let mut reputation = PeerReputation::new(None, DEFAULT_BAN_DURATION);
+        let ip = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        for _ in 0..10 {
+            reputation.note_misbehavior(
+                &NodeId::random(),
+                ip,
+                Misbehavior::UnsolicitedResponse,
+            );
+        }
+        assert_eq!(reputation.node_scores.len(), 10);
+        assert_eq!(reputation.ip_scores.len(), 1);
+
+        let now = Instant::now();
+        reputation.prune_scores(now);
+        assert_eq!(reputation.node_scores.len(), 10);
+        let later =
+            now + Duration::from_secs(10 * PENALTY_HALF_LIFE_SECS as u64);
+        reputation.prune_scores(later);
+        assert!(reputation.node_scores.is_empty());
+        assert!(reputation.ip_scores.is_empty());
+    }
+}
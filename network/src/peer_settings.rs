@@ -0,0 +1,179 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::NetworkConfiguration;
+use std::{fmt::Debug, fs, path::PathBuf};
+
+const PEER_SETTINGS_FILE: &str = "peer_settings.json";
+
+/// Peer settings which can be changed at runtime by administrators. Once
+/// changed, they are saved under the network config directory, and take
+/// precedence over the configuration when the node restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerSettings {
+    /// Reserved peers in the format of `cfxnode://NODEID@IP:PORT`, which are
+    /// always connected.
+    pub reserved_nodes: Vec<String>,
+    /// The IP filter in the format of `IpFilter::parse`, e.g.
+    /// `"none 10.0.0.0/8 -10.1.0.0/16"`.
+    pub ip_filter: String,
+    pub max_outgoing_peers: u32,
+    pub max_incoming_peers: u32,
+    /// Only connect with and accept reserved peers.
+    pub reserved_only: bool,
+}
+
+impl PeerSettings {
+    pub fn from_config(config: &NetworkConfiguration) -> Self {
+        PeerSettings {
+            reserved_nodes: config.reserved_nodes.clone(),
+            ip_filter: config.ip_filter.to_string(),
+            max_outgoing_peers: config.max_outgoing_peers,
+            max_incoming_peers: config.max_incoming_peers,
+            reserved_only: false,
+        }
+    }
+
+    /// The settings which differ from `config`, as the setting name, its
+    /// value here and its value in `config`.
+    pub fn overrides(
+        &self, config: &PeerSettings,
+    ) -> Vec<(&'static str, String, String)> {
+        fn check<T: Debug + PartialEq>(
+            overrides: &mut Vec<(&'static str, String, String)>,
+            name: &'static str, value: &T, config_value: &T,
+        )
+        {
+            if value != config_value {
+                overrides.push((
+                    name,
+                    format!("{:?}", value),
+                    format!("{:?}", config_value),
+                ));
+            }
+        }
+
+        let mut overrides = Vec::new();
+        check(
+            &mut overrides,
+            "reserved_nodes",
+            &self.reserved_nodes,
+            &config.reserved_nodes,
+        );
+        check(
+            &mut overrides,
+            "ip_filter",
+            &self.ip_filter,
+            &config.ip_filter,
+        );
+        check(
+            &mut overrides,
+            "max_outgoing_peers",
+            &self.max_outgoing_peers,
+            &config.max_outgoing_peers,
+        );
+        check(
+            &mut overrides,
+            "max_incoming_peers",
+            &self.max_incoming_peers,
+            &config.max_incoming_peers,
+        );
+        check(
+            &mut overrides,
+            "reserved_only",
+            &self.reserved_only,
+            &config.reserved_only,
+        );
+        overrides
+    }
+
+    fn file_path(path: &str) -> PathBuf {
+        let mut path = PathBuf::from(path);
+        path.push(PEER_SETTINGS_FILE);
+        path
+    }
+
+    /// Load the settings saved under `path`, if any.
+    pub fn load(path: &str) -> Option<Self> {
+        let file = fs::File::open(Self::file_path(path)).ok()?;
+        match serde_json::from_reader(file) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warn!("Error reading peer settings file: {:?}", e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        if let Err(e) = fs::create_dir_all(path) {
+            warn!("Error creating peer settings directory: {:?}", e);
+            return;
+        }
+        match fs::File::create(Self::file_path(path)) {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer_pretty(file, self) {
+                    warn!("Error writing peer settings file: {:?}", e);
+                }
+            }
+            Err(e) => {
+                warn!("Error creating peer settings file: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IpFilter;
+
+    #[test]
+    fn test_ip_filter_round_trip() {
+        let mut config = NetworkConfiguration::new();
+        config.ip_filter =
+            IpFilter::parse("private 1.2.3.0/24 -10.0.0.0/8").unwrap();
+        let settings = PeerSettings::from_config(&config);
+        assert_eq!(settings.ip_filter, "private 1.2.3.0/24 -10.0.0.0/8");
+        assert_eq!(
+            IpFilter::parse(&settings.ip_filter).unwrap(),
+            config.ip_filter
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join("conflux_test_peer_settings");
+        let path = dir.to_str().unwrap();
+        let mut settings =
+            PeerSettings::from_config(&NetworkConfiguration::new());
+        settings.reserved_only = true;
+        settings.max_incoming_peers = 5;
+        settings.save(path);
+        assert_eq!(PeerSettings::load(path), Some(settings));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = PeerSettings::from_config(&NetworkConfiguration::new());
+        assert!(config.overrides(&config).is_empty());
+
+        let mut settings = config.clone();
+        settings.max_incoming_peers = config.max_incoming_peers + 1;
+        settings.reserved_only = true;
+        assert_eq!(
+            settings.overrides(&config),
+            vec![
+                (
+                    "max_incoming_peers",
+                    settings.max_incoming_peers.to_string(),
+                    config.max_incoming_peers.to_string()
+                ),
+                ("reserved_only", "true".to_owned(), "false".to_owned()),
+            ]
+        );
+    }
+}
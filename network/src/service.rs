@@ -8,9 +8,11 @@ use crate::{
     ip_utils::{map_external_address, select_public_address},
     node_database::NodeDatabase,
     node_table::*,
+    peer_reputation::{BannedPeer, Misbehavior},
+    peer_settings::PeerSettings,
     session::{self, Session, SessionData},
     session_manager::SessionManager,
-    Capability, Error, ErrorKind, HandlerWorkType, IpFilter,
+    Capability, DisconnectReason, Error, ErrorKind, HandlerWorkType, IpFilter,
     NetworkConfiguration, NetworkContext as NetworkContextTrait,
     NetworkIoMessage, NetworkProtocolHandler, PeerId, PeerInfo, ProtocolId,
 };
@@ -246,6 +248,83 @@ impl NetworkService {
             Err("Network service not started yet!".into())
         }
     }
+
+    /// Return the node ids and IP addresses banned for misbehaviors
+    pub fn banned_peers(&self) -> Result<Vec<BannedPeer>, Error> {
+        if let Some(ref inner) = self.inner {
+            Ok(inner.node_db.read().banned_peers())
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Lift all bans of misbehaving peers
+    pub fn clear_bans(&self) -> Result<(), Error> {
+        if let Some(ref inner) = self.inner {
+            inner.node_db.write().clear_bans();
+            Ok(())
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Add a reserved peer in the format of `cfxnode://NODEID@IP:PORT`
+    pub fn add_reserved_peer(&self, url: &str) -> Result<(), Error> {
+        if let Some(ref inner) = self.inner {
+            inner.add_reserved_peer(url)
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Remove a reserved peer, and return false if it is not reserved
+    pub fn remove_reserved_peer(&self, id: &NodeId) -> Result<bool, Error> {
+        if let Some(ref inner) = self.inner {
+            Ok(inner.remove_reserved_peer(id))
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Replace the IP filter of peers
+    pub fn set_ip_filter(&self, filter: &str) -> Result<(), Error> {
+        if let Some(ref inner) = self.inner {
+            inner.set_ip_filter(filter)
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Change the maximum numbers of outgoing and incoming peers
+    pub fn set_max_peers(
+        &self, outgoing: u32, incoming: u32,
+    ) -> Result<(), Error> {
+        if let Some(ref inner) = self.inner {
+            inner.set_max_peers(outgoing, incoming);
+            Ok(())
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Toggle whether only reserved peers are connected
+    pub fn set_reserved_only(&self, reserved_only: bool) -> Result<(), Error> {
+        if let Some(ref inner) = self.inner {
+            inner.set_reserved_only(reserved_only);
+            Ok(())
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
+
+    /// Return the peer settings currently in effect
+    pub fn peer_settings(&self) -> Result<PeerSettings, Error> {
+        if let Some(ref inner) = self.inner {
+            Ok(inner.peer_settings())
+        } else {
+            Err("Network service not started yet!".into())
+        }
+    }
 }
 
 type SharedSession = Arc<RwLock<Session>>;
@@ -288,6 +367,10 @@ pub struct NetworkServiceInner {
     timer_counter: RwLock<usize>,
     pub node_db: RwLock<NodeDatabase>,
     reserved_nodes: RwLock<HashSet<NodeId>>,
+    /// The peer settings which may be changed at runtime, and the IP filter
+    /// parsed from them.
+    peer_settings: RwLock<PeerSettings>,
+    ip_filter: RwLock<IpFilter>,
     nodes: RwLock<HashMap<NodeId, NodeEntry>>,
     dropped_nodes: RwLock<HashSet<StreamToken>>,
 
@@ -426,10 +509,40 @@ impl NetworkServiceInner {
             },
         };
 
-        let allow_ips = config.ip_filter.clone();
+        // The settings changed at runtime take precedence over the
+        // configuration, and every overridden value is logged.
+        let config_settings = PeerSettings::from_config(config);
+        let peer_settings = match config
+            .config_path
+            .as_ref()
+            .and_then(|path| PeerSettings::load(path).map(|s| (path, s)))
+        {
+            Some((path, settings)) => {
+                for (name, value, config_value) in
+                    settings.overrides(&config_settings)
+                {
+                    warn!(
+                        "Peer settings saved in {} override {} of the \
+                         configuration: {} instead of {}",
+                        path, name, value, config_value
+                    );
+                }
+                settings
+            }
+            None => config_settings,
+        };
+        let ip_filter = IpFilter::parse(&peer_settings.ip_filter)
+            .unwrap_or_else(|e| {
+                warn!("Invalid IP filter in peer settings: {:?}", e);
+                config.ip_filter.clone()
+            });
         let discovery = {
             if config.discovery_enabled {
-                Some(Discovery::new(&keys, public_endpoint.clone(), allow_ips))
+                Some(Discovery::new(
+                    &keys,
+                    public_endpoint.clone(),
+                    ip_filter.clone(),
+                ))
             } else {
                 None
             }
@@ -437,7 +550,7 @@ impl NetworkServiceInner {
 
         let nodes_path = config.config_path.clone();
 
-        let mut inner = NetworkServiceInner {
+        let inner = NetworkServiceInner {
             metadata: HostMetadata {
                 keys,
                 capabilities: RwLock::new(Vec::new()),
@@ -461,8 +574,11 @@ impl NetworkServiceInner {
             node_db: RwLock::new(NodeDatabase::new(
                 nodes_path,
                 config.nodes_per_ip,
+                config.ban_duration,
             )),
             reserved_nodes: RwLock::new(HashSet::new()),
+            peer_settings: RwLock::new(peer_settings.clone()),
+            ip_filter: RwLock::new(ip_filter),
             nodes: RwLock::new(HashMap::new()),
             dropped_nodes: RwLock::new(HashSet::new()),
             delayed_queue: None,
@@ -472,9 +588,8 @@ impl NetworkServiceInner {
             inner.add_boot_node(n);
         }
 
-        let reserved_nodes = config.reserved_nodes.clone();
-        for n in reserved_nodes {
-            if let Err(e) = inner.add_reserved_node(&n) {
+        for n in &peer_settings.reserved_nodes {
+            if let Err(e) = inner.add_reserved_node(n) {
                 debug!("Error parsing node id: {}: {:?}", n, e);
             }
         }
@@ -511,7 +626,7 @@ impl NetworkServiceInner {
         }
     }
 
-    pub fn get_ip_filter(&self) -> &IpFilter { &self.config.ip_filter }
+    pub fn get_ip_filter(&self) -> IpFilter { self.ip_filter.read().clone() }
 
     fn add_boot_node(&self, id: &str) {
         match Node::from_str(id) {
@@ -527,13 +642,140 @@ impl NetworkServiceInner {
         }
     }
 
-    fn add_reserved_node(&mut self, id: &str) -> Result<(), Error> {
+    fn add_reserved_node(&self, id: &str) -> Result<NodeId, Error> {
         let n = Node::from_str(id)?;
         self.node_db.write().insert_trusted(NodeEntry {
             id: n.id.clone(),
             endpoint: n.endpoint.clone(),
         });
         self.reserved_nodes.write().insert(n.id);
+        Ok(n.id)
+    }
+
+    /// Add a reserved peer at runtime and save it in the peer settings.
+    pub fn add_reserved_peer(&self, url: &str) -> Result<(), Error> {
+        // Reserved peers must have node ids.
+        if !url.starts_with("cfxnode://") {
+            bail!(ErrorKind::InvalidNodeId);
+        }
+        let id = self.add_reserved_node(url)?;
+        self.update_peer_settings(|settings| {
+            settings.reserved_nodes.retain(|n| {
+                Node::from_str(n).map_or(true, |node| node.id != id)
+            });
+            settings.reserved_nodes.push(url.to_string());
+        });
+        Ok(())
+    }
+
+    /// Remove a reserved peer at runtime. The peer is not disconnected
+    /// unless only reserved peers are allowed. Returns false if the peer is
+    /// not reserved.
+    pub fn remove_reserved_peer(&self, id: &NodeId) -> bool {
+        if !self.reserved_nodes.write().remove(id) {
+            return false;
+        }
+        self.update_peer_settings(|settings| {
+            settings.reserved_nodes.retain(|n| {
+                Node::from_str(n).map_or(true, |node| node.id != *id)
+            });
+        });
+        true
+    }
+
+    /// Replace the IP filter at runtime. `filter` is in the format of
+    /// `IpFilter::parse`.
+    pub fn set_ip_filter(&self, filter: &str) -> Result<(), Error> {
+        let ip_filter = IpFilter::parse(filter).map_err(|e| {
+            Error::from(format!("Invalid IP filter {:?}: {:?}", filter, e))
+        })?;
+        if let Some(discovery) = self.discovery.lock().as_mut() {
+            discovery.set_ip_filter(ip_filter.clone());
+        }
+        *self.ip_filter.write() = ip_filter;
+        self.update_peer_settings(|settings| {
+            settings.ip_filter = filter.to_string();
+        });
+        Ok(())
+    }
+
+    /// Change the maximum numbers of peers at runtime. Existing sessions are
+    /// kept even if there are more peers than allowed.
+    pub fn set_max_peers(&self, outgoing: u32, incoming: u32) {
+        self.update_peer_settings(|settings| {
+            settings.max_outgoing_peers = outgoing;
+            settings.max_incoming_peers = incoming;
+        });
+    }
+
+    /// Only connect with and accept reserved peers if `reserved_only` is
+    /// true.
+    pub fn set_reserved_only(&self, reserved_only: bool) {
+        self.update_peer_settings(|settings| {
+            settings.reserved_only = reserved_only;
+        });
+    }
+
+    pub fn peer_settings(&self) -> PeerSettings {
+        self.peer_settings.read().clone()
+    }
+
+    /// Apply the change to the peer settings, save them, and drop the
+    /// sessions which are no longer allowed.
+    fn update_peer_settings<F>(&self, f: F)
+    where F: FnOnce(&mut PeerSettings) {
+        let settings = {
+            let mut settings = self.peer_settings.write();
+            f(&mut settings);
+            settings.clone()
+        };
+        if let Some(ref path) = self.config.config_path {
+            settings.save(path);
+        }
+
+        let ip_filter = self.get_ip_filter();
+        let reserved_nodes = self.reserved_nodes.read().clone();
+        let mut to_drop = Vec::new();
+        for session in self.sessions.all() {
+            let sess = session.read();
+            if sess.expired() {
+                continue;
+            }
+            let endpoint = NodeEndpoint {
+                address: sess.address(),
+                udp_port: sess.address().port(),
+            };
+            let reserved =
+                sess.id().map_or(false, |id| reserved_nodes.contains(id));
+            if !endpoint.is_allowed(&ip_filter)
+                || (settings.reserved_only && !reserved)
+            {
+                debug!("Drop session not allowed, session = {:?}", *sess);
+                to_drop.push(sess.token());
+            }
+        }
+        // The sessions are killed in the next housekeeping.
+        self.dropped_nodes.write().extend(to_drop);
+    }
+
+    /// Check whether a peer is accepted by the peer settings once its node
+    /// id is known.
+    pub fn check_peer_settings(
+        &self, id: &NodeId, originated: bool,
+    ) -> Result<(), DisconnectReason> {
+        if self.reserved_nodes.read().contains(id) {
+            return Ok(());
+        }
+        let settings = self.peer_settings.read();
+        if settings.reserved_only {
+            return Err(DisconnectReason::NotReserved);
+        }
+        if !originated {
+            let (_, _, ingress_count) = self.sessions.stat();
+            if ingress_count >= settings.max_incoming_peers as usize {
+                return Err(DisconnectReason::TooManyPeers);
+            }
+        }
         Ok(())
     }
 
@@ -542,7 +784,7 @@ impl NetworkServiceInner {
     ) -> Result<(), Error> {
         // Initialize discovery
         if let Some(discovery) = self.discovery.lock().as_mut() {
-            let allow_ips = self.config.ip_filter.clone();
+            let allow_ips = self.get_ip_filter();
             let nodes = self
                 .node_db
                 .read()
@@ -608,7 +850,8 @@ impl NetworkServiceInner {
 
     fn has_enough_outgoing_peers(&self) -> bool {
         let (_, egress_count, _) = self.sessions.stat();
-        return egress_count >= self.config.max_outgoing_peers as usize;
+        return egress_count
+            >= self.peer_settings.read().max_outgoing_peers as usize;
     }
 
     fn on_housekeeping(&self, io: &IoContext<NetworkIoMessage>) {
@@ -623,16 +866,21 @@ impl NetworkServiceInner {
         }
 
         let self_id = self.metadata.id().clone();
-        let max_outgoing_peers = self.config.max_outgoing_peers as usize;
-        //let max_incoming_peers = self.config.max_incoming_peers;
+        let (max_outgoing_peers, reserved_only) = {
+            let settings = self.peer_settings.read();
+            (settings.max_outgoing_peers as usize, settings.reserved_only)
+        };
         let max_handshakes = self.config.max_handshakes;
-        let allow_ips = self.config.ip_filter.clone();
+        let allow_ips = self.get_ip_filter();
 
         let (handshake_count, egress_count, ingress_count) =
             self.sessions.stat();
         let samples;
         {
-            let egress_attempt_count = if max_outgoing_peers > egress_count {
+            // Only reserved peers are connected in reserved-only mode.
+            let egress_attempt_count = if reserved_only {
+                0
+            } else if max_outgoing_peers > egress_count {
                 max_outgoing_peers - egress_count
             } else {
                 0
@@ -696,6 +944,10 @@ impl NetworkServiceInner {
                     return;
                 }
             };
+            if self.node_db.read().is_banned(Some(id), &address.ip()) {
+                debug!("Abort connect. Node banned");
+                return;
+            }
             match TcpStream::connect(&address) {
                 Ok(socket) => {
                     trace!("{}: connecting to {:?}", id, address);
@@ -903,6 +1155,11 @@ impl NetworkServiceInner {
                 }
             };
 
+            if self.node_db.read().is_banned(None, &address.ip()) {
+                debug!("Refuse connection from banned address {:?}", address);
+                continue;
+            }
+
             if let Err(e) = self.create_connection(socket, address, None, io) {
                 debug!("Can't accept connection: {:?}", e);
             }
@@ -959,6 +1216,33 @@ impl NetworkServiceInner {
         }
     }
 
+    fn report_peer(
+        &self, peer: PeerId, misbehavior: Misbehavior,
+        io: &IoContext<NetworkIoMessage>,
+    )
+    {
+        let session = match self.sessions.get(peer) {
+            Some(session) => session,
+            None => return,
+        };
+        let (id, ip) = {
+            let sess = session.read();
+            match sess.id() {
+                Some(id) => (*id, sess.address().ip()),
+                None => return,
+            }
+        };
+        debug!(
+            "Peer misbehaved, peer = {}, misbehavior = {:?}",
+            peer, misbehavior
+        );
+        if self.node_db.write().note_misbehavior(&id, ip, misbehavior) {
+            info!("Peer banned, node id = {:?}, ip = {}", id, ip);
+            session.write().disconnect(io, DisconnectReason::Banned);
+            self.kill_connection(peer, io, false);
+        }
+    }
+
     pub fn with_context<F>(
         &self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>,
         action: F,
@@ -1469,6 +1753,10 @@ impl<'a> NetworkContextTrait for NetworkContext<'a> {
         self.network_service.kill_connection(peer, self.io, true);
     }
 
+    fn report_peer(&self, peer: PeerId, misbehavior: Misbehavior) {
+        self.network_service.report_peer(peer, misbehavior, self.io);
+    }
+
     fn register_timer(
         &self, token: TimerToken, delay: Duration,
     ) -> Result<(), Error> {
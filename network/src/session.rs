@@ -7,6 +7,7 @@ use crate::{
         Connection as TcpConnection, PacketSizer as PacketSizerTrait,
         SendQueueStatus, MAX_PAYLOAD_SIZE,
     },
+    encryption::{Handshake, LocalHandshake, SEAL_OVERHEAD},
     hash::keccak,
     node_database::InsertResult,
     node_table::{NodeEndpoint, NodeEntry, NodeId},
@@ -22,6 +23,7 @@ use keylib::{recover, sign};
 use mio::{deprecated::*, tcp::*, *};
 use priority_send_queue::SendQueuePriority;
 use rlp::{Rlp, RlpStream};
+use snappy;
 use std::{fmt, net::SocketAddr, str};
 
 struct PacketSizer;
@@ -51,6 +53,10 @@ pub struct Session {
     sent_hello: bool,
     had_hello: bool,
     expired: bool,
+    /// The handshake parameters sent in HELLO to encrypt the session.
+    handshake: LocalHandshake,
+    /// Whether both sides support compression of user packets.
+    compression: bool,
 }
 
 pub enum SessionData {
@@ -65,6 +71,13 @@ const PACKET_DISCONNECT: u8 = 0x01;
 const PACKET_PING: u8 = 0x02;
 const PACKET_PONG: u8 = 0x03;
 pub const PACKET_USER: u8 = 0x10;
+const PACKET_USER_COMPRESSED: u8 = 0x11;
+
+/// The compression algorithm of user packets advertised in HELLO. Zero means
+/// compression is not supported.
+const COMPRESSION_SNAPPY: u8 = 1;
+/// User packets with larger payloads are compressed.
+const COMPRESSION_THRESHOLD: usize = 1024;
 
 impl Session {
     pub fn new<Message: Send + Sync + Clone + 'static>(
@@ -85,6 +98,8 @@ impl Session {
             sent_hello: false,
             had_hello: false,
             expired: false,
+            handshake: LocalHandshake::new()?,
+            compression: false,
         };
         if true {
             session.write_hello(io, host)?;
@@ -188,6 +203,23 @@ impl Session {
                 let signed = &data[(32 + 65)..];
                 let signature = H520::from_slice(&data[32..(32 + 65)]);
                 let node_id = recover(&signature.into(), &keccak(signed))?;
+                if host
+                    .node_db
+                    .read()
+                    .is_banned(Some(&node_id), &self.address.ip())
+                {
+                    debug!("Banned peer, session = {:?}", self);
+                    return Err(self.disconnect(io, DisconnectReason::Banned));
+                }
+                if let Err(reason) =
+                    host.check_peer_settings(&node_id, self.metadata.originated)
+                {
+                    debug!(
+                        "Peer not accepted, reason = {}, session = {:?}",
+                        reason, self
+                    );
+                    return Err(self.disconnect(io, reason));
+                }
                 if self.metadata.id.is_none() {
                     if let Err(reason) = host
                         .sessions
@@ -244,6 +276,24 @@ impl Session {
                     })
                 }
             }
+            PACKET_USER_COMPRESSED => {
+                if !self.compression {
+                    debug!(
+                        "Compressed packet without negotiation, session = {:?}",
+                        self
+                    );
+                    return Err(ErrorKind::BadProtocol.into());
+                }
+                if data.len() < 3 {
+                    return Err(ErrorKind::Decoder.into());
+                }
+                let mut protocol: ProtocolId = [0u8; 3];
+                protocol.clone_from_slice(&data[..3]);
+                Ok(SessionData::Message {
+                    data: decompress_payload(&data[3..])?,
+                    protocol,
+                })
+            }
             _ => {
                 debug!(
                     "read packet UNKNOWN, packet_id = {:?}, session = {:?}",
@@ -315,7 +365,7 @@ impl Session {
         if !entry.endpoint.is_valid() {
             debug!("Got invalid endpoint {:?}, session = {:?}", entry, self);
             return Err(self.disconnect(io, DisconnectReason::WrongEndpointInfo));
-        } else if !(entry.endpoint.is_allowed(host.get_ip_filter())
+        } else if !(entry.endpoint.is_allowed(&host.get_ip_filter())
             && entry.id != *host.metadata.id())
         {
             debug!(
@@ -332,6 +382,40 @@ impl Session {
             }
         }
 
+        // Legacy peers do not send the handshake parameters.
+        let remote_handshake = if rlp.item_count()? > 2 {
+            Some(rlp.val_at::<Handshake>(2)?)
+        } else {
+            None
+        };
+        let version = remote_handshake
+            .as_ref()
+            .and_then(|remote| self.handshake.negotiate(remote));
+        match (remote_handshake, version) {
+            (Some(remote), Some(version)) => {
+                let (egress, ingress) = self.handshake.agree(&remote)?;
+                self.connection.enable_encryption(egress, ingress);
+                debug!(
+                    "Session encrypted, version = {}, session = {:?}",
+                    version, self
+                );
+            }
+            _ if host.config.require_encryption => {
+                debug!(
+                    "Peer does not support encryption, session = {:?}",
+                    self
+                );
+                return Err(self.disconnect(
+                    io,
+                    DisconnectReason::EncryptionRequired,
+                ));
+            }
+            _ => debug!("Session not encrypted, session = {:?}", self),
+        }
+
+        self.compression =
+            negotiate_compression(host.config.compression_enabled, rlp)?;
+
         self.send_ping(io)?;
         self.had_hello = true;
 
@@ -354,15 +438,34 @@ impl Session {
         if self.expired() {
             return Err(ErrorKind::Expired.into());
         }
-        let packet_size =
-            1 + protocol.map(|p| p.len()).unwrap_or(0) + data.len();
-        if packet_size > MAX_PAYLOAD_SIZE {
+        let protocol_len = protocol.map(|p| p.len()).unwrap_or(0);
+        let overhead = if self.connection.is_encrypted() {
+            SEAL_OVERHEAD
+        } else {
+            0
+        };
+        // The receiver rejects compressed payloads which decompress beyond
+        // the limit, so the size is checked before compression.
+        let uncompressed_size = 1 + protocol_len + data.len();
+        if uncompressed_size + overhead > MAX_PAYLOAD_SIZE {
             error!(
                 "Packet is too big, size = {}, max = {}, session = {:?}",
-                packet_size, MAX_PAYLOAD_SIZE, self
+                uncompressed_size + overhead,
+                MAX_PAYLOAD_SIZE,
+                self
             );
             bail!(ErrorKind::OversizedPacket);
         }
+        let compressed = if packet_id == PACKET_USER && self.compression {
+            compress_payload(data)
+        } else {
+            None
+        };
+        let (packet_id, data) = match compressed {
+            Some(ref compressed) => (PACKET_USER_COMPRESSED, &compressed[..]),
+            None => (packet_id, data),
+        };
+        let packet_size = 1 + protocol_len + data.len();
         let mut packet = BytesMut::with_capacity(3 + packet_size);
         packet.put_uint_le(packet_size as u64, 3);
         packet.put_u8(packet_id);
@@ -408,9 +511,15 @@ impl Session {
         &mut self, io: &IoContext<Message>, host: &NetworkServiceInner,
     ) -> Result<(), Error> {
         debug!("Sending Hello, session = {:?}", self);
-        let mut rlp = RlpStream::new_list(2);
+        let mut rlp = RlpStream::new_list(4);
         rlp.append_list(&*host.metadata.capabilities.read());
         host.metadata.public_endpoint.to_rlp_list(&mut rlp);
+        rlp.append(&self.handshake.handshake());
+        if host.config.compression_enabled {
+            rlp.append(&COMPRESSION_SNAPPY);
+        } else {
+            rlp.append(&0u8);
+        }
 
         let mut packet =
             cfx_bytes::Bytes::with_capacity(rlp.as_raw().len() + 32 + 65);
@@ -445,9 +554,100 @@ impl Session {
     }
 }
 
+/// Whether user packets are compressed with the peer, given its HELLO.
+fn negotiate_compression(
+    compression_enabled: bool, hello: &Rlp,
+) -> Result<bool, Error> {
+    // Legacy peers do not send the supported compression algorithm.
+    let remote_compression: u8 = if hello.item_count()? > 3 {
+        hello.val_at(3)?
+    } else {
+        0
+    };
+    Ok(compression_enabled && remote_compression == COMPRESSION_SNAPPY)
+}
+
+/// The compressed payload of a user packet, if it is large enough and
+/// compression makes it smaller.
+fn compress_payload(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() <= COMPRESSION_THRESHOLD {
+        return None;
+    }
+    let compressed = snappy::compress(data);
+    if compressed.len() < data.len() {
+        Some(compressed)
+    } else {
+        None
+    }
+}
+
+/// Decompress the payload of a user packet, which must not decompress beyond
+/// `MAX_PAYLOAD_SIZE`.
+fn decompress_payload(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if snappy::decompressed_len(data)? > MAX_PAYLOAD_SIZE {
+        return Err(ErrorKind::OversizedPacket.into());
+    }
+    Ok(snappy::decompress(data)?)
+}
+
 impl fmt::Debug for Session {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Session {{ token: {}, id: {:?}, originated: {}, address: {:?}, sent_hello: {}, had_hello: {}, expired: {} }}",
-               self.token(), self.id(), self.metadata.originated, self.address, self.sent_hello, self.had_hello, self.expired)
+        write!(f, "Session {{ token: {}, id: {:?}, originated: {}, address: {:?}, sent_hello: {}, had_hello: {}, expired: {}, encrypted: {}, compression: {} }}",
+               self.token(), self.id(), self.metadata.originated, self.address, self.sent_hello, self.had_hello, self.expired, self.connection.is_encrypted(), self.compression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(compression: Option<u8>) -> Vec<u8> {
+        let mut rlp =
+            RlpStream::new_list(if compression.is_some() { 4 } else { 3 });
+        rlp.append(&0u8).append(&0u8).append(&0u8);
+        if let Some(compression) = compression {
+            rlp.append(&compression);
+        }
+        rlp.out()
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let data = vec![7u8; 4 * COMPRESSION_THRESHOLD];
+        let compressed = compress_payload(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress_payload(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_small_payload_not_compressed() {
+        assert!(compress_payload(&[7u8; COMPRESSION_THRESHOLD]).is_none());
+    }
+
+    #[test]
+    fn test_decompression_bomb_rejected() {
+        let data = vec![0u8; MAX_PAYLOAD_SIZE + 1];
+        let compressed = snappy::compress(&data);
+        assert!(compressed.len() < MAX_PAYLOAD_SIZE);
+        match decompress_payload(&compressed).unwrap_err().kind() {
+            ErrorKind::OversizedPacket => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_negotiate_compression() {
+        let snappy = hello(Some(COMPRESSION_SNAPPY));
+        assert!(negotiate_compression(true, &Rlp::new(&snappy)).unwrap());
+        assert!(!negotiate_compression(false, &Rlp::new(&snappy)).unwrap());
+
+        let disabled = hello(Some(0));
+        assert!(!negotiate_compression(true, &Rlp::new(&disabled)).unwrap());
+    }
+
+    #[test]
+    fn test_legacy_peer_not_compressed() {
+        let legacy = hello(None);
+        assert!(!negotiate_compression(true, &Rlp::new(&legacy)).unwrap());
     }
 }
@@ -58,6 +58,9 @@ impl Service {
         );
     }
 
+    /// `data_size` is the size of a frame as sent on the wire, i.e. after
+    /// compression and with the encryption overhead, so that the throttling
+    /// reflects the actual bandwidth used.
     pub(crate) fn on_enqueue(
         &mut self, data_size: usize,
     ) -> Result<usize, Error> {
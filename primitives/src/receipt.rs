@@ -2,8 +2,8 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::log_entry::LogEntry;
-use cfx_types::{Bloom, U256};
+use crate::log_entry::{LocalizedLogEntry, LogEntry};
+use cfx_types::{Address, Bloom, H256, U256};
 use heapsize::HeapSizeOf;
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 
@@ -37,6 +37,32 @@ impl Receipt {
     }
 }
 
+/// Receipt with additional information about the transaction and where it is
+/// executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedReceipt {
+    pub transaction_hash: H256,
+    /// Index of the transaction within the block.
+    pub transaction_index: usize,
+    pub block_hash: H256,
+    /// The epoch in which the block is executed.
+    pub epoch_number: usize,
+    pub from: Address,
+    /// The callee, or `None` for a contract creation.
+    pub to: Option<Address>,
+    /// Gas used by this transaction.
+    pub gas_used: U256,
+    /// The total gas used in the block following execution of the transaction.
+    pub cumulative_gas_used: U256,
+    /// The created contract if the contract creation succeeded.
+    pub contract_created: Option<Address>,
+    pub logs: Vec<LocalizedLogEntry>,
+    pub log_bloom: Bloom,
+    pub outcome_status: u8,
+    /// Why the transaction failed, or `None` if it succeeded.
+    pub failure_reason: Option<String>,
+}
+
 impl Encodable for Receipt {
     fn rlp_append(&self, s: &mut RlpStream) {
         s.begin_list(4);
@@ -71,6 +71,13 @@ pub enum TransactionError {
     TooBig,
     /// Invalid RLP encoding
     InvalidRlp(String),
+    /// Transaction is signed for another chain
+    ChainIdMismatch {
+        /// Chain id of this chain
+        expected: u64,
+        /// Transaction chain id
+        got: u64,
+    },
 }
 
 impl From<keylib::Error> for TransactionError {
@@ -115,6 +122,10 @@ impl fmt::Display for TransactionError {
             InvalidRlp(ref err) => {
                 format!("Transaction has invalid RLP structure: {}.", err)
             }
+            ChainIdMismatch { expected, got } => format!(
+                "Chain id mismatch. Expected={}, Given={}",
+                expected, got
+            ),
         };
 
         f.write_fmt(format_args!("Transaction error ({})", msg))
@@ -171,6 +182,9 @@ pub struct Transaction {
     pub value: U256,
     /// Transaction data.
     pub data: Bytes,
+    /// The chain the transaction is signed for, which prevents replaying it
+    /// on other chains.
+    pub chain_id: u64,
 }
 
 impl Transaction {
@@ -213,19 +227,21 @@ impl Decodable for Transaction {
             action: r.val_at(3)?,
             value: r.val_at(4)?,
             data: r.val_at(5)?,
+            chain_id: r.val_at(6)?,
         })
     }
 }
 
 impl Encodable for Transaction {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(6);
+        s.begin_list(7);
         s.append(&self.nonce);
         s.append(&self.gas_price);
         s.append(&self.gas);
         s.append(&self.action);
         s.append(&self.value);
         s.append(&self.data);
+        s.append(&self.chain_id);
     }
 }
 
@@ -261,7 +277,7 @@ impl Deref for TransactionWithSignature {
 
 impl Decodable for TransactionWithSignature {
     fn decode(d: &Rlp) -> Result<Self, DecoderError> {
-        if d.item_count()? != 9 {
+        if d.item_count()? != 10 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
         let hash = keccak(d.as_raw());
@@ -275,10 +291,11 @@ impl Decodable for TransactionWithSignature {
                 action: d.val_at(3)?,
                 value: d.val_at(4)?,
                 data: d.val_at(5)?,
+                chain_id: d.val_at(6)?,
             },
-            v: d.val_at(6)?,
-            r: d.val_at(7)?,
-            s: d.val_at(8)?,
+            v: d.val_at(7)?,
+            r: d.val_at(8)?,
+            s: d.val_at(9)?,
             hash,
             rlp_size,
         })
@@ -315,13 +332,14 @@ impl TransactionWithSignature {
 
     /// Append object with a signature into RLP stream
     fn rlp_append_sealed_transaction(&self, s: &mut RlpStream) {
-        s.begin_list(9);
+        s.begin_list(10);
         s.append(&self.nonce);
         s.append(&self.gas_price);
         s.append(&self.gas);
         s.append(&self.action);
         s.append(&self.value);
         s.append(&self.data);
+        s.append(&self.chain_id);
         s.append(&self.v);
         s.append(&self.r);
         s.append(&self.s);
@@ -494,3 +512,40 @@ impl HeapSizeOf for SignedTransaction {
         mem::size_of::<Self>() + self.transaction.heap_size_of_children()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Transaction, TransactionWithSignature};
+    use keylib::{Generator, Random};
+
+    #[test]
+    fn chain_id_is_signed() {
+        let key_pair = Random.generate().unwrap();
+        let tx = Transaction {
+            nonce: 0.into(),
+            gas_price: 1.into(),
+            gas: 21000.into(),
+            action: Action::Call(Default::default()),
+            value: 100.into(),
+            data: vec![],
+            chain_id: 1,
+        }
+        .sign(key_pair.secret());
+        assert_eq!(tx.sender(), key_pair.address());
+
+        let decoded: TransactionWithSignature =
+            rlp::decode(&rlp::encode(&tx.transaction)).unwrap();
+        assert_eq!(decoded.unsigned, tx.transaction.unsigned);
+        assert_eq!(decoded.hash(), tx.hash());
+        assert_eq!(decoded.chain_id, 1);
+
+        // Replaying the signature with another chain id recovers another
+        // sender.
+        let mut replayed = decoded.clone();
+        replayed.unsigned.chain_id = 2;
+        let sender = replayed
+            .recover_public()
+            .map(|public| keylib::public_to_address(&public));
+        assert_ne!(sender.ok(), Some(key_pair.address()));
+    }
+}
@@ -74,6 +74,13 @@ fn main() {
                 .help("Specify the PORT for the HTTP JSON-RPC API server.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("jsonrpc-ws-port")
+                .long("jsonrpc-ws-port")
+                .value_name("PORT")
+                .help("Specify the PORT for the WebSocket JSON-RPC API server.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("jsonrpc-cors")
                 .long("jsonrpc-cors")
@@ -110,6 +117,13 @@ fn main() {
                 .help("Sets a custom config file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("chain")
+                .long("chain")
+                .value_name("FILE")
+                .help("Sets the chain spec file (TOML, or JSON with a .json extension).")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("bootnodes")
                 .long("bootnodes")
@@ -0,0 +1,58 @@
+#!/usr/bin/env python3
+from conflux.rpc import RpcClient
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import *
+
+
+class CheckpointSyncTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.setup_clean_chain = True
+        self.num_nodes = 2
+
+    def setup_network(self):
+        self.add_nodes(self.num_nodes)
+        # The second node is started with a checkpoint once the first one
+        # has mined enough blocks.
+        self.start_node(0)
+
+    def run_test(self):
+        full = RpcClient(self.nodes[0])
+
+        receiver = full.rand_addr()
+        full.send_tx(full.new_tx(receiver=receiver, value=1000), True)
+        full.generate_blocks(60, num_txs=1)
+
+        checkpoint_epoch = full.epoch_number() - 10
+        checkpoint = full.block_by_epoch(full.EPOCH_NUM(checkpoint_epoch))
+        initialize_datadir(self.options.tmpdir, 1, dict(
+            self.conf_parameters,
+            checkpoint_epoch=str(checkpoint_epoch),
+            checkpoint_hash="\"{}\"".format(checkpoint["hash"]),
+            checkpoint_state_root="\"{}\"".format(checkpoint["deferredStateRoot"])))
+        self.start_node(1)
+        connect_nodes(self.nodes, 1, 0)
+        synced = RpcClient(self.nodes[1])
+
+        best_epoch = full.epoch_number()
+        wait_until(lambda: synced.epoch_number() == best_epoch)
+        assert_equal(synced.best_block_hash(), full.best_block_hash())
+        self.log.info("synced from checkpoint at epoch {}".format(checkpoint_epoch))
+
+        # Epochs after the state epoch of the checkpoint are executed on top
+        # of the synced state, with the rewards of the epochs before it.
+        epoch = synced.EPOCH_NUM(best_epoch - 5)
+        assert_equal(synced.get_balance(receiver, epoch), 1000)
+        assert_equal(synced.get_balance(full.GENESIS_ADDR, epoch), full.get_balance(full.GENESIS_ADDR, epoch))
+        block = synced.block_by_epoch(epoch)
+        assert_equal(synced.get_balance(block["miner"], epoch), full.get_balance(block["miner"], epoch))
+        self.log.info("state verified")
+
+        full.generate_blocks(10, num_txs=1)
+        best_epoch = full.epoch_number()
+        wait_until(lambda: synced.epoch_number() == best_epoch)
+        assert_equal(synced.best_block_hash(), full.best_block_hash())
+        self.log.info("new blocks synced")
+
+
+if __name__ == "__main__":
+    CheckpointSyncTest().main()
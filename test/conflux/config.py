@@ -9,4 +9,6 @@ default_config = dict(
     GENESIS_STATE_ROOT=decode_hex("0xb11554381a6ee59a94e33efcd7288e09f332cf4bff51e9b6ce8a5b194ba55b92"),
     GENESIS_RECEIPTS_ROOT=decode_hex("0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"),
     GENESIS_AUTHOR=decode_hex("0x0000000000000000000000000000000000000003"),
+    CHAIN_ID=1,
+    NETWORK_ID=0,
 )
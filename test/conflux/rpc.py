@@ -9,6 +9,7 @@ from .utils import privtoaddr, sha3_256
 import sys
 sys.path.append("..")
 
+from test_framework.authproxy import JSONRPCException
 from test_framework.util import (
     assert_greater_than, 
     assert_greater_than_or_equal, 
@@ -101,6 +102,25 @@ class RpcClient:
     def gas_price(self) -> int:
         return int(self.node.cfx_gasPrice(), 0)
 
+    def fee_history(self, epoch_count: int, percentiles: list) -> dict:
+        return self.node.cfx_feeHistory(hex(epoch_count), percentiles)
+
+    def hashrate(self) -> int:
+        return int(self.node.cfx_hashrate(), 0)
+
+    def is_mining(self) -> bool:
+        return self.node.cfx_mining()
+
+    def get_work(self):
+        try:
+            return self.node.cfx_getWork()
+        except JSONRPCException:
+            # The node is not mining.
+            return None
+
+    def submit_work(self, pow_hash: str, nonce: int) -> bool:
+        return self.node.cfx_submitWork(pow_hash, hex(nonce))
+
     def epoch_number(self, epoch: str = None) -> int:
         if epoch is None:
             return int(self.node.cfx_epochNumber(), 0)
@@ -113,6 +133,42 @@ class RpcClient:
         else:
             return int(self.node.cfx_getBalance(addr, epoch), 0)
 
+    def get_proof(self, addr: str, storage_keys: list = [], epoch: str = None) -> dict:
+        if epoch is None:
+            return self.node.cfx_getProof(addr, storage_keys)
+        else:
+            return self.node.cfx_getProof(addr, storage_keys, epoch)
+
+    def get_code(self, addr: str, epoch: str = None) -> str:
+        if epoch is None:
+            return self.node.cfx_getCode(addr)
+        else:
+            return self.node.cfx_getCode(addr, epoch)
+
+    def get_storage_at(self, addr: str, key: str, epoch: str = None) -> str:
+        if epoch is None:
+            return self.node.cfx_getStorageAt(addr, key)
+        else:
+            return self.node.cfx_getStorageAt(addr, key, epoch)
+
+    def get_account(self, addr: str, epoch: str = None) -> dict:
+        if epoch is None:
+            return self.node.cfx_getAccount(addr)
+        else:
+            return self.node.cfx_getAccount(addr, epoch)
+
+    def get_logs(self, filter: dict) -> list:
+        return self.node.cfx_getLogs(filter)
+
+    def new_filter(self, filter: dict) -> str:
+        return self.node.cfx_newFilter(filter)
+
+    def get_filter_changes(self, filter_id: str) -> list:
+        return self.node.cfx_getFilterChanges(filter_id)
+
+    def uninstall_filter(self, filter_id: str) -> bool:
+        return self.node.cfx_uninstallFilter(filter_id)
+
     def get_nonce(self, addr: str, epoch: str = None) -> int:
         if epoch is None:
             return int(self.node.cfx_getTransactionCount(addr), 0)
@@ -154,7 +210,7 @@ class RpcClient:
     def get_tx(self, tx_hash: str) -> dict:
         return self.node.cfx_getTransactionByHash(tx_hash)
 
-    def new_tx(self, sender = None, receiver = None, nonce = None, gas_price=1, gas=21000, value=100, data=b'', sign=True, priv_key=None):
+    def new_tx(self, sender = None, receiver = None, nonce = None, gas_price=1, gas=21000, value=100, data=b'', sign=True, priv_key=None, chain_id=default_config["CHAIN_ID"]):
         if sender is None:
             sender = self.GENESIS_ADDR
             if priv_key is None:
@@ -167,7 +223,7 @@ class RpcClient:
             nonce = self.get_nonce(sender)
 
         action = eth_utils.decode_hex(receiver)
-        tx = Transaction(nonce, gas_price, gas, action, value, data)
+        tx = Transaction(nonce, gas_price, gas, action, value, data, chain_id)
         
         if sign:
             return tx.sign(priv_key)
@@ -199,16 +255,64 @@ class RpcClient:
     def get_peers(self) -> list:
         return self.node.getpeerinfo()
 
+    def list_banned(self) -> list:
+        return self.node.listbanned()
+
+    def clear_banned(self):
+        self.node.clearbanned()
+
+    def add_reserved_peer(self, url: str):
+        self.node.admin_addReservedPeer(url)
+
+    def remove_reserved_peer(self, node_id: str) -> bool:
+        return self.node.admin_removeReservedPeer(node_id)
+
+    def set_ip_filter(self, ip_filter: str):
+        self.node.admin_setIpFilter(ip_filter)
+
+    def set_max_peers(self, outgoing: int, incoming: int):
+        self.node.admin_setMaxPeers(outgoing, incoming)
+
+    def set_reserved_only(self, reserved_only: bool):
+        self.node.admin_setReservedOnly(reserved_only)
+
+    def peer_settings(self) -> dict:
+        return self.node.admin_peerSettings()
+
     def chain(self) -> list:
         return self.node.cfx_getChain()
 
     def get_receipt(self, tx_hash: str) -> dict:
         return self.node.gettransactionreceipt(tx_hash)
 
+    def get_transaction_receipt(self, tx_hash: str) -> dict:
+        return self.node.cfx_getTransactionReceipt(tx_hash)
+
     def txpool_status(self) -> (int, int):
         status = self.node.txpool_status()
         return (status["pending"], status["ready"])
 
+    def txpool_remove_transaction(self, tx_hash: str) -> bool:
+        return self.node.txpool_removeTransaction(tx_hash)
+
+    def txpool_clear_sender(self, addr: str) -> int:
+        return self.node.txpool_clearSender(addr)
+
+    def txpool_add_local_sender(self, addr: str) -> bool:
+        return self.node.txpool_addLocalSender(addr)
+
+    def txpool_remove_local_sender(self, addr: str) -> bool:
+        return self.node.txpool_removeLocalSender(addr)
+
+    def txpool_local_senders(self) -> list:
+        return self.node.txpool_localSenders()
+
+    def trace_block(self, block_hash: str) -> list:
+        return self.node.trace_block(block_hash)
+
+    def trace_transaction(self, tx_hash: str) -> dict:
+        return self.node.trace_transaction(tx_hash)
+
     def new_tx_for_call(self, contract_addr:str, data_hex:str, nonce:int=None):
         if nonce is None:
             nonce = self.get_nonce(self.GENESIS_ADDR)
@@ -222,6 +326,7 @@ class RpcClient:
             "gasPrice": hex(1),
             "gas": hex(1000000),
             "data": data_hex,
+            "chainId": hex(default_config["CHAIN_ID"]),
             "v": hex(0),
             "r": hex(0),
             "s": hex(0),
@@ -5,6 +5,7 @@ from rlp.sedes import big_endian_int, binary
 
 from .exceptions import InvalidTransaction
 from . import utils
+from .config import default_config
 from .utils import TT256, mk_contract_address, zpad, int_to_32bytearray, \
     big_endian_to_int, ecsign, ecrecover_to_pub, normalize_key, str_to_bytes, \
     encode_hex, address
@@ -13,7 +14,7 @@ from .utils import TT256, mk_contract_address, zpad, int_to_32bytearray, \
 class Transaction(rlp.Serializable):
     """
     A transaction is stored as:
-    [nonce, gasprice, startgas, to, value, data, v, r, s]
+    [nonce, gasprice, startgas, to, value, data, chain_id, v, r, s]
 
     nonce is the number of transactions already sent by that account, encoded
     in binary form (eg.  0 -> '', 7 -> '\x07', 1000 -> '\x03\xd8').
@@ -36,6 +37,7 @@ class Transaction(rlp.Serializable):
         ('action', address),
         ('value', big_endian_int),
         ('data', binary),
+        ('chain_id', big_endian_int),
         ('v', big_endian_int),
         ('r', big_endian_int),
         ('s', big_endian_int),
@@ -43,11 +45,11 @@ class Transaction(rlp.Serializable):
 
     _sender = None
 
-    def __init__(self, nonce, gas_price, gas, action, value, data, v=0, r=0,
-                 s=0):
+    def __init__(self, nonce, gas_price, gas, action, value, data,
+                 chain_id=default_config["CHAIN_ID"], v=0, r=0, s=0):
 
         super(Transaction, self).__init__(
-            nonce, gas_price, gas, action, value, data, v, r, s
+            nonce, gas_price, gas, action, value, data, chain_id, v, r, s
         )
         if self.gas_price >= TT256 or \
                 self.value >= TT256 or self.nonce >= TT256:
@@ -121,5 +123,6 @@ def unsigned_tx_from_tx(tx):
         gas=tx.gas,
         value=tx.value,
         action=tx.action,
-        data=tx.data
+        data=tx.data,
+        chain_id=tx.chain_id
     )
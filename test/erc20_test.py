@@ -2,6 +2,7 @@
 from http.client import CannotSendRequest
 from eth_utils import decode_hex
 
+from conflux.config import default_config
 from conflux.rpc import RpcClient
 from conflux.utils import encode_hex, privtoaddr, parse_as_int
 from test_framework.block_gen_thread import BlockGenThread
@@ -104,6 +105,7 @@ class P2PTest(ConfluxTestFramework):
         tx["value"] = int_to_hex(tx['value'])
         tx["hash"] = "0x"+"0"*64
         tx["nonce"] = int_to_hex(nonce)
+        tx["chainId"] = int_to_hex(default_config["CHAIN_ID"])
         tx["v"] = "0x0"
         tx["r"] = "0x0"
         tx["s"] = "0x0"
@@ -0,0 +1,51 @@
+#!/usr/bin/env python3
+from conflux.rpc import RpcClient
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import *
+
+
+class LightNodeTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.setup_clean_chain = True
+        self.num_nodes = 2
+
+    def setup_network(self):
+        self.add_nodes(self.num_nodes)
+        # The second node only syncs block headers from the first one.
+        initialize_datadir(self.options.tmpdir, 1, dict(self.conf_parameters, light_node="true"))
+        self.start_nodes()
+        connect_nodes(self.nodes, 1, 0)
+
+    def run_test(self):
+        full = RpcClient(self.nodes[0])
+        light = RpcClient(self.nodes[1])
+
+        receiver = full.rand_addr()
+        tx = full.new_tx(receiver=receiver, value=1000)
+        tx_hash = full.send_tx(tx, True)
+        # The state of an epoch is committed by the header of a later pivot
+        # block, so mine more blocks to make the transaction provable.
+        full.generate_blocks(10)
+
+        best_epoch = full.epoch_number()
+        wait_until(lambda: light.epoch_number() == best_epoch)
+        assert_equal(light.best_block_hash(), full.best_block_hash())
+        self.log.info("headers synced")
+
+        epoch = light.EPOCH_NUM(best_epoch - 5)
+        assert_equal(light.get_balance(receiver, epoch), 1000)
+        assert_equal(light.get_balance(full.GENESIS_ADDR, epoch), full.get_balance(full.GENESIS_ADDR, epoch))
+        assert_equal(light.get_nonce(full.GENESIS_ADDR, epoch), full.get_nonce(full.GENESIS_ADDR, epoch))
+        assert_equal(light.get_proof(full.GENESIS_ADDR, [], epoch), full.get_proof(full.GENESIS_ADDR, [], epoch))
+        self.log.info("state verified")
+
+        block_hash = full.get_tx(tx_hash)["blockHash"]
+        block = light.block_by_hash(block_hash, True)
+        assert_equal(block["hash"], block_hash)
+        assert_equal([tx["hash"] for tx in block["transactions"]], [tx_hash])
+        assert_equal(light.block_by_hash(light.rand_hash()), None)
+        self.log.info("transactions verified")
+
+
+if __name__ == "__main__":
+    LightNodeTest().main()
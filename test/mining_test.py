@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+import json
+import socket
+
+import rlp
+
+from conflux.rpc import RpcClient
+from conflux.utils import sha3_256
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import *
+
+
+def solve(pow_hash: str, boundary: str) -> int:
+    block_hash = bytes.fromhex(pow_hash[2:])
+    boundary = int(boundary, 16)
+    nonce = 0
+    while int.from_bytes(sha3_256(rlp.encode([block_hash, nonce])), "big") >= boundary:
+        nonce += 1
+    return nonce
+
+
+class MiningTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.setup_clean_chain = True
+        self.num_nodes = 1
+        self.stratum_port = p2p_port(1)
+        self.conf_parameters = {
+            "start_mining": "true",
+            # Blocks are only mined by the test as an external miner.
+            "mining_threads": "0",
+            "mining_author": "\"{}\"".format("1" * 40),
+            "stratum_port": str(self.stratum_port),
+        }
+
+    def setup_network(self):
+        self.setup_nodes()
+
+    def run_test(self):
+        client = RpcClient(self.nodes[0])
+        assert_equal(client.is_mining(), True)
+        assert_equal(client.hashrate(), 0)
+        self.test_getwork()
+        self.test_stratum()
+
+    def test_getwork(self):
+        client = RpcClient(self.nodes[0])
+        wait_until(lambda: client.get_work() is not None)
+        block_count = self.nodes[0].getblockcount()
+        (pow_hash, boundary) = client.get_work()
+        nonce = solve(pow_hash, boundary)
+        assert_equal(client.submit_work(pow_hash, nonce), True)
+        wait_until(lambda: self.nodes[0].getblockcount() == block_count + 1)
+
+        # The work of the next block is ready.
+        wait_until(lambda: client.get_work() not in [None, [pow_hash, boundary]])
+        (pow_hash, boundary) = client.get_work()
+        nonce = solve(pow_hash, boundary)
+        assert_equal(client.submit_work("0x" + "0" * 64, nonce), False)
+        self.log.info("getwork passed")
+
+    def test_stratum(self):
+        sock = socket.create_connection(("127.0.0.1", self.stratum_port))
+        stream = sock.makefile("rw")
+
+        def send(request):
+            stream.write(json.dumps(request) + "\n")
+            stream.flush()
+
+        def read():
+            return json.loads(stream.readline())
+
+        send({"id": 1, "method": "mining.subscribe", "params": []})
+        assert_equal(read(), {"id": 1, "result": True, "error": None})
+
+        # The current job is pushed right after subscription.
+        message = read()
+        assert_equal(message["method"], "mining.notify")
+        (pow_hash, boundary) = message["params"]
+        nonce = solve(pow_hash, boundary)
+        send({"id": 2, "method": "mining.submit", "params": [pow_hash, hex(nonce)]})
+
+        # The job of the next block is pushed once the block is mined, which
+        # may happen before the response is sent.
+        messages = [read(), read()]
+        messages.sort(key=lambda m: m["id"] is None)
+        assert_equal(messages[0], {"id": 2, "result": True, "error": None})
+        assert_equal(messages[1]["method"], "mining.notify")
+        assert messages[1]["params"][0] != pow_hash
+        sock.close()
+        self.log.info("stratum passed")
+
+
+if __name__ == "__main__":
+    MiningTest().main()
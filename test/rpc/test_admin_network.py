@@ -0,0 +1,43 @@
+import os
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_raises_rpc_error
+
+class TestAdminNetwork(RpcClient):
+    def test_reserved_peers(self):
+        node_id = os.urandom(64).hex()
+        url = "cfxnode://{}@127.0.0.1:32323".format(node_id)
+        self.add_reserved_peer(url)
+        assert url in self.peer_settings()["reservedNodes"]
+
+        assert_equal(self.remove_reserved_peer("0x" + node_id), True)
+        assert url not in self.peer_settings()["reservedNodes"]
+        assert_equal(self.remove_reserved_peer("0x" + node_id), False)
+
+        # Reserved peers must have node ids.
+        assert_raises_rpc_error(None, None, self.add_reserved_peer, "127.0.0.1:32323")
+
+    def test_ip_filter(self):
+        old_filter = self.peer_settings()["ipFilter"]
+        self.set_ip_filter("all -10.0.0.0/8")
+        assert_equal(self.peer_settings()["ipFilter"], "all -10.0.0.0/8")
+        assert_raises_rpc_error(None, None, self.set_ip_filter, "all 10.0.0.0/33")
+        self.set_ip_filter(old_filter)
+        assert_equal(self.peer_settings()["ipFilter"], old_filter)
+
+    def test_max_peers(self):
+        settings = self.peer_settings()
+        self.set_max_peers(settings["maxOutgoingPeers"] + 1, settings["maxIncomingPeers"] + 1)
+        new_settings = self.peer_settings()
+        assert_equal(new_settings["maxOutgoingPeers"], settings["maxOutgoingPeers"] + 1)
+        assert_equal(new_settings["maxIncomingPeers"], settings["maxIncomingPeers"] + 1)
+        self.set_max_peers(settings["maxOutgoingPeers"], settings["maxIncomingPeers"])
+
+    def test_reserved_only(self):
+        assert_equal(self.peer_settings()["reservedOnly"], False)
+        self.set_reserved_only(True)
+        assert_equal(self.peer_settings()["reservedOnly"], True)
+        self.set_reserved_only(False)
+        assert_equal(self.peer_settings()["reservedOnly"], False)
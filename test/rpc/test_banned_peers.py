@@ -0,0 +1,12 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal
+
+class TestBannedPeers(RpcClient):
+    def test_list_and_clear(self):
+        # Test nodes behave well and are never banned.
+        assert_equal(self.list_banned(), [])
+        self.clear_banned()
+        assert_equal(self.list_banned(), [])
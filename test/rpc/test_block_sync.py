@@ -1,6 +1,7 @@
 import sys
 sys.path.append("..")
 
+from conflux.config import default_config
 from conflux.rpc import RpcClient
 from test_framework.util import assert_equal
 
@@ -14,6 +15,20 @@ class TestBlockSync(RpcClient):
     def test_tx_dup(self):
         pass
 
+    def test_tx_other_chain(self):
+        # tx signed for another chain is packed but never executed
+        cur_nonce = self.get_nonce(self.GENESIS_ADDR)
+        receiver = self.rand_addr()
+        tx = self.new_tx(receiver=receiver, nonce=cur_nonce, chain_id=default_config["CHAIN_ID"] + 1)
+
+        best_block = self.best_block_hash()
+        new_block = self.generate_custom_block(best_block, [], [tx])
+        assert_equal(self.best_block_hash(), new_block)
+
+        self.generate_blocks_to_state()
+        assert_equal(self.get_nonce(self.GENESIS_ADDR), cur_nonce)
+        assert_equal(self.get_balance(receiver), 0)
+
     def test_txpool_ready_remove(self):
         # add tx into pool
         cur_nonce = self.get_nonce(self.GENESIS_ADDR)
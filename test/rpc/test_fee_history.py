@@ -0,0 +1,42 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_greater_than, assert_raises_rpc_error
+
+class TestFeeHistory(RpcClient):
+    def test_percentiles(self):
+        sender = self.GENESIS_ADDR
+        n = self.get_nonce(sender)
+
+        # Pack all txs into one block, so they are executed in one epoch.
+        txs = []
+        for p in [10, 30, 20]:
+            tx = self.new_tx(nonce=n, gas_price=p)
+            txs.append(self.send_tx(tx))
+            n += 1
+        block_hash = self.generate_block(len(txs))
+        self.generate_blocks_to_state()
+        for tx in txs:
+            assert_equal(self.get_tx(tx)["blockHash"], block_hash)
+
+        epoch = int(self.block_by_hash(block_hash)["epochNumber"], 0)
+        history = self.fee_history(20, [0, 50, 100])
+        oldest = int(history["oldestEpoch"], 0)
+        assert oldest <= epoch
+        assert_equal(len(history["gasUsedRatio"]), len(history["gasPrice"]))
+        assert_greater_than(oldest + len(history["gasPrice"]), epoch)
+
+        index = epoch - oldest
+        assert_equal(history["gasPrice"][index], ["0xa", "0x14", "0x1e"])
+        assert_greater_than(history["gasUsedRatio"][index], 0)
+
+        # Later epochs have no transactions.
+        for prices in history["gasPrice"][index + 1:]:
+            assert_equal(prices, ["0x0", "0x0", "0x0"])
+        for ratio in history["gasUsedRatio"][index + 1:]:
+            assert_equal(ratio, 0)
+
+    def test_invalid_percentiles(self):
+        assert_raises_rpc_error(None, None, self.fee_history, 1, [50, 10])
+        assert_raises_rpc_error(None, None, self.fee_history, 1, [101])
@@ -0,0 +1,43 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_raises_rpc_error
+
+class TestGetCode(RpcClient):
+    # Init code which stores 7 at slot 0 and creates a contract with code 0xfe.
+    INIT_CODE = "0x600760005560fe60005360016000f3"
+
+    def deploy(self) -> str:
+        tx = self.new_contract_tx("", self.INIT_CODE)
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+        return self.get_tx(tx.hash_hex())["contractCreated"]
+
+    def test_code_and_storage(self):
+        contract_addr = self.deploy()
+        assert_equal(self.get_code(contract_addr), "0xfe")
+        assert_equal(self.get_storage_at(contract_addr, self.ZERO_HASH), "0x" + "0" * 63 + "7")
+        assert_equal(self.get_storage_at(contract_addr, self.rand_hash()), self.ZERO_HASH)
+
+        # The contract doesn't exist in the genesis epoch.
+        assert_equal(self.get_code(contract_addr, self.EPOCH_EARLIEST), "0x")
+        assert_equal(self.get_storage_at(contract_addr, self.ZERO_HASH, self.EPOCH_EARLIEST), self.ZERO_HASH)
+
+    def test_code_of_normal_account(self):
+        assert_equal(self.get_code(self.GENESIS_ADDR), "0x")
+        assert_equal(self.get_code(self.rand_addr()), "0x")
+
+    def test_account(self):
+        contract_addr = self.deploy()
+        account = self.get_account(contract_addr)
+        assert_equal(account["address"], contract_addr)
+        assert_equal(account["balance"], "0x0")
+
+        account = self.get_account(self.GENESIS_ADDR)
+        assert_equal(int(account["balance"], 0), self.get_balance(self.GENESIS_ADDR))
+        assert_equal(int(account["nonce"], 0), self.get_nonce(self.GENESIS_ADDR))
+
+        assert_equal(self.get_account(self.rand_addr()), None)
+
+    def test_epoch_not_executed(self):
+        assert_raises_rpc_error(None, None, self.get_code, self.GENESIS_ADDR, self.EPOCH_LATEST_MINED)
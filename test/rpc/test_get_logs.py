@@ -0,0 +1,61 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_raises_rpc_error
+
+class TestGetLogs(RpcClient):
+    # Init code which emits a log with the topic and creates a contract
+    # without code.
+    def new_log_contract_tx(self, topic: str):
+        return self.new_contract_tx("", "0x7f" + topic[2:] + "60006000a100")
+
+    def test_logs_of_contract(self):
+        topic = self.rand_hash()
+        tx = self.new_log_contract_tx(topic)
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+        contract_addr = self.get_tx(tx.hash_hex())["contractCreated"]
+
+        logs = self.get_logs({"fromEpoch": self.EPOCH_EARLIEST, "address": contract_addr})
+        assert_equal(len(logs), 1)
+        assert_equal(logs[0]["address"], contract_addr)
+        assert_equal(logs[0]["topics"], [topic])
+        assert_equal(logs[0]["transactionHash"], tx.hash_hex())
+        assert_equal(logs[0]["transactionLogIndex"], 0)
+
+        logs = self.get_logs({"fromEpoch": self.EPOCH_EARLIEST, "topics": [topic]})
+        assert_equal(len(logs), 1)
+
+        logs = self.get_logs({"fromEpoch": self.EPOCH_EARLIEST, "topics": [self.rand_hash()]})
+        assert_equal(logs, [])
+
+    def test_invalid_epoch_range(self):
+        assert_raises_rpc_error(None, None, self.get_logs, {"fromEpoch": self.EPOCH_LATEST_STATE, "toEpoch": self.EPOCH_EARLIEST})
+
+    def test_future_to_epoch(self):
+        # toEpoch is capped at the latest state epoch without overflowing
+        logs = self.get_logs({"fromEpoch": self.EPOCH_LATEST_STATE, "toEpoch": hex(2 ** 64 - 1), "topics": [self.rand_hash()]})
+        assert_equal(logs, [])
+
+    def test_too_many_topics(self):
+        assert_raises_rpc_error(None, None, self.get_logs, {"topics": [None] * 5})
+
+    def test_filter_changes(self):
+        topic = self.rand_hash()
+        filter_id = self.new_filter({"topics": [topic]})
+        assert_equal(self.get_filter_changes(filter_id), [])
+
+        tx = self.new_log_contract_tx(topic)
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+
+        changes = self.get_filter_changes(filter_id)
+        assert_equal(len(changes), 1)
+        assert_equal(changes[0]["transactionHash"], tx.hash_hex())
+
+        # Logs are only returned once.
+        self.generate_blocks_to_state()
+        assert_equal(self.get_filter_changes(filter_id), [])
+
+        assert_equal(self.uninstall_filter(filter_id), True)
+        assert_equal(self.uninstall_filter(filter_id), False)
+        assert_raises_rpc_error(None, None, self.get_filter_changes, filter_id)
@@ -0,0 +1,39 @@
+import eth_utils
+import rlp
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_greater_than, assert_raises_rpc_error
+
+class TestGetProof(RpcClient):
+    def test_genesis_account(self):
+        proof = self.get_proof(self.GENESIS_ADDR)
+        assert_equal(proof["address"], self.GENESIS_ADDR)
+        assert proof["account"] is not None
+        assert_greater_than(len(proof["accountProof"]), 0)
+        assert_equal(proof["storageProof"], [])
+
+        # The account rlp is [address, balance, nonce, code_hash].
+        balance = rlp.decode(eth_utils.decode_hex(proof["account"]))[1]
+        assert_equal(int.from_bytes(balance, "big"), self.get_balance(self.GENESIS_ADDR))
+
+    def test_address_not_exists(self):
+        addr = self.rand_addr()
+        proof = self.get_proof(addr)
+        assert_equal(proof["account"], None)
+        assert_greater_than(len(proof["accountProof"]), 0)
+
+    def test_storage_not_exists(self):
+        key = self.rand_hash()
+        proof = self.get_proof(self.GENESIS_ADDR, [key])
+        assert_equal(len(proof["storageProof"]), 1)
+        assert_equal(proof["storageProof"][0]["key"], key)
+        assert_equal(proof["storageProof"][0]["value"], None)
+
+    def test_epoch_earliest(self):
+        proof = self.get_proof(self.GENESIS_ADDR, [], self.EPOCH_EARLIEST)
+        assert proof["account"] is not None
+
+    def test_epoch_latest_mined(self):
+        assert_raises_rpc_error(None, None, self.get_proof, self.GENESIS_ADDR, [], self.EPOCH_LATEST_MINED)
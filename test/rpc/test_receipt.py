@@ -0,0 +1,53 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_greater_than
+
+class TestReceipt(RpcClient):
+    def test_receipt_of_contract(self):
+        # Init code which emits a log with the topic and creates a contract
+        # without code.
+        topic = self.rand_hash()
+        tx = self.new_contract_tx("", "0x7f" + topic[2:] + "60006000a100")
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+
+        receipt = self.get_transaction_receipt(tx.hash_hex())
+        assert_equal(receipt["transactionHash"], tx.hash_hex())
+        assert_equal(receipt["from"], self.GENESIS_ADDR)
+        assert_equal(receipt["to"], None)
+        assert_equal(receipt["outcomeStatus"], 0)
+        assert_equal(receipt["failureReason"], None)
+
+        tx_info = self.get_tx(tx.hash_hex())
+        assert_equal(receipt["blockHash"], tx_info["blockHash"])
+        assert_equal(receipt["contractCreated"], tx_info["contractCreated"])
+        assert_equal(receipt["transactionIndex"], int(tx_info["transactionIndex"], 0))
+        block = self.block_by_hash(receipt["blockHash"])
+        assert_equal(receipt["epochNumber"], block["epochNumber"])
+
+        gas_used = int(receipt["gasUsed"], 0)
+        assert_greater_than(gas_used, 0)
+        assert gas_used <= int(receipt["cumulativeGasUsed"], 0)
+
+        assert_equal(len(receipt["logs"]), 1)
+        log = receipt["logs"][0]
+        assert_equal(log["address"], receipt["contractCreated"])
+        assert_equal(log["topics"], [topic])
+        assert_equal(log["transactionHash"], tx.hash_hex())
+        assert_equal(log["transactionLogIndex"], 0)
+        assert receipt["logsBloom"] != "0x" + "0" * 512
+
+    def test_receipt_of_failed_transaction(self):
+        # Init code with an invalid instruction.
+        tx = self.new_contract_tx("", "0xfe")
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+
+        receipt = self.get_transaction_receipt(tx.hash_hex())
+        assert_equal(receipt["gasUsed"], hex(tx.gas))
+        assert_equal(receipt["contractCreated"], None)
+        assert_equal(receipt["logs"], [])
+        assert receipt["failureReason"] is not None
+
+    def test_receipt_of_unknown_transaction(self):
+        assert_equal(self.get_transaction_receipt(self.rand_hash()), None)
@@ -3,6 +3,7 @@ import rlp
 import sys
 sys.path.append("..")
 
+from conflux.config import default_config
 from conflux.rpc import RpcClient
 from test_framework.util import assert_equal, assert_raises_rpc_error, assert_is_hash_string
 
@@ -39,6 +40,10 @@ class TestSendTx(RpcClient):
         tx = self.new_tx(gas = 10**9 + 1)
         assert_raises_rpc_error(None, None, self.send_tx, tx)
 
+    def test_chain_id_mismatch(self):
+        tx = self.new_tx(chain_id = default_config["CHAIN_ID"] + 1)
+        assert_raises_rpc_error(None, None, self.send_tx, tx)
+
     def test_price_zero(self):
         tx = self.new_tx(gas_price = 0)
         assert_raises_rpc_error(None, None, self.send_tx, tx)
@@ -149,6 +154,25 @@ class TestSendTx(RpcClient):
         self.send_tx(missed_tx, True)
         self.wait_for_receipt(new_tx.hash_hex())
 
+    def test_replace_price_bump_too_low(self):
+        cur_nonce = self.get_nonce(self.GENESIS_ADDR)
+        tx = self.new_tx(nonce=cur_nonce, gas_price=100)
+        assert_equal(self.send_tx(tx), tx.hash_hex())
+        assert_equal(self.txpool_status(), (0, 1))
+
+        # replace with less than 10% higher gas price
+        new_tx = self.new_tx(nonce=cur_nonce, gas_price=109)
+        assert_raises_rpc_error(None, None, self.send_tx, new_tx)
+        assert_equal(self.get_tx(tx.hash_hex())["hash"], tx.hash_hex())
+
+        # replace with 10% higher gas price
+        new_tx = self.new_tx(nonce=cur_nonce, gas_price=110)
+        assert_equal(self.send_tx(new_tx), new_tx.hash_hex())
+        assert_equal(self.txpool_status(), (0, 1))
+        assert_equal(self.get_tx(tx.hash_hex()), None)
+
+        self.wait_for_receipt(new_tx.hash_hex())
+
     def test_nonce_stale(self):
         cur_nonce = self.get_nonce(self.GENESIS_ADDR)
         # random receiver to ensure unique tx hash
@@ -0,0 +1,50 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal, assert_greater_than
+
+class TestTrace(RpcClient):
+    def test_trace_transaction(self):
+        # Init code which stores 1 at slot 0 and creates a contract without
+        # code.
+        tx = self.new_contract_tx("", "0x600160005500")
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+
+        trace = self.trace_transaction(tx.hash_hex())
+        assert_equal(trace["transactionHash"], tx.hash_hex())
+        assert_equal(trace["error"], None)
+
+        call = trace["call"]
+        assert_equal(call["type"], "create")
+        assert_equal(call["to"], self.get_tx(tx.hash_hex())["contractCreated"])
+        assert_equal(call["input"], "0x600160005500")
+        assert_equal(call["error"], None)
+        assert_equal(call["calls"], [])
+
+        ops = trace["vmTrace"]["operations"]
+        assert_equal([op["instruction"] for op in ops], [0x60, 0x60, 0x55, 0x00])
+        assert_equal(ops[2]["executed"]["storeDiff"], {"location": "0x0", "value": "0x1"})
+
+    def test_trace_failed_transaction(self):
+        # Init code with an invalid instruction.
+        tx = self.new_contract_tx("", "0xfe")
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+
+        call = self.trace_transaction(tx.hash_hex())["call"]
+        assert_equal(call["gasUsed"], call["gas"])
+        assert call["error"] is not None
+
+    def test_trace_block(self):
+        tx = self.new_contract_tx("", "0x00")
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+        block_hash = self.get_tx(tx.hash_hex())["blockHash"]
+
+        traces = self.trace_block(block_hash)
+        assert_greater_than(len(traces), 0)
+        trace = [t for t in traces if t["transactionHash"] == tx.hash_hex()][0]
+        assert_equal(trace["call"]["type"], "create")
+        assert_equal(trace["vmTrace"], None)
+
+    def test_trace_unknown_transaction(self):
+        assert_equal(self.trace_transaction(self.rand_hash()), None)
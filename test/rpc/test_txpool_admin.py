@@ -0,0 +1,43 @@
+import sys
+sys.path.append("..")
+
+from conflux.rpc import RpcClient
+from test_framework.util import assert_equal
+
+class TestTxPoolAdmin(RpcClient):
+    def test_remove_transaction(self):
+        nonce = self.get_nonce(self.GENESIS_ADDR)
+        tx = self.new_tx(nonce=nonce + 1)
+        assert_equal(self.send_tx(tx), tx.hash_hex())
+        assert_equal(self.txpool_status(), (1, 0))
+
+        assert_equal(self.txpool_remove_transaction(tx.hash_hex()), True)
+        assert_equal(self.txpool_status(), (0, 0))
+        assert_equal(self.txpool_remove_transaction(tx.hash_hex()), False)
+
+    def test_clear_sender(self):
+        nonce = self.get_nonce(self.GENESIS_ADDR)
+        for i in range(1, 3):
+            tx = self.new_tx(nonce=nonce + i)
+            assert_equal(self.send_tx(tx), tx.hash_hex())
+        assert_equal(self.txpool_status(), (2, 0))
+
+        assert_equal(self.txpool_clear_sender(self.GENESIS_ADDR), 2)
+        assert_equal(self.txpool_status(), (0, 0))
+        assert_equal(self.txpool_clear_sender(self.GENESIS_ADDR), 0)
+
+    def test_local_senders(self):
+        addr = self.rand_addr()
+        assert_equal(self.txpool_add_local_sender(addr), True)
+        assert_equal(self.txpool_add_local_sender(addr), False)
+        assert addr in self.txpool_local_senders()
+
+        assert_equal(self.txpool_remove_local_sender(addr), True)
+        assert_equal(self.txpool_remove_local_sender(addr), False)
+        assert addr not in self.txpool_local_senders()
+
+    def test_local_sender_packed(self):
+        assert_equal(self.txpool_add_local_sender(self.GENESIS_ADDR), True)
+        tx = self.new_tx()
+        assert_equal(self.send_tx(tx, True), tx.hash_hex())
+        assert_equal(self.txpool_remove_local_sender(self.GENESIS_ADDR), True)
@@ -31,7 +31,7 @@ def create_block(parent_hash=default_config["GENESIS_PREVHASH"], height=0, times
 
 def create_transaction(nonce=0, gas_price=1, gas=21000, value=0, receiver=default_config['GENESIS_COINBASE'],
                        data=b'', v=0, r=0, s=0, pri_key=default_config["GENESIS_PRI_KEY"]):
-    transaction = Transaction(nonce, gas_price, gas, receiver, value, data, v, r, s)
+    transaction = Transaction(nonce, gas_price, gas, receiver, value, data, v=v, r=r, s=s)
     return transaction.sign(pri_key)
 
 
@@ -19,6 +19,7 @@ import struct
 import sys
 import threading
 
+from conflux.config import default_config
 from conflux.transactions import Transaction
 from conflux.utils import hash32, hash20, sha3, int_to_bytes, sha3_256, ecrecover_to_pub, ec_random_keys, ecsign, \
     bytes_to_int, encode_int32, int_to_hex, zpad, rzpad
@@ -285,7 +286,7 @@ class P2PInterface(P2PConnection):
     # Message receiving methods
 
     def send_status(self):
-        status = Status(self.protocol_version, 0,
+        status = Status(self.protocol_version, default_config["NETWORK_ID"],
                         self.genesis.block_header.hash, 0, [self.best_block_hash])
         self.send_protocol_msg(status)
 
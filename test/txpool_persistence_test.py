@@ -0,0 +1,39 @@
+#!/usr/bin/env python3
+from conflux.rpc import RpcClient
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import *
+
+
+class TxPoolPersistenceTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.setup_clean_chain = True
+        self.num_nodes = 1
+
+    def setup_network(self):
+        self.setup_nodes()
+
+    def run_test(self):
+        client = RpcClient(self.nodes[0])
+        client.generate_blocks_to_state()
+
+        # A transaction with a future nonce stays in the pending pool.
+        tx = client.new_tx(nonce=client.get_nonce(client.GENESIS_ADDR) + 1)
+        assert_equal(client.send_tx(tx), tx.hash_hex())
+        assert_equal(client.txpool_status(), (1, 0))
+
+        self.stop_node(0)
+        self.log.info("node 0 stopped")
+        self.start_node(0)
+        client = RpcClient(self.nodes[0])
+
+        # The local transaction is restored into the pool on startup.
+        assert_equal(client.txpool_status(), (1, 0))
+
+        # Once the nonce gap is filled, both transactions are packed.
+        tx2 = client.new_tx(nonce=tx.nonce - 1)
+        assert_equal(client.send_tx(tx2), tx2.hash_hex())
+        client.wait_for_receipt(tx.hash_hex(), num_txs=2)
+
+
+if __name__ == "__main__":
+    TxPoolPersistenceTest().main()
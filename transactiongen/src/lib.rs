@@ -150,6 +150,7 @@ impl TransactionGenerator {
             value: balance_to_transfer,
             action: Action::Call(receiver_address),
             data: Bytes::new(),
+            chain_id: self.consensus.machine.params().chain_id,
         };
         let r = tx.sign(sender_kp.secret());
         r
@@ -274,6 +275,7 @@ impl TransactionGenerator {
                 value: balance_to_transfer,
                 action: Action::Call(receiver_address),
                 data: Bytes::new(),
+                chain_id: txgen.consensus.machine.params().chain_id,
             };
 
             let signed_tx = tx.sign(sender_kp.secret());
@@ -299,6 +301,7 @@ pub struct SpecialTransactionGenerator {
     accounts: HashMap<Address, (KeyPair, Account, U256)>,
     address_by_index: Vec<Address>,
     erc20_address: Address,
+    chain_id: u64,
 }
 
 // Allow use of hex() in H256, etc.
@@ -308,7 +311,7 @@ impl SpecialTransactionGenerator {
 
     pub fn new(
         start_key_pair: KeyPair, contract_creator: &Address,
-        start_balance: U256, start_erc20_balance: U256,
+        start_balance: U256, start_erc20_balance: U256, chain_id: u64,
     ) -> SpecialTransactionGenerator
     {
         let start_address = public_to_address(start_key_pair.public());
@@ -346,6 +349,7 @@ impl SpecialTransactionGenerator {
             accounts,
             address_by_index,
             erc20_address,
+            chain_id,
         }
     }
 
@@ -427,6 +431,7 @@ impl SpecialTransactionGenerator {
                 value: balance_to_transfer,
                 action: Action::Call(receiver_address),
                 data: vec![0u8; 128],
+                chain_id: self.chain_id,
             };
             let signed_transaction = tx.sign(sender_kp.secret());
             let rlp_size = signed_transaction.transaction.rlp_bytes().len();
@@ -507,6 +512,7 @@ impl SpecialTransactionGenerator {
                 value: 0.into(),
                 action: Action::Call(self.erc20_address.clone()),
                 data: tx_data,
+                chain_id: self.chain_id,
             };
             let signed_transaction = tx.sign(sender_kp.secret());
             let rlp_size = signed_transaction.transaction.rlp_bytes().len();